@@ -1,4 +1,4 @@
-use std::io::Stdout;
+use std::io::{IsTerminal, Stdout};
 use std::time::Duration;
 use std::time::Instant;
 
@@ -9,12 +9,12 @@ use ratatui::crossterm::{
 use ratatui::layout::Rect;
 use ratatui::prelude::CrosstermBackend;
 use ratatui::style::Styled;
-use ratatui::widgets::{HighlightSpacing, List, ListState, StatefulWidget};
+use ratatui::text::Line;
+use ratatui::widgets::{HighlightSpacing, List, ListState, Paragraph, StatefulWidget};
 use ratatui::{
     Terminal,
     buffer::Buffer,
-    crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, poll, read},
-    style::{Modifier, Style, palette::tailwind::SLATE},
+    crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, read},
     widgets::{Block, Borders, Widget},
 };
 
@@ -23,18 +23,23 @@ use std::io::stdout;
 use crate::my_widgets::LogKind;
 use crate::{
     apps::AppAction::*,
-    apps::file_sync_manager::SyncEngine,
-    my_widgets::{MyWidgets, get_center_rect},
+    apps::file_sync_manager::{OperationLimit, QuickActions, ShutdownReport, SyncEngine},
+    apps::frame_metrics::{FrameMetrics, FrameSample},
+    apps::notifier::{RESIZE_DEBOUNCE, ResizeDebounce, Wake, ui_notifier},
+    my_widgets::{MyWidgets, get_center_rect, render_detail_popup, theme::Theme},
     *,
 };
 
+pub mod desktop_notify;
 pub mod file_sync_manager;
+pub mod frame_metrics;
+pub mod notifier;
+pub mod setup_wizard;
 
-pub const MENU_SELECTED_STYLE: Style = Style::new().bg(SLATE.c800).add_modifier(Modifier::BOLD);
-pub const MENU_HIGHLIGHT_STYLE: Style =
-    Style::new().bg(SLATE.c800).fg(ratatui::style::Color::Green);
-pub const MENU_STYLE: Style = Style::new().bg(SLATE.c600).add_modifier(Modifier::BOLD);
 // const THROTTLE_DURATION: Duration = Duration::from_millis(100);
+/// 没有输入和变化时重绘的间隔，用于刷新运行时间等
+const IDLE_REDRAW: Duration = Duration::from_secs(1);
+const METRICS_OVERLAY_WIDTH: u16 = 28;
 
 #[derive(PartialEq, Eq)]
 pub enum AppAction {
@@ -52,9 +57,21 @@ pub struct Apps {
     apps: Vec<(String, Box<dyn MyWidgets>)>,
     current_app: usize,
     menu: AppsMenu,
+    theme: Theme,
+    metrics: FrameMetrics,
+    show_metrics: bool,
+    /// 退出时仍有后台任务，等待再次确认，内容为运行中的任务
+    quit_confirm: Option<String>,
+    #[allow(dead_code)]
     last_event_time: Instant,
 }
 
+impl std::default::Default for Apps {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Apps {
     pub fn new() -> Self {
         let mut state = ListState::default();
@@ -63,6 +80,10 @@ impl Apps {
             apps: Vec::new(),
             current_app: 0,
             menu: AppsMenu { show: false, state },
+            theme: Theme::detect(),
+            metrics: FrameMetrics::default(),
+            show_metrics: false,
+            quit_confirm: None,
             last_event_time: Instant::now(),
         }
     }
@@ -72,12 +93,36 @@ impl Apps {
         terminal: &mut Terminal<CrosstermBackend<Stdout>>,
     ) -> Result<bool, std::io::Error> {
         // let data_time_now = Local::now();
+        // 终端输入在单独的线程中阻塞读取，与后台变化一起唤醒界面
+        let notifier = ui_notifier();
+        std::thread::spawn(move || {
+            while let Ok(event) = read() {
+                notifier.push_input(event);
+            }
+        });
+        let mut resize = ResizeDebounce::new(RESIZE_DEBOUNCE);
         'app: loop {
-            terminal
-                .draw(|frame| frame.render_widget(&mut *self, frame.area()))
-                .unwrap();
-
-            if poll(Duration::ZERO)? {
+            let frame_start = Instant::now();
+            // 调整大小期间不重绘，停下后按最终大小只重新布局一次
+            resize.ready(frame_start);
+            for (_, app) in self.apps.iter_mut() {
+                app.tick();
+            }
+            let draw = if resize.is_pending() {
+                Duration::ZERO
+            } else {
+                terminal
+                    .draw(|frame| frame.render_widget(&mut *self, frame.area()))
+                    .unwrap();
+                frame_start.elapsed()
+            };
+            let mut handle_event = Duration::ZERO;
+            let mut events = 0;
+
+            let wake = notifier.wait(resize.timeout(Instant::now(), IDLE_REDRAW));
+            if let Wake::Input(Event::Resize(width, height)) = wake {
+                resize.on_resize((width, height), Instant::now());
+            } else if let Wake::Input(event) = wake {
                 // 渲染计算量过大时限制操作频率。实际应优先优化计算缓存
                 // let mut events = Vec::new();
 
@@ -94,13 +139,23 @@ impl Apps {
                 //         }
                 //     }
                 // }
-                let event = read()?;
+                let handle_start = Instant::now();
+                let result = self.handle_event(event.clone());
+                handle_event = handle_start.elapsed();
+                events = 1;
 
-                if let Ok(ExitProgress) = self.handle_event(event.clone()) {
+                if let Ok(ExitProgress) = result {
                     break 'app;
                 }
             }
 
+            self.metrics.record(FrameSample {
+                at: frame_start,
+                draw,
+                handle_event,
+                events,
+            });
+
             // thread::sleep(Duration::from_millis(33));
         }
 
@@ -111,14 +166,14 @@ impl Apps {
         let block = Block::new()
             .borders(Borders::ALL)
             .title("Menu")
-            .set_style(MENU_STYLE);
+            .set_style(self.theme.menu());
 
         let apps = self.get_apps();
 
         let menu_list = List::new(apps.iter().map(AsRef::as_ref).collect::<Vec<&str>>())
             .block(block)
             .highlight_spacing(HighlightSpacing::WhenSelected)
-            .highlight_style(MENU_SELECTED_STYLE)
+            .highlight_style(self.theme.menu_selected())
             .highlight_symbol(">");
 
         StatefulWidget::render(menu_list, area, buf, &mut self.menu.state);
@@ -130,6 +185,20 @@ impl Apps {
         // }
         // self.last_event_time = Instant::now();
 
+        if let Event::Key(KeyEvent {
+            code: KeyCode::F(12),
+            kind: KeyEventKind::Press,
+            ..
+        }) = event
+        {
+            self.show_metrics = !self.show_metrics;
+            return Ok(Default);
+        }
+
+        if self.quit_confirm.is_some() {
+            return Ok(self.handle_quit_confirm(event));
+        }
+
         let result = if self.menu.show {
             self.handle_menu_event(event)
         } else {
@@ -162,20 +231,14 @@ impl Apps {
                         self.toggle_menu();
                     }
                 }
-                KeyCode::Char('q') => {
-                    if self.menu.show {
-                        return Ok(ExitProgress);
-                    }
+                KeyCode::Char('q') if self.menu.show => {
+                    return Ok(self.request_quit());
                 }
-                KeyCode::Up => {
-                    if self.menu.show {
-                        self.menu.state.select_previous();
-                    }
+                KeyCode::Up if self.menu.show => {
+                    self.menu.state.select_previous();
                 }
-                KeyCode::Down => {
-                    if self.menu.show {
-                        self.menu.state.select_next();
-                    }
+                KeyCode::Down if self.menu.show => {
+                    self.menu.state.select_next();
                 }
                 _ => {}
             }
@@ -184,11 +247,93 @@ impl Apps {
         Ok(Default)
     }
 
+    /// Exit at once if nothing is running, otherwise ask for confirmation first.
+    fn request_quit(&mut self) -> AppAction {
+        let running: Vec<String> = self
+            .apps
+            .iter()
+            .filter_map(|(name, app)| app.busy_summary().map(|s| format!("{}: {}", name, s)))
+            .collect();
+        if running.is_empty() {
+            return ExitProgress;
+        }
+        self.quit_confirm = Some(running.join("\n"));
+        Default
+    }
+
+    /// Pressing q again (or y, Enter) quits, Esc or n goes back.
+    fn handle_quit_confirm(&mut self, event: Event) -> AppAction {
+        if let Event::Key(KeyEvent {
+            code,
+            kind: KeyEventKind::Press,
+            ..
+        }) = event
+        {
+            match code {
+                KeyCode::Char('q') | KeyCode::Char('y') | KeyCode::Enter => return ExitProgress,
+                KeyCode::Esc | KeyCode::Char('n') => self.quit_confirm = None,
+                _ => {}
+            }
+        }
+        Default
+    }
+
+    pub fn render_metrics(&self, area: Rect, buf: &mut Buffer) {
+        let summary = self.metrics.summary();
+        let text = vec![
+            Line::from(format!("frames: {}", summary.frames)),
+            Line::from(format!("draw p50: {:?}", summary.draw_p50)),
+            Line::from(format!("draw p95: {:?}", summary.draw_p95)),
+            Line::from(format!("event p95: {:?}", summary.handle_event_p95)),
+            Line::from(format!("events/s: {:.1}", summary.events_per_sec)),
+            Line::from(format!("dirty/redraw: {:.2}", summary.dirty_ratio)),
+            Line::from(format!(
+                "log mem: {} KiB",
+                self.log_memory_estimate() / 1024
+            )),
+        ];
+
+        let area = Rect {
+            x: area.right().saturating_sub(METRICS_OVERLAY_WIDTH),
+            y: area.y,
+            width: METRICS_OVERLAY_WIDTH.min(area.width),
+            height: (text.len() as u16 + 2).min(area.height),
+        };
+        Apps::clear_area(area, buf);
+        Paragraph::new(text)
+            .block(Block::bordered().title("Metrics (F12)"))
+            .style(self.theme.menu())
+            .render(area, buf);
+    }
+
+    pub fn get_metrics(&self) -> &FrameMetrics {
+        &self.metrics
+    }
+
+    /// Estimated bytes held by the log lists of all apps.
+    pub fn log_memory_estimate(&self) -> usize {
+        self.apps.iter().map(|(_, app)| app.memory_estimate()).sum()
+    }
+
+    /// Frame metrics and log memory in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        format!(
+            "{}# TYPE one_server_log_memory_bytes gauge\none_server_log_memory_bytes {}\n",
+            self.metrics.to_prometheus(),
+            self.log_memory_estimate()
+        )
+    }
+
     pub fn add_widgets(mut self, name: String, widgets: Box<dyn MyWidgets>) -> Self {
         self.apps.push((name, widgets));
         self
     }
 
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
     pub fn set_current_app(mut self, index: usize) -> Self {
         self.current_app = index;
         self
@@ -214,6 +359,14 @@ impl Apps {
         }
     }
 
+    /// Shut down every app before exit, returning the reports of those with background work.
+    pub fn shutdown(&mut self) -> Vec<ShutdownReport> {
+        self.apps
+            .iter_mut()
+            .filter_map(|(_, app)| app.shutdown_report())
+            .collect()
+    }
+
     pub fn get_all_logs_str(&self) -> Vec<String> {
         self.apps
             .iter()
@@ -228,19 +381,63 @@ pub fn run_tui() {
     let backend = CrosstermBackend::new(stdout());
     let mut terminal = Terminal::new(backend).unwrap();
 
-    let app = Apps::new();
+    let config = load_config().file_sync_manager;
+    let theme = Theme::from_mode(color_mode(), stdout().is_terminal());
+    // 观察器热加载配置文件的修改
+    let config_watcher = ConfigWatcher::start();
 
-    let path = load_config().file_sync_manager.observed_path;
-
-    let file_monitor = (
-        String::from("file_monitor"),
-        Box::new(SyncEngine::new("file_monitor".to_string(), path, 50)),
-    );
+    if config.desktop_notifications {
+        // 未启用 desktop-notifications 功能或没有桌面时不发送
+        if let Some(toast) = desktop_notify::os_toast() {
+            desktop_notify::enable_error_toasts(toast);
+        }
+    }
+    let quick_actions = QuickActions::from_config(&config.quick_actions).unwrap_or_default();
+    // 站点配置已在启动时校验
+    let sites = config.site_configs().unwrap();
+
+    // 所有站点共用一个并发上限
+    let op_limit = OperationLimit::new(config.max_concurrent_operations);
+    let mut apps = Apps::new().with_theme(theme);
+    // 每个站点一个应用，各自有独立的观察器和扫描器
+    for site in sites {
+        let file_monitor = (
+            site.name.clone(),
+            Box::new(
+                SyncEngine::new(site.name.clone(), site.primary_path(), 50)
+                    .with_checkpoint(config.checkpoint_path_of(&site.name))
+                    .with_site(site)
+                    .with_max_display_chars(config.max_display_chars)
+                    .with_wrap_mode(config.wrap_mode)
+                    .with_record_notes_to_db(config.record_notes_to_db)
+                    .with_dry_run(dry_run_mode(&config))
+                    .with_recursive(config.recursive)
+                    .with_follow_log_symlink(config.follow_log_symlink)
+                    .with_config_watcher(config_watcher.as_ref())
+                    .with_max_log_memory_mb(config.max_log_memory_mb)
+                    .with_shutdown_timeouts(config.shutdown_timeouts)
+                    .with_size_units(config.size_units)
+                    .with_export_path_style(config.export_path_style)
+                    .with_skip_hidden(config.skip_hidden)
+                    .with_today_time_zone(config.today_time_zone())
+                    .with_scan_reconcile(config.scan_reconcile)
+                    .with_scan_jitter(config.scan_jitter)
+                    .with_scan_guard(config.scan_guard.clone())
+                    .with_op_limit(op_limit.clone())
+                    .with_theme(theme)
+                    .with_quick_actions(quick_actions.clone()),
+            ),
+        );
+        apps = add_widgets!(apps, file_monitor);
+    }
+    let mut apps = apps.set_current_app(0);
+    apps.run(&mut terminal).unwrap();
 
-    add_widgets!(app, file_monitor)
-        .set_current_app(0)
-        .run(&mut terminal)
-        .unwrap();
+    let reports = apps.shutdown();
+    ratatui::restore();
+    for report in reports.iter().filter(|r| !r.is_clean()) {
+        eprintln!("{}", report);
+    }
 }
 
 impl Widget for &mut Apps {
@@ -259,6 +456,18 @@ impl Widget for &mut Apps {
             Apps::clear_area(area, buf);
             self.render_menu(area, buf);
         }
+
+        if self.show_metrics {
+            self.render_metrics(area, buf);
+        }
+
+        if let Some(running) = &self.quit_confirm {
+            let content = format!(
+                "{}\n\nPress q again to quit anyway, Esc to go back.",
+                running
+            );
+            render_detail_popup(&content, area, buf, "Still running, quit?");
+        }
     }
 }
 
@@ -270,3 +479,48 @@ macro_rules!  add_widgets {
         )*
     };
 }
+
+// MARK: test
+#[test]
+fn test_quit_while_busy_asks_first() {
+    let key = |code| Event::Key(KeyEvent::from(code));
+    let dir = std::env::temp_dir().join("test_quit_while_busy_asks_first");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    // 空闲时直接退出
+    let mut apps = Apps::new().add_widgets(
+        "idle".to_string(),
+        Box::new(SyncEngine::new("idle".to_string(), dir.clone(), 20)),
+    );
+    apps.toggle_menu();
+    assert!(apps.handle_event(key(KeyCode::Char('q'))).unwrap() == ExitProgress);
+
+    let mut engine = SyncEngine::new("busy".to_string(), dir.clone(), 20);
+    engine.scanner.set_path(dir.clone());
+    engine
+        .scanner
+        .start_periodic_scan(file_sync_manager::MIN_SCAN_INTERVAL);
+    let mut apps = Apps::new().add_widgets("busy".to_string(), Box::new(engine));
+    apps.toggle_menu();
+
+    // 忙碌时先弹出确认，Esc 返回
+    assert!(apps.handle_event(key(KeyCode::Char('q'))).unwrap() == Default);
+    assert_eq!(
+        apps.quit_confirm.as_deref(),
+        Some("busy: scanner Running(Periodic)")
+    );
+    let area = Rect::new(0, 0, 80, 24);
+    let mut buf = Buffer::empty(area);
+    (&mut apps).render(area, &mut buf);
+    let screen: String = buf.content().iter().map(|c| c.symbol()).collect();
+    assert!(screen.contains("busy: scanner Running(Periodic)"));
+    assert!(apps.handle_event(key(KeyCode::Esc)).unwrap() == Default);
+    assert_eq!(apps.quit_confirm, None);
+
+    // 再按一次 q 强制退出
+    assert!(apps.handle_event(key(KeyCode::Char('q'))).unwrap() == Default);
+    assert!(apps.handle_event(key(KeyCode::Char('q'))).unwrap() == ExitProgress);
+
+    apps.shutdown();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,174 @@
+use std::{
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use crate::{DirScannerEventKind, EventKind, LogObserverEventKind, OneEvent};
+
+/// 两次系统通知之间的最短间隔，期间的错误合并到下一次通知
+pub const TOAST_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 通知正文的最大字符数
+const BODY_MAX_CHARS: usize = 200;
+
+/// 发送系统通知的方式，测试时替换为记录调用的实现
+pub trait Toast: Send {
+    fn show(&self, summary: &str, body: &str);
+}
+
+/// 错误事件转为系统通知，间隔内的错误只计数
+pub struct ErrorToasts {
+    toast: Box<dyn Toast>,
+    interval: Duration,
+    last_shown: Option<Instant>,
+    /// 上次通知后被合并的错误数
+    suppressed: usize,
+}
+
+impl ErrorToasts {
+    pub fn new(toast: Box<dyn Toast>, interval: Duration) -> Self {
+        Self {
+            toast,
+            interval,
+            last_shown: None,
+            suppressed: 0,
+        }
+    }
+
+    /// Show a toast for `event` if it is an error, returning whether one was shown.
+    pub fn on_event(&mut self, event: &OneEvent) -> bool {
+        if !is_error(event) {
+            return false;
+        }
+        if self
+            .last_shown
+            .is_some_and(|last| last.elapsed() < self.interval)
+        {
+            self.suppressed += 1;
+            return false;
+        }
+
+        let mut body: String = event.content.chars().take(BODY_MAX_CHARS).collect();
+        if self.suppressed > 0 {
+            body.push_str(&format!(
+                "\n({} more errors since the last notification)",
+                self.suppressed
+            ));
+        }
+        self.toast.show("One Server error", &body);
+        self.last_shown = Some(Instant::now());
+        self.suppressed = 0;
+        true
+    }
+}
+
+fn is_error(event: &OneEvent) -> bool {
+    matches!(
+        event.kind,
+        EventKind::LogObserverEvent(LogObserverEventKind::Error)
+            | EventKind::DirScannerEvent(DirScannerEventKind::Error)
+    )
+}
+
+static ERROR_TOASTS: OnceLock<Mutex<ErrorToasts>> = OnceLock::new();
+
+/// Send toasts for error events from now on, later calls are ignored.
+pub fn enable_error_toasts(toast: Box<dyn Toast>) {
+    let _ = ERROR_TOASTS.set(Mutex::new(ErrorToasts::new(toast, TOAST_INTERVAL)));
+}
+
+/// Called for every logged event, does nothing unless toasts are enabled.
+pub fn toast_on_error(event: &OneEvent) {
+    if let Some(toasts) = ERROR_TOASTS.get() {
+        toasts.lock().unwrap().on_event(event);
+    }
+}
+
+/// The OS notifier, `None` when built without the `desktop-notifications` feature
+/// or when there is no desktop to show it on.
+pub fn os_toast() -> Option<Box<dyn Toast>> {
+    #[cfg(feature = "desktop-notifications")]
+    if has_desktop() {
+        return Some(Box::new(OsToast));
+    }
+    None
+}
+
+#[cfg(feature = "desktop-notifications")]
+struct OsToast;
+
+#[cfg(feature = "desktop-notifications")]
+impl Toast for OsToast {
+    fn show(&self, summary: &str, body: &str) {
+        let mut notification = notify_rust::Notification::new();
+        notification.summary(summary).body(body);
+        // 在单独的线程中发送，不阻塞持有日志锁的调用方，发送失败时忽略
+        std::thread::spawn(move || {
+            let _ = notification.show();
+        });
+    }
+}
+
+/// Whether a notification can be shown: always on Windows and macOS, and on other
+/// systems only with a graphical session and a D-Bus session bus.
+#[cfg(feature = "desktop-notifications")]
+fn has_desktop() -> bool {
+    if cfg!(any(windows, target_os = "macos")) {
+        return true;
+    }
+    let set = |name: &str| std::env::var_os(name).is_some_and(|v| !v.is_empty());
+    (set("DISPLAY") || set("WAYLAND_DISPLAY")) && set("DBUS_SESSION_BUS_ADDRESS")
+}
+
+// MARK: test
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct RecordingToast(std::sync::Arc<Mutex<Vec<String>>>);
+
+#[cfg(test)]
+impl Toast for RecordingToast {
+    fn show(&self, _summary: &str, body: &str) {
+        self.0.lock().unwrap().push(body.to_string());
+    }
+}
+
+#[test]
+fn test_error_event_shows_toast() {
+    let event = |kind: EventKind, content: &str| OneEvent {
+        kind,
+        content: content.to_string(),
+        time: None,
+    };
+    let recording = RecordingToast::default();
+    let mut toasts = ErrorToasts::new(Box::new(recording.clone()), Duration::from_millis(100));
+
+    let info = event(
+        EventKind::LogObserverEvent(LogObserverEventKind::Info),
+        "Observer started",
+    );
+    assert!(!toasts.on_event(&info));
+    let db_down = event(
+        EventKind::DirScannerEvent(DirScannerEventKind::Error),
+        "Failed to get DB connection",
+    );
+    assert!(toasts.on_event(&db_down));
+
+    // 间隔内的错误只计数，之后的通知带上数量
+    let watcher = event(
+        EventKind::LogObserverEvent(LogObserverEventKind::Error),
+        "Watcher failed",
+    );
+    assert!(!toasts.on_event(&watcher));
+    assert!(!toasts.on_event(&watcher));
+    std::thread::sleep(Duration::from_millis(120));
+    assert!(toasts.on_event(&watcher));
+
+    let shown = recording.0.lock().unwrap().clone();
+    assert_eq!(
+        shown,
+        [
+            "Failed to get DB connection",
+            "Watcher failed\n(2 more errors since the last notification)"
+        ]
+    );
+}
@@ -1,46 +1,97 @@
+pub mod db_recent;
+pub mod dest_roots;
 pub mod dir_scanner;
+pub mod importer;
+pub mod line_parser;
 pub mod log_observer;
+pub mod log_symlink;
+pub mod long_path;
 pub mod menujson;
+pub mod op_limit;
+pub mod quick_actions;
+pub mod recorded_files;
 pub mod registry;
+pub mod registry_backend;
+pub mod remote_log;
+pub mod retry_queue;
+pub mod scan_guard;
+pub mod scan_schedule;
+pub mod shutdown;
+pub mod status_history;
 
+pub use db_recent::{DB_RECENT_LIMIT, DbRecent, DbRecentState};
 pub use dir_scanner::*;
 pub use log_observer::*;
 pub use menujson::MENU_JSON;
+pub use op_limit::{OperationLimit, OperationPermit};
+pub use quick_actions::{QUICK_MENU, QuickAction, QuickActions};
+pub use recorded_files::*;
+pub use retry_queue::{FailedInsert, RetryQueue};
+pub use scan_guard::ScanGuard;
+pub use scan_schedule::{ScanJitter, ScanSchedule};
+pub use shutdown::*;
+pub use status_history::{StatusTransition, merge_histories};
 
-use ratatui::style::Stylize;
 use ratatui::symbols;
 
 use std::cell::RefCell;
 use std::path::PathBuf;
-use std::time::Duration;
-use std::vec;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use chrono::Utc;
+use chrono::{FixedOffset, Utc};
 use ratatui::layout::Alignment;
 use ratatui::text::{Line, Text};
-use ratatui::widgets::{ListState, Paragraph, StatefulWidget, Tabs, Widget};
+use ratatui::widgets::{Gauge, ListState, Paragraph, StatefulWidget, Tabs, Widget};
 use ratatui::{
     buffer::Buffer,
     crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind},
     layout::{Constraint, Direction, Rect},
-    style::{Color, Modifier, Style},
     widgets::{Block, Borders, StatefulWidgetRef, WidgetRef},
 };
 
-use crate::my_widgets::{LogKind, render_input_popup};
-use crate::{DirScannerEventKind, OneEvent};
+use crate::my_widgets::{
+    LogKind, render_detail_popup, render_input_popup,
+    theme::Theme,
+    wrap_list::{WrapList, trim_to_memory_cap},
+};
+use crate::{
+    ConfigWatcher, DirScannerEventKind, ExportPathStyle, OneEvent, OperatorEventKind, SiteConfig,
+    SizeUnits, WrapMode,
+};
 use crate::{
     EventKind, TIME_ZONE,
     apps::AppAction::{self, *},
+    apps::file_sync_manager::{
+        importer::ImportOptions,
+        registry::{FileInfo, LoggedPath, RecordSource},
+    },
     my_widgets::{
         MyWidgets, dichotomize_area_with_midlines,
         menu::{MenuItem, MenuState, SerializableMenuItem},
     },
 };
 
-const TITLE_STYLE: Style = Style::new().fg(Color::Green).add_modifier(Modifier::BOLD);
+const RECORDED_FILES_CAPACITY: usize = 1000;
+const LOG_TABS: [&str; 5] = ["observer", "scanner", "files", "db", "retry"];
+const DETAIL_TITLE: &str = "Detail (Esc to close)";
+/// 关闭时等待后台线程退出的最长时间
+pub const CLOSE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// 等待确认的扫描
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PendingScan {
+    Once,
+    /// 只记入日志，不写数据库
+    DryRun,
+    /// 只扫描当天修改的文件
+    Today,
+    Periodic(Duration, ScanJitter),
+}
 
 #[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
 enum CurrentArea {
     LogArea,
     ControlPanelArea,
@@ -68,28 +119,63 @@ pub struct SyncEngine {
     menu_selected_string: String,
     pub observer: LogObserver,
     pub scanner: DirScanner,
+    pub recorded_files: Arc<Mutex<RecordedFiles>>,
+    pub db_recent: Arc<Mutex<DbRecent>>,
+    /// 观察器和扫描器共用的写入失败队列
+    pub retry_queue: Arc<Mutex<RetryQueue>>,
     log_list_state: RefCell<ListState>,
     log_tabs: usize,
     input_content: String,
     input_title: String,
+    detail_content: Option<String>,
+    detail_title: &'static str,
     current_area: CurrentArea,
+    theme: Theme,
+    record_notes_to_db: bool,
+    /// 日志列表合计的内存上限（字节），0 表示不限制
+    max_log_memory: usize,
+    quick_actions: QuickActions,
+    shutdown_timeouts: ShutdownTimeouts,
+    size_units: SizeUnits,
+    /// 扫描保护要求确认时暂存的扫描
+    pending_scan: Option<PendingScan>,
+    op_limit: OperationLimit,
 }
 
 impl SyncEngine {
     pub fn new(title: String, path: PathBuf, log_size: usize) -> Self {
         let menu_struct = serde_json::from_str(MENU_JSON).unwrap();
+        let recorded_files = Arc::new(Mutex::new(RecordedFiles::new(RECORDED_FILES_CAPACITY)));
+        let retry_queue = Arc::new(Mutex::new(RetryQueue::default()));
         SyncEngine {
             title,
             menu_struct,
             menu_state: RefCell::new(MenuState::default()),
             menu_selected_string: String::new(),
-            observer: LogObserver::new(path, log_size),
-            scanner: DirScanner::new(log_size),
+            observer: LogObserver::new(path, log_size)
+                .with_recorded_files(recorded_files.clone())
+                .with_retry_queue(retry_queue.clone()),
+            scanner: DirScanner::new(log_size)
+                .with_recorded_files(recorded_files.clone())
+                .with_retry_queue(retry_queue.clone()),
+            recorded_files,
+            db_recent: Arc::new(Mutex::new(DbRecent::new())),
+            retry_queue,
             log_list_state: RefCell::new(ListState::default()),
             log_tabs: 0,
             input_content: String::new(),
             input_title: String::new(),
+            detail_content: None,
+            detail_title: DETAIL_TITLE,
+            theme: Theme::detect(),
             current_area: CurrentArea::ControlPanelArea,
+            record_notes_to_db: false,
+            max_log_memory: 0,
+            quick_actions: QuickActions::default(),
+            shutdown_timeouts: ShutdownTimeouts::default(),
+            size_units: SizeUnits::default(),
+            pending_scan: None,
+            op_limit: OperationLimit::default(),
         }
     }
 
@@ -113,12 +199,551 @@ impl SyncEngine {
         result.join("-")
     }
 
+    /// Limit the displayed length of each log event, the full text stays available for export.
+    pub fn with_max_display_chars(self, max_display_chars: usize) -> Self {
+        self.observer
+            .shared_state
+            .lock()
+            .unwrap()
+            .logs
+            .set_max_display_chars(max_display_chars);
+        self.scanner
+            .shared_state
+            .lock()
+            .unwrap()
+            .logs
+            .set_max_display_chars(max_display_chars);
+        self
+    }
+
+    /// Wrap the observer and scanner logs in `wrap_mode`.
+    pub fn with_wrap_mode(self, wrap_mode: WrapMode) -> Self {
+        self.observer
+            .shared_state
+            .lock()
+            .unwrap()
+            .logs
+            .set_wrap_mode(wrap_mode);
+        self.scanner
+            .shared_state
+            .lock()
+            .unwrap()
+            .logs
+            .set_wrap_mode(wrap_mode);
+        self
+    }
+
+    /// Use `theme` for the engine and all of its lists instead of the detected one.
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self.observer
+            .shared_state
+            .lock()
+            .unwrap()
+            .logs
+            .set_theme(theme);
+        self.scanner
+            .shared_state
+            .lock()
+            .unwrap()
+            .logs
+            .set_theme(theme);
+        self.recorded_files.lock().unwrap().set_theme(theme);
+        self.db_recent.lock().unwrap().set_theme(theme);
+        self.retry_queue.lock().unwrap().set_theme(theme);
+        self
+    }
+
+    /// Show file sizes in the scanner log, recorded files and status area in `size_units`.
+    ///
+    /// The observer takes the units from the config when it starts.
+    pub fn with_size_units(mut self, size_units: SizeUnits) -> Self {
+        self.size_units = size_units;
+        self.scanner.set_size_units(size_units);
+        self.recorded_files
+            .lock()
+            .unwrap()
+            .set_size_units(size_units);
+        self.db_recent.lock().unwrap().set_size_units(size_units);
+        self
+    }
+
+    /// Rewrite path separators in `style` when exporting recorded files or the last scan.
+    pub fn with_export_path_style(mut self, style: ExportPathStyle) -> Self {
+        self.scanner.set_export_path_style(style);
+        self.recorded_files
+            .lock()
+            .unwrap()
+            .set_export_path_style(style);
+        self
+    }
+
+    /// Default initial delay and jitter of periodic scans, quick actions may override them.
+    pub fn with_scan_jitter(mut self, scan_jitter: ScanJitter) -> Self {
+        self.scanner.set_scan_jitter(scan_jitter);
+        self
+    }
+
+    /// Ask for confirmation before scanning roots or deny-listed paths, and warn about
+    /// huge trees.
+    pub fn with_scan_guard(mut self, scan_guard: ScanGuard) -> Self {
+        self.scanner.set_scan_guard(scan_guard);
+        self
+    }
+
+    /// Limit concurrent scans and DB writes with `op_limit`, shared by every engine in
+    /// the process.
+    pub fn with_op_limit(mut self, op_limit: OperationLimit) -> Self {
+        self.scanner.set_op_limit(op_limit.clone());
+        self.observer.set_op_limit(op_limit.clone());
+        self.op_limit = op_limit;
+        self
+    }
+
+    /// Leave hidden files out of scans.
+    ///
+    /// The observer takes the setting from the config when it starts.
+    pub fn with_skip_hidden(mut self, skip_hidden: bool) -> Self {
+        self.scanner.set_skip_hidden(skip_hidden);
+        self
+    }
+
+    /// Delete the records of files no longer found by a one-off scan, see
+    /// [`DirScanner::set_reconcile`].
+    pub fn with_scan_reconcile(mut self, reconcile: bool) -> Self {
+        self.scanner.set_reconcile(reconcile);
+        self
+    }
+
+    /// Start "today only" scans at midnight in `time_zone`.
+    pub fn with_today_time_zone(mut self, time_zone: FixedOffset) -> Self {
+        self.scanner.set_today_time_zone(time_zone);
+        self
+    }
+
+    /// Let the observer and scanner follow edits to the config file, see
+    /// [`LogObserver::set_config_watcher`].
+    pub fn with_config_watcher(mut self, watcher: Option<&ConfigWatcher>) -> Self {
+        if let Some(watcher) = watcher {
+            self.observer.set_config_watcher(watcher);
+            self.scanner.set_config(watcher.config());
+        }
+        self
+    }
+
+    /// Watch the subdirectories of the observed path too, see [`LogObserver::set_recursive`].
+    pub fn with_recursive(self, recursive: bool) -> Self {
+        self.observer.set_recursive(recursive);
+        self
+    }
+
+    /// Follow observed paths that are symlinks, see [`LogObserver::set_follow_symlink`].
+    pub fn with_follow_log_symlink(self, follow: bool) -> Self {
+        self.observer.set_follow_symlink(follow);
+        self
+    }
+
+    /// Only log the paths the observer would insert, see [`LogObserver::set_dry_run`].
+    pub fn with_dry_run(self, dry_run: bool) -> Self {
+        self.observer.set_dry_run(dry_run);
+        self
+    }
+
+    /// Also write operator notes to the DB `operator_notes` table.
+    pub fn with_record_notes_to_db(mut self, record_notes_to_db: bool) -> Self {
+        self.record_notes_to_db = record_notes_to_db;
+        self
+    }
+
+    /// Cap the memory used by the observer and scanner logs, 0 disables the cap.
+    pub fn with_max_log_memory_mb(mut self, mb: usize) -> Self {
+        self.max_log_memory = mb * 1024 * 1024;
+        self
+    }
+
+    /// Observe `site`, whose path should also be the one given to `new`.
+    pub fn with_site(mut self, site: SiteConfig) -> Self {
+        self.observer.set_site(site);
+        self
+    }
+
+    /// Save the observer's read positions to `path` and resume from it on start.
+    pub fn with_checkpoint(mut self, path: Option<PathBuf>) -> Self {
+        self.observer.set_checkpoint(path);
+        self
+    }
+
+    /// Limit how long each shutdown phase waits.
+    pub fn with_shutdown_timeouts(mut self, shutdown_timeouts: ShutdownTimeouts) -> Self {
+        self.shutdown_timeouts = shutdown_timeouts;
+        // 手动停止观察器与关闭时的排空阶段等待相同的时间
+        self.observer
+            .set_stop_timeout(shutdown_timeouts.timeout(ShutdownPhase::DrainObserver));
+        self
+    }
+
+    /// Add `quick_actions` to a "quick" submenu of the control panel.
+    pub fn with_quick_actions(mut self, quick_actions: QuickActions) -> Self {
+        self.menu_struct.children.retain(|c| c.name != QUICK_MENU);
+        if !quick_actions.is_empty() {
+            self.menu_struct.children.push(SerializableMenuItem {
+                name: QUICK_MENU.to_string(),
+                content: "Quick actions from config.".to_string(),
+                children: quick_actions
+                    .names()
+                    .map(|name| SerializableMenuItem {
+                        name: name.to_string(),
+                        content: format!("Run quick action {}.", name),
+                        children: Vec::new(),
+                    })
+                    .collect(),
+            });
+        }
+        self.quick_actions = quick_actions;
+        self
+    }
+
+    /// Run the quick action `name` through the same calls as the menu flow it replaces.
+    pub fn run_quick_action(&mut self, name: &str) -> std::io::Result<()> {
+        let Some(action) = self.quick_actions.get(name).cloned() else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Undefined quick action: {}", name),
+            ));
+        };
+        self.scanner.add_logs(OneEvent {
+            time: Some(Utc::now().with_timezone(TIME_ZONE)),
+            kind: EventKind::DirScannerEvent(DirScannerEventKind::Info),
+            content: format!("Running quick action {:?}: {:?}", name, action),
+        });
+
+        match action {
+            QuickAction::MonitorStart => {
+                self.observer
+                    .start_observer()
+                    .map_err(std::io::Error::other)?;
+            }
+            QuickAction::MonitorStop => {
+                self.observer.stop_observer();
+            }
+            QuickAction::ScannerStart { path } => {
+                self.scanner.set_path(path);
+                self.start_scan_guarded(PendingScan::Once)?;
+            }
+            QuickAction::ScannerStartPeriodic {
+                path,
+                interval,
+                initial_delay_secs,
+                jitter_percent,
+            } => {
+                let jitter = self
+                    .scanner
+                    .scan_jitter()
+                    .with_override(initial_delay_secs, jitter_percent);
+                self.scanner.set_path(path);
+                self.start_scan_guarded(PendingScan::Periodic(interval, jitter))?;
+            }
+            QuickAction::ScannerStop => self.scanner.stop_periodic_scan(),
+            QuickAction::ScannerRecord { path } => self.scanner.record_file(path),
+        }
+        Ok(())
+    }
+
+    /// Start `scan`, or hold it and ask for confirmation in the input area if the scan
+    /// guard requires it.
+    fn start_scan_guarded(&mut self, scan: PendingScan) -> std::io::Result<()> {
+        let Some(reason) = self.scanner.guard_check() else {
+            return self.start_scan(scan);
+        };
+        self.pending_scan = Some(scan);
+        self.input_content.clear();
+        self.input_title = format!("{}, type y to scan anyway", reason);
+        self.menu_selected_string = "scanner-confirm".to_string();
+        self.set_current_area(CurrentArea::InputArea);
+        Ok(())
+    }
+
+    fn start_scan(&mut self, scan: PendingScan) -> std::io::Result<()> {
+        match scan {
+            PendingScan::Once => self.scanner.start_scanner(),
+            PendingScan::DryRun => self.scanner.start_scanner_dry_run(),
+            PendingScan::Today => {
+                let path = self.scanner.get_path().to_path_buf();
+                self.scanner.scan_today(path)
+            }
+            PendingScan::Periodic(interval, jitter) => {
+                self.scanner.start_periodic_scan_with(interval, jitter);
+                Ok(())
+            }
+        }
+    }
+
+    /// Why the scan waiting for confirmation needs it, `None` if no scan is waiting.
+    pub fn pending_scan_reason(&self) -> Option<String> {
+        self.pending_scan.and_then(|_| self.scanner.guard_check())
+    }
+
+    /// Start the scan waiting for confirmation if `confirmed`, otherwise drop it.
+    pub fn resolve_pending_scan(&mut self, confirmed: bool) -> std::io::Result<()> {
+        let Some(scan) = self.pending_scan.take() else {
+            return Ok(());
+        };
+        if confirmed {
+            self.scanner.confirm_scan();
+            self.start_scan(scan)
+        } else {
+            self.scanner.cancel_scan();
+            Ok(())
+        }
+    }
+
+    /// Event counts of the observer and scanner logs, e.g. "Errors: 3, Info: 120".
+    pub fn log_counts_summary(&self) -> String {
+        // 分别加锁取出计数，不同时持有两把锁
+        let observer = self
+            .observer
+            .shared_state
+            .lock()
+            .unwrap()
+            .logs
+            .counts_by_kind();
+        let scanner = self
+            .scanner
+            .shared_state
+            .lock()
+            .unwrap()
+            .logs
+            .counts_by_kind();
+        crate::my_widgets::wrap_list::counts_summary(&[observer, scanner])
+    }
+
+    /// Estimated bytes held by the observer and scanner logs.
+    pub fn log_memory_estimate(&self) -> usize {
+        let observer = self
+            .observer
+            .shared_state
+            .lock()
+            .unwrap()
+            .logs
+            .memory_estimate();
+        let scanner = self
+            .scanner
+            .shared_state
+            .lock()
+            .unwrap()
+            .logs
+            .memory_estimate();
+        observer + scanner
+    }
+
+    /// Trim the oldest log entries if the logs exceed `max_log_memory`.
+    pub fn enforce_log_memory_cap(&self) {
+        if self.max_log_memory == 0 {
+            return;
+        }
+        let removed = {
+            let mut observer = self.observer.shared_state.lock().unwrap();
+            let mut scanner = self.scanner.shared_state.lock().unwrap();
+            trim_to_memory_cap(
+                &mut [&mut observer.logs, &mut scanner.logs],
+                self.max_log_memory,
+            )
+        };
+        if removed > 0 {
+            self.observer.add_logs(OneEvent {
+                time: Some(Utc::now().with_timezone(TIME_ZONE)),
+                kind: EventKind::LogObserverEvent(crate::LOE::Info),
+                content: format!(
+                    "Trimmed {} old log entries to stay under {} MB",
+                    removed,
+                    self.max_log_memory / 1024 / 1024
+                ),
+            });
+        }
+    }
+
+    /// Add an operator note to the observer's event log, so it shows up in exports and the disk log.
+    pub fn add_note(&mut self, note: &str) {
+        let note = note.trim().to_string();
+        if note.is_empty() {
+            return;
+        }
+        self.observer.add_logs(OneEvent {
+            time: Some(Utc::now().with_timezone(TIME_ZONE)),
+            kind: EventKind::OperatorEvent(OperatorEventKind::Note),
+            content: note.clone(),
+        });
+
+        if self.record_notes_to_db {
+            let shared_state = self.observer.shared_state.clone();
+            let op_limit = self.op_limit.clone();
+            let config = self.observer.loaded_config();
+            thread::spawn(move || {
+                let rt = tokio::runtime::Runtime::new().unwrap();
+                let _permit = rt.block_on(op_limit.acquire(|| {}));
+                let result = match config {
+                    Ok(config) => rt.block_on(registry::record_note(&note, &config)),
+                    Err(e) => Err(std::io::Error::other(e)),
+                };
+                if let Err(e) = result {
+                    shared_state.lock().unwrap().logs.add_raw_item(OneEvent {
+                        time: Some(Utc::now().with_timezone(TIME_ZONE)),
+                        kind: EventKind::LogObserverEvent(crate::LOE::Error),
+                        content: format!("Failed to record note: {}", e),
+                    });
+                }
+            });
+        }
+    }
+
+    /// Query the most recent DB rows in the background for the db tab.
+    ///
+    /// Does nothing while a query is still running.
+    pub fn refresh_db_recent(&self) {
+        if !self.db_recent.lock().unwrap().start_loading() {
+            return;
+        }
+        let db_recent = self.db_recent.clone();
+        let config = self.observer.loaded_config();
+        thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().unwrap();
+            let result = match config {
+                Ok(config) => rt.block_on(registry::recent_inserts(DB_RECENT_LIMIT, &config)),
+                Err(e) => Err(std::io::Error::other(e)),
+            };
+            db_recent.lock().unwrap().set_result(result);
+        });
+    }
+
+    /// Retry the failed insert at `index` in the background, it leaves the retry tab
+    /// once written.
+    ///
+    /// Does nothing if there is no such entry or it is already being retried.
+    pub fn retry_failed_insert(&self, index: usize) {
+        let config = self.observer.loaded_config();
+        self.retry_failed_insert_with(index, |paths, source| async move {
+            let config = config.map_err(std::io::Error::other)?;
+            registry::update_logged_paths_to_db(paths, source, &config).await
+        });
+    }
+
+    fn retry_failed_insert_with<W, Fut>(&self, index: usize, write: W)
+    where
+        W: FnOnce(Vec<LoggedPath>, Option<RecordSource>) -> Fut + Send + 'static,
+        Fut: Future<Output = std::io::Result<Vec<FileInfo>>>,
+    {
+        let Some(item) = self.retry_queue.lock().unwrap().start_retry(index) else {
+            return;
+        };
+        let retry_queue = self.retry_queue.clone();
+        let recorded_files = self.recorded_files.clone();
+        thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().unwrap();
+            let result = rt
+                .block_on(write(vec![item.path], item.source))
+                .map(|recorded| recorded_files.lock().unwrap().record(&recorded))
+                .map_err(|e| e.to_string());
+            retry_queue.lock().unwrap().finish_retry(item.id, result);
+        });
+    }
+
+    /// Status transitions of the observer and scanner, oldest first.
+    pub fn status_history(&self) -> Vec<StatusTransition> {
+        merge_histories([
+            self.observer.status_history(),
+            self.scanner.status_history(),
+        ])
+    }
+
+    fn open_status_history(&mut self) {
+        let history = self.status_history();
+        let content = if history.is_empty() {
+            "No status changes yet".to_string()
+        } else {
+            history
+                .iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        self.detail_title = "Status history (Esc to close)";
+        self.detail_content = Some(content);
+    }
+
+    fn open_note_input(&mut self) {
+        self.input_title = "Input note".to_string();
+        self.menu_selected_string = "operator-note".to_string();
+        self.set_current_area(CurrentArea::InputArea);
+    }
+
+    /// Stop the observer and scanner threads and wait for them to exit.
+    pub fn shutdown(&mut self) {
+        self.shutdown_with_report();
+    }
+
+    /// Whether the observer or scanner is still processing, callers can poll this
+    /// to wait for quiescence before exiting.
+    pub fn is_busy(&self) -> bool {
+        self.observer.is_busy() || self.scanner.is_busy()
+    }
+
+    /// Shut down in a fixed order so work already received is finished before its thread stops.
+    ///
+    /// Each phase waits at most its configured timeout; the outcome of every phase is
+    /// logged to the observer log unless there was nothing to stop.
+    pub fn shutdown_with_report(&mut self) -> ShutdownReport {
+        let timeouts = self.shutdown_timeouts;
+        let mut report = ShutdownReport::default();
+
+        report.run(ShutdownPhase::StopInput, || {
+            let observer = self.observer.stop_input();
+            let scanner = self.scanner.stop_input();
+            if observer || scanner {
+                PhaseOutcome::Done
+            } else {
+                PhaseOutcome::Skipped
+            }
+        });
+        report.run(ShutdownPhase::DrainObserver, || {
+            self.observer
+                .drain(timeouts.timeout(ShutdownPhase::DrainObserver))
+        });
+        report.run(ShutdownPhase::DrainScanner, || {
+            self.scanner
+                .drain(timeouts.timeout(ShutdownPhase::DrainScanner))
+        });
+        report.run(ShutdownPhase::StopBackground, || {
+            self.observer
+                .stop_supervisor(timeouts.timeout(ShutdownPhase::StopBackground))
+        });
+
+        report.status_history = self.status_history();
+        if !report.is_noop() {
+            for phase in &report.phases {
+                let kind = match phase.outcome {
+                    PhaseOutcome::Done | PhaseOutcome::Skipped => crate::LOE::Info,
+                    _ => crate::LOE::Error,
+                };
+                self.observer.add_logs(OneEvent {
+                    time: Some(Utc::now().with_timezone(TIME_ZONE)),
+                    kind: EventKind::LogObserverEvent(kind),
+                    content: phase.to_string(),
+                });
+            }
+        }
+        report
+    }
+
+    pub fn get_title(&self) -> &str {
+        &self.title
+    }
+
     pub fn toggle_area(&mut self) {
         self.current_area.toggle();
     }
 
     fn toggle_tabs(&mut self) {
-        self.log_tabs = (self.log_tabs + 1) % 2;
+        self.log_tabs = (self.log_tabs + 1) % LOG_TABS.len();
     }
 
     fn clear_input(&mut self) {
@@ -134,74 +759,191 @@ impl SyncEngine {
     pub fn render_control_panel(&self, area: Rect, buf: &mut Buffer, if_highlight: bool) {
         let mut state = self.menu_state.borrow_mut();
 
-        if let Ok(menu_item) = MenuItem::from_json(MENU_JSON) {
-            let block = Block::default()
-                .borders(if if_highlight {
-                    Borders::ALL
-                } else {
-                    Borders::NONE
-                })
-                .title("Control Panel")
-                .title_style(TITLE_STYLE)
-                .title_alignment(Alignment::Center);
-
-            menu_item.borrow_mut().set_block(block);
-            StatefulWidgetRef::render_ref(&*menu_item.borrow(), area, buf, &mut *state);
-        }
+        let menu_item = MenuItem::from_item(self.menu_struct.clone());
+        let block = Block::default()
+            .borders(if if_highlight {
+                Borders::ALL
+            } else {
+                Borders::NONE
+            })
+            .title(if self.observer.is_dry_run() {
+                "Control Panel [DRY RUN]"
+            } else {
+                "Control Panel"
+            })
+            .title_style(self.theme.title())
+            .title_alignment(Alignment::Center);
+
+        menu_item.borrow_mut().set_block(block);
+        menu_item.borrow_mut().set_theme(self.theme);
+        StatefulWidgetRef::render_ref(&*menu_item.borrow(), area, buf, &mut *state);
     }
 
     pub fn render_status_area(&self, area: Rect, buf: &mut Buffer) {
         let block = Block::default()
             .borders(Borders::NONE)
-            .title("Status Area")
-            .title_style(TITLE_STYLE)
+            .title("Status Area (h: history)")
+            .title_style(self.theme.title())
             .title_alignment(Alignment::Center);
 
-        let status = Line::from(format!("Status: {:?}", self.observer.get_status()));
+        // 只加锁一次，避免渲染时与观察器线程反复争用
+        let snapshot = self.observer.snapshot();
+
+        let status = Line::from(format!(
+            "Status: {:?}{}",
+            snapshot.status,
+            if snapshot.stalled { " (stalled)" } else { "" }
+        ));
 
-        let lunch_time = Line::from(format!("Lunch time: {}", self.observer.get_lunch_time()));
+        let lunch_time = Line::from(format!("Lunch time: {}", snapshot.lunch_time_str()));
 
-        let elapsed_time = Line::from(format!(
-            "Elapsed time: {}",
-            self.observer.get_elapsed_time()
+        let elapsed_time = Line::from(format!("Elapsed time: {}", snapshot.elapsed_time_str()));
+
+        let files_got = Line::from(format!("Files got: {}", snapshot.files_got));
+        // 观察多个目录时分别列出
+        let files_got_by_root: Vec<Line> = if snapshot.files_got_by_root.len() > 1 {
+            snapshot
+                .files_got_by_root
+                .iter()
+                .map(|(root, got)| Line::from(format!("  {}: {}", root.display(), got)))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let bytes_read = Line::from(format!(
+            "Bytes read: {}",
+            self.size_units.format(snapshot.bytes_read)
         ));
 
-        let files_got = Line::from(format!("Files got: {}", self.observer.files_got()));
+        let file_reading = Line::from(format!("File reading: {}", snapshot.file_reading.display()));
 
-        let file_reading = Line::from(format!(
-            "File reading: {}",
-            self.observer.file_reading().display()
+        let watch_mode = Line::from(format!(
+            "Watch mode: {}, {}",
+            snapshot.watch_mode_str(),
+            if snapshot.recursive {
+                "recursive"
+            } else {
+                "non-recursive"
+            }
         ));
 
-        let scanner_status = Line::from(format!("Scanner status: {:?}", self.scanner.get_status()));
+        let path_mapping = Line::from(format!(
+            "Path mapping: {:?} (rewritten {}, raw {})",
+            snapshot.path_mapping, snapshot.paths_rewritten, snapshot.paths_raw
+        ));
 
-        let files_recorded = Line::from(format!(
-            "Files recorded: {:?}",
-            self.observer.files_recorded()
+        let scanner_status = Line::from(format!(
+            "Scanner status: {:?}{}",
+            self.scanner.get_status(),
+            match self.scanner.next_scan() {
+                Some(next) => format!(", next scan {}", next.format("%H:%M:%S")),
+                None => String::new(),
+            }
         ));
 
-        let text = Text::from(vec![
-            status,
-            lunch_time,
-            elapsed_time,
-            files_got,
+        let files_recorded = Line::from(format!("Files recorded: {:?}", snapshot.files_recorded));
+
+        let log_memory = Line::from(format!(
+            "Log memory: {}",
+            self.size_units.format(self.log_memory_estimate() as u64)
+        ));
+
+        let log_counts = Line::from(format!("Log counts: {}", self.log_counts_summary()));
+
+        let mut lines = Vec::new();
+        if snapshot.dry_run {
+            lines.push(Line::styled(
+                format!(
+                    "DRY RUN: not writing to DB ({} would-be inserts)",
+                    snapshot.dry_run_paths
+                ),
+                self.theme.fg(ratatui::style::Color::Yellow),
+            ));
+        }
+        lines.extend([status, lunch_time, elapsed_time, files_got]);
+        lines.extend(files_got_by_root);
+        lines.extend([
+            bytes_read,
             files_recorded,
             file_reading,
+            watch_mode,
+            path_mapping,
             scanner_status,
+            log_memory,
+            log_counts,
         ]);
 
+        // 标题加完整状态放不下时改用一两行的紧凑形式
+        if (area.height as usize) <= lines.len() {
+            let text = Text::from(self.compact_status_lines(&snapshot, area.height));
+            Paragraph::new(text).render_ref(area, buf);
+            return;
+        }
+        let line_count = lines.len();
+        let text = Text::from(lines);
+
         Paragraph::new(text).block(block).render_ref(area, buf);
+
+        // 单次扫描时在状态下方显示写入进度
+        if self.scanner.get_status() == crate::ProgressStatus::Running(crate::Running::Once)
+            && (area.height as usize) > line_count + 1
+        {
+            let progress = self.scanner.get_scan_progress().clamp(0.0, 1.0);
+            let gauge_area = Rect {
+                y: area.y + area.height - 1,
+                height: 1,
+                ..area
+            };
+            Gauge::default()
+                .gauge_style(self.theme.fg(ratatui::style::Color::Cyan))
+                .ratio(progress as f64)
+                .label(format!("Scan {:.0}%", progress * 100.0))
+                .render(gauge_area, buf);
+        }
+    }
+
+    /// Status collapsed to one line, plus a second one when `height` allows.
+    fn compact_status_lines(&self, snapshot: &ObStatusSnapshot, height: u16) -> Vec<Line<'static>> {
+        let mut first = format!(
+            "OBS:{:?}{} {} files | SCAN:{:?} | {}",
+            snapshot.status,
+            if snapshot.stalled { "(stalled)" } else { "" },
+            snapshot.files_got,
+            self.scanner.get_status(),
+            snapshot.elapsed_hours_str()
+        );
+        if snapshot.recursive {
+            first.push_str(" | RECURSIVE");
+        }
+        if snapshot.dry_run {
+            first.push_str(" | DRY RUN");
+        }
+        let mut lines = vec![Line::from(first)];
+        if height >= 2 {
+            lines.push(Line::from(format!(
+                "Recorded {} | Read {} | Log {}",
+                snapshot.files_recorded,
+                self.size_units.format(snapshot.bytes_read),
+                self.size_units.format(self.log_memory_estimate() as u64)
+            )));
+        }
+        lines
     }
 
     pub fn render_log_area(&self, area: Rect, buf: &mut Buffer, if_highlight: bool) {
+        let title = match self.current_logs_follow() {
+            Some((false, unseen)) => format!("Log Area (paused, {} new above)", unseen),
+            _ => "Log Area".to_string(),
+        };
         let block = Block::default()
             .borders(if if_highlight {
                 Borders::ALL
             } else {
                 Borders::NONE
             })
-            .title("Log Area")
-            .title_style(TITLE_STYLE)
+            .title(title)
+            .title_style(self.theme.title())
             .title_alignment(Alignment::Center);
         block.render_ref(area, buf);
 
@@ -212,9 +954,9 @@ impl SyncEngine {
             height: 1,
         };
 
-        Tabs::new(vec!["observer", "scanner"])
-            .style(Style::default().white())
-            .highlight_style(Style::default().green().bg(Color::Yellow))
+        Tabs::new(LOG_TABS.to_vec())
+            .style(self.theme.text())
+            .highlight_style(self.theme.tab_highlight())
             .select(self.log_tabs)
             .divider(symbols::DOT)
             .render(tabs_area, buf);
@@ -229,18 +971,115 @@ impl SyncEngine {
         self.render_logs(log_area, buf);
     }
 
+    /// Follow state and unseen count of the log list in the current tab.
+    fn current_logs_follow(&self) -> Option<(bool, usize)> {
+        let follow = |logs: &WrapList| (logs.is_follow(), logs.unseen_count());
+        match self.log_tabs {
+            0 => Some(follow(&self.observer.shared_state.lock().unwrap().logs)),
+            1 => Some(follow(&self.scanner.shared_state.lock().unwrap().logs)),
+            _ => None,
+        }
+    }
+
+    /// Full text of the selected event in the current tab.
+    fn selected_log_text(&self) -> Option<String> {
+        let index = self.log_list_state.borrow().selected()?;
+        match self.log_tabs {
+            0 => self.observer.get_logs_str().get(index).cloned(),
+            1 => self.scanner.get_logs_str().get(index).cloned(),
+            2 => self
+                .recorded_files
+                .lock()
+                .unwrap()
+                .get_list_string()
+                .get(index)
+                .cloned(),
+            3 => self
+                .db_recent
+                .lock()
+                .unwrap()
+                .get_list_string()
+                .get(index)
+                .cloned(),
+            _ => self
+                .retry_queue
+                .lock()
+                .unwrap()
+                .get_list_string()
+                .get(index)
+                .cloned(),
+        }
+    }
+
+    fn toggle_logs_follow(&mut self) {
+        match self.log_tabs {
+            0 => self
+                .observer
+                .shared_state
+                .lock()
+                .unwrap()
+                .logs
+                .toggle_follow(),
+            1 => self
+                .scanner
+                .shared_state
+                .lock()
+                .unwrap()
+                .logs
+                .toggle_follow(),
+            _ => {}
+        }
+    }
+
     pub fn render_logs(&self, area: Rect, buf: &mut Buffer) {
+        let mut state = self.log_list_state.borrow_mut();
         // 不应clone，会导致wrap_len状态无法保存到实例
-        let list = if self.log_tabs == 0 {
-            &mut self.observer.shared_state.lock().unwrap().logs
-        } else {
-            &mut self.scanner.shared_state.lock().unwrap().logs
-        };
+        match self.log_tabs {
+            0 => StatefulWidget::render(
+                &mut self.observer.shared_state.lock().unwrap().logs,
+                area,
+                buf,
+                &mut *state,
+            ),
+            1 => StatefulWidget::render(
+                &mut self.scanner.shared_state.lock().unwrap().logs,
+                area,
+                buf,
+                &mut *state,
+            ),
+            2 => StatefulWidget::render(
+                &*self.recorded_files.lock().unwrap(),
+                area,
+                buf,
+                &mut *state,
+            ),
+            3 => StatefulWidget::render(&*self.db_recent.lock().unwrap(), area, buf, &mut *state),
+            _ => StatefulWidget::render(&*self.retry_queue.lock().unwrap(), area, buf, &mut *state),
+        }
+    }
+}
 
-        StatefulWidget::render(list, area, buf, &mut *self.log_list_state.borrow_mut());
+impl Drop for SyncEngine {
+    fn drop(&mut self) {
+        self.shutdown();
     }
 }
 
+/// 在超时时间内等待线程结束，超时则返回 `None` 并放弃该线程
+pub fn join_with_timeout<T>(
+    handle: thread::JoinHandle<T>,
+    timeout: Duration,
+) -> Option<thread::Result<T>> {
+    let deadline = Instant::now() + timeout;
+    while !handle.is_finished() {
+        if Instant::now() >= deadline {
+            return None;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    Some(handle.join())
+}
+
 impl WidgetRef for SyncEngine {
     fn render_ref(&self, area: Rect, buf: &mut Buffer) {
         let (left_area, _midline, right_area) = dichotomize_area_with_midlines(
@@ -270,6 +1109,10 @@ impl WidgetRef for SyncEngine {
         if self.current_area == CurrentArea::InputArea {
             render_input_popup(&self.input_content, area, buf, &self.input_title);
         }
+
+        if let Some(content) = &self.detail_content {
+            render_detail_popup(content, area, buf, self.detail_title);
+        }
     }
 }
 
@@ -277,33 +1120,115 @@ impl MyWidgets for SyncEngine {
     fn handle_event(&mut self, event: Event) -> Result<AppAction, std::io::Error> {
         // if in menu area
         match self.current_area {
+            _ if self.detail_content.is_some() => {
+                if let Event::Key(KeyEvent {
+                    code: KeyCode::Esc | KeyCode::Enter,
+                    kind: KeyEventKind::Press,
+                    ..
+                }) = event
+                {
+                    self.detail_content = None;
+                }
+            }
             CurrentArea::ControlPanelArea => match event {
                 Event::Key(KeyEvent {
                     code: KeyCode::Enter,
                     kind: KeyEventKind::Press,
                     ..
-                }) => {
-                    if !self.menu_state.borrow().selected_indices.is_empty() {
-                        match self.get_menu_result().as_str() {
-                            "monitor-start" => {
-                                self.observer.start_observer().unwrap();
-                            }
-                            "monitor-stop" => {
-                                self.observer.stop_observer();
-                            }
-                            "scanner-start" => {
-                                self.input_title = "Input path".to_string();
-                                self.menu_selected_string = "scanner-start".to_string();
-                                self.set_current_area(CurrentArea::InputArea);
-                            }
-                            "scanner-start-periodic" => {
-                                self.input_title = "Input path and interval".to_string();
-                                self.menu_selected_string = "scanner-start-periodic".to_string();
-                                self.set_current_area(CurrentArea::InputArea);
+                }) if !self.menu_state.borrow().selected_indices.is_empty() => {
+                    match self.get_menu_result().as_str() {
+                        "monitor-start" => {
+                            self.observer.start_observer().unwrap();
+                        }
+                        "monitor-stop" => {
+                            self.observer.stop_observer();
+                        }
+                        "monitor-dry-run" => {
+                            self.observer.set_dry_run(!self.observer.is_dry_run());
+                        }
+                        "monitor-recursive" => {
+                            self.observer.set_recursive(!self.observer.is_recursive());
+                        }
+                        "monitor-reload-config" => {
+                            // 结果已记入观察器日志
+                            let _ = self.observer.reload_config();
+                        }
+                        "monitor-reset-offset" => {
+                            self.input_title = "Input watched log path".to_string();
+                            self.menu_selected_string = "monitor-reset-offset".to_string();
+                            self.set_current_area(CurrentArea::InputArea);
+                        }
+                        "scanner-start" => {
+                            self.input_title = "Input path".to_string();
+                            self.menu_selected_string = "scanner-start".to_string();
+                            self.set_current_area(CurrentArea::InputArea);
+                        }
+                        "scanner-dry-run" => {
+                            self.input_title = "Input path to dry run".to_string();
+                            self.menu_selected_string = "scanner-dry-run".to_string();
+                            self.set_current_area(CurrentArea::InputArea);
+                        }
+                        "scanner-today" => {
+                            self.input_title = "Input path to scan for today's files".to_string();
+                            self.menu_selected_string = "scanner-today".to_string();
+                            self.set_current_area(CurrentArea::InputArea);
+                        }
+                        "scanner-record" => {
+                            self.input_title = "Input file path".to_string();
+                            self.menu_selected_string = "scanner-record".to_string();
+                            self.set_current_area(CurrentArea::InputArea);
+                        }
+                        "scanner-import" => {
+                            self.input_title = "Input path list file".to_string();
+                            self.menu_selected_string = "scanner-import".to_string();
+                            self.set_current_area(CurrentArea::InputArea);
+                        }
+                        "scanner-export" => {
+                            self.input_title = "Input CSV path for the last scan".to_string();
+                            self.menu_selected_string = "scanner-export".to_string();
+                            self.set_current_area(CurrentArea::InputArea);
+                        }
+                        "scanner-start-periodic" => {
+                            self.input_title = "Input path and interval".to_string();
+                            self.menu_selected_string = "scanner-start-periodic".to_string();
+                            self.set_current_area(CurrentArea::InputArea);
+                        }
+                        "operator-note" => {
+                            self.open_note_input();
+                        }
+                        "operator-export-csv" => {
+                            self.input_title = "Input CSV path".to_string();
+                            self.menu_selected_string = "operator-export-csv".to_string();
+                            self.set_current_area(CurrentArea::InputArea);
+                        }
+                        result => {
+                            if let Some(name) = result
+                                .strip_prefix(QUICK_MENU)
+                                .and_then(|r| r.strip_prefix('-'))
+                                && let Err(e) = self.run_quick_action(name)
+                            {
+                                self.scanner.add_logs(OneEvent {
+                                    time: Some(Utc::now().with_timezone(TIME_ZONE)),
+                                    kind: EventKind::DirScannerEvent(DirScannerEventKind::Error),
+                                    content: format!("Quick action failed: {}", e),
+                                });
                             }
-                            _ => {}
-                        };
-                    }
+                        }
+                    };
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('n'),
+                    kind: KeyEventKind::Press,
+                    ..
+                }) => {
+                    self.open_note_input();
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('h'),
+                    kind: KeyEventKind::Press,
+                    ..
+                }) => {
+                    self.open_status_history();
                 }
                 Event::Key(KeyEvent {
                     code: KeyCode::Up,
@@ -357,6 +1282,10 @@ impl MyWidgets for SyncEngine {
                 }) = event
                 {
                     match code {
+                        KeyCode::Enter => {
+                            self.detail_title = DETAIL_TITLE;
+                            self.detail_content = self.selected_log_text();
+                        }
                         KeyCode::Left | KeyCode::Right => {
                             self.toggle_tabs();
                         }
@@ -366,6 +1295,42 @@ impl MyWidgets for SyncEngine {
                         KeyCode::Down => {
                             self.log_list_state.borrow_mut().scroll_down_by(1);
                         }
+                        KeyCode::Char('f') => {
+                            self.toggle_logs_follow();
+                        }
+                        KeyCode::Char('n') => {
+                            self.open_note_input();
+                        }
+                        KeyCode::Char('h') => {
+                            self.open_status_history();
+                        }
+                        // 只在手动刷新时查询数据库
+                        KeyCode::Char('r') if self.log_tabs == 3 => {
+                            self.refresh_db_recent();
+                        }
+                        KeyCode::Char('r') if self.log_tabs == 4 => {
+                            if let Some(index) = self.log_list_state.borrow().selected() {
+                                self.retry_failed_insert(index);
+                            }
+                        }
+                        KeyCode::Char('d') if self.log_tabs == 4 => {
+                            let selected = self.log_list_state.borrow().selected();
+                            if let Some(item) =
+                                selected.and_then(|i| self.retry_queue.lock().unwrap().discard(i))
+                            {
+                                let msg = format!("Discarded failed insert {}", item.path_str());
+                                self.scanner.add_logs(OneEvent {
+                                    kind: EventKind::DirScannerEvent(DirScannerEventKind::Info),
+                                    content: msg,
+                                    time: Some(Utc::now().with_timezone(TIME_ZONE)),
+                                });
+                            }
+                        }
+                        KeyCode::Char('/') if self.log_tabs == 2 => {
+                            self.input_title = "Filter files by path".to_string();
+                            self.menu_selected_string = "files-filter".to_string();
+                            self.set_current_area(CurrentArea::InputArea);
+                        }
                         KeyCode::Esc => {
                             return Ok(ToggleMenu);
                         }
@@ -402,10 +1367,26 @@ impl MyWidgets for SyncEngine {
                     "scanner-start" => {
                         self.scanner
                             .set_path(PathBuf::from(self.input_content.clone()));
-                        self.scanner.start_scanner()?;
 
                         self.clear_input();
                         self.set_current_area(CurrentArea::ControlPanelArea);
+                        self.start_scan_guarded(PendingScan::Once)?;
+                    }
+                    "scanner-dry-run" => {
+                        self.scanner
+                            .set_path(PathBuf::from(self.input_content.clone()));
+
+                        self.clear_input();
+                        self.set_current_area(CurrentArea::ControlPanelArea);
+                        self.start_scan_guarded(PendingScan::DryRun)?;
+                    }
+                    "scanner-today" => {
+                        self.scanner
+                            .set_path(PathBuf::from(self.input_content.clone()));
+
+                        self.clear_input();
+                        self.set_current_area(CurrentArea::ControlPanelArea);
+                        self.start_scan_guarded(PendingScan::Today)?;
                     }
                     "scanner-start-periodic" => {
                         self.scanner
@@ -417,10 +1398,16 @@ impl MyWidgets for SyncEngine {
                         self.set_current_area(CurrentArea::InputArea);
                     }
                     "scanner-start-periodic-with-delay" => {
-                        match self.input_content.trim().parse::<u64>() {
+                        let interval = self.input_content.trim().parse::<u64>();
+                        self.clear_input();
+                        self.set_current_area(CurrentArea::ControlPanelArea);
+                        match interval {
                             Ok(val) => {
-                                self.scanner
-                                    .start_periodic_scan(Duration::from_secs(val * 60));
+                                let jitter = self.scanner.scan_jitter();
+                                self.start_scan_guarded(PendingScan::Periodic(
+                                    Duration::from_secs(val * 60),
+                                    jitter,
+                                ))?;
                             }
                             Err(_) => {
                                 self.scanner.add_logs(OneEvent {
@@ -430,13 +1417,100 @@ impl MyWidgets for SyncEngine {
                                 });
                             }
                         };
+                    }
+                    "scanner-confirm" => {
+                        let confirmed = matches!(
+                            self.input_content.trim().to_lowercase().as_str(),
+                            "y" | "yes"
+                        );
                         self.clear_input();
                         self.set_current_area(CurrentArea::ControlPanelArea);
+                        self.resolve_pending_scan(confirmed)?;
                     }
                     "scanner-stop" => {
                         self.scanner.stop_periodic_scan();
                         self.set_current_area(CurrentArea::ControlPanelArea);
                     }
+                    "scanner-record" => {
+                        self.scanner
+                            .record_file(PathBuf::from(self.input_content.trim()));
+                        self.clear_input();
+                        self.set_current_area(CurrentArea::ControlPanelArea);
+                    }
+                    "monitor-reset-offset" => {
+                        // 失败时已记入观察器日志
+                        let path = PathBuf::from(self.input_content.trim().trim_matches('"'));
+                        let _ = self.observer.reset_offset(&path);
+                        self.clear_input();
+                        self.set_current_area(CurrentArea::ControlPanelArea);
+                    }
+                    "scanner-import" => {
+                        let list = PathBuf::from(self.input_content.trim().trim_matches('"'));
+                        let options = crate::try_load_config()
+                            .map_err(|e| e.to_string())
+                            .and_then(|c| {
+                                ImportOptions::from_config(&c.file_sync_manager)
+                                    .map_err(|e| e.to_string())
+                            });
+                        match options {
+                            Ok(options) => self.scanner.import_list(list, options),
+                            Err(e) => self.scanner.add_logs(OneEvent {
+                                time: Some(Utc::now().with_timezone(TIME_ZONE)),
+                                kind: EventKind::DirScannerEvent(DirScannerEventKind::Error),
+                                content: format!("Failed to import {}: {}", list.display(), e),
+                            }),
+                        }
+                        self.clear_input();
+                        self.set_current_area(CurrentArea::ControlPanelArea);
+                    }
+                    "operator-note" => {
+                        let note = self.input_content.clone();
+                        self.add_note(&note);
+                        self.clear_input();
+                        self.set_current_area(CurrentArea::ControlPanelArea);
+                    }
+                    "scanner-export" => {
+                        // 成功时已记入扫描器日志
+                        let path = PathBuf::from(self.input_content.trim().trim_matches('"'));
+                        if let Err(e) = self.scanner.export_last_scan_to_csv(&path) {
+                            self.scanner.add_logs(OneEvent {
+                                time: Some(Utc::now().with_timezone(TIME_ZONE)),
+                                kind: EventKind::DirScannerEvent(DirScannerEventKind::Error),
+                                content: format!("Failed to export {}: {}", path.display(), e),
+                            });
+                        }
+                        self.clear_input();
+                        self.set_current_area(CurrentArea::ControlPanelArea);
+                    }
+                    "operator-export-csv" => {
+                        let path = PathBuf::from(self.input_content.trim().trim_matches('"'));
+                        let result = self.recorded_files.lock().unwrap().export_csv(&path);
+                        let (kind, content) = match result {
+                            Ok(rows) => (
+                                DirScannerEventKind::Info,
+                                format!("Exported {} recorded files to {}", rows, path.display()),
+                            ),
+                            Err(e) => (
+                                DirScannerEventKind::Error,
+                                format!("Failed to export {}: {}", path.display(), e),
+                            ),
+                        };
+                        self.scanner.add_logs(OneEvent {
+                            time: Some(Utc::now().with_timezone(TIME_ZONE)),
+                            kind: EventKind::DirScannerEvent(kind),
+                            content,
+                        });
+                        self.clear_input();
+                        self.set_current_area(CurrentArea::ControlPanelArea);
+                    }
+                    "files-filter" => {
+                        self.recorded_files
+                            .lock()
+                            .unwrap()
+                            .set_filter(self.input_content.trim().to_string());
+                        self.clear_input();
+                        self.set_current_area(CurrentArea::LogArea);
+                    }
                     _ => {}
                 },
                 Event::Key(KeyEvent {
@@ -444,11 +1518,14 @@ impl MyWidgets for SyncEngine {
                     kind: KeyEventKind::Press,
                     ..
                 }) => {
+                    if self.menu_selected_string == "scanner-confirm" {
+                        self.clear_input();
+                        self.resolve_pending_scan(false)?;
+                    }
                     self.set_current_area(CurrentArea::ControlPanelArea);
                 }
                 _ => {}
             },
-            _ => {}
         }
 
         Ok(Default)
@@ -463,6 +1540,427 @@ impl MyWidgets for SyncEngine {
             }
             LogKind::Observer => self.observer.get_logs_str(),
             LogKind::Scanner => self.scanner.get_logs_str(),
+            LogKind::RecordedFiles => self.recorded_files.lock().unwrap().get_list_string(),
         }
     }
+
+    fn memory_estimate(&self) -> usize {
+        self.log_memory_estimate()
+    }
+
+    fn busy_summary(&self) -> Option<String> {
+        let mut running = Vec::new();
+        if self.observer.is_busy() {
+            running.push(format!("observer {:?}", self.observer.get_status()));
+        }
+        if self.scanner.is_busy() {
+            running.push(format!("scanner {:?}", self.scanner.get_status()));
+        }
+        (!running.is_empty()).then(|| running.join(", "))
+    }
+
+    fn shutdown_report(&mut self) -> Option<ShutdownReport> {
+        Some(self.shutdown_with_report())
+    }
+
+    fn tick(&mut self) {
+        self.enforce_log_memory_cap();
+    }
+}
+
+// MARK: test
+#[cfg(target_os = "linux")]
+#[test]
+fn test_drop_engine_joins_threads() {
+    fn thread_count() -> usize {
+        std::fs::read_to_string("/proc/self/status")
+            .unwrap()
+            .lines()
+            .find_map(|l| l.strip_prefix("Threads:"))
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap()
+    }
+
+    let dir = std::env::temp_dir().join("test_drop_engine_joins_threads");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut baseline = None;
+    for _ in 0..50 {
+        let mut engine = SyncEngine::new("test".to_string(), dir.clone(), 10);
+        engine.observer.start_observer().unwrap();
+        drop(engine);
+        baseline.get_or_insert_with(thread_count);
+    }
+
+    // 其他测试并行运行时线程数会有波动，留出余量；泄漏时每轮至少多一个线程
+    assert!(thread_count() < baseline.unwrap() + 20);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_operator_note_in_export() {
+    let note = "DB maintenance 02:00–02:30, errors expected";
+    let mut engine = SyncEngine::new("test".to_string(), PathBuf::from("."), 10);
+    engine.add_note(&format!("  {}  ", note));
+    // 空备注不记录
+    engine.add_note("   ");
+
+    let exported = engine.get_logs_str(LogKind::All);
+    let notes: Vec<&String> = exported
+        .iter()
+        .filter(|l| l.starts_with("[OPERATOR][NOTE]"))
+        .collect();
+    assert_eq!(notes.len(), 1);
+    assert!(notes[0].ends_with(note));
+    assert_eq!(engine.get_logs_str(LogKind::Observer), exported);
+}
+
+#[test]
+fn test_quick_action_starts_periodic_scan() {
+    let key = |code| Event::Key(KeyEvent::from(code));
+    let scan_dir = std::env::temp_dir().join("test_quick_action_scan");
+    std::fs::create_dir_all(&scan_dir).unwrap();
+
+    let quick_actions = QuickActions::from_config(&[crate::QuickActionConfig {
+        name: "Nightly CTA scan".to_string(),
+        action: "scanner-start-periodic".to_string(),
+        path: Some(scan_dir.display().to_string()),
+        interval: Some("12h".to_string()),
+        initial_delay_secs: None,
+        jitter_percent: None,
+    }])
+    .unwrap();
+    let mut engine = SyncEngine::new("test".to_string(), PathBuf::from("."), 20)
+        .with_quick_actions(quick_actions);
+
+    // 控制面板中选择 quick → Nightly CTA scan
+    engine.handle_event(key(KeyCode::Right)).unwrap();
+    for _ in 0..3 {
+        engine.handle_event(key(KeyCode::Down)).unwrap();
+    }
+    engine.handle_event(key(KeyCode::Right)).unwrap();
+    assert_eq!(engine.get_menu_result(), "quick-Nightly CTA scan");
+    engine.handle_event(key(KeyCode::Enter)).unwrap();
+
+    assert_eq!(engine.scanner.get_path(), scan_dir.as_path());
+    assert_eq!(
+        engine.scanner.get_status(),
+        crate::ProgressStatus::Running(crate::Running::Periodic)
+    );
+    let logs = engine.get_logs_str(LogKind::Scanner);
+    assert!(logs.iter().any(|l| l.ends_with(&format!(
+        "Periodic scanner started on {}, interval 43200s",
+        scan_dir.display()
+    ))));
+
+    let err = engine.run_quick_action("Undefined").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+
+    engine.shutdown();
+    std::fs::remove_dir_all(&scan_dir).unwrap();
+}
+
+#[test]
+fn test_guarded_scan_waits_for_confirmation() {
+    let key = |code| Event::Key(KeyEvent::from(code));
+    let scan_dir = std::env::temp_dir().join("test_guarded_scan");
+    std::fs::create_dir_all(&scan_dir).unwrap();
+
+    let quick_actions = QuickActions::from_config(&[crate::QuickActionConfig {
+        name: "Scan denied".to_string(),
+        action: "scanner-start-periodic".to_string(),
+        path: Some(scan_dir.display().to_string()),
+        interval: Some("12h".to_string()),
+        initial_delay_secs: None,
+        jitter_percent: None,
+    }])
+    .unwrap();
+    let mut engine = SyncEngine::new("test".to_string(), PathBuf::from("."), 20)
+        .with_scan_guard(ScanGuard {
+            deny_list: vec![scan_dir.display().to_string()],
+            ..ScanGuard::default()
+        })
+        .with_quick_actions(quick_actions);
+
+    // 拒绝列表中的路径先询问，输入 n 取消
+    engine.run_quick_action("Scan denied").unwrap();
+    assert!(
+        engine
+            .pending_scan_reason()
+            .unwrap()
+            .contains("scan deny list")
+    );
+    assert_eq!(engine.current_area, CurrentArea::InputArea);
+    assert_eq!(engine.scanner.get_status(), crate::ProgressStatus::Stopped);
+    engine.handle_event(key(KeyCode::Char('n'))).unwrap();
+    engine.handle_event(key(KeyCode::Enter)).unwrap();
+    assert_eq!(engine.pending_scan_reason(), None);
+    assert_eq!(engine.scanner.get_status(), crate::ProgressStatus::Stopped);
+    let logs = engine.get_logs_str(LogKind::Scanner);
+    assert!(logs.iter().any(|l| l.ends_with("cancelled")));
+
+    // 输入 y 后开始扫描
+    engine.run_quick_action("Scan denied").unwrap();
+    engine.handle_event(key(KeyCode::Char('y'))).unwrap();
+    engine.handle_event(key(KeyCode::Enter)).unwrap();
+    assert_eq!(engine.current_area, CurrentArea::ControlPanelArea);
+    assert_eq!(
+        engine.scanner.get_status(),
+        crate::ProgressStatus::Running(crate::Running::Periodic)
+    );
+    let logs = engine.get_logs_str(LogKind::Scanner);
+    assert!(logs.iter().any(|l| l.contains("Scan confirmed:")));
+
+    engine.shutdown();
+    std::fs::remove_dir_all(&scan_dir).unwrap();
+}
+
+#[test]
+fn test_shutdown_drains_received_events() {
+    let dir = std::env::temp_dir().join("test_shutdown_drains_received_events");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut engine = SyncEngine::new("test".to_string(), dir.clone(), 200);
+    engine.observer.start_observer().unwrap();
+    // 等待 watcher 就绪
+    thread::sleep(Duration::from_millis(300));
+
+    // 不含 STOR 行，不会写数据库，只验证每个文件都读到末尾
+    let files: Vec<PathBuf> = (0..20)
+        .map(|i| dir.join(format!("u_ex2506{:02}.log", i)))
+        .collect();
+    for (i, file) in files.iter().enumerate() {
+        std::fs::write(file, "#Fields: date time\n".repeat(i + 1)).unwrap();
+    }
+    // 事件已进入通道，但观察器还未全部处理
+    thread::sleep(Duration::from_millis(50));
+
+    let report = engine.shutdown_with_report();
+    assert!(report.is_clean(), "{}", report);
+    assert_eq!(
+        report.phases.iter().map(|p| p.phase).collect::<Vec<_>>(),
+        ShutdownPhase::ALL
+    );
+    assert_eq!(
+        report.outcome(ShutdownPhase::DrainObserver),
+        Some(&PhaseOutcome::Done)
+    );
+    assert_eq!(
+        report.outcome(ShutdownPhase::DrainScanner),
+        Some(&PhaseOutcome::Skipped)
+    );
+
+    let ss = engine.observer.shared_state.lock().unwrap();
+    for file in &files {
+        let size = std::fs::metadata(file).unwrap().len();
+        assert_eq!(ss.last_read_pos(file), Some(size), "{}", file.display());
+    }
+    assert_eq!(ss.status, crate::ProgressStatus::Stopped);
+    drop(ss);
+
+    let logs = engine.get_logs_str(LogKind::Observer);
+    assert!(logs.iter().any(|l| l.contains("Observer drained")));
+    assert!(logs.iter().any(|l| l.ends_with(&format!(
+        "Shutdown phase drain observer: done in {:?}",
+        report.phases[1].elapsed
+    ))));
+
+    // 再次关闭时没有需要停止的工作
+    assert!(engine.shutdown_with_report().is_noop());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_is_busy_while_scanning() {
+    let dir = std::env::temp_dir().join("test_is_busy_while_scanning");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    // 文件多一些，使扫描不会在检查前就结束
+    for i in 0..1000 {
+        std::fs::write(dir.join(format!("{}.csv", i)), "x").unwrap();
+    }
+
+    let mut engine = SyncEngine::new("test".to_string(), PathBuf::from("."), 20);
+    assert!(!engine.is_busy());
+
+    engine.scanner.set_path(dir.clone());
+    engine.scanner.start_scanner().unwrap();
+    assert!(engine.is_busy());
+
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while engine.is_busy() {
+        assert!(Instant::now() < deadline, "scan didn't finish");
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(engine.scanner.get_status(), crate::ProgressStatus::Finished);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_db_tab_refreshes_on_demand() {
+    let key = |code| Event::Key(KeyEvent::from(code));
+    let mut engine = SyncEngine::new("test".to_string(), PathBuf::from("."), 20);
+    engine.handle_event(key(KeyCode::Tab)).unwrap();
+    for _ in 0..3 {
+        engine.handle_event(key(KeyCode::Right)).unwrap();
+    }
+    assert_eq!(LOG_TABS[engine.log_tabs], "db");
+    assert_eq!(
+        *engine.db_recent.lock().unwrap().state(),
+        DbRecentState::NotLoaded
+    );
+
+    // 没有配置数据库时显示为一行错误
+    engine.handle_event(key(KeyCode::Char('r'))).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while *engine.db_recent.lock().unwrap().state() == DbRecentState::Loading
+        && Instant::now() < deadline
+    {
+        thread::sleep(Duration::from_millis(20));
+    }
+    if std::env::var("DB_URL").is_err() {
+        assert_eq!(
+            engine.db_recent.lock().unwrap().get_list_string(),
+            ["Query failed: DB_URL or db_url in config must be set"]
+        );
+    }
+}
+
+#[test]
+fn test_status_history_popup() {
+    let key = |code| Event::Key(KeyEvent::from(code));
+    let mut engine = SyncEngine::new("test".to_string(), PathBuf::from("."), 20);
+    engine.handle_event(key(KeyCode::Char('h'))).unwrap();
+    assert_eq!(
+        engine.detail_content.as_deref(),
+        Some("No status changes yet")
+    );
+    engine.handle_event(key(KeyCode::Esc)).unwrap();
+    assert!(engine.detail_content.is_none());
+
+    engine
+        .observer
+        .set_status(crate::ProgressStatus::Failed, Some("watch lost"));
+    engine.handle_event(key(KeyCode::Char('h'))).unwrap();
+    assert_eq!(engine.detail_title, "Status history (Esc to close)");
+    assert!(
+        engine
+            .detail_content
+            .as_deref()
+            .unwrap()
+            .ends_with("observer Stopped -> Failed: watch lost")
+    );
+    // 弹窗打开时其他按键不生效
+    engine.handle_event(key(KeyCode::Tab)).unwrap();
+    assert!(engine.current_area == CurrentArea::ControlPanelArea);
+    engine.handle_event(key(KeyCode::Enter)).unwrap();
+    assert!(engine.detail_content.is_none());
+
+    // 状态变化也写入事件日志和关闭报告
+    assert!(
+        engine
+            .get_logs_str(LogKind::Observer)
+            .iter()
+            .any(|l| l.contains("Status ") && l.ends_with("observer Stopped -> Failed: watch lost"))
+    );
+    let report = engine.shutdown_with_report();
+    assert_eq!(report.status_history.len(), 1);
+    assert!(report.to_string().contains("Status history:\n  "));
+}
+
+#[test]
+fn test_compact_status_on_small_height() {
+    let engine = SyncEngine::new("test".to_string(), PathBuf::from("."), 20);
+    let render = |height: u16| -> Vec<String> {
+        let area = Rect::new(0, 0, 60, height);
+        let mut buf = Buffer::empty(area);
+        engine.render_status_area(area, &mut buf);
+        (0..height)
+            .map(|y| {
+                (0..area.width)
+                    .map(|x| buf[(x, y)].symbol())
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string()
+            })
+            .collect()
+    };
+
+    let small = render(1);
+    assert_eq!(small, ["OBS:Stopped 0 files | SCAN:Stopped | 0.0h"]);
+    let two = render(2);
+    assert_eq!(two[0], small[0]);
+    assert!(two[1].starts_with("Recorded 0 | Read 0 B | Log "));
+
+    // 高度足够时显示完整状态
+    let full = render(20);
+    assert!(full[0].contains("Status Area (h: history)"));
+    assert_eq!(full[1], "Status: Stopped");
+    assert!(full.contains(&"Log counts: none".to_string()), "{full:?}");
+    assert!(!full[19].contains("Scan"));
+
+    // 单次扫描时最后一行显示进度
+    {
+        let mut ss = engine.scanner.shared_state.lock().unwrap();
+        ss.scanner_status = crate::ProgressStatus::Running(crate::Running::Once);
+        ss.scan_progress = 0.25;
+        ss.scan_total_files = 4000;
+    }
+    assert_eq!(engine.scanner.get_scan_progress(), 0.25);
+    let scanning = render(20);
+    assert!(scanning[19].contains("Scan 25%"), "{:?}", scanning[19]);
+    assert_eq!(
+        render(1),
+        ["OBS:Stopped 0 files | SCAN:Running(Once) | 0.0h"]
+    );
+}
+
+#[test]
+fn test_failed_insert_retry_queue() {
+    if std::env::var("DB_URL").is_ok() {
+        return;
+    }
+    let dir = std::env::temp_dir().join("test_failed_insert_retry_queue");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("CTA_1.csv"), "x").unwrap();
+
+    // 没有配置数据库，扫描到的文件写入失败后进入重试队列
+    let mut engine = SyncEngine::new("test".to_string(), PathBuf::from("."), 20);
+    engine.scanner.set_path(dir.clone());
+    engine.scanner.start_scanner().unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while engine.scanner.is_busy() {
+        assert!(Instant::now() < deadline, "scan didn't finish");
+        thread::sleep(Duration::from_millis(10));
+    }
+    let items = engine.retry_queue.lock().unwrap().items().to_vec();
+    assert_eq!(items.len(), 1);
+    assert!(items[0].path_str().ends_with("CTA_1.csv"));
+    assert_eq!(items[0].error, "DB_URL or db_url in config must be set");
+
+    // 重试成功后移出队列并计入已记录文件
+    engine.retry_failed_insert_with(0, |paths, _| async move {
+        Ok(paths
+            .iter()
+            .map(|p| match p {
+                LoggedPath::Mapped { path, .. } => FileInfo::from_path(path).unwrap(),
+                LoggedPath::Raw(raw) => FileInfo::from_raw(raw),
+            })
+            .collect())
+    });
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !engine.retry_queue.lock().unwrap().is_empty() {
+        assert!(Instant::now() < deadline, "retry didn't finish");
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(engine.recorded_files.lock().unwrap().filtered().count(), 1);
+
+    std::fs::remove_dir_all(&dir).unwrap();
 }
@@ -0,0 +1,206 @@
+use ratatui::{
+    layout::Constraint,
+    style::Color,
+    widgets::{ListState, Paragraph, Row, StatefulWidget, Table, TableState, Widget},
+};
+
+use crate::{
+    SizeUnits,
+    apps::{file_sync_manager::registry::RecentInsert, notifier::notify_ui},
+    my_widgets::theme::Theme,
+};
+
+/// 数据库标签页一次查询的行数
+pub const DB_RECENT_LIMIT: usize = 20;
+
+/// 数据库标签页的内容，只在按 r 时查询，避免增加数据库负载
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum DbRecentState {
+    #[default]
+    NotLoaded,
+    Loading,
+    Loaded(Vec<RecentInsert>),
+    Failed(String),
+}
+
+/// 数据库中最近插入的记录
+#[derive(Debug, Clone, Default)]
+pub struct DbRecent {
+    state: DbRecentState,
+    theme: Theme,
+    size_units: SizeUnits,
+}
+
+impl DbRecent {
+    pub fn new() -> Self {
+        Self {
+            state: DbRecentState::NotLoaded,
+            theme: Theme::detect(),
+            size_units: SizeUnits::default(),
+        }
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    pub fn set_size_units(&mut self, size_units: SizeUnits) {
+        self.size_units = size_units;
+    }
+
+    pub fn state(&self) -> &DbRecentState {
+        &self.state
+    }
+
+    /// Mark a query as started, returning `false` if one is already running.
+    pub fn start_loading(&mut self) -> bool {
+        if self.state == DbRecentState::Loading {
+            return false;
+        }
+        self.state = DbRecentState::Loading;
+        true
+    }
+
+    pub fn set_result(&mut self, result: std::io::Result<Vec<RecentInsert>>) {
+        self.state = match result {
+            Ok(rows) => DbRecentState::Loaded(rows),
+            Err(e) => DbRecentState::Failed(e.to_string()),
+        };
+        notify_ui();
+    }
+
+    /// The status line shown in place of rows, if there are no rows to show.
+    fn status_text(&self) -> Option<String> {
+        match &self.state {
+            DbRecentState::NotLoaded => Some("Press r to load the most recent DB rows".to_string()),
+            DbRecentState::Loading => Some("Loading...".to_string()),
+            DbRecentState::Loaded(rows) if rows.is_empty() => Some("No rows".to_string()),
+            DbRecentState::Loaded(_) => None,
+            DbRecentState::Failed(e) => Some(format!("Query failed: {}", e)),
+        }
+    }
+
+    pub fn create_text(&self, row: &RecentInsert) -> String {
+        format!(
+            "{} {} {} {}",
+            row.time_inserted,
+            self.size_units.format(row.size),
+            row.cust_code.as_deref().unwrap_or("-"),
+            row.path
+        )
+    }
+
+    pub fn get_list_string(&self) -> Vec<String> {
+        match (&self.state, self.status_text()) {
+            (_, Some(status)) => vec![status],
+            (DbRecentState::Loaded(rows), None) => {
+                rows.iter().map(|r| self.create_text(r)).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl StatefulWidget for &DbRecent {
+    type State = ListState;
+    fn render(
+        self,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+        state: &mut Self::State,
+    ) {
+        let rows = match (&self.state, self.status_text()) {
+            (DbRecentState::Loaded(rows), None) => rows,
+            (state, status) => {
+                // 未加载、加载中、无记录或查询失败时显示一行提示
+                let style = if matches!(state, DbRecentState::Failed(_)) {
+                    self.theme.fg(Color::Red)
+                } else {
+                    self.theme.text()
+                };
+                Paragraph::new(status.unwrap_or_default())
+                    .style(style)
+                    .render(area, buf);
+                return;
+            }
+        };
+
+        let rows: Vec<Row> = rows
+            .iter()
+            .map(|r| {
+                Row::new(vec![
+                    r.filename.clone(),
+                    self.size_units.format(r.size),
+                    r.cust_code.clone().unwrap_or_default(),
+                    r.time_inserted.clone(),
+                ])
+            })
+            .collect();
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Fill(1),
+                Constraint::Length(10),
+                Constraint::Length(12),
+                Constraint::Length(19),
+            ],
+        )
+        .header(
+            Row::new(vec!["filename", "size", "cust_code", "time_inserted"])
+                .style(self.theme.fg(Color::Cyan)),
+        )
+        .row_highlight_style(self.theme.highlight());
+        let mut table_state = TableState::default().with_selected(state.selected());
+        StatefulWidget::render(table, area, buf, &mut table_state);
+    }
+}
+
+// MARK: test
+#[test]
+fn test_db_recent_states() {
+    let row = |name: &str, time: &str| RecentInsert {
+        path: format!(r"E:\testdata\{}", name),
+        filename: name.to_string(),
+        size: 2048,
+        cust_code: Some("CTA".to_string()),
+        time_inserted: time.to_string(),
+    };
+    let mut recent = DbRecent::new();
+    assert_eq!(
+        recent.get_list_string(),
+        ["Press r to load the most recent DB rows"]
+    );
+
+    assert!(recent.start_loading());
+    assert!(!recent.start_loading());
+    recent.set_result(Err(std::io::Error::other(
+        "DB_URL or db_url in config must be set",
+    )));
+    assert_eq!(
+        recent.get_list_string(),
+        ["Query failed: DB_URL or db_url in config must be set"]
+    );
+
+    assert!(recent.start_loading());
+    recent.set_result(Ok(vec![
+        row("b.csv", "2025-06-01 10:00:01"),
+        row("a.csv", "2025-06-01 10:00:00"),
+    ]));
+    assert_eq!(
+        recent.get_list_string(),
+        [
+            r"2025-06-01 10:00:01 2.0 KiB CTA E:\testdata\b.csv",
+            r"2025-06-01 10:00:00 2.0 KiB CTA E:\testdata\a.csv",
+        ]
+    );
+
+    // 失败时渲染为一行错误，不会 panic
+    recent.set_result(Err(std::io::Error::other("timeout")));
+    let area = ratatui::prelude::Rect::new(0, 0, 60, 3);
+    let mut buf = ratatui::prelude::Buffer::empty(area);
+    StatefulWidget::render(&recent, area, &mut buf, &mut ListState::default());
+    let first_line: String = (0..area.width)
+        .map(|x| buf[(x, 0)].symbol().to_string())
+        .collect();
+    assert!(first_line.starts_with("Query failed: timeout"));
+}
@@ -0,0 +1,186 @@
+use std::{collections::HashMap, path::Path};
+
+/// Lexical form of `path` used to compare with the allowed roots: `/` separators,
+/// `.` and `..` resolved, and lowercase on Windows.
+///
+/// Fails if `..` climbs above the start of the path.
+fn normalize(path: &str) -> Result<String, String> {
+    let path = path.trim().replace('\\', "/");
+    let absolute = path.starts_with('/');
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                // 盘符不能被 .. 去掉
+                if parts.pop().is_none_or(|p| p.ends_with(':')) {
+                    return Err(format!("{} escapes its root with ..", path));
+                }
+            }
+            part => parts.push(part),
+        }
+    }
+    let key = format!("{}{}", if absolute { "/" } else { "" }, parts.join("/"));
+    Ok(if cfg!(windows) {
+        key.to_lowercase()
+    } else {
+        key
+    })
+}
+
+/// Whether the normalized `key` is `root` or lies under it.
+fn is_within(key: &str, root: &str) -> bool {
+    key == root
+        || key
+            .strip_prefix(root)
+            .is_some_and(|rest| root.ends_with('/') || rest.starts_with('/'))
+}
+
+/// Check that every allowed root is an absolute path without `..`.
+pub fn check_roots(roots: &[String]) -> Result<(), String> {
+    for root in roots {
+        let key = normalize(root).map_err(|e| format!("Allowed destination root {}", e))?;
+        let absolute =
+            key.starts_with('/') || key.split('/').next().is_some_and(|p| p.ends_with(':'));
+        if !absolute || root.split(['/', '\\']).any(|p| p == "..") {
+            return Err(format!(
+                "Allowed destination root {} must be an absolute path without ..",
+                root
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Check that `path` stays inside one of `roots`, any path is allowed when `roots` is empty.
+///
+/// The path is first normalized, rejecting `..` escapes. Then the deepest existing
+/// directory under the matching root is canonicalized, so a symlink pointing out of
+/// the root is rejected too.
+pub fn check_destination(path: &Path, roots: &[String]) -> Result<(), String> {
+    if roots.is_empty() {
+        return Ok(());
+    }
+    let display = path.display();
+    let key = normalize(&path.to_string_lossy())?;
+    let Some(root) = roots
+        .iter()
+        .find(|root| normalize(root).is_ok_and(|root| is_within(&key, &root)))
+    else {
+        return Err(format!(
+            "{} is outside the allowed destination roots {:?}",
+            display, roots
+        ));
+    };
+
+    // 根目录不存在时其下也不会有符号链接
+    let Ok(canonical_root) = Path::new(root).canonicalize() else {
+        return Ok(());
+    };
+    let root_key = normalize(root)?;
+    let existing = Path::new(&key)
+        .ancestors()
+        .take_while(|a| is_within(&a.to_string_lossy(), &root_key))
+        .find_map(|a| a.canonicalize().ok());
+    match existing {
+        Some(resolved) if !resolved.starts_with(&canonical_root) => Err(format!(
+            "{} resolves to {} outside the allowed destination root {}",
+            display,
+            resolved.display(),
+            root
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Check the destination of every rule in `prefix_map` against `roots`.
+pub fn check_prefix_map(
+    prefix_map: &HashMap<String, [String; 2]>,
+    roots: &[String],
+) -> Result<(), String> {
+    // 按名称排序，报错稳定
+    let mut rules: Vec<_> = prefix_map.iter().collect();
+    rules.sort_by_key(|(name, _)| *name);
+    for (name, [_, to]) in rules {
+        check_destination(Path::new(to), roots)
+            .map_err(|e| format!("Prefix map rule {}: {}", name, e))?;
+    }
+    Ok(())
+}
+
+// MARK: test
+#[test]
+fn test_destination_traversal() {
+    let roots = vec!["/data/ftp".to_string(), r"E:\testdata".to_string()];
+    assert_eq!(check_roots(&roots), Ok(()));
+    assert!(check_roots(&["data".to_string()]).is_err());
+    assert!(check_roots(&["/data/../etc".to_string()]).is_err());
+
+    for ok in [
+        "/data/ftp",
+        "/data/ftp/CTA/2025/a.csv",
+        "/data/ftp/./CTA/../AC03/b.csv",
+        r"E:\testdata\CTA\a.csv",
+        "E:/testdata/nested/deep/c.csv",
+    ] {
+        assert_eq!(check_destination(Path::new(ok), &roots), Ok(()), "{}", ok);
+    }
+    for bad in [
+        "/data/ftp/../../etc/passwd",
+        "/data/ftp2/a.csv",
+        "/data/ftp/CTA/../../other/a.csv",
+        r"E:\testdata\..\Windows\a.csv",
+        r"C:\Windows\System32\a.csv",
+        "/../data/ftp/a.csv",
+        "relative/a.csv",
+    ] {
+        assert!(
+            check_destination(Path::new(bad), &roots).is_err(),
+            "{}",
+            bad
+        );
+    }
+    // 未配置时不限制
+    assert_eq!(check_destination(Path::new(r"C:\Windows"), &[]), Ok(()));
+
+    let map = HashMap::from([
+        (
+            "default".to_string(),
+            ["".to_string(), r"E:\testdata".to_string()],
+        ),
+        (
+            "typo".to_string(),
+            ["\\CTA".to_string(), r"C:\Windows".to_string()],
+        ),
+    ]);
+    let err = check_prefix_map(&map, &roots).unwrap_err();
+    assert!(err.starts_with(r"Prefix map rule typo: C:\Windows is outside"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_destination_symlink_escape() {
+    let base = std::env::temp_dir().join("test_destination_symlink_escape");
+    let _ = std::fs::remove_dir_all(&base);
+    let root = base.join("root");
+    let outside = base.join("outside");
+    std::fs::create_dir_all(root.join("nested/deep")).unwrap();
+    std::fs::create_dir_all(&outside).unwrap();
+    std::os::unix::fs::symlink(&outside, root.join("escape")).unwrap();
+    std::os::unix::fs::symlink(root.join("nested"), root.join("inner")).unwrap();
+
+    let roots = vec![root.display().to_string()];
+    // 目录内的符号链接和尚不存在的文件
+    for ok in ["nested/deep/a.csv", "inner/deep/b.csv", "new_dir/c.csv"] {
+        assert_eq!(check_destination(&root.join(ok), &roots), Ok(()), "{}", ok);
+    }
+    let err = check_destination(&root.join("escape/a.csv"), &roots).unwrap_err();
+    assert!(
+        err.contains("outside the allowed destination root"),
+        "{}",
+        err
+    );
+    assert!(check_destination(&root.join("escape/sub/a.csv"), &roots).is_err());
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
@@ -0,0 +1,318 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use encoding_rs::Encoding;
+
+use crate::{
+    FileMonitorConfig,
+    apps::file_sync_manager::registry::{FileInfo, RecordSource},
+};
+
+/// 每批送入 sink 的路径数
+pub const IMPORT_BATCH_SIZE: usize = 100;
+
+/// 批量导入路径列表时使用的配置项
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    pub encoding: &'static Encoding,
+    /// 小写、不含点的扩展名，为空时不过滤
+    pub extensions: Vec<String>,
+    pub progress_lines: usize,
+    pub batch_size: usize,
+}
+
+impl ImportOptions {
+    pub fn from_config(config: &FileMonitorConfig) -> io::Result<Self> {
+        let encoding =
+            Encoding::for_label(config.import_encoding.trim().as_bytes()).ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Unknown import_encoding {:?}", config.import_encoding),
+                )
+            })?;
+        Ok(Self {
+            encoding,
+            extensions: config
+                .import_extensions
+                .iter()
+                .map(|e| e.trim().trim_start_matches('.').to_lowercase())
+                .filter(|e| !e.is_empty())
+                .collect(),
+            progress_lines: config.import_progress_lines.max(1),
+            batch_size: IMPORT_BATCH_SIZE,
+        })
+    }
+
+    fn extension_allowed(&self, path: &Path) -> bool {
+        self.extensions.is_empty()
+            || path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| self.extensions.contains(&e.to_lowercase()))
+    }
+}
+
+/// 一次导入中各类行的计数
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub lines: usize,
+    pub imported: usize,
+    pub comments: usize,
+    pub blank: usize,
+    pub relative: usize,
+    pub filtered: usize,
+    pub duplicates: usize,
+    pub missing: usize,
+    pub malformed: usize,
+    pub unreadable: usize,
+    /// sink 写入失败的路径数
+    pub failed: usize,
+    pub last_error: Option<String>,
+}
+
+impl fmt::Display for ImportSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} lines, imported {}, skipped {} comments, {} blank, {} relative, {} filtered, \
+             {} duplicates; errors: {} missing, {} malformed, {} unreadable, {} failed to record",
+            self.lines,
+            self.imported,
+            self.comments,
+            self.blank,
+            self.relative,
+            self.filtered,
+            self.duplicates,
+            self.missing,
+            self.malformed,
+            self.unreadable,
+            self.failed
+        )?;
+        if let Some(e) = &self.last_error {
+            write!(f, " (last error: {})", e)?;
+        }
+        Ok(())
+    }
+}
+
+// 去掉首尾空白和引号，并统一为本机路径分隔符
+fn normalize_line(line: &str) -> String {
+    let line = line.trim().trim_matches('"').trim();
+    if cfg!(windows) {
+        line.replace('/', r"\")
+    } else {
+        line.to_string()
+    }
+}
+
+// 去重使用的键，Windows 路径不区分大小写
+fn dedup_key(path: &str) -> String {
+    if cfg!(windows) {
+        path.to_lowercase()
+    } else {
+        path.to_string()
+    }
+}
+
+/// Register every absolute path listed in `list` through `sink`, as if the observer had seen them.
+///
+/// `progress` is called every `options.progress_lines` lines. Returns the summary and the
+/// file infos the sink recorded.
+pub async fn import_path_list<F, Fut>(
+    list: &Path,
+    options: &ImportOptions,
+    mut sink: F,
+    mut progress: impl FnMut(&ImportSummary),
+) -> io::Result<(ImportSummary, Vec<FileInfo>)>
+where
+    F: FnMut(Vec<FileInfo>) -> Fut,
+    Fut: Future<Output = io::Result<Vec<FileInfo>>>,
+{
+    let bytes = tokio::fs::read(list).await?;
+    // 带 BOM 时以 BOM 为准
+    let (content, _, _) = options.encoding.decode(&bytes);
+
+    let mut summary = ImportSummary::default();
+    let mut recorded = Vec::new();
+    let mut seen = HashSet::new();
+    let mut batch = Vec::new();
+
+    for line in content.lines() {
+        summary.lines += 1;
+        let path = normalize_line(line);
+
+        if path.is_empty() {
+            summary.blank += 1;
+        } else if path.starts_with('#') {
+            summary.comments += 1;
+        } else if path.chars().any(|c| c.is_control() || c == '\u{FFFD}') {
+            // 控制字符或按所选编码无法解码的字节
+            summary.malformed += 1;
+        } else if !Path::new(&path).is_absolute() {
+            summary.relative += 1;
+        } else if !options.extension_allowed(Path::new(&path)) {
+            summary.filtered += 1;
+        } else if !seen.insert(dedup_key(&path)) {
+            summary.duplicates += 1;
+        } else {
+            match FileInfo::from_path(&PathBuf::from(&path)) {
+                Ok(info) if Path::new(&path).is_file() => batch.push(FileInfo {
+                    source: Some(RecordSource::Import),
+                    ..info
+                }),
+                Ok(_) => summary.missing += 1,
+                Err(e) if e.kind() == ErrorKind::NotFound => summary.missing += 1,
+                Err(_) => summary.unreadable += 1,
+            }
+        }
+
+        if batch.len() >= options.batch_size {
+            flush_batch(&mut batch, &mut sink, &mut summary, &mut recorded).await;
+        }
+        if summary.lines % options.progress_lines == 0 {
+            progress(&summary);
+        }
+    }
+    flush_batch(&mut batch, &mut sink, &mut summary, &mut recorded).await;
+
+    Ok((summary, recorded))
+}
+
+async fn flush_batch<F, Fut>(
+    batch: &mut Vec<FileInfo>,
+    sink: &mut F,
+    summary: &mut ImportSummary,
+    recorded: &mut Vec<FileInfo>,
+) where
+    F: FnMut(Vec<FileInfo>) -> Fut,
+    Fut: Future<Output = io::Result<Vec<FileInfo>>>,
+{
+    if batch.is_empty() {
+        return;
+    }
+    let len = batch.len();
+    match sink(std::mem::take(batch)).await {
+        Ok(infos) => {
+            // sink 按客户代码筛掉的也算作已过滤
+            summary.filtered += len.saturating_sub(infos.len());
+            summary.imported += infos.len();
+            recorded.extend(infos);
+        }
+        Err(e) => {
+            summary.failed += len;
+            summary.last_error = Some(e.to_string());
+        }
+    }
+}
+
+// MARK: test
+#[test]
+fn test_import_path_list() {
+    use std::sync::{Arc, Mutex};
+
+    let base = std::env::temp_dir().join("test_import_path_list");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(base.join("sub")).unwrap();
+    let a = base.join("a.csv");
+    let b = base.join("sub").join("b.CSV");
+    let c = base.join("c.tmp");
+    for file in [&a, &b, &c] {
+        std::fs::write(file, "x").unwrap();
+    }
+
+    let list = base.join("list.txt");
+    let content = [
+        "# exported from the MES".to_string(),
+        a.display().to_string(),
+        format!("  \"{}\"  ", b.display()),
+        "".to_string(),
+        a.display().to_string(),
+        base.join("missing.csv").display().to_string(),
+        "relative/d.csv".to_string(),
+        c.display().to_string(),
+        format!("{}\u{7}bad.csv", base.display()),
+        base.join("sub").display().to_string(),
+    ]
+    .join("\n");
+    std::fs::write(&list, content).unwrap();
+
+    let options = ImportOptions {
+        encoding: encoding_rs::UTF_8,
+        extensions: vec!["csv".to_string()],
+        progress_lines: 4,
+        batch_size: 1,
+    };
+    let sunk = Arc::new(Mutex::new(Vec::new()));
+    let mut progress = Vec::new();
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let (summary, recorded) = rt
+        .block_on(import_path_list(
+            &list,
+            &options,
+            |infos: Vec<FileInfo>| {
+                let sunk = sunk.clone();
+                async move {
+                    sunk.lock().unwrap().push(infos.len());
+                    Ok(infos)
+                }
+            },
+            |s| progress.push(s.lines),
+        ))
+        .unwrap();
+
+    assert_eq!(
+        summary,
+        ImportSummary {
+            lines: 10,
+            imported: 2,
+            comments: 1,
+            blank: 1,
+            relative: 1,
+            filtered: 2,
+            duplicates: 1,
+            missing: 1,
+            malformed: 1,
+            unreadable: 0,
+            failed: 0,
+            last_error: None,
+        }
+    );
+    // 每批一个路径，按列表顺序送入
+    assert_eq!(*sunk.lock().unwrap(), [1, 1]);
+    assert_eq!(progress, [4, 8]);
+    assert_eq!(recorded[0].filename, "a.csv");
+    assert_eq!(recorded[1].filename, "b.CSV");
+    assert!(
+        recorded
+            .iter()
+            .all(|i| i.source == Some(RecordSource::Import))
+    );
+
+    // 按配置的编码解码
+    let gbk_file = base.join("数据.csv");
+    std::fs::write(&gbk_file, "x").unwrap();
+    let gbk_line = gbk_file.display().to_string();
+    let (gbk, _, _) = encoding_rs::GBK.encode(&gbk_line);
+    std::fs::write(&list, gbk).unwrap();
+    let gbk_options = ImportOptions {
+        encoding: encoding_rs::GBK,
+        ..options
+    };
+    let (summary, recorded) = rt
+        .block_on(import_path_list(
+            &list,
+            &gbk_options,
+            |infos| async move { Ok(infos) },
+            |_| {},
+        ))
+        .unwrap();
+    assert_eq!(summary.imported, 1);
+    assert_eq!(recorded[0].filename, "数据.csv");
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
@@ -0,0 +1,139 @@
+use regex::Regex;
+
+use crate::DEFAULT_LINE_MARKER;
+
+/// 从一行日志中取出上传完成的文件路径，不同 FTP 服务器的日志格式各自实现
+pub trait LogLineParser: Send + Sync {
+    /// The uploaded FTP path in `line`, `None` if the line isn't a finished upload.
+    ///
+    /// The observer passes lines without their line ending.
+    fn extract_path<'l>(&self, line: &'l str) -> Option<&'l str>;
+}
+
+impl std::fmt::Debug for dyn LogLineParser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("dyn LogLineParser")
+    }
+}
+
+/// IIS FTP 日志，`STOR 226` 等标记之后的部分为路径
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IisFtpParser {
+    markers: Vec<String>,
+}
+
+impl Default for IisFtpParser {
+    fn default() -> Self {
+        Self::new(&[])
+    }
+}
+
+impl IisFtpParser {
+    /// Match any of `markers`, `DEFAULT_LINE_MARKER` when there are none.
+    pub fn new(markers: &[String]) -> Self {
+        let markers = if markers.is_empty() {
+            vec![DEFAULT_LINE_MARKER.to_string()]
+        } else {
+            markers.to_vec()
+        };
+        Self { markers }
+    }
+
+    pub fn markers(&self) -> &[String] {
+        &self.markers
+    }
+
+    /// The first word of each marker, e.g. `STOR`.
+    pub fn verbs(&self) -> impl Iterator<Item = &str> {
+        self.markers
+            .iter()
+            .filter_map(|m| m.split_whitespace().next())
+    }
+}
+
+impl LogLineParser for IisFtpParser {
+    fn extract_path<'l>(&self, line: &'l str) -> Option<&'l str> {
+        // 标记后须有空白再接路径，避免匹配到 "STOR 2260" 之类
+        self.markers.iter().find_map(|marker| {
+            line.split_once(marker.as_str())
+                .map(|(_, rest)| rest)
+                .filter(|rest| rest.starts_with(' ') || rest.starts_with('\t'))
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+        })
+    }
+}
+
+/// 用正则匹配 vsftpd、proftpd 等格式的日志，名为 path 的捕获组为路径
+#[derive(Debug, Clone)]
+pub struct RegexParser {
+    regex: Regex,
+}
+
+impl RegexParser {
+    /// Compile `pattern`, which must have a capture group named `path`.
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let regex =
+            Regex::new(pattern).map_err(|e| format!("Invalid log_pattern {:?}: {}", pattern, e))?;
+        if !regex.capture_names().any(|name| name == Some("path")) {
+            return Err(format!(
+                "log_pattern {:?} has no capture group named path",
+                pattern
+            ));
+        }
+        Ok(Self { regex })
+    }
+}
+
+impl LogLineParser for RegexParser {
+    fn extract_path<'l>(&self, line: &'l str) -> Option<&'l str> {
+        // 去掉换行符，$ 才能匹配行尾
+        self.regex
+            .captures(line.trim_end_matches(['\r', '\n']))?
+            .name("path")
+            .map(|path| path.as_str().trim())
+            .filter(|path| !path.is_empty())
+    }
+}
+
+// MARK: test
+#[test]
+fn test_line_parsers() {
+    let iis = IisFtpParser::default();
+    assert_eq!(
+        iis.extract_path("2025-06-01 08:00:00 10.53.2.70 STOR 226 /AC03/a b.csv\r\n"),
+        Some("/AC03/a b.csv")
+    );
+    assert_eq!(iis.extract_path("10.53.2.70 STOR 2260 /AC03/a.csv"), None);
+    assert_eq!(iis.extract_path("10.53.2.70 STOR 226"), None);
+    assert_eq!(iis.verbs().collect::<Vec<_>>(), ["STOR"]);
+
+    let custom = IisFtpParser::new(&["STOR - 226".to_string(), "APPE 226".to_string()]);
+    assert_eq!(custom.extract_path("APPE 226 /a.csv"), Some("/a.csv"));
+    assert_eq!(custom.extract_path("STOR 226 /a.csv"), None);
+
+    let vsftpd = RegexParser::new(r#"OK UPLOAD: Client "[^"]+", "(?P<path>[^"]+)""#).unwrap();
+    assert_eq!(
+        vsftpd.extract_path(
+            "Sun Jun  1 08:00:00 2025 [pid 1] [ftp] OK UPLOAD: Client \"10.53.2.70\", \"/AC03/a.csv\", 10 bytes\n"
+        ),
+        Some("/AC03/a.csv")
+    );
+    assert_eq!(vsftpd.extract_path("FAIL UPLOAD: Client \"x\""), None);
+    assert!(RegexParser::new("(").is_err());
+    assert!(
+        RegexParser::new("STOR (.+)")
+            .unwrap_err()
+            .contains("no capture group named path")
+    );
+
+    // 自定义实现可作为 trait 对象使用
+    let parsers: Vec<Box<dyn LogLineParser>> = vec![Box::new(iis), Box::new(vsftpd)];
+    assert_eq!(
+        parsers
+            .iter()
+            .filter_map(|p| p.extract_path("STOR 226 /b.csv"))
+            .collect::<Vec<_>>(),
+        ["/b.csv"]
+    );
+}
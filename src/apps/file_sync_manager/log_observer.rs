@@ -1,32 +1,92 @@
 use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    future::Future,
     io::SeekFrom,
     path::{Path, PathBuf},
-    sync::{Arc, Mutex, mpsc},
+    sync::{Arc, Mutex, RwLock, Weak, mpsc},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use indexmap::IndexMap;
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
 
-use chrono::{DateTime, FixedOffset, TimeDelta, Utc};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeDelta, Utc};
 use futures::{self, StreamExt, stream};
-use notify::{Event as NotifyEvent, EventKind, RecursiveMode, Result, Watcher};
+use notify::{Event as NotifyEvent, EventKind, RecursiveMode, Result, Watcher, event::ModifyKind};
 use tokio::{
     fs,
     io::{AsyncBufReadExt, AsyncSeekExt, BufReader},
 };
 
 use crate::{
+    ConfigError, ConfigWatcher,
     EK::*,
+    FileMonitorConfig,
     LOE::*,
-    OneEvent,
+    LogParserKind, OneEvent, PathMapping,
     ProgressStatus::{self, *},
-    TIME_ZONE,
-    apps::file_sync_manager::registry,
+    SharedConfig, SiteConfig, SizeUnits, TIME_ZONE, WatchMode,
+    apps::file_sync_manager::{
+        CLOSE_TIMEOUT, OperationLimit, PhaseOutcome, RecordedFiles, dest_roots, is_hidden_path,
+        join_with_timeout,
+        line_parser::{IisFtpParser, LogLineParser, RegexParser},
+        log_symlink::SymlinkFollower,
+        registry::{self, FileInfo, LoggedPath, RecordSource},
+        remote_log::{RemoteLog, RemoteReader, RemoteTail, is_remote},
+        retry_queue::RetryQueue,
+        status_history::{STATUS_HISTORY_LEN, StatusHistory, StatusTransition},
+    },
+    apps::{desktop_notify::toast_on_error, notifier::notify_ui},
     load_config,
     my_widgets::wrap_list::WrapList,
+    try_load_config,
+    util::retry::Backoff,
 };
 
+/// 配置了检查点的观察器，崩溃时由 panic hook 保存
+static CHECKPOINTS: Mutex<Vec<(Weak<Mutex<ObSharedState>>, PathBuf)>> = Mutex::new(Vec::new());
+
+/// Save the checkpoint of every live observer, called from the panic hook.
+///
+/// Observers whose state is locked, e.g. by the panicking thread, are skipped
+/// rather than waited for.
+pub fn save_checkpoints_on_panic() {
+    let Ok(checkpoints) = CHECKPOINTS.try_lock() else {
+        return;
+    };
+    for (shared_state, path) in checkpoints.iter() {
+        if let Some(ss) = shared_state.upgrade()
+            && let Ok(ss) = ss.try_lock()
+        {
+            let _ = ss.save_checkpoint(path);
+        }
+    }
+}
+
+/// 心跳超过该时长未更新则认为观察器线程卡住
+pub const STALL_THRESHOLD: Duration = Duration::from_secs(60);
+const SUPERVISOR_TICK: Duration = Duration::from_millis(200);
+/// 运行期间保存检查点的间隔，崩溃时最多重复读取这段时间内的日志
+pub const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(30);
+/// 监视日志目录失败时的重试策略
+const WATCH_BACKOFF: Backoff = Backoff::new(5, Duration::from_secs(1))
+    .with_max_delay(Duration::from_secs(8))
+    .with_jitter(20);
+
+/// 工作线程检查是否停止的间隔
+const STOP_POLL: Duration = Duration::from_millis(100);
+/// poll 方式的最短检查间隔，过小的间隔会让轮询线程几乎不休眠
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// 读取命名管道时检查是否停止的间隔
+const PIPE_POLL: Duration = Duration::from_millis(500);
+/// 远程日志没有新内容时再次查询大小的间隔
+#[cfg_attr(not(feature = "sftp"), allow(dead_code))]
+const REMOTE_POLL: Duration = Duration::from_secs(1);
+
 macro_rules! log {
     ($shared_state:expr, $kind:expr, $content:expr $(,)* ) => {
         $shared_state.lock().unwrap().add_logs(OneEvent {
@@ -36,10 +96,89 @@ macro_rules! log {
         })
     };
 }
+/// Whether `path` is a FIFO, or a named pipe on Windows, which is read as a stream
+/// instead of watched for modifications.
+#[cfg(unix)]
+pub fn is_pipe(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    std::fs::metadata(path).is_ok_and(|m| m.file_type().is_fifo())
+}
+
+#[cfg(windows)]
+pub fn is_pipe(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|p| p.to_ascii_lowercase().starts_with(r"\\.\pipe\"))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn is_pipe(_path: &Path) -> bool {
+    false
+}
+
 pub struct LogObserver {
     pub path: PathBuf,
     pub shared_state: Arc<Mutex<ObSharedState>>,
     pub handle: Option<thread::JoinHandle<Result<()>>>,
+    pub recorded_files: Arc<Mutex<RecordedFiles>>,
+    supervisor: Option<thread::JoinHandle<()>>,
+    /// 所属站点，覆盖顶层配置中的前缀映射
+    site: Option<SiteConfig>,
+    op_limit: OperationLimit,
+    /// stop_observer 等待已收到的事件处理完的最长时间，超时后不再等待
+    stop_timeout: Duration,
+    /// 写入数据库失败的路径，等待操作员重试
+    pub retry_queue: Arc<Mutex<RetryQueue>>,
+}
+
+/// 观察器状态的一次性快照，只需加锁一次
+#[derive(Debug, Clone)]
+pub struct ObStatusSnapshot {
+    pub status: ProgressStatus,
+    pub stalled: bool,
+    pub launch_time: DateTime<FixedOffset>,
+    pub elapsed_time: TimeDelta,
+    pub files_got: usize,
+    /// 每个观察目录及其中得到的文件数，按配置顺序
+    pub files_got_by_root: Vec<(PathBuf, usize)>,
+    pub files_recorded: usize,
+    pub file_reading: PathBuf,
+    pub path_mapping: PathMapping,
+    pub paths_rewritten: usize,
+    pub paths_raw: usize,
+    pub bytes_read: u64,
+    pub dry_run: bool,
+    pub dry_run_paths: usize,
+    pub recursive: bool,
+    /// 轮询间隔，使用系统通知时为空
+    pub poll_interval: Option<Duration>,
+}
+
+impl ObStatusSnapshot {
+    /// How the observer finds log changes, e.g. `native` or `poll every 2s`.
+    pub fn watch_mode_str(&self) -> String {
+        match self.poll_interval {
+            Some(interval) => format!("poll every {:?}", interval),
+            None => "native".to_string(),
+        }
+    }
+
+    pub fn lunch_time_str(&self) -> String {
+        self.launch_time.format("%Y-%m-%d %H:%M:%S").to_string()
+    }
+
+    /// Elapsed time in hours with one decimal, e.g. `0.2h`.
+    pub fn elapsed_hours_str(&self) -> String {
+        format!("{:.1}h", self.elapsed_time.num_seconds() as f64 / 3600.0)
+    }
+
+    pub fn elapsed_time_str(&self) -> String {
+        format!(
+            "{}h {}m {}s",
+            self.elapsed_time.num_seconds() / 3600,
+            (self.elapsed_time.num_seconds() % 3600) / 60,
+            self.elapsed_time.num_seconds() % 60
+        )
+    }
 }
 
 pub struct ObSharedState {
@@ -48,20 +187,440 @@ pub struct ObSharedState {
     pub status: ProgressStatus,
     pub file_statistic: FileStatistics,
     pub logs: WrapList,
+    last_heartbeat: Instant,
+    /// 上次保存检查点的时间
+    last_checkpoint: Instant,
+    /// 保存读取位置的文件，重启后从这里继续读取
+    checkpoint_path: Option<PathBuf>,
+    /// 上次写入后保存检查点失败，持续失败时只报告一次
+    checkpoint_failed: bool,
+    /// 最近处理过的修改事件，按 (路径, 文件大小) 去重
+    recent_events: HashMap<PathBuf, (u64, Instant)>,
+    /// 只记录将要插入的路径，不写数据库
+    dry_run: bool,
+    /// 同时监视子目录中的日志
+    recursive: bool,
+    /// 观察路径为符号链接时监视它指向的文件
+    follow_symlink: bool,
+    /// 由 ConfigWatcher 热加载的配置，为空时在启动时读取一次配置文件
+    config: Option<SharedConfig>,
+    /// 代替配置中 log_parser 的解析方式
+    line_parser: Option<Arc<dyn LogLineParser>>,
+    /// 最近的状态变化
+    status_history: StatusHistory,
+    /// 本次运行观察的目录
+    roots: Vec<PathBuf>,
+    /// 本次运行的轮询间隔，使用系统通知时为空
+    poll_interval: Option<Duration>,
 }
 
 #[derive(Default)]
 pub struct FileStatistics {
     files_watched: IndexMap<PathBuf, FileWatchInfo>,
     files_got: usize,
+    /// 按观察目录分别统计的 files_got
+    files_got_by_root: HashMap<PathBuf, usize>,
     files_recorded: usize,
     file_reading: PathBuf,
+    path_mapping: PathMapping,
+    /// 按前缀映射改写的路径数
+    paths_rewritten: usize,
+    /// 原样记录的路径数
+    paths_raw: usize,
+    /// 从日志文件读取的字节数
+    bytes_read: u64,
+    /// 演练模式下本应插入的路径数
+    dry_run_paths: usize,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Default)]
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub struct FileWatchInfo {
     last_read_pos: u64,
     file_size: u64,
+    /// 文件开头的哈希，用于重启后识别同名但内容已替换的文件
+    head: Option<FileHead>,
+    /// 已读部分中最近的 `#Fields:` 头，从中间继续读取时沿用
+    #[serde(default)]
+    fields: Option<W3cFields>,
+    /// 文件所在的观察目录，不在任何观察目录下时为空
+    #[serde(default)]
+    root: Option<PathBuf>,
+}
+
+/// 文件前 `len` 字节的 FNV-1a 哈希
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileHead {
+    pub len: u64,
+    pub hash: u64,
+}
+
+/// 文件开头参与哈希的最大字节数
+const FILE_HEAD_LEN: u64 = 64;
+
+/// 处理修改事件时使用的配置项
+#[derive(Debug, Clone, Copy)]
+pub struct ObserveOptions<'a> {
+    pub max_files_watched: usize,
+    pub dedup_window: Duration,
+    pub strict_parse: bool,
+    pub path_mapping: PathMapping,
+    /// 路径前缀映射，`None` 时只还原转义字符并转换分隔符
+    pub prefix_map: Option<&'a HashMap<String, [String; 2]>>,
+    pub size_units: SizeUnits,
+    /// 映射后的路径超过该长度时警告，0 表示不检查
+    pub max_path_len: usize,
+    /// 跳过隐藏文件和隐藏目录下的路径
+    pub skip_hidden: bool,
+    /// 映射后的路径必须位于其中之一，为空时不限制
+    pub allowed_roots: &'a [String],
+    /// 上传完成的日志行标记，为空时使用 `DEFAULT_LINE_MARKER`
+    pub line_markers: &'a [String],
+    /// 配置后代替 `line_markers` 的正则
+    pub log_pattern: Option<&'a str>,
+    /// 日志行的解析方式，为空时按是否配置了 `log_pattern` 选择
+    pub log_parser: Option<LogParserKind>,
+}
+
+impl<'a> ObserveOptions<'a> {
+    pub fn from_config(config: &'a FileMonitorConfig) -> Self {
+        Self {
+            max_files_watched: config.max_observed_files,
+            dedup_window: Duration::from_millis(config.dedup_window_ms),
+            strict_parse: config.strict_parse,
+            path_mapping: config.path_mapping,
+            prefix_map: Some(&config.prefix_map_of_extract_path),
+            size_units: config.size_units,
+            max_path_len: config.max_path_len,
+            skip_hidden: config.skip_hidden,
+            allowed_roots: &config.allowed_destination_roots,
+            line_markers: &config.line_markers,
+            log_pattern: config.log_pattern.as_deref(),
+            log_parser: config.log_parser,
+        }
+    }
+}
+
+/// 包含标记中的动词（如 STOR）但不符合 `<标记> <path>` 格式的日志行
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// 行号，从 1 开始
+    pub line: u64,
+    /// 该行在文件中的起始字节
+    pub offset: u64,
+    pub content: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "line {} (byte {}): {}",
+            self.line, self.offset, self.content
+        )
+    }
+}
+
+/// 日志中一条上传完成的记录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractedPath {
+    /// 日志中的 FTP 路径
+    pub path: String,
+    /// 该行结束处的偏移量
+    pub offset: u64,
+    /// 请求时间，只有读到 `#Fields:` 头且包含 date 和 time 列时才有
+    pub time: Option<DateTime<FixedOffset>>,
+}
+
+/// 日志行中的上传路径及请求时间
+type Upload = (String, Option<DateTime<FixedOffset>>);
+
+/// W3C 扩展日志 `#Fields:` 头中各列的位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct W3cFields {
+    count: usize,
+    method: usize,
+    status: Option<usize>,
+    uri: usize,
+    date: Option<usize>,
+    time: Option<usize>,
+}
+
+impl W3cFields {
+    /// Parse a `#Fields:` line, `None` if it isn't one or lacks `cs-method` or `cs-uri-stem`.
+    pub fn parse(line: &str) -> Option<Self> {
+        let names: Vec<&str> = line.strip_prefix("#Fields:")?.split_whitespace().collect();
+        let find = |name: &str| names.iter().position(|n| *n == name);
+        Some(Self {
+            count: names.len(),
+            method: find("cs-method")?,
+            status: find("sc-status"),
+            uri: find("cs-uri-stem")?,
+            date: find("date"),
+            time: find("time"),
+        })
+    }
+
+    /// The columns of `line`, `None` if it has fewer than the header.
+    ///
+    /// Columns are separated by single spaces. FTP paths may contain spaces, so the
+    /// extra parts all belong to `cs-uri-stem`.
+    fn split<'l>(&self, line: &'l str) -> Option<Vec<&'l str>> {
+        let parts: Vec<&str> = line.split(' ').collect();
+        let extra = parts.len().checked_sub(self.count)?;
+        let start: usize = parts[..self.uri].iter().map(|p| p.len() + 1).sum();
+        let uri_parts = &parts[self.uri..=self.uri + extra];
+        let len = uri_parts.iter().map(|p| p.len()).sum::<usize>() + extra;
+        let mut columns = parts[..self.uri].to_vec();
+        columns.push(&line[start..start + len]);
+        columns.extend(&parts[self.uri + extra + 1..]);
+        Some(columns)
+    }
+
+    /// Whether `line` has the header's columns, with a method and a numeric status
+    /// where the header puts them. Other lines are matched as if there were no header.
+    fn fits(&self, line: &str) -> bool {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let Some(columns) = self.split(line) else {
+            return false;
+        };
+        let is_word = |s: &str, f: fn(&u8) -> bool| !s.is_empty() && s.as_bytes().iter().all(f);
+        is_word(columns[self.method], u8::is_ascii_uppercase)
+            && self
+                .status
+                .is_none_or(|i| is_word(columns[i], u8::is_ascii_digit))
+    }
+
+    /// The path of an upload whose method and status match one of `markers`, an
+    /// error if its path is missing, `None` for other requests.
+    fn parse_upload(
+        &self,
+        line: &str,
+        markers: &[&str],
+        line_no: u64,
+        offset: u64,
+    ) -> Option<std::result::Result<Upload, ParseError>> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let malformed = || ParseError {
+            line: line_no,
+            offset,
+            content: line.to_string(),
+        };
+        // 标记的第一个词为方法，有多个词时最后一个为状态码
+        let verbs: Vec<(&str, Option<&str>)> = markers
+            .iter()
+            .filter_map(|m| {
+                let words: Vec<&str> = m.split_whitespace().collect();
+                Some((
+                    *words.first()?,
+                    (words.len() > 1).then(|| words[words.len() - 1]),
+                ))
+            })
+            .collect();
+        let columns = self.split(line)?;
+        let status = self.status.map(|i| columns[i]);
+        let is_upload = verbs.iter().any(|(verb, code)| {
+            *verb == columns[self.method] && (code.is_none() || status.is_none() || *code == status)
+        });
+        if !is_upload {
+            return None;
+        }
+        let path = columns[self.uri].trim();
+        if path.is_empty() || path == "-" {
+            return Some(Err(malformed()));
+        }
+        Some(Ok((path.to_string(), self.time(&columns))))
+    }
+
+    /// The request time of a line, logged in UTC.
+    fn time(&self, columns: &[&str]) -> Option<DateTime<FixedOffset>> {
+        let (date, time) = (columns[self.date?], columns[self.time?]);
+        let naive =
+            NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M:%S")
+                .ok()?;
+        Some(naive.and_utc().with_timezone(TIME_ZONE))
+    }
+}
+
+/// 从日志行中取出上传文件路径的方式
+#[derive(Debug)]
+pub enum LineMatcher {
+    /// IIS 日志按标记匹配，读到 `#Fields:` 头后改为按列匹配
+    Markers(IisFtpParser, Cell<Option<W3cFields>>),
+    /// 正则或自定义的解析方式
+    Parser(Arc<dyn LogLineParser>),
+}
+
+impl LineMatcher {
+    /// The parser chosen by `options.log_parser`, or by whether `options.log_pattern`
+    /// is set when it isn't configured.
+    ///
+    /// A pattern that doesn't compile or has no `path` group is an error, and so is
+    /// the regex parser without a pattern.
+    pub fn new(options: ObserveOptions<'_>) -> std::result::Result<Self, String> {
+        let kind = options.log_parser.unwrap_or(match options.log_pattern {
+            Some(_) => LogParserKind::Regex,
+            None => LogParserKind::Iis,
+        });
+        match (kind, options.log_pattern) {
+            (LogParserKind::Iis, _) => Ok(Self::markers(options.line_markers)),
+            (LogParserKind::Regex, Some(pattern)) => {
+                Ok(Self::Parser(Arc::new(RegexParser::new(pattern)?)))
+            }
+            (LogParserKind::Regex, None) => {
+                Err("log_parser is regex but log_pattern is not set".to_string())
+            }
+        }
+    }
+
+    /// Use `custom` if set, otherwise the parser configured in `options`.
+    pub fn with_custom(
+        custom: Option<Arc<dyn LogLineParser>>,
+        options: ObserveOptions<'_>,
+    ) -> std::result::Result<Self, String> {
+        match custom {
+            Some(parser) => Ok(Self::Parser(parser)),
+            None => Self::new(options),
+        }
+    }
+
+    /// Match `markers`, with no `#Fields:` header seen yet.
+    pub fn markers(markers: &[String]) -> Self {
+        Self::Markers(IisFtpParser::new(markers), Cell::new(None))
+    }
+
+    /// The latest `#Fields:` header seen, to continue from later.
+    pub fn w3c_fields(&self) -> Option<W3cFields> {
+        match self {
+            Self::Markers(_, fields) => fields.get(),
+            Self::Parser(_) => None,
+        }
+    }
+
+    /// Continue a file whose `#Fields:` header was read earlier.
+    pub fn set_w3c_fields(&self, header: Option<W3cFields>) {
+        if let Self::Markers(_, fields) = self {
+            fields.set(header);
+        }
+    }
+
+    /// The uploaded path in `line` with its request time if known, an error for a
+    /// malformed upload line, `None` for any other line.
+    ///
+    /// With markers, a `#Fields:` header switches to matching the `cs-method`,
+    /// `sc-status` and `cs-uri-stem` columns for the lines after it.
+    fn parse(
+        &self,
+        line: &str,
+        line_no: u64,
+        offset: u64,
+    ) -> Option<std::result::Result<Upload, ParseError>> {
+        match self {
+            Self::Markers(parser, fields) => {
+                if line.starts_with("#Fields:") {
+                    fields.set(W3cFields::parse(line.trim_end()));
+                    return None;
+                }
+                match fields.get() {
+                    Some(header) if !line.starts_with('#') && header.fits(line) => {
+                        let markers: Vec<&str> =
+                            parser.markers().iter().map(String::as_str).collect();
+                        header.parse_upload(line, &markers, line_no, offset)
+                    }
+                    _ => LogObserver::parse_stor_line(line, parser, line_no, offset)
+                        .map(|r| r.map(|path| (path, None))),
+                }
+            }
+            Self::Parser(parser) => parser
+                .extract_path(line.trim_end_matches(['\r', '\n']))
+                .map(|path| Ok((path.to_string(), None))),
+        }
+    }
+}
+
+/// 恢复偏移量时发现的不一致
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OffsetMismatch {
+    Missing,
+    /// 文件比记录的偏移量小，可能已被轮转或截断
+    Truncated {
+        size: u64,
+        offset: u64,
+    },
+    /// 文件开头与记录的不一致，内容已被替换
+    Replaced,
+}
+
+impl FileWatchInfo {
+    pub fn new(last_read_pos: u64, file_size: u64, head: Option<FileHead>) -> Self {
+        Self {
+            last_read_pos,
+            file_size,
+            head,
+            fields: None,
+            root: None,
+        }
+    }
+
+    /// The observed path the file was found under.
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    pub fn last_read_pos(&self) -> u64 {
+        self.last_read_pos
+    }
+
+    /// Check the stored offset against the file currently at `path`.
+    pub fn verify(&self, path: &Path) -> std::result::Result<(), OffsetMismatch> {
+        let Ok(meta) = std::fs::metadata(path) else {
+            return Err(OffsetMismatch::Missing);
+        };
+        if meta.len() < self.last_read_pos {
+            return Err(OffsetMismatch::Truncated {
+                size: meta.len(),
+                offset: self.last_read_pos,
+            });
+        }
+        if let Some(head) = self.head {
+            match FileHead::read(path, head.len) {
+                Ok(current) if current == head => {}
+                _ => return Err(OffsetMismatch::Replaced),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FileHead {
+    /// Hash the first `len` bytes of the file, or fewer if the file is shorter.
+    pub fn read(path: &Path, len: u64) -> std::io::Result<Self> {
+        Ok(Self::of(&Self::read_start(path, len)?))
+    }
+
+    /// The first `len` bytes of the file, or fewer if the file is shorter.
+    pub fn read_start(path: &Path, len: u64) -> std::io::Result<Vec<u8>> {
+        use std::io::Read;
+
+        let mut buf = Vec::with_capacity(len as usize);
+        std::fs::File::open(path)?.take(len).read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Hash all of `bytes`.
+    pub fn of(bytes: &[u8]) -> Self {
+        let hash = bytes.iter().fold(0xcbf29ce484222325u64, |hash, b| {
+            (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+        });
+        Self {
+            len: bytes.len() as u64,
+            hash,
+        }
+    }
+
+    /// Whether `start`, the first bytes of a file, still begin with the hashed bytes.
+    pub fn matches_start(&self, start: &[u8]) -> bool {
+        start.len() as u64 >= self.len && Self::of(&start[..self.len as usize]) == *self
+    }
 }
 
 impl LogObserver {
@@ -74,60 +633,384 @@ impl LogObserver {
             status: Stopped,
             file_statistic: FileStatistics::default(),
             logs: WrapList::new(log_size),
+            last_heartbeat: Instant::now(),
+            last_checkpoint: Instant::now(),
+            checkpoint_path: None,
+            checkpoint_failed: false,
+            recent_events: HashMap::new(),
+            dry_run: false,
+            recursive: false,
+            follow_symlink: false,
+            config: None,
+            line_parser: None,
+            status_history: StatusHistory::new("observer", STATUS_HISTORY_LEN),
+            roots: Vec::new(),
+            poll_interval: None,
         }));
 
         LogObserver {
             path,
             shared_state,
             handle: None,
+            recorded_files: Arc::new(Mutex::new(RecordedFiles::new(log_size))),
+            supervisor: None,
+            site: None,
+            op_limit: OperationLimit::default(),
+            stop_timeout: CLOSE_TIMEOUT,
+            retry_queue: Arc::new(Mutex::new(RetryQueue::default())),
         }
     }
 
-    pub fn stop_observer(&mut self) {
-        let status = self.shared_state.lock().unwrap().status;
-        if status == Stopped || status == Stopping {
+    /// How long [`Self::stop_observer`] waits for the worker, `CLOSE_TIMEOUT` by default.
+    pub fn set_stop_timeout(&mut self, timeout: Duration) {
+        self.stop_timeout = timeout;
+    }
+
+    /// Extract paths with `parser` instead of the parser chosen in the config, for log
+    /// formats neither markers nor a regex can handle.
+    pub fn with_line_parser(mut self, parser: Arc<dyn LogLineParser>) -> Self {
+        self.set_line_parser(Some(parser));
+        self
+    }
+
+    /// See [`Self::with_line_parser`], `None` goes back to the configured parser.
+    pub fn set_line_parser(&mut self, parser: Option<Arc<dyn LogLineParser>>) {
+        self.shared_state.lock().unwrap().line_parser = parser;
+    }
+
+    /// Save read positions to `path` after every recorded batch, on close, on panic
+    /// and every `CHECKPOINT_INTERVAL` while running, resuming from it if it already
+    /// exists.
+    ///
+    /// A missing or corrupt checkpoint is reported and ignored, the files are then
+    /// read from the start as if there were none.
+    pub fn with_checkpoint(mut self, path: Option<PathBuf>) -> Self {
+        self.set_checkpoint(path);
+        self
+    }
+
+    /// See [`Self::with_checkpoint`].
+    pub fn set_checkpoint(&mut self, path: Option<PathBuf>) {
+        let Some(path) = path else {
+            return;
+        };
+        if path.exists() {
+            match ObSharedState::load_checkpoint(&path) {
+                Ok(infos) => {
+                    self.restore_watch_infos(infos);
+                }
+                Err(e) => log!(
+                    self.shared_state,
+                    Warning,
+                    format!("Ignored checkpoint {}: {}", path.display(), e)
+                ),
+            }
+        }
+        let mut checkpoints = CHECKPOINTS.lock().unwrap();
+        // 顺便清理已释放的观察器
+        checkpoints.retain(|(ss, _)| ss.strong_count() > 0);
+        checkpoints.push((Arc::downgrade(&self.shared_state), path.clone()));
+        drop(checkpoints);
+        self.shared_state.lock().unwrap().checkpoint_path = Some(path);
+    }
+
+    /// Write the read positions to the checkpoint, if one is configured.
+    pub fn save_checkpoint(&self) {
+        let ss = self.shared_state.lock().unwrap();
+        let Some(path) = ss.checkpoint_path.clone() else {
+            return;
+        };
+        let saved = ss.save_checkpoint(&path);
+        drop(ss);
+        if let Err(e) = saved {
             log!(
                 self.shared_state,
                 Error,
-                "Observer is already stopped or stopping.".to_string()
+                format!("Failed to save checkpoint {}: {}", path.display(), e)
             );
+        }
+    }
+
+    /// Use `site`'s prefix map and source tag from the next start on.
+    pub fn set_site(&mut self, site: SiteConfig) {
+        self.site = Some(site);
+    }
+
+    pub fn with_recorded_files(mut self, recorded_files: Arc<Mutex<RecordedFiles>>) -> Self {
+        self.recorded_files = recorded_files;
+        self
+    }
+
+    /// Queue paths that fail to insert in `retry_queue`, shared with the scanner.
+    pub fn with_retry_queue(mut self, retry_queue: Arc<Mutex<RetryQueue>>) -> Self {
+        self.retry_queue = retry_queue;
+        self
+    }
+
+    /// Log the paths that would be inserted instead of writing them to the DB.
+    ///
+    /// Nothing is buffered, so switching takes effect from the next batch.
+    pub fn set_dry_run(&self, dry_run: bool) {
+        let mut ss = self.shared_state.lock().unwrap();
+        if ss.dry_run == dry_run {
             return;
         }
+        ss.dry_run = dry_run;
+        drop(ss);
+        let msg = if dry_run {
+            "Dry run on, nothing is written to the DB"
+        } else {
+            "Dry run off, paths are written to the DB again"
+        };
+        log!(self.shared_state, Info, msg.to_string());
+    }
 
-        self.shared_state.lock().unwrap().set_status(Stopped);
+    pub fn is_dry_run(&self) -> bool {
+        self.shared_state.lock().unwrap().dry_run
+    }
 
-        let ss_clone = self.shared_state.clone();
+    /// Also watch the subdirectories of the observed path, e.g. per-site `W3SVC1/` folders.
+    ///
+    /// The watch is set up on start, so switching while running takes effect on the next start.
+    pub fn set_recursive(&self, recursive: bool) {
+        let mut ss = self.shared_state.lock().unwrap();
+        if ss.recursive == recursive {
+            return;
+        }
+        ss.recursive = recursive;
+        let running = matches!(ss.status, Running(_));
+        drop(ss);
+        let msg = format!(
+            "Recursive watch {}{}",
+            if recursive { "on" } else { "off" },
+            if running {
+                ", restart the observer to apply"
+            } else {
+                ""
+            }
+        );
+        log!(self.shared_state, Info, msg);
+    }
 
-        if let Some(handle) = self.handle.take() {
-            let future = async move {
-                loop {
-                    if handle.is_finished() {
-                        ss_clone.lock().unwrap().reset_time();
-                        log!(ss_clone, Stop, "Observer is stopping.".to_string());
-                    } else {
-                        log!(ss_clone, Error, "Observer doesn't stop.".to_string());
-                    }
-                    tokio::time::sleep(Duration::from_millis(500)).await;
-                }
+    pub fn is_recursive(&self) -> bool {
+        self.shared_state.lock().unwrap().recursive
+    }
+
+    /// Watch what an observed symlink points to, switching when it is repointed.
+    ///
+    /// Like [`LogObserver::set_recursive`], this takes effect on the next start.
+    pub fn set_follow_symlink(&self, follow: bool) {
+        self.shared_state.lock().unwrap().follow_symlink = follow;
+    }
+
+    /// The watched files with their read positions, in the order they were first seen.
+    pub fn watched_offsets(&self) -> Vec<(PathBuf, FileWatchInfo)> {
+        let ss = self.shared_state.lock().unwrap();
+        let watched = ss.file_statistic.files_watched.iter();
+        watched.map(|(p, info)| (p.clone(), info.clone())).collect()
+    }
+
+    /// Read `path` from the start on its next modify event, returning its previous
+    /// read position. Fails if `path` isn't watched.
+    pub fn reset_offset(&self, path: &Path) -> std::result::Result<u64, String> {
+        let mut ss = self.shared_state.lock().unwrap();
+        // 输入的路径可能是相对路径或写法不同
+        let key = Some(path.to_path_buf())
+            .filter(|p| ss.file_statistic.files_watched.contains_key(p))
+            .or_else(|| path.canonicalize().ok())
+            .filter(|p| ss.file_statistic.files_watched.contains_key(p));
+        let Some(key) = key else {
+            drop(ss);
+            let msg = format!("Can't reset offset, {:?} is not watched", path);
+            log!(self.shared_state, Error, msg.clone());
+            return Err(msg);
+        };
+        let info = ss.file_statistic.files_watched.get_mut(&key).unwrap();
+        let old = info.last_read_pos;
+        info.last_read_pos = 0;
+        info.head = None;
+        // 从头读取时重新读到 #Fields 头
+        info.fields = None;
+        // 大小未变的下一次修改事件不能被当作重复跳过
+        ss.recent_events.remove(&key);
+        drop(ss);
+        let msg = format!(
+            "Offset of {:?} reset from {} to 0, read again from the start on the next change",
+            key, old
+        );
+        log!(self.shared_state, Info, msg);
+        Ok(old)
+    }
+
+    /// Take the config from `watcher`, so edits to the config file apply while running.
+    ///
+    /// Each reload is logged, an invalid edit as an error while the previous config stays.
+    pub fn set_config_watcher(&mut self, watcher: &ConfigWatcher) {
+        self.shared_state.lock().unwrap().config = Some(watcher.config());
+        let shared_state = Arc::downgrade(&self.shared_state);
+        watcher.subscribe(move |result| {
+            let Some(shared_state) = shared_state.upgrade() else {
+                return;
             };
+            match result {
+                Ok(()) => log!(shared_state, Info, "Config reloaded".to_string()),
+                Err(e) => log!(
+                    shared_state,
+                    Error,
+                    format!("Config not reloaded, keeping the previous one: {}", e)
+                ),
+            }
+        });
+    }
+
+    /// Read the config file again, the running observer uses it from the next event.
+    ///
+    /// Failing to load is logged and keeps the previous config.
+    pub fn reload_config(&self) -> std::result::Result<(), ConfigError> {
+        let config = match try_load_config() {
+            Ok(config) => config,
+            Err(e) => {
+                let msg = format!("Config not reloaded, keeping the previous one: {}", e);
+                log!(self.shared_state, Error, msg);
+                return Err(e);
+            }
+        };
+        {
+            let mut ss = self.shared_state.lock().unwrap();
+            match &ss.config {
+                Some(shared) => *shared.write().unwrap() = config,
+                None => ss.config = Some(Arc::new(RwLock::new(config))),
+            }
+        }
+        log!(self.shared_state, Info, "Config reloaded".to_string());
+        Ok(())
+    }
+
+    /// Load the config file unless a config is already set, so running doesn't read it again.
+    fn load_config_once(&self) -> std::result::Result<(), ConfigError> {
+        let mut ss = self.shared_state.lock().unwrap();
+        if ss.config.is_none() {
+            ss.config = Some(Arc::new(RwLock::new(try_load_config()?)));
+        }
+        Ok(())
+    }
+
+    /// The config the observer runs with, loading the config file if none is set yet.
+    pub fn loaded_config(&self) -> std::result::Result<FileMonitorConfig, ConfigError> {
+        self.load_config_once()?;
+        Ok(self.shared_state.lock().unwrap().current_config())
+    }
+
+    /// Share `op_limit` with the scanners and other observers, DB writes take a permit
+    /// from it first.
+    pub fn set_op_limit(&mut self, op_limit: OperationLimit) {
+        self.op_limit = op_limit;
+    }
+
+    /// Stop taking new events and wait up to the stop timeout for the worker to handle
+    /// the ones already received and exit, see [`Self::set_stop_timeout`].
+    ///
+    /// The worker logs one stop event when it exits. A worker that doesn't exit in time
+    /// is logged as one error and left to exit on its own.
+    pub fn stop_observer(&mut self) -> PhaseOutcome {
+        let status = self.shared_state.lock().unwrap().status;
+        if status == Stopped || status == Stopping {
+            log!(
+                self.shared_state,
+                Error,
+                "Observer is already stopped or stopping.".to_string()
+            );
+            return PhaseOutcome::Skipped;
+        }
+
+        // 未在运行时没有待处理的事件，直接停止
+        if !self.stop_input() {
+            self.set_status(Stopped, Some("stop requested"));
+        }
+        let outcome = self.drain(self.stop_timeout);
+        match &outcome {
+            PhaseOutcome::TimedOut => {
+                let msg = format!(
+                    "Observer didn't stop within {:?}, gave up waiting",
+                    self.stop_timeout
+                );
+                log!(self.shared_state, Error, msg);
+            }
+            PhaseOutcome::Failed(e) => {
+                log!(self.shared_state, Error, format!("Observer {}", e));
+            }
+            PhaseOutcome::Done | PhaseOutcome::Skipped => {}
+        }
+        self.stop_supervisor(CLOSE_TIMEOUT);
+        self.save_checkpoint();
+        outcome
+    }
 
-            tokio::spawn(future);
+    /// The directories to watch, the path given to `new` and the site's other observed paths.
+    pub fn observed_paths(&self) -> Vec<PathBuf> {
+        let mut paths = vec![self.path.clone()];
+        if let Some(site) = &self.site {
+            let others = site.observed_path.as_slice().iter();
+            paths.extend(others.filter(|p| **p != self.path).cloned());
         }
+        paths
     }
 
     pub fn start_observer(&mut self) -> Result<()> {
-        if !Path::new(&self.path).exists() {
+        let paths = self.observed_paths();
+        if let Some(missing) = paths
+            .iter()
+            .find(|p| !p.exists() && !is_pipe(p) && !is_remote(p))
+        {
             let current_path = std::env::current_dir()?;
             log!(
                 self.shared_state,
                 Error,
                 format!(
-                    "Start failed: path does not exist, current path: {}, please configure the path parameter in cfg.json ",
+                    "Start failed: path {} does not exist, current path: {}, please configure the path parameter in cfg.json ",
+                    missing.display(),
                     current_path.display()
                 )
             );
             return Ok(());
         }
+        if paths.len() > 1 && paths.iter().any(|p| is_pipe(p) || is_remote(p)) {
+            log!(
+                self.shared_state,
+                Error,
+                "Start failed: a pipe or ssh:// path must be the only observed path".to_string()
+            );
+            return Ok(());
+        }
+
+        if let Some(Err(e)) = RemoteLog::parse(&self.path) {
+            log!(self.shared_state, Error, format!("Start failed: {}", e));
+            return Ok(());
+        }
+        if cfg!(not(feature = "sftp")) && is_remote(&self.path) {
+            log!(
+                self.shared_state,
+                Error,
+                "Start failed: ssh:// observed paths need a build with the sftp feature"
+                    .to_string()
+            );
+            return Ok(());
+        }
+
+        if let Err(e) = self.load_config_once() {
+            log!(self.shared_state, Error, format!("Start failed: {}", e));
+            return Ok(());
+        }
+        let (config, custom) = {
+            let ss = self.shared_state.lock().unwrap();
+            (ss.current_config(), ss.line_parser.clone())
+        };
+        if let Err(e) = Self::check_destination_roots(&config, self.site.as_ref()).and_then(|_| {
+            LineMatcher::with_custom(custom, ObserveOptions::from_config(&config)).map(|_| ())
+        }) {
+            log!(self.shared_state, Error, format!("Start failed: {}", e));
+            return Ok(());
+        }
 
         let status = self.shared_state.lock().unwrap().status;
         match status {
@@ -143,39 +1026,279 @@ impl LogObserver {
         }
 
         self.set_launch_time();
-        self.set_status(Running(crate::Running::Periodic));
+        // 先写启动事件，状态变化记在同一次运行中
+        log!(self.shared_state, Start, "Observer started".to_string());
+        self.set_status(Running(crate::Running::Periodic), Some("observer started"));
 
         let time = Utc::now().with_timezone(TIME_ZONE);
-        self.shared_state.lock().unwrap().launch_time = time;
+        {
+            let mut ss = self.shared_state.lock().unwrap();
+            ss.launch_time = time;
+            ss.roots = paths.clone();
+        }
 
         let cloned_shared_state = Arc::clone(&self.shared_state);
         let path = self.path.clone();
-        let handle =
-            thread::spawn(move || LogObserver::inner_observer(cloned_shared_state, path, None));
+        let recorded_files = Arc::clone(&self.recorded_files);
+        let site = self.site.clone();
+        let op_limit = self.op_limit.clone();
+        let retry_queue = Arc::clone(&self.retry_queue);
+        let poll_interval = (config.watch_mode == WatchMode::Poll)
+            .then(|| Duration::from_millis(config.poll_interval_ms).max(MIN_POLL_INTERVAL));
+        let handle = thread::spawn(move || {
+            #[cfg(feature = "sftp")]
+            if let Some(Ok(remote)) = RemoteLog::parse(&path) {
+                return LogObserver::inner_remote_observer(
+                    cloned_shared_state,
+                    recorded_files,
+                    retry_queue,
+                    remote,
+                    site,
+                    op_limit,
+                );
+            }
+            if is_pipe(&path) {
+                LogObserver::inner_pipe_observer(
+                    cloned_shared_state,
+                    recorded_files,
+                    retry_queue,
+                    path,
+                    site,
+                    op_limit,
+                )
+            } else {
+                LogObserver::inner_observer(
+                    cloned_shared_state,
+                    recorded_files,
+                    retry_queue,
+                    paths,
+                    site,
+                    op_limit,
+                    poll_interval,
+                )
+            }
+        });
 
         self.handle = Some(handle);
-
-        log!(self.shared_state, Start, "Observer started".to_string());
+        self.shared_state.lock().unwrap().heartbeat();
+        self.supervisor = Some(Self::spawn_supervisor(
+            Arc::clone(&self.shared_state),
+            self.shared_state.lock().unwrap().checkpoint_path.clone(),
+        ));
         Ok(())
     }
 
-    // 线程中运行
+    /// Watch the worker's heartbeat while it runs and report once per stall, saving
+    /// the checkpoint, if any, every `CHECKPOINT_INTERVAL`.
+    fn spawn_supervisor(
+        shared_state: Arc<Mutex<ObSharedState>>,
+        checkpoint: Option<PathBuf>,
+    ) -> thread::JoinHandle<()> {
+        thread::spawn(move || {
+            let mut reported = false;
+            let mut save_failed = false;
+            loop {
+                thread::sleep(SUPERVISOR_TICK);
+                let (status, stalled, since) = {
+                    let ss = shared_state.lock().unwrap();
+                    (
+                        ss.status,
+                        ss.is_stalled(STALL_THRESHOLD),
+                        ss.last_heartbeat.elapsed(),
+                    )
+                };
+                if !matches!(status, Running(_)) {
+                    break;
+                }
+                if stalled && !reported {
+                    let msg = format!(
+                        "Observer seems stuck, no heartbeat for {}s",
+                        since.as_secs()
+                    );
+                    log!(shared_state, Error, msg);
+                } else if !stalled && reported {
+                    log!(shared_state, Info, "Observer recovered".to_string());
+                }
+                reported = stalled;
+
+                let Some(path) = &checkpoint else {
+                    continue;
+                };
+                let saved = shared_state
+                    .lock()
+                    .unwrap()
+                    .save_checkpoint_if_due(path, CHECKPOINT_INTERVAL);
+                match saved {
+                    // 持续失败时只报告一次
+                    Some(Err(e)) if !save_failed => {
+                        let msg = format!("Failed to save checkpoint {}: {}", path.display(), e);
+                        log!(shared_state, Warning, msg);
+                        save_failed = true;
+                    }
+                    Some(Ok(())) => save_failed = false,
+                    _ => {}
+                }
+            }
+        })
+    }
+
+    /// Restore persisted offsets, resetting those that no longer match the file on disk.
+    ///
+    /// All mismatches are reported in one summary event; the mismatched files
+    /// are returned with the reason.
+    pub fn restore_watch_infos(
+        &self,
+        infos: IndexMap<PathBuf, FileWatchInfo>,
+    ) -> Vec<(PathBuf, OffsetMismatch)> {
+        let mut mismatches = Vec::new();
+        let mut restored = IndexMap::with_capacity(infos.len());
+        for (path, info) in infos {
+            match info.verify(&path) {
+                Ok(()) => {
+                    restored.insert(path, info);
+                }
+                Err(OffsetMismatch::Missing) => {
+                    mismatches.push((path, OffsetMismatch::Missing));
+                }
+                Err(mismatch) => {
+                    // 从头重新读取
+                    restored.insert(path.clone(), FileWatchInfo::default());
+                    mismatches.push((path, mismatch));
+                }
+            }
+        }
+
+        let total = restored.len();
+        self.shared_state
+            .lock()
+            .unwrap()
+            .file_statistic
+            .files_watched = restored;
+
+        let msg = if mismatches.is_empty() {
+            format!("Restored {} watched files", total)
+        } else {
+            let files: Vec<String> = mismatches
+                .iter()
+                .map(|(path, mismatch)| format!("{} ({:?})", path.display(), mismatch))
+                .collect();
+            format!(
+                "Restored {} watched files, {} reset: {}",
+                total,
+                mismatches.len(),
+                files.join(", ")
+            )
+        };
+        log!(self.shared_state, Info, msg);
+        mismatches
+    }
+
+    /// Whether the worker is running but hasn't sent a heartbeat within `STALL_THRESHOLD`.
+    pub fn is_stalled(&self) -> bool {
+        self.shared_state
+            .lock()
+            .unwrap()
+            .is_stalled(STALL_THRESHOLD)
+    }
+
+    // 线程中运行，所有目录共用一个 watcher，事件汇入同一处理流程
     fn inner_observer(
         shared_state: Arc<Mutex<ObSharedState>>,
-        path: PathBuf,
+        recorded_files: Arc<Mutex<RecordedFiles>>,
+        retry_queue: Arc<Mutex<RetryQueue>>,
+        paths: Vec<PathBuf>,
+        site: Option<SiteConfig>,
+        op_limit: OperationLimit,
         poll_duration: Option<Duration>,
     ) -> Result<()> {
         let rt = tokio::runtime::Runtime::new().unwrap();
         rt.block_on(async {
             let (tx, rx) = mpsc::channel::<Result<NotifyEvent>>();
-            let mut watcher = notify::recommended_watcher(tx).unwrap();
-            // 设为轮询模式
-            if let Some(duration) = poll_duration {
-                watcher
-                    .configure(notify::Config::default().with_poll_interval(duration))
-                    .unwrap();
+            // 网络共享上收不到系统通知，改为定期检查
+            let created: Result<(Box<dyn Watcher>, String)> = match poll_duration {
+                Some(duration) => notify::PollWatcher::new(
+                    tx,
+                    notify::Config::default().with_poll_interval(duration),
+                )
+                .map(|w| {
+                    let msg = format!("Watching in poll mode, every {:?}", duration);
+                    (Box::new(w) as Box<dyn Watcher>, msg)
+                }),
+                None => notify::recommended_watcher(tx).map(|w| {
+                    let msg = "Watching in native mode".to_string();
+                    (Box::new(w) as Box<dyn Watcher>, msg)
+                }),
+            };
+            // 系统通知句柄用尽时创建失败，停止而不是让线程崩溃
+            let (mut watcher, msg) = match created {
+                Ok(created) => created,
+                Err(e) => {
+                    log!(
+                        shared_state,
+                        Error,
+                        format!("Failed to create watcher: {}", e)
+                    );
+                    shared_state
+                        .lock()
+                        .unwrap()
+                        .set_status(Stopped, Some("failed to create watcher"));
+                    return Err(e);
+                }
+            };
+            log!(shared_state, Info, msg);
+            shared_state.lock().unwrap().poll_interval = poll_duration;
+            let (mode, follow) = {
+                let ss = shared_state.lock().unwrap();
+                let mode = if ss.recursive {
+                    RecursiveMode::Recursive
+                } else {
+                    RecursiveMode::NonRecursive
+                };
+                (mode, ss.follow_symlink)
+            };
+            let mut follower = if follow {
+                SymlinkFollower::new(&paths)
+            } else {
+                SymlinkFollower::default()
+            };
+            for link in follower.links() {
+                let msg = format!("Following symlink {:?} to {:?}", link.observed, link.target);
+                log!(shared_state, Info, msg);
+            }
+            // 跟随的链接改为监视链接和目标所在的目录
+            let plain_paths: Vec<PathBuf> = paths
+                .iter()
+                .filter(|p| !follower.follows(p))
+                .cloned()
+                .collect();
+            let mut link_dirs = follower.watch_dirs();
+            let watches = plain_paths
+                .iter()
+                .map(|p| (p, mode))
+                .chain(link_dirs.iter().map(|d| (d, RecursiveMode::NonRecursive)));
+            for (path, mode) in watches {
+                // 网络共享上的日志目录可能暂时不可用
+                let watched = WATCH_BACKOFF
+                    .retry(|attempt| {
+                        let result = watcher.watch(path, mode);
+                        if let Err(e) = &result {
+                            let msg =
+                                format!("Failed to watch {:?} on attempt {}: {}", path, attempt, e);
+                            log!(shared_state, Warning, msg);
+                        }
+                        std::future::ready(result)
+                    })
+                    .await;
+                if let Err(e) = watched {
+                    let msg = format!("Gave up watching {:?}: {}", path, e);
+                    log!(shared_state, Error, msg);
+                    shared_state
+                        .lock()
+                        .unwrap()
+                        .set_status(Stopped, Some("gave up watching the log directory"));
+                    return Err(e);
+                }
             }
-            watcher.watch(&path, RecursiveMode::NonRecursive).unwrap();
 
             let ss_clone = shared_state.clone();
             let should_stop_future = async move {
@@ -188,119 +1311,207 @@ impl LogObserver {
                     if should_stop == Stopped {
                         break;
                     }
-                    tokio::task::yield_now().await;
+                    tokio::time::sleep(STOP_POLL).await;
                 }
             };
 
             let ss_clone2 = shared_state.clone();
+            let watcher_ref = &mut watcher;
             let iterate_future = async move {
-                let max_files_watched = load_config().file_sync_manager.max_observed_files;
+                let shared_config = ss_clone2.lock().unwrap().config.clone();
+                let mut config = ss_clone2.lock().unwrap().current_config();
+                ss_clone2.lock().unwrap().file_statistic.path_mapping = config.path_mapping;
+                let mut observe_glob = Self::build_glob_set(&ss_clone2, &config.observe_file_glob);
+                // 本次运行中已提示过被忽略的路径
+                let mut ignored_paths = HashSet::new();
                 'outer: loop {
-                    match rx.recv_timeout(Duration::from_millis(500)) {
+                    ss_clone2.lock().unwrap().heartbeat();
+                    // Stopping 时不再等待新事件，只处理通道中已收到的事件
+                    let draining = ss_clone2.lock().unwrap().status == Stopping;
+                    let received = if draining {
+                        rx.try_recv().map_err(|e| match e {
+                            mpsc::TryRecvError::Empty => mpsc::RecvTimeoutError::Timeout,
+                            mpsc::TryRecvError::Disconnected => {
+                                mpsc::RecvTimeoutError::Disconnected
+                            }
+                        })
+                    } else {
+                        rx.recv_timeout(STOP_POLL)
+                    };
+                    let paths = match received {
+                        // 跟随的链接本身变化时重新解析，读取它现在指向的文件
+                        Ok(Ok(event)) if event.paths.iter().any(|p| follower.is_link(p)) => {
+                            let msg = format!(
+                                "Notify event: {:?} on followed symlink {:?}",
+                                event.kind, event.paths
+                            );
+                            log!(ss_clone2, ModifiedFile, msg);
+                            Self::refresh_links(
+                                &ss_clone2,
+                                &mut follower,
+                                &mut **watcher_ref,
+                                &mut link_dirs,
+                            );
+                            let targets = event.paths.iter().filter_map(|p| follower.target_of(p));
+                            match targets.map(Path::to_path_buf).next() {
+                                Some(target) => vec![target],
+                                None => continue,
+                            }
+                        }
+                        Ok(Ok(NotifyEvent {
+                            kind: EventKind::Modify(ModifyKind::Name(mode)),
+                            paths,
+                            ..
+                        })) => {
+                            let tag = ss_clone2.lock().unwrap().root_tag(&paths);
+                            let msg =
+                                format!("{}Notify event: rename {:?}, {:?}", tag, mode, paths);
+                            log!(ss_clone2, ModifiedFile, msg);
+                            // 轮换时旧文件被改名，不再存在的路径不再跟踪，新文件在首次修改时加入
+                            for path in paths.iter().filter(|p| !p.exists()) {
+                                ss_clone2
+                                    .lock()
+                                    .unwrap()
+                                    .forget_watched_file(path, "was renamed away");
+                            }
+                            continue;
+                        }
+                        Ok(Ok(NotifyEvent {
+                            kind: EventKind::Remove(rkind),
+                            paths,
+                            ..
+                        })) => {
+                            let tag = ss_clone2.lock().unwrap().root_tag(&paths);
+                            let msg =
+                                format!("{}Notify event: remove {:?}, {:?}", tag, rkind, paths);
+                            log!(ss_clone2, DeletedFile, msg);
+                            // 删除的文件不再占用 max_files_watched 的名额
+                            for path in &paths {
+                                ss_clone2
+                                    .lock()
+                                    .unwrap()
+                                    .forget_watched_file(path, "was deleted");
+                            }
+                            continue;
+                        }
+                        Ok(Ok(NotifyEvent {
+                            kind: EventKind::Create(ckind),
+                            paths,
+                            ..
+                        })) => {
+                            let tag = ss_clone2.lock().unwrap().root_tag(&paths);
+                            let msg =
+                                format!("{}Notify event: create {:?}, {:?}", tag, ckind, paths);
+                            log!(ss_clone2, CreatedFile, msg);
+                            // 已跟踪的路径被重新创建说明日志被轮换，新文件从头读取
+                            for path in &paths {
+                                ss_clone2.lock().unwrap().reset_rotated_file(
+                                    path,
+                                    0,
+                                    "was created again",
+                                );
+                            }
+                            // 新建的日志立即读取创建时已有的内容，不等第一次修改
+                            if !paths.first().is_some_and(|p| p.is_file()) {
+                                continue;
+                            }
+                            paths
+                        }
                         Ok(Ok(NotifyEvent {
                             kind: EventKind::Modify(ckind),
                             paths,
                             ..
                         })) => {
+                            let tag = ss_clone2.lock().unwrap().root_tag(&paths);
                             let msg = format!(
-                                "Notify event: {:?}, {:?}",
+                                "{}Notify event: {:?}, {:?}",
+                                tag,
                                 EventKind::Modify(ckind),
                                 paths
                             );
                             log!(ss_clone2, ModifiedFile, msg);
-
-                            let path = paths[0].clone();
-
-                            // update and get old file size
-                            let old_file_size = ss_clone2
-                                .lock()
-                                .unwrap()
-                                .update_file_watchinfo(&path, max_files_watched)
-                                .unwrap_or_default()
-                                .file_size;
-
-                            let current_file_size = ss_clone2
-                                .lock()
-                                .unwrap()
-                                .file_statistic
-                                .files_watched
-                                .get(&path)
-                                .unwrap()
-                                .file_size;
-
-                            let msg = format!(
-                                "File watched updated from {} bytes to {}",
-                                old_file_size, current_file_size
-                            );
-                            log!(ss_clone2, Info, msg);
-
-                            // get file's size and last_read_pos
-                            let (last_read_pos, file_size) = {
-                                let ss = ss_clone2.lock().unwrap();
-                                ss.file_statistic
-                                    .files_watched
-                                    .get(&path)
-                                    .cloned()
-                                    .map(|info| (info.last_read_pos, info.file_size))
-                                    .unwrap_or((0, 0))
-                            };
-
-                            // if the Observer is stopped, break the loop
-                            if ss_clone2.lock().unwrap().status == Stopped {
-                                break 'outer;
-                            }
-
-                            // iterate the file's path strings
-                            if file_size > last_read_pos {
-                                let paths_stream =
-                                    Box::pin(Self::extract_path_stream(&path, last_read_pos).await);
-
-                                ss_clone2.lock().unwrap().set_files_reading(&path);
-                                // collect the paths
-                                let paths_and_offset: Vec<(PathBuf, u64)> =
-                                    paths_stream.collect().await;
-
-                                let paths: Vec<PathBuf> =
-                                    paths_and_offset.iter().map(|f| f.0.clone()).collect();
-                                registry::update_file_infos_to_db(paths).await.unwrap();
-
-                                // the offset is the file's size
-                                let offset = file_size;
-                                let last_offset = ss_clone2
-                                    .lock()
-                                    .unwrap()
-                                    .set_file_watchinfo(
-                                        &path,
-                                        FileWatchInfo {
-                                            last_read_pos: offset,
-                                            file_size,
-                                        },
-                                    )
-                                    .unwrap_or(FileWatchInfo {
-                                        last_read_pos: 0,
-                                        file_size: 0,
-                                    })
-                                    .last_read_pos;
-
-                                let bytes_read = offset - last_offset;
-
-                                let msg = format!("Read {} bytes from file {:?}", bytes_read, path);
-                                log!(ss_clone2, Info, msg);
-
+                            paths
+                        }
+                        Ok(_) => continue,
+                        Err(mpsc::RecvTimeoutError::Timeout) => {
+                            if draining {
                                 ss_clone2
                                     .lock()
                                     .unwrap()
-                                    .add_file_got(paths_and_offset.len());
+                                    .set_status(Stopped, Some("drained"));
+                                log!(ss_clone2, Info, "Observer drained".to_string());
+                                break 'outer;
+                            }
+                            if ss_clone2.lock().unwrap().status == Stopped {
+                                break 'outer;
                             }
+                            continue;
                         }
-                        Ok(_) => {}
-                        Err(mpsc::RecvTimeoutError::Timeout) => continue,
                         Err(e) => {
                             let msg = format!("Error: {:?}", e);
                             log!(ss_clone2, Error, msg);
                             break;
                         }
+                    };
+
+                    // if the Observer is stopped, break the loop
+                    if ss_clone2.lock().unwrap().status == Stopped {
+                        break 'outer;
+                    }
+
+                    // 配置热加载后从下一个事件起生效
+                    if let Some(shared_config) = &shared_config {
+                        let latest = shared_config.read().unwrap().file_sync_manager.clone();
+                        if latest.observe_file_glob != config.observe_file_glob {
+                            observe_glob =
+                                Self::build_glob_set(&ss_clone2, &latest.observe_file_glob);
+                        }
+                        config = latest;
+                        ss_clone2.lock().unwrap().file_statistic.path_mapping = config.path_mapping;
+                    }
+                    let site = Self::current_site(&config, site.as_ref());
+                    let (options, source) = Self::site_options(&config, site.as_ref());
+
+                    // 只因跟随链接而监视的目录中，其他文件不读取
+                    if !follower.links().is_empty()
+                        && !follower.is_target(&paths[0])
+                        && !plain_paths.iter().any(|p| paths[0].starts_with(p))
+                    {
+                        continue;
+                    }
+                    if !Self::is_observed_file(&observe_glob, &paths[0]) {
+                        if ignored_paths.insert(paths[0].clone()) {
+                            let msg = format!(
+                                "Ignored file not matching observe_file_glob: {:?}",
+                                paths[0]
+                            );
+                            log!(ss_clone2, Debug, msg);
+                        }
+                        continue;
                     }
+
+                    Self::handle_modified_file(
+                        &ss_clone2,
+                        &recorded_files,
+                        &paths[0],
+                        options,
+                        |paths| {
+                            Self::sink_paths(
+                                &ss_clone2,
+                                &op_limit,
+                                Some((&retry_queue, &source)),
+                                paths,
+                                |paths| {
+                                    registry::update_logged_paths_to_db(
+                                        paths,
+                                        source.clone(),
+                                        &config,
+                                    )
+                                },
+                            )
+                        },
+                    )
+                    .await;
                 }
             };
 
@@ -309,283 +1520,3519 @@ impl LogObserver {
             log!(shared_state, Stop, "Observer stopped".to_string());
 
             drop(watcher);
-        });
-        Ok(())
-    }
-
-    // 读取指定路径中从指定偏移量开始的内容，并提取FTP接收的文件路径
-    async fn extract_path_stream(
-        path: &PathBuf,
-        offset: u64,
-    ) -> impl stream::Stream<Item = (PathBuf, u64)> + '_ {
-        let file = fs::File::open(path).await.unwrap();
-        let mut reader = BufReader::new(file);
-        reader.seek(SeekFrom::Start(offset)).await.unwrap();
-
-        stream::unfold(
-            (reader, offset),
-            move |(mut reader, mut current_offset)| async move {
-                loop {
-                    let mut line = String::new();
-                    match reader.read_line(&mut line).await {
-                        Ok(0) => return None, // EOF
-                        Ok(n) => {
-                            let new_offset = current_offset + n as u64;
-
-                            if let Some(words) = line.split_once("STOR 226 ") {
-                                let path_str = words.1.trim_end();
-                                return Some((
-                                    (Self::handle_pathstring(path_str), new_offset),
-                                    (reader, new_offset),
-                                ));
-                            }
-                            current_offset = new_offset;
-                        }
-                        Err(e) => {
-                            eprintln!("Error reading log line: {}", e);
-                            return None;
-                        }
-                    }
-                }
-            },
-        )
+            Ok(())
+        })
     }
 
-    fn handle_pathstring(path: &str) -> PathBuf {
-        // 转换为windows风格
-        // 因IIS FTP日志会将文件路径字符串中的空格替换为 +
-        let path = path.replace('/', r#"\"#).replace('+', " ");
-
-        // 读取配置
-        let prefix_map = load_config().file_sync_manager.prefix_map_of_extract_path;
-
-        // 遍历所有映射，优先非"default"
-        for (_key, pair) in prefix_map.iter().filter(|(k, _)| *k != "default") {
-            let (from, to) = (&pair[0], &pair[1]);
-            if path.starts_with(from) && !from.is_empty() {
-                let replaced = format!("{}{}", to, path.trim_start_matches(from));
-                return PathBuf::from(replaced);
-            }
+    /// Resolve the followed links again after an event on one of them, logging those
+    /// now pointing elsewhere and moving the watches to the directories they need.
+    fn refresh_links(
+        shared_state: &Arc<Mutex<ObSharedState>>,
+        follower: &mut SymlinkFollower,
+        watcher: &mut dyn Watcher,
+        link_dirs: &mut Vec<PathBuf>,
+    ) {
+        for repointed in follower.refresh() {
+            let msg = format!(
+                "Symlink {:?} now points to {:?}, was {:?}",
+                repointed.link, repointed.new, repointed.old
+            );
+            log!(shared_state, Info, msg);
         }
-        // 没有匹配到则用"default"
-        if let Some(pair) = prefix_map.get("default") {
-            let (from, to) = (&pair[0], &pair[1]);
-            let replaced = format!("{}{}", to, path.trim_start_matches(from));
-            return PathBuf::from(replaced);
+        let dirs = follower.watch_dirs();
+        for dir in link_dirs.iter().filter(|d| !dirs.contains(d)) {
+            let _ = watcher.unwatch(dir);
         }
-        // 没有default则原样返回
-        PathBuf::from(path)
+        for dir in dirs.iter().filter(|d| !link_dirs.contains(d)) {
+            if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                let msg = format!("Failed to watch {:?}: {}", dir, e);
+                log!(shared_state, Error, msg);
+            }
+        }
+        *link_dirs = dirs;
     }
 
-    pub fn set_launch_time(&self) {
-        self.shared_state.lock().unwrap().launch_time = Utc::now().with_timezone(TIME_ZONE);
-    }
+    // 线程中运行，日志源为命名管道时逐行读取，不跟踪偏移量
+    fn inner_pipe_observer(
+        shared_state: Arc<Mutex<ObSharedState>>,
+        recorded_files: Arc<Mutex<RecordedFiles>>,
+        retry_queue: Arc<Mutex<RetryQueue>>,
+        path: PathBuf,
+        site: Option<SiteConfig>,
+        op_limit: OperationLimit,
+    ) -> Result<()> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let config = shared_state.lock().unwrap().current_config();
+        let (options, source) = Self::site_options(&config, site.as_ref());
+        rt.block_on(Self::observe_pipe(
+            &shared_state,
+            &recorded_files,
+            &path,
+            options,
+            |paths| {
+                Self::sink_paths(
+                    &shared_state,
+                    &op_limit,
+                    Some((&retry_queue, &source)),
+                    paths,
+                    |paths| registry::update_logged_paths_to_db(paths, source.clone(), &config),
+                )
+            },
+        ));
+        // 等待写端时的阻塞打开或读取无法取消，不等它们结束
+        rt.shutdown_background();
 
-    pub fn get_lunch_time(&self) -> String {
-        self.shared_state
+        shared_state
             .lock()
             .unwrap()
-            .launch_time
-            .format("%Y-%m-%d %H:%M:%S")
-            .to_string()
-    }
-
-    pub fn get_elapsed_time(&self) -> String {
-        let ss = self.shared_state.lock().unwrap();
-        format!(
-            "{}h {}m {}s",
-            ss.elapsed_time.num_seconds() / 3600,
-            (ss.elapsed_time.num_seconds() % 3600) / 60,
-            ss.elapsed_time.num_seconds() % 60
-        )
-    }
-
-    pub fn reset_time(&self) {
-        let mut ss = self.shared_state.lock().unwrap();
-        ss.reset_time();
-    }
-
-    pub fn set_status(&self, status: ProgressStatus) {
-        self.shared_state.lock().unwrap().set_status(status);
-    }
-
-    pub fn get_status(&self) -> ProgressStatus {
-        self.shared_state.lock().unwrap().get_status()
+            .set_status(Stopped, Some("pipe reader stopped"));
+        log!(shared_state, Stop, "Observer stopped".to_string());
+        Ok(())
     }
 
-    pub fn files_got(&self) -> usize {
-        self.shared_state.lock().unwrap().file_statistic.files_got
-    }
+    // 线程中运行，通过 SFTP 跟踪远程日志文件
+    #[cfg(feature = "sftp")]
+    fn inner_remote_observer(
+        shared_state: Arc<Mutex<ObSharedState>>,
+        recorded_files: Arc<Mutex<RecordedFiles>>,
+        retry_queue: Arc<Mutex<RetryQueue>>,
+        remote: RemoteLog,
+        site: Option<SiteConfig>,
+        op_limit: OperationLimit,
+    ) -> Result<()> {
+        let mut reader =
+            match crate::apps::file_sync_manager::remote_log::SftpReader::connect(&remote) {
+                Ok(reader) => reader,
+                Err(e) => {
+                    let msg = format!("Failed to connect to {}: {}", remote.host, e);
+                    log!(shared_state, Error, msg);
+                    shared_state
+                        .lock()
+                        .unwrap()
+                        .set_status(Stopped, Some("remote connection failed"));
+                    return Err(notify::Error::io(e));
+                }
+            };
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let config = shared_state.lock().unwrap().current_config();
+        let (options, source) = Self::site_options(&config, site.as_ref());
+        let name = PathBuf::from(format!(
+            "ssh://{}@{}:{}{}",
+            remote.user, remote.host, remote.port, remote.path
+        ));
+        rt.block_on(Self::observe_remote(
+            &shared_state,
+            &recorded_files,
+            &name,
+            &mut reader,
+            options,
+            |paths| {
+                Self::sink_paths(
+                    &shared_state,
+                    &op_limit,
+                    Some((&retry_queue, &source)),
+                    paths,
+                    |paths| registry::update_logged_paths_to_db(paths, source.clone(), &config),
+                )
+            },
+        ));
 
-    pub fn file_reading(&self) -> PathBuf {
-        self.shared_state
+        shared_state
             .lock()
             .unwrap()
-            .file_statistic
-            .file_reading
-            .clone()
+            .set_status(Stopped, Some("remote reader stopped"));
+        log!(shared_state, Stop, "Observer stopped".to_string());
+        Ok(())
     }
 
-    pub fn files_recorded(&self) -> usize {
-        self.shared_state
-            .lock()
-            .unwrap()
-            .file_statistic
-            .files_recorded
+    /// Pass `paths` to `write` once `op_limit` has a free slot, or in dry-run mode only
+    /// log them.
+    ///
+    /// The mode is checked for every batch, so toggling it applies from the next batch.
+    async fn sink_paths<W, Fut>(
+        shared_state: &Arc<Mutex<ObSharedState>>,
+        op_limit: &OperationLimit,
+        retry: Option<(&Arc<Mutex<RetryQueue>>, &Option<RecordSource>)>,
+        paths: Vec<LoggedPath>,
+        write: W,
+    ) -> std::io::Result<Vec<FileInfo>>
+    where
+        W: FnOnce(Vec<LoggedPath>) -> Fut,
+        Fut: Future<Output = std::io::Result<Vec<FileInfo>>>,
+    {
+        if shared_state.lock().unwrap().dry_run {
+            return Ok(Self::dry_run_sink(shared_state, &paths));
+        }
+        let _permit = op_limit
+            .acquire(|| {
+                log!(
+                    shared_state,
+                    Info,
+                    "Waiting for other scans or DB writes to finish".to_string()
+                )
+            })
+            .await;
+        let Some((retry_queue, source)) = retry else {
+            return write(paths).await;
+        };
+        // 写入失败的路径进入重试队列
+        let result = write(paths.clone()).await;
+        if let Err(e) = &result {
+            retry_queue
+                .lock()
+                .unwrap()
+                .add_failed(paths, source.clone(), &e.to_string());
+        }
+        result
     }
 
-    pub fn get_logs_str(&self) -> Vec<String> {
-        let logs = &self.shared_state.lock().unwrap().logs;
-        logs.get_raw_list_string()
+    /// Log each path as a would-be insert and count it, recording nothing.
+    fn dry_run_sink(
+        shared_state: &Arc<Mutex<ObSharedState>>,
+        paths: &[LoggedPath],
+    ) -> Vec<FileInfo> {
+        for path in paths {
+            let path = match path {
+                LoggedPath::Mapped { path, .. } => path.display().to_string(),
+                LoggedPath::Raw(raw) => raw.clone(),
+            };
+            log!(
+                shared_state,
+                Info,
+                format!("Dry run, would insert {}", path)
+            );
+        }
+        shared_state.lock().unwrap().file_statistic.dry_run_paths += paths.len();
+        Vec::new()
     }
 
-    pub fn get_logs_item(&self) -> Vec<OneEvent> {
-        self.shared_state.lock().unwrap().logs.get_raw_list().into()
+    /// Check that every prefix map destination, the site's included, is inside the
+    /// allowed destination roots.
+    fn check_destination_roots(
+        config: &FileMonitorConfig,
+        site: Option<&SiteConfig>,
+    ) -> std::result::Result<(), String> {
+        let roots = &config.allowed_destination_roots;
+        dest_roots::check_roots(roots)?;
+        dest_roots::check_prefix_map(&config.prefix_map_of_extract_path, roots)?;
+        if let Some(prefix_map) = site.and_then(|s| s.prefix_map_of_extract_path.as_ref()) {
+            dest_roots::check_prefix_map(prefix_map, roots)?;
+        }
+        Ok(())
     }
-}
 
-impl ObSharedState {
-    fn add_logs(&mut self, event: OneEvent) {
-        self.logs.add_raw_item(event);
+    /// Observe options with the site's prefix map, and the source to record paths under.
+    /// `site` as it is in `config`, which may have been reloaded since the observer started.
+    fn current_site(config: &FileMonitorConfig, site: Option<&SiteConfig>) -> Option<SiteConfig> {
+        let site = site?;
+        let latest = config
+            .site_configs()
+            .ok()
+            .and_then(|sites| sites.into_iter().find(|s| s.name == site.name));
+        Some(latest.unwrap_or_else(|| site.clone()))
     }
 
-    /// Set or init watch file's `FileStatistics` if not exist, and return the old value.
-    fn update_file_watchinfo(
-        &mut self,
-        path: &PathBuf,
-        max_files_watched: usize,
-    ) -> Option<FileWatchInfo> {
-        let file_size = std::fs::metadata(path).unwrap().len();
+    fn site_options<'a>(
+        config: &'a FileMonitorConfig,
+        site: Option<&'a SiteConfig>,
+    ) -> (ObserveOptions<'a>, Option<RecordSource>) {
+        let mut options = ObserveOptions::from_config(config);
+        if let Some(prefix_map) = site.and_then(|s| s.prefix_map_of_extract_path.as_ref()) {
+            options.prefix_map = Some(prefix_map);
+        }
+        let source = site
+            .and_then(|s| s.source_tag.clone())
+            .map(RecordSource::Site);
+        (options, source)
+    }
 
-        let file_watch_info = if let Some(info) = self.file_statistic.files_watched.get(path) {
-            FileWatchInfo {
-                last_read_pos: info.last_read_pos,
-                file_size,
+    /// Record the STOR lines written to the pipe at `path` as they arrive, until the
+    /// writer closes it or the observer stops.
+    ///
+    /// A pipe has no size and can't seek, so no offsets are tracked.
+    async fn observe_pipe<F, Fut>(
+        shared_state: &Arc<Mutex<ObSharedState>>,
+        recorded_files: &Arc<Mutex<RecordedFiles>>,
+        path: &Path,
+        options: ObserveOptions<'_>,
+        mut sink: F,
+    ) where
+        F: FnMut(Vec<LoggedPath>) -> Fut,
+        Fut: Future<Output = std::io::Result<Vec<FileInfo>>>,
+    {
+        shared_state.lock().unwrap().file_statistic.path_mapping = options.path_mapping;
+        let msg = format!("Reading log lines from pipe {:?}", path);
+        log!(shared_state, Info, msg);
+
+        // 没有写端时打开会一直阻塞
+        let file = {
+            let open = fs::File::open(path);
+            tokio::pin!(open);
+            loop {
+                tokio::select! {
+                    file = &mut open => break file,
+                    _ = tokio::time::sleep(PIPE_POLL) => {
+                        if !Self::pipe_keep_running(shared_state) {
+                            return;
+                        }
+                    }
+                }
             }
-        } else {
-            FileWatchInfo {
-                last_read_pos: 0,
-                file_size,
+        };
+        let file = match file {
+            Ok(file) => file,
+            Err(e) => {
+                let msg = format!("Failed to open pipe {:?}: {}", path, e);
+                log!(shared_state, Error, msg);
+                return;
+            }
+        };
+        shared_state.lock().unwrap().set_files_reading(path);
+        let custom = shared_state.lock().unwrap().line_parser.clone();
+        let matcher = match LineMatcher::with_custom(custom, options) {
+            Ok(matcher) => matcher,
+            Err(e) => {
+                log!(shared_state, Error, e);
+                return;
             }
         };
 
-        // 插入前检查容量，超出则移除最早的
-        if !self.file_statistic.files_watched.contains_key(path)
-            && self.file_statistic.files_watched.len() >= max_files_watched
-        {
-            // 移除最早插入的项
-            self.file_statistic.files_watched.shift_remove_index(0);
+        let mut reader = BufReader::new(file);
+        let mut line_no = 0;
+        let mut offset = 0;
+        loop {
+            let mut buf = Vec::new();
+            let read = {
+                let read = reader.read_until(b'\n', &mut buf);
+                tokio::pin!(read);
+                loop {
+                    tokio::select! {
+                        read = &mut read => break read,
+                        _ = tokio::time::sleep(PIPE_POLL) => {
+                            if !Self::pipe_keep_running(shared_state) {
+                                return;
+                            }
+                        }
+                    }
+                }
+            };
+            let n = match read {
+                Ok(0) => {
+                    let msg = format!("Pipe {:?} closed by the writer", path);
+                    log!(shared_state, Info, msg);
+                    return;
+                }
+                Ok(n) => n,
+                Err(e) => {
+                    let msg = format!("Failed to read pipe {:?}: {}", path, e);
+                    log!(shared_state, Error, msg);
+                    return;
+                }
+            };
+            line_no += 1;
+            shared_state.lock().unwrap().file_statistic.bytes_read += n as u64;
+
+            let line = String::from_utf8_lossy(&buf);
+            match matcher.parse(&line, line_no, offset) {
+                Some(Ok((raw, _))) => {
+                    Self::record_raw_paths(
+                        shared_state,
+                        recorded_files,
+                        path,
+                        &[raw.as_str()],
+                        options,
+                        &mut sink,
+                    )
+                    .await;
+                }
+                Some(Err(e)) if options.strict_parse => {
+                    let msg = format!("Malformed STOR line in {:?}, {}", path, e);
+                    log!(shared_state, Warning, msg);
+                }
+                _ => {}
+            }
+            offset += n as u64;
+        }
+    }
+
+    /// Record the STOR lines appended to the remote log behind `reader`, from its size at
+    /// the first poll on, until the observer stops or reading fails.
+    ///
+    /// The size is polled again right away while there is new content, and every
+    /// `REMOTE_POLL` otherwise.
+    #[cfg_attr(not(feature = "sftp"), allow(dead_code))]
+    async fn observe_remote<F, Fut>(
+        shared_state: &Arc<Mutex<ObSharedState>>,
+        recorded_files: &Arc<Mutex<RecordedFiles>>,
+        name: &Path,
+        reader: &mut dyn RemoteReader,
+        options: ObserveOptions<'_>,
+        mut sink: F,
+    ) where
+        F: FnMut(Vec<LoggedPath>) -> Fut,
+        Fut: Future<Output = std::io::Result<Vec<FileInfo>>>,
+    {
+        shared_state.lock().unwrap().file_statistic.path_mapping = options.path_mapping;
+        let msg = format!("Tailing remote log {}", name.display());
+        log!(shared_state, Info, msg);
+        shared_state.lock().unwrap().set_files_reading(name);
+        let custom = shared_state.lock().unwrap().line_parser.clone();
+        let matcher = match LineMatcher::with_custom(custom, options) {
+            Ok(matcher) => matcher,
+            Err(e) => {
+                log!(shared_state, Error, e);
+                return;
+            }
+        };
+
+        let mut tail = RemoteTail::new();
+        while Self::pipe_keep_running(shared_state) {
+            let (lines, read) = match tail.poll(reader) {
+                Ok(polled) => polled,
+                Err(e) => {
+                    let msg = format!("Failed to read remote log {}: {}", name.display(), e);
+                    log!(shared_state, Error, msg);
+                    return;
+                }
+            };
+            shared_state.lock().unwrap().file_statistic.bytes_read += read;
+
+            for line in lines {
+                match matcher.parse(&line.text, line.line_no, line.offset) {
+                    Some(Ok((raw, _))) => {
+                        Self::record_raw_paths(
+                            shared_state,
+                            recorded_files,
+                            name,
+                            &[raw.as_str()],
+                            options,
+                            &mut sink,
+                        )
+                        .await;
+                    }
+                    Some(Err(e)) if options.strict_parse => {
+                        let msg = format!("Malformed STOR line in {}, {}", name.display(), e);
+                        log!(shared_state, Warning, msg);
+                    }
+                    _ => {}
+                }
+            }
+            if read == 0 {
+                tokio::time::sleep(REMOTE_POLL).await;
+            }
+        }
+    }
+
+    /// Beat the heartbeat, returning whether the pipe reader should keep waiting.
+    fn pipe_keep_running(shared_state: &Arc<Mutex<ObSharedState>>) -> bool {
+        let mut ss = shared_state.lock().unwrap();
+        ss.heartbeat();
+        ss.elapsed_time = Utc::now().with_timezone(TIME_ZONE) - ss.launch_time;
+        matches!(ss.status, Running(_))
+    }
+
+    /// Compile the observe globs, skipping and reporting invalid patterns.
+    fn build_glob_set(shared_state: &Arc<Mutex<ObSharedState>>, patterns: &[String]) -> GlobSet {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            match Glob::new(pattern) {
+                Ok(glob) => {
+                    builder.add(glob);
+                }
+                Err(e) => {
+                    let msg = format!("Invalid observe_file_glob {:?}: {}", pattern, e);
+                    log!(shared_state, Error, msg);
+                }
+            }
+        }
+        builder.build().unwrap_or_else(|_| GlobSet::empty())
+    }
+
+    /// Whether the file name of `path` matches one of the observe globs.
+    fn is_observed_file(glob_set: &GlobSet, path: &Path) -> bool {
+        path.file_name()
+            .is_some_and(|name| glob_set.is_match(Path::new(name)))
+    }
+
+    /// Read the new content of a modified log file and hand the extracted paths to `sink`.
+    ///
+    /// No lock is held across an `.await`, and `shared_state` and `recorded_files` are never
+    /// locked at the same time, so the UI can always take a status snapshot promptly.
+    async fn handle_modified_file<F, Fut>(
+        shared_state: &Arc<Mutex<ObSharedState>>,
+        recorded_files: &Arc<Mutex<RecordedFiles>>,
+        path: &Path,
+        options: ObserveOptions<'_>,
+        sink: F,
+    ) where
+        F: FnOnce(Vec<LoggedPath>) -> Fut,
+        Fut: Future<Output = std::io::Result<Vec<FileInfo>>>,
+    {
+        let custom = shared_state.lock().unwrap().line_parser.clone();
+        let matcher = match LineMatcher::with_custom(custom, options) {
+            Ok(matcher) => matcher,
+            Err(e) => {
+                log!(shared_state, Error, e);
+                return;
+            }
+        };
+        // 在加锁前读取元数据，避免慢速IO阻塞其他线程
+        let current_file_size = match fs::metadata(path).await {
+            Ok(meta) => meta.len(),
+            Err(e) => {
+                let msg = format!("Failed to read metadata of {:?}: {}", path, e);
+                log!(shared_state, Error, msg);
+                return;
+            }
+        };
+        // 与记录的开头比较，发现截断后又写入超过原读取位置的情况
+        let start = FileHead::read_start(path, FILE_HEAD_LEN).ok();
+
+        // update and get old file size
+        let old_info = {
+            let mut ss = shared_state.lock().unwrap();
+            if ss.is_duplicate_event(path, current_file_size, options.dedup_window) {
+                drop(ss);
+                let msg = format!("Skipped duplicate modify event for {:?}", path);
+                log!(shared_state, Debug, msg);
+                return;
+            }
+            ss.update_file_watchinfo(
+                path,
+                options.max_files_watched,
+                current_file_size,
+                start.as_deref(),
+            )
+            .unwrap_or_default()
+        };
+
+        let msg = format!(
+            "File watched updated from {} to {}",
+            options.size_units.format(old_info.file_size),
+            options.size_units.format(current_file_size)
+        );
+        log!(shared_state, Info, msg);
+
+        let (last_read_pos, file_size) = (old_info.last_read_pos, current_file_size);
+
+        // iterate the file's path strings
+        if file_size > last_read_pos {
+            matcher.set_w3c_fields(old_info.fields);
+            // 读取元数据后文件仍可能被删除
+            let paths_stream = match Self::extract_path_stream(path, &matcher, last_read_pos).await
+            {
+                Ok(stream) => Box::pin(stream),
+                Err(e) => {
+                    let msg = format!("Failed to read {:?}: {}", path, e);
+                    log!(shared_state, Error, msg);
+                    return;
+                }
+            };
+
+            shared_state.lock().unwrap().set_files_reading(path);
+            // collect the paths
+            let (extracted, parse_errors): (Vec<_>, Vec<_>) = paths_stream
+                .collect::<Vec<_>>()
+                .await
+                .into_iter()
+                .partition(|r| r.is_ok());
+            let extracted: Vec<ExtractedPath> = extracted.into_iter().flatten().collect();
+            if options.strict_parse && !parse_errors.is_empty() {
+                // 行号相对于本次读取的起点，补上之前的行数
+                let base_line = Self::count_lines_before(path, last_read_pos).await;
+                for e in parse_errors.into_iter().filter_map(|r| r.err()) {
+                    let e = ParseError {
+                        line: base_line + e.line,
+                        ..e
+                    };
+                    let msg = format!("Malformed STOR line in {:?}, {}", path, e);
+                    log!(shared_state, Warning, msg);
+                }
+            }
+
+            let raws: Vec<&str> = extracted.iter().map(|e| e.path.as_str()).collect();
+            if !Self::record_raw_paths(shared_state, recorded_files, path, &raws, options, sink)
+                .await
+            {
+                return;
+            }
+            let head = FileHead::read(path, FILE_HEAD_LEN).ok();
+
+            // the offset is the file's size
+            let offset = file_size;
+            let last_offset = {
+                let mut ss = shared_state.lock().unwrap();
+                let root = ss.root_of(path);
+                let last_offset = ss
+                    .set_file_watchinfo(
+                        path,
+                        FileWatchInfo {
+                            last_read_pos: offset,
+                            file_size,
+                            head,
+                            fields: matcher.w3c_fields(),
+                            root,
+                        },
+                    )
+                    .unwrap_or_default()
+                    .last_read_pos;
+                // 写入成功后立即保存，重启时不再重复写入这一批
+                ss.save_batch_checkpoint();
+                last_offset
+            };
+
+            let bytes_read = offset - last_offset;
+
+            shared_state.lock().unwrap().file_statistic.bytes_read += bytes_read;
+            let msg = format!(
+                "Read {} from file {:?}",
+                options.size_units.format(bytes_read),
+                path
+            );
+            log!(shared_state, Info, msg);
+        }
+    }
+
+    /// Map and filter FTP paths read from `source`, then record them through `sink`.
+    ///
+    /// Returns `false` if the sink failed, which is logged.
+    async fn record_raw_paths<F, Fut>(
+        shared_state: &Arc<Mutex<ObSharedState>>,
+        recorded_files: &Arc<Mutex<RecordedFiles>>,
+        source: &Path,
+        raws: &[&str],
+        options: ObserveOptions<'_>,
+        sink: F,
+    ) -> bool
+    where
+        F: FnOnce(Vec<LoggedPath>) -> Fut,
+        Fut: Future<Output = std::io::Result<Vec<FileInfo>>>,
+    {
+        let mut paths: Vec<LoggedPath> = raws
+            .iter()
+            .filter_map(|raw| {
+                let path = Self::map_logged_path(raw, options);
+                // 映射后的路径不能离开允许的目标根目录
+                if let LoggedPath::Mapped { path: mapped, .. } = &path
+                    && let Err(e) = dest_roots::check_destination(mapped, options.allowed_roots)
+                {
+                    log!(shared_state, Error, format!("Rejected {}: {}", raw, e));
+                    return None;
+                }
+                Some(path)
+            })
+            .collect();
+        if options.skip_hidden {
+            paths.retain(|p| {
+                let hidden = match p {
+                    LoggedPath::Mapped { path, .. } => is_hidden_path(path),
+                    LoggedPath::Raw(raw) => is_hidden_path(Path::new(raw)),
+                };
+                if hidden {
+                    let msg = format!("Skipped hidden path {:?}", p);
+                    log!(shared_state, Debug, msg);
+                }
+                !hidden
+            });
+        }
+        for path in &paths {
+            if let LoggedPath::Mapped { path, .. } = path {
+                let len = path.as_os_str().len();
+                if options.max_path_len > 0 && len > options.max_path_len {
+                    let msg = format!(
+                        "Mapped path is {} chars, longer than {}: {}",
+                        len,
+                        options.max_path_len,
+                        path.display()
+                    );
+                    log!(shared_state, Warning, msg);
+                }
+            }
+        }
+        shared_state
+            .lock()
+            .unwrap()
+            .add_logged_paths(options.path_mapping, &paths);
+        let recorded = if paths.is_empty() {
+            Ok(Vec::new())
+        } else {
+            sink(paths).await
+        };
+        let recorded = match recorded {
+            Ok(recorded) => recorded,
+            Err(e) => {
+                let msg = format!("Failed to record paths from {:?}: {}", source, e);
+                log!(shared_state, Error, msg);
+                return false;
+            }
+        };
+        recorded_files.lock().unwrap().record(&recorded);
+        let mut ss = shared_state.lock().unwrap();
+        ss.add_file_recorded(recorded.len());
+        ss.add_file_got(source, raws.len());
+        true
+    }
+
+    // 统计 offset 之前的行数，仅在诊断模式下出现格式错误时调用
+    async fn count_lines_before(path: &Path, offset: u64) -> u64 {
+        if offset == 0 {
+            return 0;
+        }
+        match fs::read(path).await {
+            Ok(content) => {
+                let end = (offset as usize).min(content.len());
+                content[..end].iter().filter(|b| **b == b'\n').count() as u64
+            }
+            Err(_) => 0,
+        }
+    }
+
+    // 读取指定路径中从指定偏移量开始的内容，并提取FTP接收的文件路径
+    //
+    // 包含 STOR 却不符合格式的行以 ParseError 返回，行号从 offset 处开始计
+    async fn extract_path_stream<'a>(
+        path: &'a Path,
+        matcher: &'a LineMatcher,
+        offset: u64,
+    ) -> std::io::Result<
+        impl stream::Stream<Item = std::result::Result<ExtractedPath, ParseError>> + 'a,
+    > {
+        let file = fs::File::open(path).await?;
+        let mut reader = BufReader::new(file);
+        reader.seek(SeekFrom::Start(offset)).await?;
+
+        Ok(stream::unfold(
+            (reader, offset, 0u64),
+            move |(mut reader, mut current_offset, mut line_no)| async move {
+                loop {
+                    let mut line = String::new();
+                    match reader.read_line(&mut line).await {
+                        Ok(0) => return None, // EOF
+                        Ok(n) => {
+                            let new_offset = current_offset + n as u64;
+                            line_no += 1;
+
+                            match matcher.parse(&line, line_no, current_offset) {
+                                Some(Ok((path, time))) => {
+                                    let extracted = ExtractedPath {
+                                        path,
+                                        offset: new_offset,
+                                        time,
+                                    };
+                                    return Some((Ok(extracted), (reader, new_offset, line_no)));
+                                }
+                                Some(Err(error)) => {
+                                    return Some((Err(error), (reader, new_offset, line_no)));
+                                }
+                                None => {}
+                            }
+                            current_offset = new_offset;
+                        }
+                        Err(e) => {
+                            eprintln!("Error reading log line: {}", e);
+                            return None;
+                        }
+                    }
+                }
+            },
+        ))
+    }
+
+    /// The FTP path `parser` finds in `line`. A line with a marker's verb that
+    /// doesn't match is an error, any other line is `None`.
+    fn parse_stor_line(
+        line: &str,
+        parser: &IisFtpParser,
+        line_no: u64,
+        offset: u64,
+    ) -> Option<std::result::Result<String, ParseError>> {
+        if let Some(path_str) = parser.extract_path(line) {
+            return Some(Ok(path_str.to_string()));
+        }
+        if !line.starts_with('#')
+            && line
+                .split_whitespace()
+                .any(|w| parser.verbs().any(|v| v == w))
+        {
+            return Some(Err(ParseError {
+                line: line_no,
+                offset,
+                content: line.trim_end().to_string(),
+            }));
+        }
+        None
+    }
+
+    /// Turn an FTP path from the log into what gets recorded under `mapping`.
+    fn map_logged_path(raw: &str, options: ObserveOptions<'_>) -> LoggedPath {
+        let rewrite = |raw| Self::map_prefix(raw, options.prefix_map.unwrap_or(&HashMap::new()));
+        match options.path_mapping {
+            PathMapping::Rewrite => LoggedPath::Mapped {
+                path: rewrite(raw),
+                source: None,
+            },
+            PathMapping::RecordRaw => LoggedPath::Raw(Self::normalize_raw_path(raw)),
+            PathMapping::Both => LoggedPath::Mapped {
+                path: rewrite(raw),
+                source: Some(Self::normalize_raw_path(raw)),
+            },
+        }
+    }
+
+    // 原样记录时只还原 IIS 转义的字符，保留 FTP 风格的分隔符
+    fn normalize_raw_path(raw: &str) -> String {
+        Self::decode_logged_path(raw)
+    }
+
+    /// Undo IIS's escaping of a logged path: `+` for a space, then `%XX` sequences,
+    /// so `%2B` stays a literal plus. A path that doesn't decode to UTF-8 keeps its
+    /// `%XX` sequences.
+    fn decode_logged_path(raw: &str) -> String {
+        let spaced = raw.replace('+', " ");
+        match percent_decode_str(&spaced).decode_utf8() {
+            Ok(decoded) => decoded.into_owned(),
+            Err(_) => spaced,
+        }
+    }
+
+    /// Convert an FTP path to a local path using `prefix_map`.
+    fn map_prefix(path: &str, prefix_map: &HashMap<String, [String; 2]>) -> PathBuf {
+        // 先还原 IIS 转义的字符，解码出的 / 也是分隔符，再转换为windows风格
+        let path = Self::decode_logged_path(path).replace('/', r#"\"#);
+
+        // 遍历所有映射，优先非"default"
+        for (_key, pair) in prefix_map.iter().filter(|(k, _)| *k != "default") {
+            let (from, to) = (&pair[0], &pair[1]);
+            if path.starts_with(from) && !from.is_empty() {
+                let replaced = format!("{}{}", to, path.trim_start_matches(from));
+                return PathBuf::from(replaced);
+            }
+        }
+        // 没有匹配到则用"default"
+        if let Some(pair) = prefix_map.get("default") {
+            let (from, to) = (&pair[0], &pair[1]);
+            let replaced = format!("{}{}", to, path.trim_start_matches(from));
+            return PathBuf::from(replaced);
+        }
+        // 没有default则原样返回
+        PathBuf::from(path)
+    }
+
+    /// Signal the observer thread to stop and wait for it up to `CLOSE_TIMEOUT`.
+    pub fn close(&mut self) {
+        let Some(handle) = self.handle.take() else {
+            return;
+        };
+
+        self.set_status(Stopped, Some("observer closed"));
+        if let Some(supervisor) = self.supervisor.take() {
+            let _ = join_with_timeout(supervisor, CLOSE_TIMEOUT);
+        }
+
+        match join_with_timeout(handle, CLOSE_TIMEOUT) {
+            Some(Ok(Ok(()))) => self.reset_time(),
+            Some(Ok(Err(e))) => {
+                log!(
+                    self.shared_state,
+                    Error,
+                    format!("Observer exited with error: {:?}", e)
+                );
+            }
+            Some(Err(_)) => {
+                log!(
+                    self.shared_state,
+                    Error,
+                    "Observer thread panicked before close".to_string()
+                );
+            }
+            None => {
+                log!(
+                    self.shared_state,
+                    Error,
+                    format!("Observer thread didn't exit within {:?}", CLOSE_TIMEOUT)
+                );
+            }
+        }
+        self.save_checkpoint();
+    }
+
+    /// Whether the worker is running or still finishing received events.
+    pub fn is_busy(&self) -> bool {
+        matches!(self.get_status(), Running(_) | Stopping)
+            || self.handle.as_ref().is_some_and(|h| !h.is_finished())
+    }
+
+    /// Stop waiting for new notify events, the worker still handles the ones already received.
+    ///
+    /// Returns whether the observer was running.
+    pub fn stop_input(&self) -> bool {
+        let mut ss = self.shared_state.lock().unwrap();
+        if self.handle.is_none() || !matches!(ss.status, Running(_)) {
+            return false;
+        }
+        ss.set_status(Stopping, Some("shutting down"));
+        true
+    }
+
+    /// Wait up to `timeout` for the worker to handle the received events and exit.
+    pub fn drain(&mut self, timeout: Duration) -> PhaseOutcome {
+        let Some(handle) = self.handle.take() else {
+            return PhaseOutcome::Skipped;
+        };
+        if self.get_status() != Stopping {
+            self.set_status(Stopped, Some("draining"));
+        }
+
+        match join_with_timeout(handle, timeout) {
+            Some(Ok(Ok(()))) => {
+                self.reset_time();
+                PhaseOutcome::Done
+            }
+            Some(Ok(Err(e))) => {
+                PhaseOutcome::Failed(format!("observer exited with error: {:?}", e))
+            }
+            Some(Err(_)) => PhaseOutcome::Failed("observer thread panicked".to_string()),
+            None => {
+                // 放弃剩余事件，让分离的线程尽快退出
+                self.set_status(Stopped, Some("drain timed out"));
+                PhaseOutcome::TimedOut
+            }
+        }
+    }
+
+    /// Wait up to `timeout` for the heartbeat supervisor to exit.
+    pub fn stop_supervisor(&mut self, timeout: Duration) -> PhaseOutcome {
+        let Some(supervisor) = self.supervisor.take() else {
+            return PhaseOutcome::Skipped;
+        };
+        if matches!(self.get_status(), Running(_)) {
+            self.set_status(Stopped, Some("supervisor stopped"));
+        }
+        match join_with_timeout(supervisor, timeout) {
+            Some(Ok(())) => PhaseOutcome::Done,
+            Some(Err(_)) => PhaseOutcome::Failed("supervisor thread panicked".to_string()),
+            None => PhaseOutcome::TimedOut,
+        }
+    }
+
+    pub fn set_launch_time(&self) {
+        self.shared_state.lock().unwrap().launch_time = Utc::now().with_timezone(TIME_ZONE);
+    }
+
+    pub fn get_lunch_time(&self) -> String {
+        self.snapshot().lunch_time_str()
+    }
+
+    pub fn get_elapsed_time(&self) -> String {
+        self.snapshot().elapsed_time_str()
+    }
+
+    pub fn reset_time(&self) {
+        let mut ss = self.shared_state.lock().unwrap();
+        ss.reset_time();
+    }
+
+    pub fn set_status(&self, status: ProgressStatus, reason: Option<&str>) {
+        self.shared_state.lock().unwrap().set_status(status, reason);
+    }
+
+    pub fn get_status(&self) -> ProgressStatus {
+        self.shared_state.lock().unwrap().get_status()
+    }
+
+    pub fn files_got(&self) -> usize {
+        self.shared_state.lock().unwrap().file_statistic.files_got
+    }
+
+    pub fn file_reading(&self) -> PathBuf {
+        self.shared_state
+            .lock()
+            .unwrap()
+            .file_statistic
+            .file_reading
+            .clone()
+    }
+
+    pub fn files_recorded(&self) -> usize {
+        self.shared_state
+            .lock()
+            .unwrap()
+            .file_statistic
+            .files_recorded
+    }
+
+    pub fn snapshot(&self) -> ObStatusSnapshot {
+        self.shared_state.lock().unwrap().snapshot()
+    }
+
+    pub fn get_logs_str(&self) -> Vec<String> {
+        let logs = &self.shared_state.lock().unwrap().logs;
+        logs.get_raw_list_string()
+    }
+
+    pub fn get_logs_item(&self) -> Vec<OneEvent> {
+        self.shared_state.lock().unwrap().logs.get_raw_list().into()
+    }
+
+    pub fn add_logs(&self, event: OneEvent) {
+        self.shared_state.lock().unwrap().add_logs(event);
+    }
+
+    /// The observer's recent status transitions, oldest first.
+    pub fn status_history(&self) -> Vec<StatusTransition> {
+        self.shared_state.lock().unwrap().status_history.snapshot()
+    }
+}
+
+impl Drop for LogObserver {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl ObSharedState {
+    /// Position up to which `path` has been read, `None` if it isn't watched.
+    pub fn last_read_pos(&self, path: &Path) -> Option<u64> {
+        self.file_statistic
+            .files_watched
+            .get(path)
+            .map(|info| info.last_read_pos)
+    }
+
+    fn add_logs(&mut self, event: OneEvent) {
+        toast_on_error(&event);
+        self.logs.add_raw_item(event);
+        notify_ui();
+    }
+
+    /// Whether `path` was already handled at the same `size` within `window`.
+    ///
+    /// Records the event otherwise, and forgets events older than `window`.
+    fn is_duplicate_event(&mut self, path: &Path, size: u64, window: Duration) -> bool {
+        if window.is_zero() {
+            return false;
+        }
+        let now = Instant::now();
+        self.recent_events
+            .retain(|_, (_, at)| now.duration_since(*at) < window);
+        match self.recent_events.get(path) {
+            Some((last_size, _)) if *last_size == size => true,
+            _ => {
+                self.recent_events.insert(path.to_path_buf(), (size, now));
+                false
+            }
+        }
+    }
+
+    fn heartbeat(&mut self) {
+        self.last_heartbeat = Instant::now();
+    }
+
+    fn is_stalled(&self, threshold: Duration) -> bool {
+        matches!(self.status, Running(_)) && self.last_heartbeat.elapsed() > threshold
+    }
+
+    pub fn snapshot(&self) -> ObStatusSnapshot {
+        ObStatusSnapshot {
+            stalled: self.is_stalled(STALL_THRESHOLD),
+            status: self.status,
+            launch_time: self.launch_time,
+            elapsed_time: self.elapsed_time,
+            files_got: self.file_statistic.files_got,
+            files_got_by_root: self
+                .roots
+                .iter()
+                .map(|root| {
+                    let got = self.file_statistic.files_got_by_root.get(root);
+                    (root.clone(), got.copied().unwrap_or(0))
+                })
+                .collect(),
+            files_recorded: self.file_statistic.files_recorded,
+            file_reading: self.file_statistic.file_reading.clone(),
+            path_mapping: self.file_statistic.path_mapping,
+            paths_rewritten: self.file_statistic.paths_rewritten,
+            paths_raw: self.file_statistic.paths_raw,
+            bytes_read: self.file_statistic.bytes_read,
+            dry_run: self.dry_run,
+            dry_run_paths: self.file_statistic.dry_run_paths,
+            recursive: self.recursive,
+            poll_interval: self.poll_interval,
+        }
+    }
+
+    /// Set or init watch file's `FileStatistics` if not exist, and return the old value.
+    ///
+    /// A file smaller than its read position was rotated or truncated, so the position
+    /// is reset to the start, in the returned value too.
+    fn update_file_watchinfo(
+        &mut self,
+        path: &Path,
+        max_files_watched: usize,
+        file_size: u64,
+        start: Option<&[u8]>,
+    ) -> Option<FileWatchInfo> {
+        let file_watch_info = if let Some(info) = self.file_statistic.files_watched.get(path) {
+            if file_size < info.last_read_pos {
+                let reason = format!(
+                    "was rotated or truncated ({} < read position {})",
+                    file_size, info.last_read_pos
+                );
+                return self.reset_rotated_file(path, file_size, &reason);
+            }
+            // 没有变小但开头变了，文件已被替换
+            if let (Some(head), Some(start)) = (info.head, start)
+                && !head.matches_start(start)
+            {
+                return self.reset_rotated_file(
+                    path,
+                    file_size,
+                    "was replaced, its first bytes changed",
+                );
+            }
+            FileWatchInfo {
+                file_size,
+                root: info.root.clone().or_else(|| self.root_of(path)),
+                ..info.clone()
+            }
+        } else {
+            FileWatchInfo {
+                file_size,
+                root: self.root_of(path),
+                ..Default::default()
+            }
+        };
+
+        // 插入前检查容量，超出则移除最早的
+        if !self.file_statistic.files_watched.contains_key(path)
+            && self.file_statistic.files_watched.len() >= max_files_watched
+        {
+            // 移除最早插入的项
+            self.file_statistic.files_watched.shift_remove_index(0);
+        }
+
+        self.file_statistic
+            .files_watched
+            .insert(path.to_path_buf(), file_watch_info.clone())
+    }
+
+    /// The hot-reloaded config if there is one, otherwise the config file as it is now.
+    pub fn current_config(&self) -> FileMonitorConfig {
+        match &self.config {
+            Some(config) => config.read().unwrap().file_sync_manager.clone(),
+            None => load_config().file_sync_manager,
+        }
+    }
+
+    /// Write the watched files and their read positions to `path` as JSON.
+    ///
+    /// The file is written next to `path` first and then renamed, so a crash while
+    /// saving leaves the previous checkpoint intact.
+    pub fn save_checkpoint(&self, path: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(&self.file_statistic.files_watched)?;
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, path)
+    }
+
+    /// Save to `path` once `interval` has passed since the last save, `None` if it
+    /// isn't due yet.
+    pub fn save_checkpoint_if_due(
+        &mut self,
+        path: &Path,
+        interval: Duration,
+    ) -> Option<std::io::Result<()>> {
+        if self.last_checkpoint.elapsed() < interval {
+            return None;
+        }
+        self.last_checkpoint = Instant::now();
+        Some(self.save_checkpoint(path))
+    }
+
+    /// Save the checkpoint, if one is configured, after a batch was recorded. A
+    /// persistent failure is reported once.
+    fn save_batch_checkpoint(&mut self) {
+        let Some(path) = self.checkpoint_path.clone() else {
+            return;
+        };
+        match self.save_checkpoint(&path) {
+            Ok(()) => {
+                self.last_checkpoint = Instant::now();
+                self.checkpoint_failed = false;
+            }
+            Err(e) if !self.checkpoint_failed => {
+                self.checkpoint_failed = true;
+                self.add_logs(OneEvent {
+                    kind: LogObserverEvent(Warning),
+                    content: format!("Failed to save checkpoint {}: {}", path.display(), e),
+                    time: Some(Utc::now().with_timezone(TIME_ZONE)),
+                });
+            }
+            Err(_) => {}
+        }
+    }
+
+    /// Read the watched files saved by [`Self::save_checkpoint`].
+    pub fn load_checkpoint(path: &Path) -> std::io::Result<IndexMap<PathBuf, FileWatchInfo>> {
+        let json = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Read a watched `path` from the start again after it was rotated, returning its
+    /// last info with the read position reset, or `None` if it isn't watched.
+    fn reset_rotated_file(
+        &mut self,
+        path: &Path,
+        file_size: u64,
+        reason: &str,
+    ) -> Option<FileWatchInfo> {
+        let info = self.file_statistic.files_watched.get_mut(path)?;
+        let old = FileWatchInfo {
+            last_read_pos: 0,
+            head: None,
+            fields: None,
+            ..info.clone()
+        };
+        *info = FileWatchInfo {
+            file_size,
+            ..Default::default()
+        };
+        self.add_logs(OneEvent {
+            kind: LogObserverEvent(RotatedFile),
+            content: format!("{:?} {}, reading from the start", path, reason),
+            time: Some(Utc::now().with_timezone(TIME_ZONE)),
+        });
+        Some(old)
+    }
+
+    /// Stop tracking `path`, e.g. after it was renamed away, returning its last info.
+    fn forget_watched_file(&mut self, path: &Path, reason: &str) -> Option<FileWatchInfo> {
+        let info = self.file_statistic.files_watched.shift_remove(path)?;
+        self.add_logs(OneEvent {
+            kind: LogObserverEvent(Info),
+            content: format!("{:?} {}, no longer watched", path, reason),
+            time: Some(Utc::now().with_timezone(TIME_ZONE)),
+        });
+        Some(info)
+    }
+
+    fn set_file_watchinfo(&mut self, path: &Path, info: FileWatchInfo) -> Option<FileWatchInfo> {
+        self.file_statistic
+            .files_watched
+            .insert(path.to_path_buf(), info)
+    }
+
+    fn add_logged_paths(&mut self, mapping: PathMapping, paths: &[LoggedPath]) {
+        self.file_statistic.path_mapping = mapping;
+        for path in paths {
+            match path {
+                LoggedPath::Mapped { .. } => self.file_statistic.paths_rewritten += 1,
+                LoggedPath::Raw(_) => self.file_statistic.paths_raw += 1,
+            }
+        }
+    }
+
+    /// The observed path `path` is under, the first one if they are nested.
+    fn root_of(&self, path: &Path) -> Option<PathBuf> {
+        self.roots.iter().find(|r| path.starts_with(r)).cloned()
+    }
+
+    /// `"[root] "` for an event on `paths` when several paths are observed, otherwise empty.
+    fn root_tag(&self, paths: &[PathBuf]) -> String {
+        if self.roots.len() < 2 {
+            return String::new();
+        }
+        match paths.first().and_then(|p| self.root_of(p)) {
+            Some(root) => format!("[{}] ", root.display()),
+            None => String::new(),
+        }
+    }
+
+    fn add_file_got(&mut self, source: &Path, num: usize) {
+        self.file_statistic.files_got += num;
+        if let Some(root) = self.root_of(source) {
+            *self
+                .file_statistic
+                .files_got_by_root
+                .entry(root)
+                .or_default() += num;
+        }
+    }
+
+    fn add_file_recorded(&mut self, num: usize) {
+        self.file_statistic.files_recorded += num;
+    }
+
+    fn get_status(&self) -> ProgressStatus {
+        self.status
+    }
+
+    /// Change the status, recording the transition and logging it with `reason`.
+    fn set_status(&mut self, status: ProgressStatus, reason: Option<&str>) {
+        let from = std::mem::replace(&mut self.status, status);
+        let Some(transition) = self.status_history.record(from, status, reason) else {
+            return;
+        };
+        let event = OneEvent {
+            time: Some(transition.time),
+            kind: LogObserverEvent(Info),
+            content: format!("Status {}", transition),
+        };
+        self.add_logs(event);
+    }
+
+    fn set_files_reading(&mut self, path: &Path) {
+        self.file_statistic.file_reading = path.to_path_buf();
+    }
+
+    fn reset_time(&mut self) {
+        self.launch_time = DateTime::from_timestamp(0, 0)
+            .unwrap()
+            .with_timezone(TIME_ZONE);
+        self.elapsed_time = TimeDelta::zero();
+    }
+}
+
+// MARK: test
+#[tokio::test]
+async fn test_path_construction() {
+    let prefix_map = load_config().file_sync_manager.prefix_map_of_extract_path;
+    let path = LogObserver::map_prefix(
+        "/CTA8280H/TEST-48/DA35_BP85226D_P01DB_TP16D252_250417237_BP85226_P01DB9X_HDJJ13D._PL_20250507_141512.CAT",
+        &prefix_map,
+    );
+
+    let path_ac03 = LogObserver::map_prefix("/AC03/ASDFDSAFDSA.csv", &prefix_map);
+
+    let path_with_whitespace = LogObserver::map_prefix("/OS2000/AS  DFDSAFDSA.csv", &prefix_map);
+
+    // windows iis ftp日志会将路径中间的空格替换为`+`号，将`+`不做处理
+    let path_with_special_char = LogObserver::map_prefix(
+        "/123/++Starting+Space/Mix!@#$%^&()=+{}[];',~_目录/Sub+Folder+中间+空+格/文件_🌟Unicode_引号_&_Sp++ecial_Chars_最终版_v2.0%20@2024",
+        &prefix_map,
+    );
+
+    // 百分号编码：%2B 是字面的加号，%2F 是分隔符
+    for (logged, expected) in [
+        ("/AC03/Lot%20A.csv", "E:\\CusData\\AC03\\Lot A.csv"),
+        (
+            "/AC03/C%2B%2B+Notes.csv",
+            "E:\\CusData\\AC03\\C++ Notes.csv",
+        ),
+        ("/AC03%2FSUB/x.csv", "E:\\CusData\\AC03\\SUB\\x.csv"),
+        (
+            "/OS2000/50%25+off%2Bmore%20now%2f%E7%9B%AE%E5%BD%95.csv",
+            "E:\\testdata\\OS2000\\50% off+more now\\目录.csv",
+        ),
+        // 解码后不是 UTF-8 时保留原样
+        ("/AC03/bad%FF+x.csv", "E:\\CusData\\AC03\\bad%FF x.csv"),
+    ] {
+        assert_eq!(
+            LogObserver::map_prefix(logged, &prefix_map),
+            PathBuf::from(expected),
+            "{}",
+            logged
+        );
+    }
+    assert_eq!(
+        LogObserver::normalize_raw_path("/AC03/C%2B%2B+Notes%2Fv2.csv"),
+        "/AC03/C++ Notes/v2.csv"
+    );
+
+    assert_eq!(
+        PathBuf::from("E:\\CusData\\AC03\\ASDFDSAFDSA.csv"),
+        path_ac03
+    );
+    assert_eq!(
+        PathBuf::from(
+            "E:\\testdata\\CTA8280H\\TEST-48\\DA35_BP85226D_P01DB_TP16D252_250417237_BP85226_P01DB9X_HDJJ13D._PL_20250507_141512.CAT"
+        ),
+        path
+    );
+    assert_eq!(
+        PathBuf::from("E:\\testdata\\OS2000\\AS  DFDSAFDSA.csv"),
+        path_with_whitespace
+    );
+    assert_eq!(
+        PathBuf::from(
+            "E:\\testdata\\123\\  Starting Space\\Mix!@#$%^&()= {}[];',~_目录\\Sub Folder 中间 空 格\\文件_🌟Unicode_引号_&_Sp  ecial_Chars_最终版_v2.0 @2024"
+        ),
+        path_with_special_char
+    );
+}
+
+#[test]
+fn test_file_path() {
+    let path = PathBuf::from("asset\\cfg.json");
+    if !std::fs::exists(&path).unwrap() {
+        eprintln!(
+            "File does not exist, current path is {}, and your path is {}",
+            std::env::current_dir().unwrap().display(),
+            path.display()
+        );
+        panic!();
+    }
+}
+
+#[tokio::test]
+async fn test_extract_path() {
+    assert_eq!(
+    extract_path(
+        "2025-05-07 16:42:15 10.53.2.70 STOR 226 /CTA8280H/TEST-48/DA35_BP85226D_P01DB_TP16D252_250417237_BP85226_P01DB9X_HDJJ13D._PL_20250507_141512.CAT").await,
+        PathBuf::from(
+            "E:\\testdata\\CTA8280H\\TEST-48\\DA35_BP85226D_P01DB_TP16D252_250417237_BP85226_P01DB9X_HDJJ13D._PL_20250507_141512.CAT"
+            ),
+    );
+    assert_eq!(
+        extract_path("2025-05-07 16:42:15 10.53.2.70 STOR 226 /OS2000/AS DFDSAFDSA.csv").await,
+        PathBuf::from("E:\\testdata\\OS2000\\AS DFDSAFDSA.csv"),
+    );
+}
+
+#[tokio::test]
+async fn test_configured_line_markers() {
+    let base = std::env::temp_dir().join("test_configured_line_markers");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&base).unwrap();
+    let file = base.join("u_ex250601.log");
+    std::fs::write(
+        &file,
+        "#Software: Microsoft Internet Information Services 10.0\n\
+         2025-06-01 08:00:00 10.53.2.70 STOR 226 /AC03/A.csv\n\
+         2025-06-01 08:00:01 10.53.2.71 STOR - 226 /AC03/B B.csv \n\
+         2025-06-01 08:00:02 10.53.2.71 RETR - 226 /AC03/C.csv\n\
+         2025-06-01 08:00:03 10.53.2.70 STOR 2260 /AC03/D.csv\n",
+    )
+    .unwrap();
+
+    let extract = async |markers: &[String]| {
+        let matcher = LineMatcher::markers(markers);
+        let (paths, errors): (Vec<_>, Vec<_>) =
+            LogObserver::extract_path_stream(&file, &matcher, 0)
+                .await
+                .unwrap()
+                .collect::<Vec<_>>()
+                .await
+                .into_iter()
+                .partition(|r| r.is_ok());
+        let paths: Vec<String> = paths.into_iter().map(|r| r.unwrap().path).collect();
+        let error_lines: Vec<u64> = errors.into_iter().map(|r| r.unwrap_err().line).collect();
+        (paths, error_lines)
+    };
+    let markers = |m: &[&str]| m.iter().map(|m| m.to_string()).collect::<Vec<_>>();
+
+    // 未配置时只认 STOR 226，另一种格式的行报为格式错误
+    assert_eq!(
+        extract(&[]).await,
+        (vec!["/AC03/A.csv".to_string()], vec![3, 5])
+    );
+    assert_eq!(
+        extract(&markers(&["STOR - 226"])).await,
+        (vec!["/AC03/B B.csv".to_string()], vec![2, 5])
+    );
+    assert_eq!(
+        extract(&markers(&["STOR 226", "STOR - 226"])).await,
+        (
+            vec!["/AC03/A.csv".to_string(), "/AC03/B B.csv".to_string()],
+            vec![5]
+        )
+    );
+
+    // 配置中没有该项时使用默认标记
+    let mut json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("asset/cfg.json").unwrap()).unwrap();
+    json["file_sync_manager"]
+        .as_object_mut()
+        .unwrap()
+        .remove("line_markers");
+    let config: crate::MyConfig = serde_json::from_value(json).unwrap();
+    assert_eq!(
+        config.file_sync_manager.line_markers,
+        [crate::DEFAULT_LINE_MARKER]
+    );
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[tokio::test]
+async fn test_log_pattern_vsftpd() {
+    let base = std::env::temp_dir().join("test_log_pattern_vsftpd");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&base).unwrap();
+    let file = base.join("vsftpd.log");
+    std::fs::write(
+        &file,
+        "Sun Jun  1 08:00:00 2025 [pid 2345] CONNECT: Client \"::ffff:10.53.2.70\"\n\
+         Sun Jun  1 08:00:01 2025 [pid 2344] [ftp] OK LOGIN: Client \"::ffff:10.53.2.70\"\n\
+         Sun Jun  1 08:00:02 2025 [pid 2346] [ftp] OK UPLOAD: Client \"::ffff:10.53.2.70\", \"/AC03/A B.csv\", 1024 bytes, 512.00Kbyte/sec\n\
+         Sun Jun  1 08:00:03 2025 [pid 2346] [ftp] FAIL UPLOAD: Client \"::ffff:10.53.2.70\", \"/AC03/partial.csv\", 0.00Kbyte/sec\n\
+         Sun Jun  1 08:00:04 2025 [pid 2346] [ftp] OK DOWNLOAD: Client \"::ffff:10.53.2.70\", \"/AC03/A B.csv\", 1024 bytes, 1.00Mbyte/sec\n\
+         Sun Jun  1 08:00:05 2025 [pid 2346] [ftp] OK UPLOAD: Client \"::ffff:10.53.2.70\", \"/CTA/目录/C.csv\", 2048 bytes, 1.00Mbyte/sec\n",
+    )
+    .unwrap();
+
+    let mut config: crate::MyConfig =
+        serde_json::from_str(&std::fs::read_to_string("asset/cfg.json").unwrap()).unwrap();
+    let config = &mut config.file_sync_manager;
+    assert_eq!(config.log_pattern, None);
+    assert!(matches!(
+        LineMatcher::new(ObserveOptions::from_config(config)),
+        Ok(LineMatcher::Markers(..))
+    ));
+
+    config.log_pattern = Some(r#"OK UPLOAD: Client "[^"]*", "(?P<path>[^"]+)""#.to_string());
+    let matcher = LineMatcher::new(ObserveOptions::from_config(config)).unwrap();
+    let results: Vec<_> = LogObserver::extract_path_stream(&file, &matcher, 0)
+        .await
+        .unwrap()
+        .collect()
+        .await;
+    // 其余行不匹配也不报为格式错误
+    let paths: Vec<_> = results.into_iter().map(|r| r.unwrap()).collect();
+    assert_eq!(paths.len(), 2);
+    assert_eq!(paths[0].path, "/AC03/A B.csv");
+    assert_eq!(paths[1].path, "/CTA/目录/C.csv");
+    assert_eq!(paths[1].offset, std::fs::metadata(&file).unwrap().len());
+
+    // proftpd 的 xferlog，只取传入且完成的记录
+    std::fs::write(
+        &file,
+        "Sun Jun 01 08:00:00 2025 0 10.53.2.70 1024 /AC03/D.csv b _ i r ftp ftp 0 * c\n\
+         Sun Jun 01 08:00:01 2025 0 10.53.2.70 1024 /AC03/E.csv b _ o r ftp ftp 0 * c\n\
+         Sun Jun 01 08:00:02 2025 0 10.53.2.70 10 /AC03/F.csv b _ i r ftp ftp 0 * i\n",
+    )
+    .unwrap();
+    config.log_pattern = Some(r"\d+ (?P<path>/\S+) [ab] \S+ i .* c$".to_string());
+    let matcher = LineMatcher::new(ObserveOptions::from_config(config)).unwrap();
+    let paths: Vec<_> = LogObserver::extract_path_stream(&file, &matcher, 0)
+        .await
+        .unwrap()
+        .map(|r| r.unwrap().path)
+        .collect()
+        .await;
+    assert_eq!(paths, ["/AC03/D.csv"]);
+
+    for (pattern, error) in [
+        ("OK UPLOAD: (?P<path>", "Invalid log_pattern"),
+        ("OK UPLOAD: (.+)", "has no capture group named path"),
+    ] {
+        config.log_pattern = Some(pattern.to_string());
+        let err = LineMatcher::new(ObserveOptions::from_config(config)).unwrap_err();
+        assert!(err.contains(error), "{}", err);
+    }
+
+    // 显式选择解析方式时以 log_parser 为准
+    config.log_parser = Some(LogParserKind::Iis);
+    assert!(matches!(
+        LineMatcher::new(ObserveOptions::from_config(config)),
+        Ok(LineMatcher::Markers(..))
+    ));
+    config.log_parser = Some(LogParserKind::Regex);
+    config.log_pattern = None;
+    let err = LineMatcher::new(ObserveOptions::from_config(config)).unwrap_err();
+    assert!(err.contains("log_pattern is not set"), "{}", err);
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_custom_line_parser() {
+    // 每行形如 "upload,<path>,<size>" 的自定义格式
+    struct CsvParser;
+    impl LogLineParser for CsvParser {
+        fn extract_path<'l>(&self, line: &'l str) -> Option<&'l str> {
+            let mut columns = line.split(',');
+            (columns.next()? == "upload").then(|| columns.next())?
+        }
+    }
+
+    let base = std::env::temp_dir().join("test_custom_line_parser");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&base).unwrap();
+    let log_file = base.join("transfers.csv");
+    std::fs::write(
+        &log_file,
+        "login,ftp,-\r\nupload,/AC03/a.csv,10\r\ndownload,/AC03/b.csv,5\r\nupload,/AC03/c d.csv,7\r\n",
+    )
+    .unwrap();
+
+    let observer = LogObserver::new(base.clone(), 20).with_line_parser(Arc::new(CsvParser));
+    let logged = Arc::new(Mutex::new(Vec::new()));
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let sink_logged = logged.clone();
+    rt.block_on(LogObserver::handle_modified_file(
+        &observer.shared_state,
+        &observer.recorded_files,
+        &log_file,
+        ObserveOptions {
+            max_files_watched: 10,
+            dedup_window: Duration::ZERO,
+            strict_parse: true,
+            path_mapping: PathMapping::RecordRaw,
+            prefix_map: None,
+            size_units: SizeUnits::Binary,
+            max_path_len: 0,
+            skip_hidden: false,
+            allowed_roots: &[],
+            line_markers: &[],
+            log_pattern: None,
+            log_parser: None,
+        },
+        |paths| async move {
+            sink_logged.lock().unwrap().extend(paths);
+            Ok(Vec::new())
+        },
+    ));
+    assert_eq!(
+        *logged.lock().unwrap(),
+        [
+            LoggedPath::Raw("/AC03/a.csv".to_string()),
+            LoggedPath::Raw("/AC03/c d.csv".to_string())
+        ]
+    );
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_w3c_fields_header() {
+    let base = std::env::temp_dir().join("test_w3c_fields_header");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&base).unwrap();
+    let log_file = base.join("u_ex250601.log");
+    // 列顺序与默认不同，路径中有空格
+    std::fs::write(
+        &log_file,
+        "#Software: Microsoft Internet Information Services 10.0\n\
+         #Version: 1.0\n\
+         #Date: 2025-06-01 00:00:00\n\
+         #Fields: date time cs-uri-stem c-ip cs-username cs-method sc-status x-session\n\
+         2025-06-01 08:00:00 /AC03/A  B.csv 10.53.2.70 ftp STOR 226 s1\n\
+         2025-06-01 08:00:01 /AC03/fail.csv 10.53.2.70 ftp STOR 550 s1\n\
+         2025-06-01 08:00:02 /AC03/down.csv 10.53.2.70 ftp RETR 226 s1\n\
+         2025-06-01 08:00:03 10.53.2.70 STOR\n",
+    )
+    .unwrap();
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let matcher = LineMatcher::markers(&[]);
+    let results: Vec<_> = rt.block_on(async {
+        LogObserver::extract_path_stream(&log_file, &matcher, 0)
+            .await
+            .unwrap()
+            .collect()
+            .await
+    });
+    let (paths, errors): (Vec<_>, Vec<_>) = results.into_iter().partition(|r| r.is_ok());
+    let paths: Vec<ExtractedPath> = paths.into_iter().flatten().collect();
+    assert_eq!(paths.len(), 1);
+    assert_eq!(paths[0].path, "/AC03/A  B.csv");
+    let time = NaiveDateTime::parse_from_str("2025-06-01 08:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    assert_eq!(paths[0].time.map(|t| t.naive_utc()), Some(time));
+    // 列数不足的 STOR 行为格式错误，失败的上传和下载不是
+    let errors: Vec<u64> = errors.into_iter().map(|r| r.unwrap_err().line).collect();
+    assert_eq!(errors, [8]);
+    assert!(matcher.w3c_fields().is_some());
+
+    // 从中间继续读取时沿用之前读到的列
+    let observer = LogObserver::new(base.clone(), 20);
+    let logged = Arc::new(Mutex::new(Vec::new()));
+    let handle = || {
+        let logged = logged.clone();
+        rt.block_on(LogObserver::handle_modified_file(
+            &observer.shared_state,
+            &observer.recorded_files,
+            &log_file,
+            ObserveOptions {
+                max_files_watched: 10,
+                dedup_window: Duration::ZERO,
+                strict_parse: false,
+                path_mapping: PathMapping::RecordRaw,
+                prefix_map: None,
+                size_units: SizeUnits::Binary,
+                max_path_len: 0,
+                skip_hidden: false,
+                allowed_roots: &[],
+                line_markers: &[],
+                log_pattern: None,
+                log_parser: None,
+            },
+            |paths| async move {
+                logged.lock().unwrap().extend(paths);
+                Ok(Vec::new())
+            },
+        ))
+    };
+    handle();
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&log_file)
+        .unwrap();
+    std::io::Write::write_all(
+        &mut file,
+        b"2025-06-01 09:00:00 /AC03/C.csv 10.53.2.70 ftp STOR 226 s2\n",
+    )
+    .unwrap();
+    drop(file);
+    handle();
+    assert_eq!(
+        *logged.lock().unwrap(),
+        [
+            LoggedPath::Raw("/AC03/A  B.csv".to_string()),
+            LoggedPath::Raw("/AC03/C.csv".to_string())
+        ]
+    );
+    // 列的位置随读取位置一起保存
+    let infos = observer.watched_offsets();
+    assert_eq!(infos[0].1.fields, matcher.w3c_fields());
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+/// The prefix map of the default config file.
+#[cfg(test)]
+fn test_prefix_map() -> HashMap<String, [String; 2]> {
+    load_config().file_sync_manager.prefix_map_of_extract_path
+}
+
+#[cfg(test)]
+async fn extract_path(content: &str) -> PathBuf {
+    let base = std::env::temp_dir().join("test_assdfasset");
+    std::fs::create_dir_all(&base).unwrap();
+    let file = base.join("fileasdfsfsadfasd");
+    std::fs::write(&file, content).unwrap();
+
+    let matcher = LineMatcher::markers(&[]);
+    let extracted_paths = LogObserver::extract_path_stream(&file, &matcher, 0)
+        .await
+        .unwrap();
+    futures::pin_mut!(extracted_paths);
+
+    let path = extracted_paths.next().await.unwrap().unwrap();
+    std::fs::remove_dir_all(&base).unwrap();
+    LogObserver::map_prefix(&path.path, &test_prefix_map())
+}
+
+#[test]
+fn test_snapshot_not_blocked_by_slow_sink() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Instant;
+
+    let base = std::env::temp_dir().join("test_snapshot_slow_sink");
+    std::fs::create_dir_all(&base).unwrap();
+    let log_file = base.join("u_ex250507.log");
+    std::fs::write(
+        &log_file,
+        "2025-05-07 16:42:15 10.53.2.70 STOR 226 /AC03/ASDFDSAFDSA.csv\n",
+    )
+    .unwrap();
+
+    let observer = LogObserver::new(base.clone(), 10);
+    let shared_state = observer.shared_state.clone();
+    let recorded_files = observer.recorded_files.clone();
+
+    let done = Arc::new(AtomicBool::new(false));
+    let done_clone = done.clone();
+    let ss_clone = shared_state.clone();
+    let poller = thread::spawn(move || {
+        let mut max_latency = Duration::ZERO;
+        while !done_clone.load(Ordering::SeqCst) {
+            let start = Instant::now();
+            let _ = ss_clone.lock().unwrap().snapshot();
+            max_latency = max_latency.max(start.elapsed());
+            thread::sleep(Duration::from_millis(1));
+        }
+        max_latency
+    });
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let sunk = rt.block_on(async {
+        let sunk = Arc::new(Mutex::new(Vec::new()));
+        let sunk_clone = sunk.clone();
+        LogObserver::handle_modified_file(
+            &shared_state,
+            &recorded_files,
+            &log_file,
+            ObserveOptions {
+                max_files_watched: 10,
+                dedup_window: Duration::ZERO,
+                strict_parse: false,
+                path_mapping: PathMapping::Rewrite,
+                prefix_map: None,
+                size_units: SizeUnits::Binary,
+                max_path_len: 0,
+                skip_hidden: false,
+                allowed_roots: &[],
+                line_markers: &[],
+                log_pattern: None,
+                log_parser: None,
+            },
+            |paths| {
+                async move {
+                    // 模拟慢速数据库写入
+                    tokio::time::sleep(Duration::from_millis(300)).await;
+                    sunk_clone.lock().unwrap().extend(paths);
+                    Ok(Vec::new())
+                }
+            },
+        )
+        .await;
+        sunk
+    });
+
+    done.store(true, Ordering::SeqCst);
+    let max_latency = poller.join().unwrap();
+
+    assert_eq!(sunk.lock().unwrap().len(), 1);
+    assert_eq!(shared_state.lock().unwrap().snapshot().files_got, 1);
+    assert!(
+        max_latency < Duration::from_millis(50),
+        "snapshot latency {:?}",
+        max_latency
+    );
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_observe_file_glob() {
+    let base = std::env::temp_dir().join("test_observe_file_glob");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&base).unwrap();
+
+    let mut observer = LogObserver::new(base.clone(), 10);
+    observer.start_observer().unwrap();
+    // 等待 watcher 就绪
+    thread::sleep(Duration::from_millis(300));
+
+    std::fs::write(base.join("archive.zip"), b"PK\x03\x04").unwrap();
+    std::fs::write(base.join("u_ex250507.log"), b"#Fields: date time\n").unwrap();
+    thread::sleep(Duration::from_millis(1500));
+    observer.close();
+
+    let ss = observer.shared_state.lock().unwrap();
+    let watched: Vec<&PathBuf> = ss.file_statistic.files_watched.keys().collect();
+    assert_eq!(watched, vec![&base.join("u_ex250507.log")]);
+    drop(ss);
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_stalled_worker_detected() {
+    let observer = LogObserver::new(PathBuf::from("."), 10);
+    let threshold = Duration::from_millis(20);
+    {
+        let mut ss = observer.shared_state.lock().unwrap();
+        ss.set_status(Running(crate::Running::Periodic), None);
+        ss.heartbeat();
+        assert!(!ss.is_stalled(threshold));
+    }
+
+    // 工作线程不再更新心跳
+    thread::sleep(Duration::from_millis(50));
+    let mut ss = observer.shared_state.lock().unwrap();
+    assert!(ss.is_stalled(threshold));
+
+    ss.heartbeat();
+    assert!(!ss.is_stalled(threshold));
+
+    // 已停止的观察器不算卡住
+    thread::sleep(Duration::from_millis(50));
+    ss.set_status(Stopped, None);
+    assert!(!ss.is_stalled(threshold));
+}
+
+#[test]
+fn test_duplicate_modify_events_deduped() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let base = std::env::temp_dir().join("test_dedup_modify_events");
+    std::fs::create_dir_all(&base).unwrap();
+    let log_file = base.join("u_ex250508.log");
+    std::fs::write(
+        &log_file,
+        "2025-05-08 09:12:01 10.53.2.70 STOR 226 /AC03/DEDUP.csv\n",
+    )
+    .unwrap();
+
+    let observer = LogObserver::new(base.clone(), 10);
+    let shared_state = observer.shared_state.clone();
+    let recorded_files = observer.recorded_files.clone();
+    let processed = Arc::new(AtomicUsize::new(0));
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        for _ in 0..2 {
+            let processed = processed.clone();
+            LogObserver::handle_modified_file(
+                &shared_state,
+                &recorded_files,
+                &log_file,
+                ObserveOptions {
+                    max_files_watched: 10,
+                    dedup_window: Duration::from_millis(200),
+                    strict_parse: false,
+                    path_mapping: PathMapping::Rewrite,
+                    prefix_map: None,
+                    size_units: SizeUnits::Binary,
+                    max_path_len: 0,
+                    skip_hidden: false,
+                    allowed_roots: &[],
+                    line_markers: &[],
+                    log_pattern: None,
+                    log_parser: None,
+                },
+                |_paths| async move {
+                    processed.fetch_add(1, Ordering::SeqCst);
+                    Ok(Vec::new())
+                },
+            )
+            .await;
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    });
+
+    assert_eq!(processed.load(Ordering::SeqCst), 1);
+    let logs = shared_state.lock().unwrap().logs.get_raw_list_string();
+    let updates = logs
+        .iter()
+        .filter(|l| l.contains("File watched updated"))
+        .count();
+    assert_eq!(updates, 1);
+    assert!(logs.iter().any(|l| l.contains("Skipped duplicate")));
+
+    // 窗口之外或大小变化时照常处理
+    let mut ss = shared_state.lock().unwrap();
+    let window = Duration::from_millis(20);
+    assert!(!ss.is_duplicate_event(&base, 1, window));
+    assert!(ss.is_duplicate_event(&base, 1, window));
+    assert!(!ss.is_duplicate_event(&base, 2, window));
+    thread::sleep(Duration::from_millis(30));
+    assert!(!ss.is_duplicate_event(&base, 2, window));
+    drop(ss);
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_restore_watch_infos() {
+    let base = std::env::temp_dir().join("test_restore_watch_infos");
+    std::fs::create_dir_all(&base).unwrap();
+    let clean = base.join("clean.log");
+    let truncated = base.join("truncated.log");
+    let replaced = base.join("replaced.log");
+    std::fs::write(&clean, "line one\nline two\n").unwrap();
+    std::fs::write(&truncated, "line one\nline two\n").unwrap();
+    std::fs::write(&replaced, "line one\nline two\n").unwrap();
+
+    let mut infos = IndexMap::new();
+    for path in [&clean, &truncated, &replaced] {
+        let head = FileHead::read(path, FILE_HEAD_LEN).unwrap();
+        infos.insert(path.clone(), FileWatchInfo::new(18, 18, Some(head)));
+    }
+    infos.insert(base.join("missing.log"), FileWatchInfo::new(5, 5, None));
+
+    // 停机期间：clean 追加内容，truncated 被轮转，replaced 被同大小的新文件替换
+    std::fs::write(&clean, "line one\nline two\nline three\n").unwrap();
+    std::fs::write(&truncated, "new\n").unwrap();
+    std::fs::write(&replaced, "LINE ONE\nLINE TWO\n").unwrap();
+
+    let observer = LogObserver::new(base.clone(), 10);
+    let mismatches = observer.restore_watch_infos(infos);
+
+    assert_eq!(
+        mismatches,
+        vec![
+            (
+                truncated.clone(),
+                OffsetMismatch::Truncated {
+                    size: 4,
+                    offset: 18
+                }
+            ),
+            (replaced.clone(), OffsetMismatch::Replaced),
+            (base.join("missing.log"), OffsetMismatch::Missing),
+        ]
+    );
+
+    let ss = observer.shared_state.lock().unwrap();
+    let watched = &ss.file_statistic.files_watched;
+    assert_eq!(watched.len(), 3);
+    assert_eq!(watched[&clean].last_read_pos(), 18);
+    assert_eq!(watched[&truncated].last_read_pos(), 0);
+    assert_eq!(watched[&replaced].last_read_pos(), 0);
+
+    let logs = ss.logs.get_raw_list_string();
+    assert_eq!(logs.len(), 1);
+    assert!(logs[0].contains("3 reset"));
+    drop(ss);
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_strict_parse_reports_malformed_stor() {
+    let base = std::env::temp_dir().join("test_strict_parse");
+    std::fs::create_dir_all(&base).unwrap();
+    let log_file = base.join("u_ex250510.log");
+    let first = "#Fields: date time c-ip cs-method cs-uri-stem sc-status\n\
+        2025-05-10 08:00:00 10.53.2.70 STOR 226 /AC03/GOOD1.csv\n";
+    let appended = "2025-05-10 08:00:01 10.53.2.70 USER 331 -\n\
+        2025-05-10 08:00:02 10.53.2.70 STOR 226\n\
+        2025-05-10 08:00:03 10.53.2.70 STOR 226 /AC03/GOOD2.csv\n";
+    let malformed_offset = (first.len() + appended.find("2025-05-10 08:00:02").unwrap()) as u64;
+
+    let run = |strict_parse: bool| {
+        std::fs::write(&log_file, first).unwrap();
+        let observer = LogObserver::new(base.clone(), 20);
+        let shared_state = observer.shared_state.clone();
+        let recorded_files = observer.recorded_files.clone();
+        let options = ObserveOptions {
+            max_files_watched: 10,
+            dedup_window: Duration::ZERO,
+            strict_parse,
+            path_mapping: PathMapping::Rewrite,
+            prefix_map: None,
+            size_units: SizeUnits::Binary,
+            max_path_len: 0,
+            skip_hidden: false,
+            allowed_roots: &[],
+            line_markers: &[],
+            log_pattern: None,
+            log_parser: None,
+        };
+        let sunk = Arc::new(Mutex::new(Vec::new()));
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            for content in [first, appended] {
+                if content == appended {
+                    use std::io::Write;
+                    let mut file = std::fs::OpenOptions::new()
+                        .append(true)
+                        .open(&log_file)
+                        .unwrap();
+                    file.write_all(content.as_bytes()).unwrap();
+                }
+                let sunk = sunk.clone();
+                LogObserver::handle_modified_file(
+                    &shared_state,
+                    &recorded_files,
+                    &log_file,
+                    options,
+                    |paths| async move {
+                        sunk.lock().unwrap().extend(paths);
+                        Ok(Vec::new())
+                    },
+                )
+                .await;
+            }
+        });
+
+        let sunk = sunk.lock().unwrap().len();
+        let logs = shared_state.lock().unwrap().logs.get_raw_list_string();
+        (sunk, logs)
+    };
+
+    let (sunk, logs) = run(true);
+    assert_eq!(sunk, 2);
+    let warnings: Vec<&String> = logs
+        .iter()
+        .filter(|l| l.starts_with("[OBSERVER][WARN]"))
+        .collect();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains(&format!("line 4 (byte {})", malformed_offset)));
+    assert!(warnings[0].ends_with("10.53.2.70 STOR 226"));
+
+    let (sunk, logs) = run(false);
+    assert_eq!(sunk, 2);
+    assert!(!logs.iter().any(|l| l.starts_with("[OBSERVER][WARN]")));
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_path_mapping_modes() {
+    let base = std::env::temp_dir().join("test_path_mapping_modes");
+    std::fs::create_dir_all(&base).unwrap();
+    let log_file = base.join("u_ex250520.log");
+    let content = "2025-05-20 08:00:00 10.53.2.70 STOR 226 /AC03/DAY+SHIFT.csv\n\
+        2025-05-20 08:00:01 10.53.2.70 STOR 226 /OS2000/B.csv\n";
+
+    let prefix_map = test_prefix_map();
+    let run = |path_mapping: PathMapping| {
+        std::fs::write(&log_file, content).unwrap();
+        let observer = LogObserver::new(base.clone(), 20);
+        let shared_state = observer.shared_state.clone();
+        let recorded_files = observer.recorded_files.clone();
+        let options = ObserveOptions {
+            max_files_watched: 10,
+            dedup_window: Duration::ZERO,
+            strict_parse: false,
+            path_mapping,
+            prefix_map: Some(&prefix_map),
+            size_units: SizeUnits::Binary,
+            max_path_len: 0,
+            skip_hidden: false,
+            allowed_roots: &[],
+            line_markers: &[],
+            log_pattern: None,
+            log_parser: None,
+        };
+        let sunk = Arc::new(Mutex::new(Vec::new()));
+        let sunk_clone = sunk.clone();
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(LogObserver::handle_modified_file(
+            &shared_state,
+            &recorded_files,
+            &log_file,
+            options,
+            |paths| async move {
+                sunk_clone.lock().unwrap().extend(paths);
+                Ok(Vec::new())
+            },
+        ));
+
+        let snapshot = shared_state.lock().unwrap().snapshot();
+        let sunk = sunk.lock().unwrap().clone();
+        (sunk, snapshot)
+    };
+
+    let (rewritten, snapshot) = run(PathMapping::Rewrite);
+    assert_eq!(
+        rewritten[0],
+        LoggedPath::Mapped {
+            path: LogObserver::map_prefix("/AC03/DAY+SHIFT.csv", &prefix_map),
+            source: None,
+        }
+    );
+    assert_eq!((snapshot.paths_rewritten, snapshot.paths_raw), (2, 0));
+
+    let (raw, snapshot) = run(PathMapping::RecordRaw);
+    assert_eq!(
+        raw,
+        vec![
+            LoggedPath::Raw("/AC03/DAY SHIFT.csv".to_string()),
+            LoggedPath::Raw("/OS2000/B.csv".to_string()),
+        ]
+    );
+    assert_eq!(snapshot.path_mapping, PathMapping::RecordRaw);
+    assert_eq!((snapshot.paths_rewritten, snapshot.paths_raw), (0, 2));
+    let infos = registry::collect_logged_file_infos(raw);
+    assert_eq!(infos.len(), 2);
+    assert!(infos.iter().all(|i| i.path_is_raw));
+    assert_eq!(infos[0].path, "/AC03/DAY SHIFT.csv");
+    assert_eq!(infos[0].filename, "DAY SHIFT.csv");
+
+    let (both, snapshot) = run(PathMapping::Both);
+    assert_eq!(
+        both[1],
+        LoggedPath::Mapped {
+            path: LogObserver::map_prefix("/OS2000/B.csv", &prefix_map),
+            source: Some("/OS2000/B.csv".to_string()),
+        }
+    );
+    assert_eq!((snapshot.paths_rewritten, snapshot.paths_raw), (2, 0));
+    // 改写后的路径在本机不存在，用日志文件本身验证 source_path 的传递
+    let infos = registry::collect_logged_file_infos(vec![LoggedPath::Mapped {
+        path: log_file.clone(),
+        source: Some("/OS2000/B.csv".to_string()),
+    }]);
+    assert_eq!(infos[0].source_path.as_deref(), Some("/OS2000/B.csv"));
+    assert!(!infos[0].path_is_raw);
+
+    // 站点的前缀映射优先于配置文件
+    let site_map = HashMap::from([(
+        "default".to_string(),
+        [r"\".to_string(), r"D:\site2\".to_string()],
+    )]);
+    let options = ObserveOptions {
+        max_files_watched: 10,
+        dedup_window: Duration::ZERO,
+        strict_parse: false,
+        path_mapping: PathMapping::Rewrite,
+        prefix_map: Some(&site_map),
+        size_units: SizeUnits::Binary,
+        max_path_len: 0,
+        skip_hidden: false,
+        allowed_roots: &[],
+        line_markers: &[],
+        log_pattern: None,
+        log_parser: None,
+    };
+    assert_eq!(
+        LogObserver::map_logged_path("/OS2000/B.csv", options),
+        LoggedPath::Mapped {
+            path: PathBuf::from(r"D:\site2\OS2000\B.csv"),
+            source: None,
+        }
+    );
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_long_mapped_path_warns() {
+    let base = std::env::temp_dir().join("test_long_mapped_path_warns");
+    std::fs::create_dir_all(&base).unwrap();
+    let log_file = base.join("u_ex250601.log");
+    std::fs::write(
+        &log_file,
+        "2025-06-01 08:00:00 10.53.2.70 STOR 226 /A.csv\n\
+         2025-06-01 08:00:01 10.53.2.70 STOR 226 /deep/nested/directory/B.csv\n",
+    )
+    .unwrap();
+    let prefix_map = HashMap::from([(
+        "default".to_string(),
+        [r"\".to_string(), r"E:\".to_string()],
+    )]);
+
+    let observer = LogObserver::new(base.clone(), 20);
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(LogObserver::handle_modified_file(
+        &observer.shared_state,
+        &observer.recorded_files,
+        &log_file,
+        ObserveOptions {
+            max_files_watched: 10,
+            dedup_window: Duration::ZERO,
+            strict_parse: false,
+            path_mapping: PathMapping::Rewrite,
+            prefix_map: Some(&prefix_map),
+            size_units: SizeUnits::Binary,
+            max_path_len: 20,
+            skip_hidden: false,
+            allowed_roots: &[],
+            line_markers: &[],
+            log_pattern: None,
+            log_parser: None,
+        },
+        |_paths| async move { Ok(Vec::new()) },
+    ));
+
+    let logs = observer
+        .shared_state
+        .lock()
+        .unwrap()
+        .logs
+        .get_raw_list_string();
+    let warnings: Vec<&String> = logs
+        .iter()
+        .filter(|l| l.starts_with("[OBSERVER][WARN]"))
+        .collect();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains(r"longer than 20: E:\deep\nested\directory\B.csv"));
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_observe_fifo() {
+    use std::io::Write;
+
+    let base = std::env::temp_dir().join("test_observe_fifo");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&base).unwrap();
+    let fifo = base.join("u_ex250601.log");
+    let status = std::process::Command::new("mkfifo")
+        .arg(&fifo)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(is_pipe(&fifo));
+    assert!(!is_pipe(&base));
+
+    let writer = {
+        let fifo = fifo.clone();
+        thread::spawn(move || {
+            let mut pipe = std::fs::OpenOptions::new().write(true).open(&fifo).unwrap();
+            writeln!(
+                pipe,
+                "#Fields: date time c-ip cs-method sc-status cs-uri-stem"
+            )
+            .unwrap();
+            writeln!(pipe, "2025-06-01 08:00:00 10.53.2.70 STOR 226 /A.csv").unwrap();
+            pipe.flush().unwrap();
+            thread::sleep(Duration::from_millis(100));
+            writeln!(pipe, "2025-06-01 08:00:01 10.53.2.70 RETR 226 /B.csv").unwrap();
+            writeln!(pipe, "2025-06-01 08:00:02 10.53.2.70 STOR 226 /dir/C+1.csv").unwrap();
+        })
+    };
+
+    let observer = LogObserver::new(base.clone(), 20);
+    observer.set_status(Running(crate::Running::Periodic), None);
+    let logged = Arc::new(Mutex::new(Vec::new()));
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(LogObserver::observe_pipe(
+        &observer.shared_state,
+        &observer.recorded_files,
+        &fifo,
+        ObserveOptions {
+            max_files_watched: 10,
+            dedup_window: Duration::ZERO,
+            strict_parse: false,
+            path_mapping: PathMapping::RecordRaw,
+            prefix_map: None,
+            size_units: SizeUnits::Binary,
+            max_path_len: 0,
+            skip_hidden: false,
+            allowed_roots: &[],
+            line_markers: &[],
+            log_pattern: None,
+            log_parser: None,
+        },
+        |paths| {
+            let logged = logged.clone();
+            async move {
+                logged.lock().unwrap().extend(paths);
+                Ok(Vec::new())
+            }
+        },
+    ));
+    writer.join().unwrap();
+
+    // 写端关闭后读取结束
+    assert_eq!(
+        *logged.lock().unwrap(),
+        [
+            LoggedPath::Raw("/A.csv".to_string()),
+            LoggedPath::Raw("/dir/C 1.csv".to_string()),
+        ]
+    );
+    assert_eq!(observer.files_got(), 2);
+    let logs = observer.get_logs_str();
+    assert!(logs.iter().any(|l| l.contains("closed by the writer")));
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_dry_run_skips_db_writes() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let base = std::env::temp_dir().join("test_dry_run_skips_db_writes");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&base).unwrap();
+    let log_file = base.join("u_ex250601.log");
+    std::fs::write(
+        &log_file,
+        "2025-06-01 08:00:00 10.53.2.70 STOR 226 /AC03/A.csv\n\
+         2025-06-01 08:00:01 10.53.2.70 STOR 226 /AC03/B.csv\n",
+    )
+    .unwrap();
+
+    let observer = LogObserver::new(base.clone(), 50);
+    observer.set_dry_run(true);
+    let op_limit = OperationLimit::default();
+    let writes = AtomicUsize::new(0);
+    let options = ObserveOptions {
+        max_files_watched: 10,
+        dedup_window: Duration::ZERO,
+        strict_parse: false,
+        path_mapping: PathMapping::RecordRaw,
+        prefix_map: None,
+        size_units: SizeUnits::Binary,
+        max_path_len: 0,
+        skip_hidden: false,
+        allowed_roots: &[],
+        line_markers: &[],
+        log_pattern: None,
+        log_parser: None,
+    };
+    let handle = |rt: &tokio::runtime::Runtime| {
+        rt.block_on(LogObserver::handle_modified_file(
+            &observer.shared_state,
+            &observer.recorded_files,
+            &log_file,
+            options,
+            |paths| {
+                LogObserver::sink_paths(&observer.shared_state, &op_limit, None, paths, |paths| {
+                    writes.fetch_add(1, Ordering::SeqCst);
+                    std::future::ready(Ok(paths
+                        .iter()
+                        .map(|_| FileInfo::from_raw("/AC03/C.csv"))
+                        .collect()))
+                })
+            },
+        ))
+    };
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    handle(&rt);
+
+    // 整条管道照常运行，只是没有写入
+    assert_eq!(writes.load(Ordering::SeqCst), 0);
+    let snapshot = observer.snapshot();
+    assert!(snapshot.dry_run);
+    assert_eq!(snapshot.files_got, 2);
+    assert_eq!(snapshot.paths_raw, 2);
+    assert_eq!(snapshot.dry_run_paths, 2);
+    assert_eq!(snapshot.files_recorded, 0);
+    let logs = observer.get_logs_str();
+    for path in ["/AC03/A.csv", "/AC03/B.csv"] {
+        assert!(
+            logs.iter()
+                .any(|l| l.ends_with(&format!("Dry run, would insert {}", path)))
+        );
+    }
+
+    // 关闭后从下一批开始写入
+    observer.set_dry_run(false);
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&log_file)
+        .unwrap();
+    std::io::Write::write_all(
+        &mut file,
+        b"2025-06-01 08:00:02 10.53.2.70 STOR 226 /AC03/C.csv\n",
+    )
+    .unwrap();
+    handle(&rt);
+    assert_eq!(writes.load(Ordering::SeqCst), 1);
+    let snapshot = observer.snapshot();
+    assert!(!snapshot.dry_run);
+    assert_eq!(snapshot.dry_run_paths, 2);
+    assert_eq!(snapshot.files_recorded, 1);
+    assert!(
+        observer
+            .get_logs_str()
+            .iter()
+            .any(|l| l.ends_with("Dry run off, paths are written to the DB again"))
+    );
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_observe_remote_deltas() {
+    use crate::apps::file_sync_manager::remote_log::REMOTE_READ_CHUNK;
+
+    /// 每次查询大小时追加下一段内容，用完后停止观察器
+    struct ScriptedReader {
+        data: Vec<u8>,
+        script: Vec<&'static str>,
+        shared_state: Arc<Mutex<ObSharedState>>,
+    }
+    impl RemoteReader for ScriptedReader {
+        fn size(&mut self) -> std::io::Result<u64> {
+            if self.script.is_empty() {
+                self.shared_state
+                    .lock()
+                    .unwrap()
+                    .set_status(Stopped, Some("script done"));
+            } else {
+                self.data
+                    .extend_from_slice(self.script.remove(0).as_bytes());
+            }
+            Ok(self.data.len() as u64)
+        }
+        fn read_at(&mut self, offset: u64, max: usize) -> std::io::Result<Vec<u8>> {
+            assert!(max <= REMOTE_READ_CHUNK);
+            let start = offset as usize;
+            Ok(self.data[start..(start + max).min(self.data.len())].to_vec())
         }
+    }
+
+    let observer = LogObserver::new(PathBuf::from("ssh://ftp@nas/u_ex250601.log"), 20);
+    observer.set_status(Running(crate::Running::Periodic), None);
+    let mut reader = ScriptedReader {
+        data: Vec::new(),
+        script: vec![
+            // 开始跟踪前已有的内容不记录
+            "2025-06-01 07:59:59 10.53.2.70 STOR 226 /old.csv\n",
+            "2025-06-01 08:00:00 10.53.2.70 STOR 226 /A.csv\n2025-06-01 08:00:01 10.53.2.70 RETR 226 /B.csv\n",
+            // 半行等下次读到换行再处理
+            "2025-06-01 08:00:02 10.53.2.70 STOR 226 /dir/C",
+            "+1.csv\n",
+        ],
+        shared_state: observer.shared_state.clone(),
+    };
+    let logged = Arc::new(Mutex::new(Vec::new()));
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(LogObserver::observe_remote(
+        &observer.shared_state,
+        &observer.recorded_files,
+        &observer.path,
+        &mut reader,
+        ObserveOptions {
+            max_files_watched: 10,
+            dedup_window: Duration::ZERO,
+            strict_parse: false,
+            path_mapping: PathMapping::RecordRaw,
+            prefix_map: None,
+            size_units: SizeUnits::Binary,
+            max_path_len: 0,
+            skip_hidden: false,
+            allowed_roots: &[],
+            line_markers: &[],
+            log_pattern: None,
+            log_parser: None,
+        },
+        |paths| {
+            let logged = logged.clone();
+            async move {
+                logged.lock().unwrap().extend(paths);
+                Ok(Vec::new())
+            }
+        },
+    ));
+
+    assert_eq!(
+        *logged.lock().unwrap(),
+        [
+            LoggedPath::Raw("/A.csv".to_string()),
+            LoggedPath::Raw("/dir/C 1.csv".to_string()),
+        ]
+    );
+    assert_eq!(observer.files_got(), 2);
+    let old = "2025-06-01 07:59:59 10.53.2.70 STOR 226 /old.csv\n".len();
+    assert_eq!(
+        observer.snapshot().bytes_read,
+        (reader.data.len() - old) as u64
+    );
+}
+
+#[test]
+fn test_truncated_log_read_from_start() {
+    let base = std::env::temp_dir().join("test_truncated_log_read_from_start");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&base).unwrap();
+    let log_file = base.join("u_ex250601.log");
+    std::fs::write(
+        &log_file,
+        "2025-06-01 23:59:58 10.53.2.70 STOR 226 /AC03/BEFORE_ROTATION.csv\n\
+         2025-06-01 23:59:59 10.53.2.70 STOR 226 /AC03/LAST_OF_DAY.csv\n",
+    )
+    .unwrap();
 
-        self.file_statistic
-            .files_watched
-            .insert(path.clone(), file_watch_info.clone())
-    }
+    let observer = LogObserver::new(base.clone(), 20);
+    let logged = Arc::new(Mutex::new(Vec::new()));
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let handle = || {
+        let logged = logged.clone();
+        rt.block_on(LogObserver::handle_modified_file(
+            &observer.shared_state,
+            &observer.recorded_files,
+            &log_file,
+            ObserveOptions {
+                max_files_watched: 10,
+                dedup_window: Duration::ZERO,
+                strict_parse: false,
+                path_mapping: PathMapping::RecordRaw,
+                prefix_map: None,
+                size_units: SizeUnits::Binary,
+                max_path_len: 0,
+                skip_hidden: false,
+                allowed_roots: &[],
+                line_markers: &[],
+                log_pattern: None,
+                log_parser: None,
+            },
+            |paths| async move {
+                logged.lock().unwrap().extend(paths);
+                Ok(Vec::new())
+            },
+        ))
+    };
+    handle();
+    assert_eq!(logged.lock().unwrap().len(), 2);
 
-    fn set_file_watchinfo(&mut self, path: &PathBuf, info: FileWatchInfo) -> Option<FileWatchInfo> {
-        self.file_statistic.files_watched.insert(path.clone(), info)
-    }
+    // 轮换后文件变短，之前的读取位置已超出文件大小
+    std::fs::write(
+        &log_file,
+        "#Software: Microsoft Internet Information Services\n",
+    )
+    .unwrap();
+    handle();
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&log_file)
+        .unwrap();
+    std::io::Write::write_all(
+        &mut file,
+        b"2025-06-02 00:00:01 10.53.2.70 STOR 226 /AC03/AFTER_ROTATION.csv\n",
+    )
+    .unwrap();
+    drop(file);
+    handle();
 
-    fn add_file_got(&mut self, num: usize) {
-        self.file_statistic.files_got += num;
-    }
+    assert_eq!(
+        logged.lock().unwrap().last(),
+        Some(&LoggedPath::Raw("/AC03/AFTER_ROTATION.csv".to_string()))
+    );
+    assert_eq!(logged.lock().unwrap().len(), 3);
+    let logs = observer.get_logs_str();
+    assert!(logs.iter().any(|l| l.contains("was rotated or truncated")));
 
-    fn get_status(&self) -> ProgressStatus {
-        self.status.clone()
-    }
+    // 截断后处理事件前又写入了超过原读取位置的内容，按开头不同发现
+    let old_len = std::fs::metadata(&log_file).unwrap().len();
+    std::fs::write(
+        &log_file,
+        "2025-06-03 00:00:00 10.53.2.70 STOR 226 /AC03/REPLACED_1.csv\n\
+         2025-06-03 00:00:01 10.53.2.70 STOR 226 /AC03/REPLACED_2.csv\n\
+         2025-06-03 00:00:02 10.53.2.70 STOR 226 /AC03/REPLACED_3.csv\n",
+    )
+    .unwrap();
+    assert!(std::fs::metadata(&log_file).unwrap().len() > old_len);
+    handle();
+    // 从 0 开始读取，第一行也被记录
+    assert_eq!(
+        logged.lock().unwrap()[3..],
+        ["REPLACED_1", "REPLACED_2", "REPLACED_3"]
+            .map(|f| LoggedPath::Raw(format!("/AC03/{}.csv", f)))
+    );
+    let logs = observer.get_logs_str();
+    assert!(logs.iter().any(|l| l.contains("was replaced")));
 
-    fn set_status(&mut self, status: ProgressStatus) {
-        self.status = status;
-    }
+    // 追加不改变开头，不会误判为替换
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&log_file)
+        .unwrap();
+    std::io::Write::write_all(
+        &mut file,
+        b"2025-06-03 00:00:03 10.53.2.70 STOR 226 /AC03/APPENDED.csv\n",
+    )
+    .unwrap();
+    drop(file);
+    handle();
+    assert_eq!(logged.lock().unwrap().len(), 7);
+    assert_eq!(
+        observer
+            .get_logs_str()
+            .iter()
+            .filter(|l| l.contains("was replaced"))
+            .count(),
+        1
+    );
 
-    fn set_files_reading(&mut self, path: &PathBuf) {
-        self.file_statistic.file_reading = path.clone();
-    }
+    // 改名后不再存在的文件不再跟踪
+    let renamed = base.join("u_ex250601.log.old");
+    std::fs::rename(&log_file, &renamed).unwrap();
+    let mut ss = observer.shared_state.lock().unwrap();
+    assert!(
+        ss.forget_watched_file(&log_file, "was renamed away")
+            .is_some()
+    );
+    assert!(ss.last_read_pos(&log_file).is_none());
+    drop(ss);
 
-    fn reset_time(&mut self) {
-        self.launch_time = DateTime::from_timestamp(0, 0)
-            .unwrap()
-            .with_timezone(TIME_ZONE);
-        self.elapsed_time = TimeDelta::zero();
-    }
+    std::fs::remove_dir_all(&base).unwrap();
 }
 
-// MARK: test
-#[tokio::test]
-async fn test_path_construction() {
-    let path = LogObserver::handle_pathstring(
-        "/CTA8280H/TEST-48/DA35_BP85226D_P01DB_TP16D252_250417237_BP85226_P01DB9X_HDJJ13D._PL_20250507_141512.CAT",
-    );
+#[test]
+fn test_rotated_file_event() {
+    let base = std::env::temp_dir().join("test_rotated_file_event");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&base).unwrap();
+    let log_file = base.join("u_ex250601.log");
+    std::fs::write(
+        &log_file,
+        "2025-06-01 23:59:58 10.53.2.70 STOR 226 /AC03/a.csv\n\
+         2025-06-01 23:59:59 10.53.2.70 STOR 226 /AC03/b.csv\n",
+    )
+    .unwrap();
 
-    let path_ac03 = LogObserver::handle_pathstring("/AC03/ASDFDSAFDSA.csv");
+    let observer = LogObserver::new(base.clone(), 20);
+    let logged = Arc::new(Mutex::new(Vec::new()));
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let handle = || {
+        let logged = logged.clone();
+        rt.block_on(LogObserver::handle_modified_file(
+            &observer.shared_state,
+            &observer.recorded_files,
+            &log_file,
+            ObserveOptions {
+                max_files_watched: 10,
+                dedup_window: Duration::ZERO,
+                strict_parse: false,
+                path_mapping: PathMapping::RecordRaw,
+                prefix_map: None,
+                size_units: SizeUnits::Binary,
+                max_path_len: 0,
+                skip_hidden: false,
+                allowed_roots: &[],
+                line_markers: &[],
+                log_pattern: None,
+                log_parser: None,
+            },
+            |paths| async move {
+                logged.lock().unwrap().extend(paths);
+                Ok(Vec::new())
+            },
+        ))
+    };
+    let rotations = || {
+        observer
+            .get_logs_item()
+            .iter()
+            .filter(|e| matches!(e.kind, LogObserverEvent(RotatedFile)))
+            .count()
+    };
+    handle();
+    assert_eq!(logged.lock().unwrap().len(), 2);
+    assert_eq!(rotations(), 0);
 
-    let path_with_whitespace = LogObserver::handle_pathstring("/OS2000/AS  DFDSAFDSA.csv");
+    // 截断后重新写入，比之前短
+    std::fs::write(&log_file, "").unwrap();
+    std::fs::write(
+        &log_file,
+        "2025-06-02 00:00:01 10.53.2.70 STOR 226 /AC03/c.csv\n",
+    )
+    .unwrap();
+    handle();
+    assert_eq!(rotations(), 1);
+    assert_eq!(
+        logged.lock().unwrap().last(),
+        Some(&LoggedPath::Raw("/AC03/c.csv".to_string()))
+    );
 
-    // windows iis ftp日志会将路径中间的空格替换为`+`号，将`+`不做处理
-    let path_with_special_char = LogObserver::handle_pathstring(
-        "/123/++Starting+Space/Mix!@#$%^&()=+{}[];',~_目录/Sub+Folder+中间+空+格/文件_🌟Unicode_引号_&_Sp++ecial_Chars_最终版_v2.0%20@2024",
+    // 已跟踪的文件被重新创建，即使新文件不比读取位置短也从头读取
+    std::fs::write(
+        &log_file,
+        "2025-06-02 00:00:02 10.53.2.70 STOR 226 /AC03/d.csv\n\
+         2025-06-02 00:00:03 10.53.2.70 STOR 226 /AC03/e.csv\n",
+    )
+    .unwrap();
+    let mut ss = observer.shared_state.lock().unwrap();
+    assert!(
+        ss.reset_rotated_file(&log_file, 0, "was created again")
+            .is_some()
+    );
+    assert!(
+        ss.reset_rotated_file(&base.join("other.log"), 0, "was created again")
+            .is_none()
     );
+    drop(ss);
+    handle();
+    assert_eq!(rotations(), 2);
+    let logged: Vec<_> = logged.lock().unwrap().clone();
+    assert_eq!(logged.len(), 5);
+    assert_eq!(logged[3], LoggedPath::Raw("/AC03/d.csv".to_string()));
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_reset_offset() {
+    let base = std::env::temp_dir().join("test_reset_offset");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&base).unwrap();
+    let log_file = base.join("u_ex250601.log");
+    std::fs::write(
+        &log_file,
+        "2025-06-01 08:00:00 10.53.2.70 STOR 226 /AC03/A.csv\n\
+         2025-06-01 08:00:01 10.53.2.70 STOR 226 /AC03/B.csv\n",
+    )
+    .unwrap();
+    let len = std::fs::metadata(&log_file).unwrap().len();
+
+    let observer = LogObserver::new(base.clone(), 20);
+    let logged = Arc::new(Mutex::new(Vec::new()));
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let handle = || {
+        let logged = logged.clone();
+        rt.block_on(LogObserver::handle_modified_file(
+            &observer.shared_state,
+            &observer.recorded_files,
+            &log_file,
+            ObserveOptions {
+                max_files_watched: 10,
+                dedup_window: Duration::from_secs(60),
+                strict_parse: false,
+                path_mapping: PathMapping::RecordRaw,
+                prefix_map: None,
+                size_units: SizeUnits::Binary,
+                max_path_len: 0,
+                skip_hidden: false,
+                allowed_roots: &[],
+                line_markers: &[],
+                log_pattern: None,
+                log_parser: None,
+            },
+            |paths| async move {
+                logged.lock().unwrap().extend(paths);
+                Ok(Vec::new())
+            },
+        ))
+    };
 
+    // 未监视的文件不能重置
+    assert!(observer.reset_offset(&log_file).is_err());
+    handle();
+    assert_eq!(logged.lock().unwrap().len(), 2);
+    assert_eq!(observer.watched_offsets()[0].1.last_read_pos(), len);
+
+    assert_eq!(observer.reset_offset(&log_file), Ok(len));
     assert_eq!(
-        PathBuf::from("E:\\CusData\\AC03\\ASDFDSAFDSA.csv"),
-        path_ac03
+        observer
+            .shared_state
+            .lock()
+            .unwrap()
+            .last_read_pos(&log_file),
+        Some(0)
+    );
+    // 文件没有变化，重置后的下一次事件也不被去重，从头读取
+    handle();
+    let logged = logged.lock().unwrap().clone();
+    assert_eq!(logged.len(), 4);
+    assert_eq!(logged[2], LoggedPath::Raw("/AC03/A.csv".to_string()));
+    assert!(
+        observer
+            .get_logs_str()
+            .iter()
+            .any(|l| l.contains(&format!("reset from {} to 0", len)))
+    );
+    assert!(observer.reset_offset(&base.join("other.log")).is_err());
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_checkpoint_round_trip() {
+    let base = std::env::temp_dir().join("test_checkpoint_round_trip");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&base).unwrap();
+    let log_file = base.join("u_ex250601.log");
+    std::fs::write(&log_file, "2025-06-01 10.53.2.70 STOR 226 /AC03/a.csv\n").unwrap();
+    let len = std::fs::metadata(&log_file).unwrap().len();
+    let head = FileHead::read(&log_file, FILE_HEAD_LEN).unwrap();
+    let checkpoint = base.join("checkpoint.json");
+
+    let observer = LogObserver::new(base.clone(), 20).with_checkpoint(Some(checkpoint.clone()));
+    assert!(!checkpoint.exists());
+    let info = FileWatchInfo::new(len, len, Some(head));
+    observer
+        .shared_state
+        .lock()
+        .unwrap()
+        .set_file_watchinfo(&log_file, info.clone());
+    observer.save_checkpoint();
+    drop(observer);
+
+    let infos = ObSharedState::load_checkpoint(&checkpoint).unwrap();
+    assert_eq!(infos, IndexMap::from([(log_file.clone(), info.clone())]));
+
+    // 运行期间按间隔保存，保存目录不存在时创建
+    let periodic = base.join("state").join("periodic.json");
+    let observer = LogObserver::new(base.clone(), 20);
+    let mut ss = observer.shared_state.lock().unwrap();
+    ss.set_file_watchinfo(&log_file, info.clone());
+    assert!(
+        ss.save_checkpoint_if_due(&periodic, Duration::from_secs(3600))
+            .is_none()
+    );
+    assert!(!periodic.exists());
+    assert!(
+        ss.save_checkpoint_if_due(&periodic, Duration::ZERO)
+            .unwrap()
+            .is_ok()
     );
+    drop(ss);
+    drop(observer);
+    let infos = ObSharedState::load_checkpoint(&periodic).unwrap();
+    assert_eq!(infos, IndexMap::from([(log_file.clone(), info)]));
+
+    // 重启后从保存的位置继续
+    let observer = LogObserver::new(base.clone(), 20).with_checkpoint(Some(checkpoint.clone()));
     assert_eq!(
-        PathBuf::from(
-            "E:\\testdata\\CTA8280H\\TEST-48\\DA35_BP85226D_P01DB_TP16D252_250417237_BP85226_P01DB9X_HDJJ13D._PL_20250507_141512.CAT"
-        ),
-        path
+        observer
+            .shared_state
+            .lock()
+            .unwrap()
+            .last_read_pos(&log_file),
+        Some(len)
+    );
+    assert!(
+        observer
+            .get_logs_str()
+            .iter()
+            .any(|l| l.contains("Restored 1 watched files"))
     );
+    drop(observer);
+
+    // 损坏的检查点被忽略，从头读取
+    std::fs::write(&checkpoint, "{\"u_ex250601.log\": {\"last_read_pos\": ").unwrap();
+    assert!(ObSharedState::load_checkpoint(&checkpoint).is_err());
+    let observer = LogObserver::new(base.clone(), 20).with_checkpoint(Some(checkpoint.clone()));
     assert_eq!(
-        PathBuf::from("E:\\testdata\\OS2000\\AS  DFDSAFDSA.csv"),
-        path_with_whitespace
+        observer
+            .shared_state
+            .lock()
+            .unwrap()
+            .last_read_pos(&log_file),
+        None
+    );
+    assert!(
+        observer
+            .get_logs_str()
+            .iter()
+            .any(|l| l.contains("Ignored checkpoint"))
     );
+    drop(observer);
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_checkpoint_saved_after_batch() {
+    let base = std::env::temp_dir().join("test_checkpoint_saved_after_batch");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&base).unwrap();
+    let log_file = base.join("u_ex250601.log");
+    std::fs::write(
+        &log_file,
+        "2025-06-01 08:00:00 10.53.2.70 STOR 226 /AC03/a.csv\n\
+         2025-06-01 08:00:01 10.53.2.70 STOR 226 /AC03/b.csv\n",
+    )
+    .unwrap();
+    let len = std::fs::metadata(&log_file).unwrap().len();
+    let checkpoint = base.join("state").join("checkpoint.json");
+
+    let logged = Arc::new(Mutex::new(Vec::new()));
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let handle = |observer: &LogObserver| {
+        let logged = logged.clone();
+        rt.block_on(LogObserver::handle_modified_file(
+            &observer.shared_state,
+            &observer.recorded_files,
+            &log_file,
+            ObserveOptions {
+                max_files_watched: 10,
+                dedup_window: Duration::ZERO,
+                strict_parse: false,
+                path_mapping: PathMapping::RecordRaw,
+                prefix_map: None,
+                size_units: SizeUnits::Binary,
+                max_path_len: 0,
+                skip_hidden: false,
+                allowed_roots: &[],
+                line_markers: &[],
+                log_pattern: None,
+                log_parser: None,
+            },
+            |paths| async move {
+                logged.lock().unwrap().extend(paths);
+                Ok(Vec::new())
+            },
+        ))
+    };
+
+    // 写入后立即保存，不等关闭或定时保存
+    let observer = LogObserver::new(base.clone(), 20).with_checkpoint(Some(checkpoint.clone()));
+    handle(&observer);
+    assert_eq!(logged.lock().unwrap().len(), 2);
+    let infos = ObSharedState::load_checkpoint(&checkpoint).unwrap();
+    assert_eq!(infos[&log_file].last_read_pos, len);
+    // 模拟崩溃，不经过 close 保存
+    std::mem::forget(observer);
+
+    // 重启后不再重复写入
+    let observer = LogObserver::new(base.clone(), 20).with_checkpoint(Some(checkpoint.clone()));
+    handle(&observer);
+    assert_eq!(logged.lock().unwrap().len(), 2);
+    drop(observer);
+
+    // 停止期间文件被截断，重启时从头读取
+    std::fs::write(
+        &log_file,
+        "2025-06-02 08:00:00 10.53.2.70 STOR 226 /AC03/c.csv\n",
+    )
+    .unwrap();
+    let observer = LogObserver::new(base.clone(), 20).with_checkpoint(Some(checkpoint.clone()));
     assert_eq!(
-        PathBuf::from(
-            "E:\\testdata\\123\\  Starting Space\\Mix!@#$%^&()= {}[];',~_目录\\Sub Folder 中间 空 格\\文件_🌟Unicode_引号_&_Sp  ecial_Chars_最终版_v2.0%20@2024"
-        ),
-        path_with_special_char
+        observer
+            .shared_state
+            .lock()
+            .unwrap()
+            .last_read_pos(&log_file),
+        Some(0)
     );
+    assert!(
+        observer
+            .get_logs_str()
+            .iter()
+            .any(|l| l.contains("1 reset") && l.contains("Truncated"))
+    );
+    handle(&observer);
+    assert_eq!(logged.lock().unwrap().len(), 3);
+    drop(observer);
+
+    std::fs::remove_dir_all(&base).unwrap();
 }
 
 #[test]
-fn test_file_path() {
-    let path = PathBuf::from("asset\\cfg.json");
-    if !std::fs::exists(&path).unwrap() {
-        eprintln!(
-            "File does not exist, current path is {}, and your path is {}",
-            std::env::current_dir().unwrap().display(),
-            path.display()
-        );
-        panic!();
+fn test_recursive_watch_nested_logs() {
+    let base = std::env::temp_dir().join("test_recursive_watch_nested_logs");
+    let _ = std::fs::remove_dir_all(&base);
+    let nested = base.join("W3SVC1");
+    std::fs::create_dir_all(&nested).unwrap();
+
+    let mut observer = LogObserver::new(base.clone(), 50);
+    observer.set_recursive(true);
+    assert!(observer.shared_state.lock().unwrap().snapshot().recursive);
+    observer.start_observer().unwrap();
+    // 等待 watcher 就绪
+    thread::sleep(Duration::from_millis(300));
+
+    std::fs::write(nested.join("u_ex250601.log"), b"#Fields: date time\n").unwrap();
+    thread::sleep(Duration::from_millis(1500));
+    observer.close();
+
+    let ss = observer.shared_state.lock().unwrap();
+    let watched: Vec<&PathBuf> = ss.file_statistic.files_watched.keys().collect();
+    assert_eq!(watched, vec![&nested.join("u_ex250601.log")]);
+    drop(ss);
+
+    // 同时出现很多文件时只保留最近的 max_files_watched 个
+    let mut ss = observer.shared_state.lock().unwrap();
+    for day in 1..=10 {
+        let path = nested.join(format!("u_ex2506{:02}.log", day));
+        ss.update_file_watchinfo(&path, 3, 100, None);
     }
+    let watched: Vec<PathBuf> = ss.file_statistic.files_watched.keys().cloned().collect();
+    assert_eq!(
+        watched,
+        ["u_ex250608.log", "u_ex250609.log", "u_ex250610.log"].map(|f| nested.join(f))
+    );
+    drop(ss);
+
+    std::fs::remove_dir_all(&base).unwrap();
 }
 
-#[tokio::test]
-async fn test_extract_path() {
+#[test]
+fn test_stop_observer_logs_once() {
+    let base = std::env::temp_dir().join("test_stop_observer_logs_once");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&base).unwrap();
+
+    let mut observer = LogObserver::new(base.clone(), 10);
+    observer.start_observer().unwrap();
+    thread::sleep(Duration::from_millis(300));
+
+    // 空闲的观察器应在一个检查间隔左右退出，而不是等满超时
+    let start = Instant::now();
+    assert_eq!(observer.stop_observer(), PhaseOutcome::Done);
+    assert!(
+        start.elapsed() < Duration::from_secs(2),
+        "{:?}",
+        start.elapsed()
+    );
+    assert!(observer.handle.is_none());
+    assert_eq!(observer.shared_state.lock().unwrap().status, Stopped);
+    let stopped = |observer: &LogObserver| {
+        observer
+            .get_logs_str()
+            .iter()
+            .filter(|l| l.ends_with("Observer stopped"))
+            .count()
+    };
+    assert_eq!(stopped(&observer), 1);
+
+    assert_eq!(observer.stop_observer(), PhaseOutcome::Skipped);
+    assert_eq!(stopped(&observer), 1);
+    assert!(
+        observer
+            .get_logs_str()
+            .iter()
+            .any(|l| l.ends_with("Observer is already stopped or stopping."))
+    );
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_stop_observer_gives_up_after_timeout() {
+    let mut observer = LogObserver::new(PathBuf::from("."), 10);
+    observer.set_status(Running(crate::Running::Periodic), None);
+    // 模拟迟迟不退出的工作线程
+    observer.handle = Some(thread::spawn(|| {
+        thread::sleep(Duration::from_millis(500));
+        Ok(())
+    }));
+    observer.set_stop_timeout(Duration::from_millis(50));
+
+    let start = Instant::now();
+    assert_eq!(observer.stop_observer(), PhaseOutcome::TimedOut);
+    assert!(start.elapsed() < Duration::from_millis(400));
+    assert!(observer.handle.is_none());
+    assert_eq!(observer.get_status(), Stopped);
+    let errors: Vec<String> = observer
+        .get_logs_item()
+        .into_iter()
+        .filter(|e| matches!(e.kind, LogObserverEvent(Error)))
+        .map(|e| e.content)
+        .collect();
     assert_eq!(
-    extract_path(
-        "2025-05-07 16:42:15 10.53.2.70 STOR 226 /CTA8280H/TEST-48/DA35_BP85226D_P01DB_TP16D252_250417237_BP85226_P01DB9X_HDJJ13D._PL_20250507_141512.CAT").await,
-        PathBuf::from(
-            "E:\\testdata\\CTA8280H\\TEST-48\\DA35_BP85226D_P01DB_TP16D252_250417237_BP85226_P01DB9X_HDJJ13D._PL_20250507_141512.CAT"
-            ),
+        errors,
+        ["Observer didn't stop within 50ms, gave up waiting"]
+    );
+}
+
+// inotify 对硬链接只发出创建事件，没有随后的修改事件
+#[cfg(target_os = "linux")]
+#[test]
+fn test_created_log_read_without_modify() {
+    let base = std::env::temp_dir().join("test_created_log_read_without_modify");
+    let _ = std::fs::remove_dir_all(&base);
+    let log_dir = base.join("logs");
+    let staging = base.join("staging");
+    std::fs::create_dir_all(&log_dir).unwrap();
+    std::fs::create_dir_all(&staging).unwrap();
+    let prepared = staging.join("u_ex250610.log");
+    std::fs::write(
+        &prepared,
+        "2025-06-10 00:00:00 10.53.2.70 STOR 226 /AC03/FIRST_OF_DAY.csv\n",
+    )
+    .unwrap();
+
+    let mut observer = LogObserver::new(log_dir.clone(), 50);
+    observer.set_dry_run(true);
+    observer.start_observer().unwrap();
+    // 等待 watcher 就绪
+    thread::sleep(Duration::from_millis(300));
+
+    let created = log_dir.join("u_ex250610.log");
+    std::fs::hard_link(&prepared, &created).unwrap();
+    thread::sleep(Duration::from_millis(1500));
+    observer.close();
+
+    let items = observer.get_logs_item();
+    assert!(
+        items
+            .iter()
+            .any(|e| matches!(e.kind, LogObserverEvent(CreatedFile)))
+    );
+    assert!(
+        !items
+            .iter()
+            .any(|e| matches!(e.kind, LogObserverEvent(ModifiedFile)))
     );
+    assert_eq!(observer.snapshot().dry_run_paths, 1);
+    let len = std::fs::metadata(&created).unwrap().len();
     assert_eq!(
-        extract_path("2025-05-07 16:42:15 10.53.2.70 STOR 226 /OS2000/AS DFDSAFDSA.csv").await,
-        PathBuf::from("E:\\testdata\\OS2000\\AS DFDSAFDSA.csv"),
+        observer
+            .shared_state
+            .lock()
+            .unwrap()
+            .last_read_pos(&created),
+        Some(len)
     );
+
+    std::fs::remove_dir_all(&base).unwrap();
 }
 
-async fn extract_path(content: &str) -> PathBuf {
-    let base = std::env::temp_dir().join("test_assdfasset");
+#[test]
+fn test_deleted_log_unwatched() {
+    let base = std::env::temp_dir().join("test_deleted_log_unwatched");
+    let _ = std::fs::remove_dir_all(&base);
     std::fs::create_dir_all(&base).unwrap();
-    let file = base.join("fileasdfsfsadfasd");
-    std::fs::write(&file, content).unwrap();
 
-    let extracted_paths = LogObserver::extract_path_stream(&file, 0).await;
-    futures::pin_mut!(extracted_paths);
+    let mut observer = LogObserver::new(base.clone(), 50);
+    observer.set_dry_run(true);
+    observer.start_observer().unwrap();
+    // 等待 watcher 就绪
+    thread::sleep(Duration::from_millis(300));
+
+    let log_file = base.join("u_ex250601.log");
+    std::fs::write(
+        &log_file,
+        "2025-06-01 08:00:00 10.53.2.70 STOR 226 /AC03/A.csv\n",
+    )
+    .unwrap();
+    thread::sleep(Duration::from_millis(1000));
+    assert!(
+        observer
+            .shared_state
+            .lock()
+            .unwrap()
+            .last_read_pos(&log_file)
+            .is_some()
+    );
+
+    std::fs::remove_file(&log_file).unwrap();
+    thread::sleep(Duration::from_millis(1000));
+    assert!(
+        observer
+            .shared_state
+            .lock()
+            .unwrap()
+            .last_read_pos(&log_file)
+            .is_none()
+    );
+    assert!(
+        observer
+            .get_logs_item()
+            .iter()
+            .any(|e| matches!(e.kind, LogObserverEvent(DeletedFile)))
+    );
+
+    // 删除后观察器仍在运行，新日志照常读取
+    assert!(observer.handle.as_ref().is_some_and(|h| !h.is_finished()));
+    std::fs::write(
+        base.join("u_ex250602.log"),
+        "2025-06-02 08:00:00 10.53.2.70 STOR 226 /AC03/B.csv\n",
+    )
+    .unwrap();
+    thread::sleep(Duration::from_millis(1000));
+    observer.close();
+    assert_eq!(observer.snapshot().dry_run_paths, 2);
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_follow_repointed_symlink() {
+    let base = std::env::temp_dir().join("test_follow_repointed_symlink");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(base.join("links")).unwrap();
+    std::fs::create_dir_all(base.join("targets")).unwrap();
+    let append = |name: &str, line: &str| {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(base.join("targets").join(name))
+            .unwrap();
+        std::io::Write::write_all(&mut file, line.as_bytes()).unwrap();
+    };
+    append("u_ex250601.log", "");
+    append(
+        "u_ex250602.log",
+        "2025-06-02 00:00:00 10.53.2.70 STOR 226 /AC03/B1.csv\n",
+    );
+    // 不指向的文件不读取
+    append("other.log", "");
+    let link = base.join("links/current.log");
+    std::os::unix::fs::symlink(base.join("targets/u_ex250601.log"), &link).unwrap();
+
+    let mut observer = LogObserver::new(link.clone(), 50);
+    observer.set_dry_run(true);
+    observer.set_follow_symlink(true);
+    observer.start_observer().unwrap();
+    // 等待 watcher 就绪
+    thread::sleep(Duration::from_millis(300));
+    let dry_run_paths = |observer: &LogObserver| observer.snapshot().dry_run_paths;
+
+    append(
+        "u_ex250601.log",
+        "2025-06-01 08:00:00 10.53.2.70 STOR 226 /AC03/A1.csv\n",
+    );
+    append(
+        "other.log",
+        "2025-06-01 08:00:00 10.53.2.70 STOR 226 /AC03/X.csv\n",
+    );
+    thread::sleep(Duration::from_millis(1000));
+    assert_eq!(dry_run_paths(&observer), 1);
+
+    // 链接改指向新文件后读取新文件已有的内容，旧文件不再读取
+    let tmp_link = base.join("links/current.log.tmp");
+    std::os::unix::fs::symlink(base.join("targets/u_ex250602.log"), &tmp_link).unwrap();
+    std::fs::rename(&tmp_link, &link).unwrap();
+    thread::sleep(Duration::from_millis(1000));
+    assert_eq!(dry_run_paths(&observer), 2);
+
+    append(
+        "u_ex250602.log",
+        "2025-06-02 08:00:00 10.53.2.70 STOR 226 /AC03/B2.csv\n",
+    );
+    append(
+        "u_ex250601.log",
+        "2025-06-01 09:00:00 10.53.2.70 STOR 226 /AC03/A2.csv\n",
+    );
+    thread::sleep(Duration::from_millis(1000));
+    observer.close();
+    assert_eq!(dry_run_paths(&observer), 3);
+    let logs = observer.get_logs_str();
+    assert!(logs.iter().any(|l| l.contains("now points to")));
+    assert!(!logs.iter().any(|l| l.contains("A2.csv")));
+    assert!(logs.iter().any(|l| l.contains("B2.csv")));
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_config_reload_logged() {
+    let base = std::env::temp_dir().join("test_config_reload_logged");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&base).unwrap();
+    let path = base.join("cfg.json");
+    let original = std::fs::read_to_string("asset/cfg.json").unwrap();
+    std::fs::write(&path, &original).unwrap();
+
+    let watcher = ConfigWatcher::new(path.clone()).unwrap();
+    let mut observer = LogObserver::new(base.clone(), 20);
+    observer.set_config_watcher(&watcher);
+
+    std::fs::write(
+        &path,
+        original.replace("\"strict_parse\": false", "\"strict_parse\": true"),
+    )
+    .unwrap();
+    watcher.reload();
+    assert!(
+        observer
+            .shared_state
+            .lock()
+            .unwrap()
+            .current_config()
+            .strict_parse
+    );
+    std::fs::write(&path, "not json").unwrap();
+    watcher.reload();
+    // 无效的修改不生效
+    assert!(
+        observer
+            .shared_state
+            .lock()
+            .unwrap()
+            .current_config()
+            .strict_parse
+    );
+
+    let logs = observer.get_logs_str();
+    assert!(logs.iter().any(|l| l.contains("Config reloaded")));
+    assert!(
+        logs.iter()
+            .any(|l| l.contains("Config not reloaded, keeping the previous one: Invalid JSON"))
+    );
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_poll_watch_mode() {
+    let base = std::env::temp_dir().join("test_poll_watch_mode");
+    let _ = std::fs::remove_dir_all(&base);
+    let log_dir = base.join("logs");
+    std::fs::create_dir_all(&log_dir).unwrap();
+    let path = base.join("cfg.json");
+    let original = std::fs::read_to_string("asset/cfg.json").unwrap();
+    let poll = original
+        .replace("\"watch_mode\": \"native\"", "\"watch_mode\": \"poll\"")
+        .replace("\"poll_interval_ms\": 2000", "\"poll_interval_ms\": 200");
+    std::fs::write(&path, poll).unwrap();
+
+    let watcher = ConfigWatcher::new(path.clone()).unwrap();
+    let mut observer = LogObserver::new(log_dir.clone(), 50);
+    observer.set_config_watcher(&watcher);
+    observer.set_dry_run(true);
+    observer.start_observer().unwrap();
+    thread::sleep(Duration::from_millis(300));
+
+    std::fs::write(
+        log_dir.join("u_ex250601.log"),
+        "2025-06-01 08:00:00 10.53.2.70 STOR 226 /AC03/A.csv\n",
+    )
+    .unwrap();
+    thread::sleep(Duration::from_millis(1000));
+    observer.close();
+
+    assert!(
+        observer
+            .get_logs_str()
+            .iter()
+            .any(|l| l.ends_with("Watching in poll mode, every 200ms"))
+    );
+    let snapshot = observer.snapshot();
+    assert_eq!(snapshot.dry_run_paths, 1);
+    // 状态区和 ds status 显示当前的监视方式
+    assert_eq!(snapshot.poll_interval, Some(Duration::from_millis(200)));
+    assert_eq!(snapshot.watch_mode_str(), "poll every 200ms");
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_multiple_observed_paths() {
+    let base = std::env::temp_dir().join("test_multiple_observed_paths");
+    let _ = std::fs::remove_dir_all(&base);
+    let (dir1, dir2) = (base.join("FTPSVC1"), base.join("FTPSVC2"));
+    std::fs::create_dir_all(&dir1).unwrap();
+    std::fs::create_dir_all(&dir2).unwrap();
+    // 合计只跟踪一个文件
+    let cfg = base.join("cfg.json");
+    let original = std::fs::read_to_string("asset/cfg.json").unwrap();
+    std::fs::write(
+        &cfg,
+        original.replace("\"max_observed_files\": 1000", "\"max_observed_files\": 1"),
+    )
+    .unwrap();
+    let watcher = ConfigWatcher::new(cfg).unwrap();
+
+    let mut observer = LogObserver::new(dir1.clone(), 50);
+    observer.set_config_watcher(&watcher);
+    observer.set_site(SiteConfig {
+        name: "ftp".to_string(),
+        observed_path: crate::OneOrMany::Many(vec![dir1.clone(), dir2.clone()]),
+        prefix_map_of_extract_path: None,
+        source_tag: None,
+    });
+    assert_eq!(observer.observed_paths(), [dir1.clone(), dir2.clone()]);
+    observer.start_observer().unwrap();
+    // 等待 watcher 就绪
+    thread::sleep(Duration::from_millis(300));
+
+    std::fs::write(dir1.join("u_ex250601.log"), b"#Fields: date time\n").unwrap();
+    thread::sleep(Duration::from_millis(500));
+    std::fs::write(dir2.join("u_ex250601.log"), b"#Fields: date time\n").unwrap();
+    thread::sleep(Duration::from_millis(1500));
+    observer.close();
+
+    let ss = observer.shared_state.lock().unwrap();
+    let watched: Vec<&PathBuf> = ss.file_statistic.files_watched.keys().collect();
+    assert_eq!(watched, vec![&dir2.join("u_ex250601.log")]);
+    // 跟踪的文件记录所在的观察目录
+    let info = &ss.file_statistic.files_watched[0];
+    assert_eq!(info.root(), Some(dir2.as_path()));
+    drop(ss);
+    let logs = observer.get_logs_str();
+    // 每个事件标明来自哪个观察目录
+    let event_for = |dir: &Path| {
+        let file = format!("{:?}", dir.join("u_ex250601.log"));
+        let tag = format!("[{}] Notify event", dir.display());
+        logs.iter().any(|l| l.contains(&tag) && l.contains(&file))
+    };
+    assert!(event_for(&dir1));
+    assert!(event_for(&dir2));
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_config_loaded_once() {
+    let observer = LogObserver::new(PathBuf::from("."), 20);
+    assert!(observer.shared_state.lock().unwrap().config.is_none());
+    observer.load_config_once().unwrap();
+    let shared = observer
+        .shared_state
+        .lock()
+        .unwrap()
+        .config
+        .clone()
+        .unwrap();
+
+    // 已加载时不再读取配置文件，使用内存中的配置
+    shared.write().unwrap().file_sync_manager.strict_parse = true;
+    observer.load_config_once().unwrap();
+    let current = || observer.shared_state.lock().unwrap().current_config();
+    assert!(current().strict_parse);
+
+    // 按需重新读取后替换同一份配置
+    observer.reload_config().unwrap();
+    assert!(!current().strict_parse);
+    assert!(!shared.read().unwrap().file_sync_manager.strict_parse);
+    assert!(
+        observer
+            .get_logs_str()
+            .iter()
+            .any(|l| l.contains("Config reloaded"))
+    );
+
+    // 路径映射只使用传入的前缀表
+    let options = ObserveOptions {
+        max_files_watched: 10,
+        dedup_window: Duration::ZERO,
+        strict_parse: false,
+        path_mapping: PathMapping::Rewrite,
+        prefix_map: None,
+        size_units: SizeUnits::Binary,
+        max_path_len: 0,
+        skip_hidden: false,
+        allowed_roots: &[],
+        line_markers: &[],
+        log_pattern: None,
+        log_parser: None,
+    };
+    assert_eq!(
+        LogObserver::map_logged_path("/AC03/a+b.csv", options),
+        LoggedPath::Mapped {
+            path: PathBuf::from(r"\AC03\a b.csv"),
+            source: None,
+        }
+    );
+}
+
+#[test]
+fn test_vanished_log_keeps_running() {
+    let base = std::env::temp_dir().join("test_vanished_log_keeps_running");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&base).unwrap();
+
+    let mut observer = LogObserver::new(base.clone(), 50);
+    observer.set_dry_run(true);
+    observer.start_observer().unwrap();
+    thread::sleep(Duration::from_millis(300));
+
+    // 修改事件到达时文件已经不存在，记录错误后继续
+    let gone = base.join("u_ex250601.log");
+    let config = observer.shared_state.lock().unwrap().current_config();
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(LogObserver::handle_modified_file(
+        &observer.shared_state,
+        &observer.recorded_files,
+        &gone,
+        ObserveOptions::from_config(&config),
+        |_| async { Ok(Vec::new()) },
+    ));
+    let errors: Vec<String> = observer
+        .get_logs_item()
+        .iter()
+        .filter(|e| matches!(e.kind, LogObserverEvent(Error)))
+        .map(|e| e.content.clone())
+        .collect();
+    assert!(
+        errors
+            .iter()
+            .any(|e| e.starts_with("Failed to read metadata of") && e.contains("u_ex250601.log")),
+        "{:?}",
+        errors
+    );
+
+    // 写入后立即删除，观察器线程不受影响
+    for _ in 0..5 {
+        std::fs::write(
+            &gone,
+            "2025-06-01 08:00:00 10.53.2.70 STOR 226 /AC03/A.csv\n",
+        )
+        .unwrap();
+        std::fs::remove_file(&gone).unwrap();
+    }
+    thread::sleep(Duration::from_millis(500));
+    assert!(observer.handle.as_ref().is_some_and(|h| !h.is_finished()));
+    assert_eq!(observer.get_status(), Running(crate::Running::Periodic));
 
-    let path = extracted_paths.next().await.unwrap();
+    observer.close();
     std::fs::remove_dir_all(&base).unwrap();
-    path.0
+}
+
+#[test]
+fn test_files_got_by_root() {
+    let observer = LogObserver::new(PathBuf::from("/logs/FTPSVC1"), 20);
+    let mut ss = observer.shared_state.lock().unwrap();
+    let (root1, root2) = (
+        PathBuf::from("/logs/FTPSVC1"),
+        PathBuf::from("/logs/FTPSVC2"),
+    );
+    ss.roots = vec![root1.clone(), root2.clone()];
+
+    ss.add_file_got(&root1.join("u_ex250601.log"), 3);
+    ss.add_file_got(&root1.join("u_ex250602.log"), 1);
+    // 跟随的链接指向观察目录之外时只计入总数
+    ss.add_file_got(Path::new("/elsewhere/u_ex250601.log"), 2);
+    let snapshot = ss.snapshot();
+    assert_eq!(snapshot.files_got, 6);
+    // 没有得到文件的目录也列出
+    assert_eq!(snapshot.files_got_by_root, [(root1.clone(), 4), (root2, 0)]);
+
+    assert_eq!(
+        ss.root_tag(&[root1.join("u_ex250601.log")]),
+        format!("[{}] ", root1.display())
+    );
+    ss.roots.truncate(1);
+    assert_eq!(ss.root_tag(&[root1.join("u_ex250601.log")]), "");
 }
@@ -0,0 +1,175 @@
+use std::path::{Path, PathBuf};
+
+/// 观察路径中的一个符号链接及其当前指向的日志文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FollowedLink {
+    /// 配置中写的观察路径
+    pub observed: PathBuf,
+    /// 所在目录解析后的链接路径，与监视事件中的路径写法一致
+    pub link: PathBuf,
+    /// 解析后的目标文件，链接暂时不存在或悬空时为 `None`
+    pub target: Option<PathBuf>,
+}
+
+/// 链接改为指向另一个文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repointed {
+    pub link: PathBuf,
+    pub old: Option<PathBuf>,
+    pub new: Option<PathBuf>,
+}
+
+/// 跟随指向日志文件的符号链接，链接改指向其他文件时切换读取的目标
+///
+/// 监视链接所在的目录以发现链接被替换，监视目标所在的目录以读取写入和轮换。
+#[derive(Debug, Default)]
+pub struct SymlinkFollower {
+    links: Vec<FollowedLink>,
+}
+
+impl SymlinkFollower {
+    /// Follow the paths in `paths` that are symlinks, the others are watched as they are.
+    pub fn new(paths: &[PathBuf]) -> Self {
+        let links = paths
+            .iter()
+            .filter(|p| is_symlink(p))
+            .map(|observed| {
+                let link = resolve_dir(observed);
+                FollowedLink {
+                    observed: observed.clone(),
+                    target: resolve_target(&link),
+                    link,
+                }
+            })
+            .collect();
+        Self { links }
+    }
+
+    pub fn links(&self) -> &[FollowedLink] {
+        &self.links
+    }
+
+    /// Whether the observed path `path` is followed instead of watched directly.
+    pub fn follows(&self, path: &Path) -> bool {
+        self.links.iter().any(|l| l.observed == path)
+    }
+
+    /// Whether `path` is one of the followed links.
+    pub fn is_link(&self, path: &Path) -> bool {
+        self.links.iter().any(|l| l.link == path)
+    }
+
+    /// Whether `path` is what a followed link currently points to.
+    pub fn is_target(&self, path: &Path) -> bool {
+        self.links.iter().any(|l| l.target.as_deref() == Some(path))
+    }
+
+    /// The current target of the followed link `link`.
+    pub fn target_of(&self, link: &Path) -> Option<&Path> {
+        self.links
+            .iter()
+            .find(|l| l.link == link)
+            .and_then(|l| l.target.as_deref())
+    }
+
+    /// The directories of the links and of their targets, each once.
+    pub fn watch_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        let files = self
+            .links
+            .iter()
+            .flat_map(|l| [Some(&l.link), l.target.as_ref()]);
+        for dir in files.flatten().filter_map(|f| f.parent()) {
+            if !dirs.iter().any(|d| d == dir) {
+                dirs.push(dir.to_path_buf());
+            }
+        }
+        dirs
+    }
+
+    /// Resolve every link again, returning those now pointing elsewhere.
+    pub fn refresh(&mut self) -> Vec<Repointed> {
+        let mut repointed = Vec::new();
+        for link in &mut self.links {
+            let new = resolve_target(&link.link);
+            if new != link.target {
+                repointed.push(Repointed {
+                    link: link.link.clone(),
+                    old: std::mem::replace(&mut link.target, new.clone()),
+                    new,
+                });
+            }
+        }
+        repointed
+    }
+}
+
+/// Whether `path` itself is a symlink, without following it.
+pub fn is_symlink(path: &Path) -> bool {
+    std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink())
+}
+
+/// `path` with its directory resolved, so it matches the paths of watcher events.
+fn resolve_dir(path: &Path) -> PathBuf {
+    let resolved = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .and_then(|dir| std::fs::canonicalize(dir).ok());
+    match (resolved, path.file_name()) {
+        (Some(dir), Some(name)) => dir.join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// The file `link` points to through all its links, `None` if it is dangling.
+fn resolve_target(link: &Path) -> Option<PathBuf> {
+    std::fs::canonicalize(link).ok().filter(|p| p.is_file())
+}
+
+// MARK: test
+#[cfg(unix)]
+#[test]
+fn test_symlink_follower() {
+    let base = std::env::temp_dir().join("test_symlink_follower");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(base.join("links")).unwrap();
+    std::fs::create_dir_all(base.join("targets")).unwrap();
+    let base = std::fs::canonicalize(&base).unwrap();
+    for name in ["a.log", "b.log"] {
+        std::fs::write(base.join("targets").join(name), "").unwrap();
+    }
+    let link = base.join("links/current.log");
+    std::os::unix::fs::symlink(base.join("targets/a.log"), &link).unwrap();
+
+    let plain = base.join("targets");
+    let mut follower = SymlinkFollower::new(&[link.clone(), plain.clone()]);
+    assert!(follower.follows(&link));
+    assert!(!follower.follows(&plain));
+    assert!(follower.is_link(&link));
+    assert_eq!(
+        follower.target_of(&link),
+        Some(&*base.join("targets/a.log"))
+    );
+    assert_eq!(
+        follower.watch_dirs(),
+        [base.join("links"), base.join("targets")]
+    );
+    assert_eq!(follower.refresh(), []);
+
+    // 链接暂时删除时没有目标，重新创建后指向新文件
+    std::fs::remove_file(&link).unwrap();
+    assert_eq!(follower.refresh()[0].new, None);
+    std::os::unix::fs::symlink(base.join("targets/b.log"), &link).unwrap();
+    assert_eq!(
+        follower.refresh(),
+        [Repointed {
+            link: link.clone(),
+            old: None,
+            new: Some(base.join("targets/b.log")),
+        }]
+    );
+    assert!(follower.is_target(&base.join("targets/b.log")));
+    assert!(!follower.is_target(&base.join("targets/a.log")));
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
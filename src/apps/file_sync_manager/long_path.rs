@@ -0,0 +1,104 @@
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+
+/// Windows 传统 API 的路径长度上限
+pub const MAX_PATH: usize = 260;
+
+/// Path to pass to filesystem calls, with the `\\?\` prefix on Windows so paths longer
+/// than `MAX_PATH` still work.
+///
+/// Shares get `\\?\UNC\`; already prefixed paths, and all paths on other platforms,
+/// are returned unchanged.
+#[cfg(windows)]
+pub fn extended_path(path: &Path) -> Cow<'_, Path> {
+    let Some(s) = path.to_str() else {
+        return Cow::Borrowed(path);
+    };
+    if s.starts_with(r"\\?\") || s.starts_with(r"\\.\") {
+        return Cow::Borrowed(path);
+    }
+    // 扩展前缀下不会再解析 . 和 ..，先转为绝对路径
+    let absolute = match std::path::absolute(path) {
+        Ok(absolute) => absolute,
+        Err(_) => return Cow::Borrowed(path),
+    };
+    let absolute = absolute.to_string_lossy().replace('/', r"\");
+    Cow::Owned(PathBuf::from(match absolute.strip_prefix(r"\\") {
+        Some(share) => format!(r"\\?\UNC\{}", share),
+        None => format!(r"\\?\{}", absolute),
+    }))
+}
+
+#[cfg(not(windows))]
+pub fn extended_path(path: &Path) -> Cow<'_, Path> {
+    Cow::Borrowed(path)
+}
+
+/// Remove the `\\?\` or `\\?\UNC\` prefix, giving the form stored in the DB.
+pub fn strip_extended_prefix(path: &Path) -> PathBuf {
+    let s = path.to_string_lossy();
+    if let Some(share) = s.strip_prefix(r"\\?\UNC\") {
+        PathBuf::from(format!(r"\\{}", share))
+    } else if let Some(rest) = s.strip_prefix(r"\\?\") {
+        PathBuf::from(rest)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+// MARK: test
+#[test]
+fn test_strip_extended_prefix() {
+    assert_eq!(
+        strip_extended_prefix(Path::new(r"\\?\E:\testdata\a.csv")),
+        PathBuf::from(r"E:\testdata\a.csv")
+    );
+    assert_eq!(
+        strip_extended_prefix(Path::new(r"\\?\UNC\nas\share\a.csv")),
+        PathBuf::from(r"\\nas\share\a.csv")
+    );
+    assert_eq!(
+        strip_extended_prefix(Path::new("/tmp/a.csv")),
+        PathBuf::from("/tmp/a.csv")
+    );
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_extended_path_passes_through() {
+    let path = Path::new("/tmp/testdata/a.csv");
+    assert!(matches!(extended_path(path), Cow::Borrowed(p) if p == path));
+}
+
+#[cfg(windows)]
+#[test]
+fn test_extended_path_over_max_path() {
+    use crate::apps::file_sync_manager::registry::FileInfo;
+
+    assert_eq!(
+        extended_path(Path::new(r"E:\testdata\a.csv")),
+        Path::new(r"\\?\E:\testdata\a.csv")
+    );
+    assert_eq!(
+        extended_path(Path::new(r"\\nas\share\a.csv")),
+        Path::new(r"\\?\UNC\nas\share\a.csv")
+    );
+
+    let base = std::env::temp_dir().join("test_extended_path_over_max_path");
+    let mut dir = base.clone();
+    while dir.as_os_str().len() <= MAX_PATH {
+        dir.push("d".repeat(50));
+    }
+    std::fs::create_dir_all(extended_path(&dir)).unwrap();
+    let file = dir.join("long.csv");
+    std::fs::write(extended_path(&file), "x").unwrap();
+
+    let info = FileInfo::from_path(&file).unwrap();
+    assert_eq!(info.size, 1);
+    assert!(info.path.len() > MAX_PATH);
+    assert!(!info.path.starts_with(r"\\?\"));
+
+    std::fs::remove_dir_all(extended_path(&base)).unwrap();
+}
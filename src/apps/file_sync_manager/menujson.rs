@@ -16,6 +16,26 @@ pub const MENU_JSON: &str = r#"
                     "name": "stop",
                     "content": "This is a description of Skyrim.",
                     "children": []
+                },
+                {
+                    "name": "dry-run",
+                    "content": "Toggle dry run: list the paths that would be inserted without writing to DB.",
+                    "children": []
+                },
+                {
+                    "name": "recursive",
+                    "content": "Toggle recursive watch: also observe logs in subfolders, applied on the next start.",
+                    "children": []
+                },
+                {
+                    "name": "reset-offset",
+                    "content": "Read a watched log from the start again on its next change.",
+                    "children": []
+                },
+                {
+                    "name": "reload-config",
+                    "content": "Read the config file again, applied from the next event.",
+                    "children": []
                 }
             ]
         },
@@ -28,6 +48,16 @@ pub const MENU_JSON: &str = r#"
                     "content": "This is a description of Skyrim.",
                     "children": []
                 },
+                {
+                    "name": "dry-run",
+                    "content": "Scan and only log the files that would be recorded.",
+                    "children": []
+                },
+                {
+                    "name": "today",
+                    "content": "Scan for the files modified today.",
+                    "children": []
+                },
                 {
                     "name": "start-periodic",
                     "content": "Start periodic scan.",
@@ -38,9 +68,40 @@ pub const MENU_JSON: &str = r#"
                     "content": "Stop periodic scan.",
                     "children": []
 
+                },
+                {
+                    "name": "record",
+                    "content": "Record a single file to DB.",
+                    "children": []
+                },
+                {
+                    "name": "import",
+                    "content": "Import absolute paths listed in a text file.",
+                    "children": []
+                },
+                {
+                    "name": "export",
+                    "content": "Export the files found by the last scan to a CSV file.",
+                    "children": []
+                }
+            ]
+        },
+        {
+            "name": "operator",
+            "content": "Operator actions.",
+            "children": [
+                {
+                    "name": "note",
+                    "content": "Add a note to the event log (n).",
+                    "children": []
+                },
+                {
+                    "name": "export-csv",
+                    "content": "Export the recorded files to a CSV file.",
+                    "children": []
                 }
             ]
         }
     ]
 }
-"#;
\ No newline at end of file
+"#;
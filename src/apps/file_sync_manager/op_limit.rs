@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// 限制同时进行的扫描和数据库写入，所有引擎共用同一个实例
+#[derive(Debug, Clone, Default)]
+pub struct OperationLimit {
+    /// `None` 表示不限制
+    semaphore: Option<Arc<Semaphore>>,
+}
+
+/// 持有期间占用一个名额，释放时归还
+#[derive(Debug)]
+pub struct OperationPermit {
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl OperationLimit {
+    /// Allow at most `max` operations at once, 0 means no limit.
+    pub fn new(max: usize) -> Self {
+        Self {
+            semaphore: (max > 0).then(|| Arc::new(Semaphore::new(max))),
+        }
+    }
+
+    /// Take a permit if one is free right away.
+    pub fn try_acquire(&self) -> Option<OperationPermit> {
+        match &self.semaphore {
+            Some(semaphore) => {
+                semaphore
+                    .clone()
+                    .try_acquire_owned()
+                    .ok()
+                    .map(|permit| OperationPermit {
+                        _permit: Some(permit),
+                    })
+            }
+            None => Some(OperationPermit { _permit: None }),
+        }
+    }
+
+    /// Wait for a permit, `on_wait` is called first if none is free right away.
+    pub async fn acquire(&self, on_wait: impl FnOnce()) -> OperationPermit {
+        if let Some(permit) = self.try_acquire() {
+            return permit;
+        }
+        on_wait();
+        let semaphore = self.semaphore.clone().expect("unlimited never waits");
+        OperationPermit {
+            // 信号量不会被关闭
+            _permit: Some(semaphore.acquire_owned().await.unwrap()),
+        }
+    }
+}
+
+// MARK: test
+#[test]
+fn test_operation_limit() {
+    let limit = OperationLimit::new(2);
+    let first = limit.try_acquire().unwrap();
+    let _second = limit.try_acquire().unwrap();
+    assert!(limit.try_acquire().is_none());
+    drop(first);
+    assert!(limit.try_acquire().is_some());
+
+    // 不限制时总能取得
+    let unlimited = OperationLimit::new(0);
+    let _permits: Vec<_> = (0..100).map(|_| unlimited.try_acquire().unwrap()).collect();
+
+    // 等待时先回调，名额归还后取得
+    let limit = OperationLimit::new(1);
+    let held = limit.try_acquire().unwrap();
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let mut waited = false;
+    rt.block_on(async {
+        let release = async {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            drop(held);
+        };
+        let (_, _permit) = tokio::join!(release, limit.acquire(|| waited = true));
+    });
+    assert!(waited);
+}
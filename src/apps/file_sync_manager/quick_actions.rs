@@ -0,0 +1,192 @@
+use std::{path::PathBuf, time::Duration};
+
+use crate::{
+    QuickActionConfig,
+    apps::file_sync_manager::{ScanJitter, check_scan_interval},
+};
+
+/// 快捷操作在菜单中的父级名称
+pub const QUICK_MENU: &str = "quick";
+
+/// 快捷操作对应的菜单流程及其预填参数
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuickAction {
+    MonitorStart,
+    MonitorStop,
+    ScannerStart {
+        path: PathBuf,
+    },
+    /// `initial_delay_secs` 和 `jitter_percent` 为 `None` 时使用配置中的默认值
+    ScannerStartPeriodic {
+        path: PathBuf,
+        interval: Duration,
+        initial_delay_secs: Option<u64>,
+        jitter_percent: Option<u8>,
+    },
+    ScannerStop,
+    ScannerRecord {
+        path: PathBuf,
+    },
+}
+
+impl QuickAction {
+    pub fn from_config(config: &QuickActionConfig) -> Result<Self, String> {
+        let path = || {
+            config
+                .path
+                .as_deref()
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(PathBuf::from)
+                .ok_or_else(|| format!("action {} requires a path", config.action))
+        };
+        Ok(match config.action.as_str() {
+            "monitor-start" => QuickAction::MonitorStart,
+            "monitor-stop" => QuickAction::MonitorStop,
+            "scanner-start" => QuickAction::ScannerStart { path: path()? },
+            "scanner-start-periodic" => {
+                let interval = config
+                    .interval
+                    .as_deref()
+                    .ok_or_else(|| format!("action {} requires an interval", config.action))?;
+                let interval = parse_interval(interval)?;
+                check_scan_interval(interval)?;
+                ScanJitter::default()
+                    .with_override(None, config.jitter_percent)
+                    .check()?;
+                QuickAction::ScannerStartPeriodic {
+                    path: path()?,
+                    interval,
+                    initial_delay_secs: config.initial_delay_secs,
+                    jitter_percent: config.jitter_percent,
+                }
+            }
+            "scanner-stop" => QuickAction::ScannerStop,
+            "scanner-record" => QuickAction::ScannerRecord { path: path()? },
+            action => return Err(format!("unknown action {:?}", action)),
+        })
+    }
+}
+
+/// Parse an interval like `12h`, `30m` or `90s`; a bare number is in minutes.
+pub fn parse_interval(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (number, unit_secs) = match s.char_indices().last() {
+        Some((i, 'd')) => (&s[..i], 86400),
+        Some((i, 'h')) => (&s[..i], 3600),
+        Some((i, 'm')) => (&s[..i], 60),
+        Some((i, 's')) => (&s[..i], 1),
+        _ => (s, 60),
+    };
+    match number.trim().parse::<f64>() {
+        Ok(n) if n > 0.0 && n.is_finite() => Ok(Duration::from_secs_f64(n * unit_secs as f64)),
+        _ => Err(format!("invalid interval {:?}", s)),
+    }
+}
+
+/// 按配置顺序保存的具名快捷操作
+#[derive(Debug, Clone, Default)]
+pub struct QuickActions {
+    actions: Vec<(String, QuickAction)>,
+}
+
+impl QuickActions {
+    /// Parse all quick actions, reporting every invalid or duplicate entry at once.
+    pub fn from_config(configs: &[QuickActionConfig]) -> Result<Self, String> {
+        let mut actions: Vec<(String, QuickAction)> = Vec::new();
+        let mut errors = Vec::new();
+        for config in configs {
+            let name = config.name.trim();
+            if name.is_empty() {
+                errors.push("quick action with an empty name".to_string());
+                continue;
+            }
+            if actions.iter().any(|(n, _)| n == name) {
+                errors.push(format!("duplicate quick action {:?}", name));
+                continue;
+            }
+            match QuickAction::from_config(config) {
+                Ok(action) => actions.push((name.to_string(), action)),
+                Err(e) => errors.push(format!("quick action {:?}: {}", name, e)),
+            }
+        }
+        if errors.is_empty() {
+            Ok(Self { actions })
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&QuickAction> {
+        self.actions
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, action)| action)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.actions.iter().map(|(n, _)| n.as_str())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
+
+// MARK: test
+#[test]
+fn test_parse_quick_actions() {
+    let config =
+        |name: &str, action: &str, path: Option<&str>, interval: Option<&str>| QuickActionConfig {
+            name: name.to_string(),
+            action: action.to_string(),
+            path: path.map(str::to_string),
+            interval: interval.map(str::to_string),
+            initial_delay_secs: None,
+            jitter_percent: None,
+        };
+
+    assert_eq!(parse_interval("12h"), Ok(Duration::from_secs(12 * 3600)));
+    assert_eq!(parse_interval("30"), Ok(Duration::from_secs(30 * 60)));
+    assert_eq!(parse_interval("1.5m"), Ok(Duration::from_secs(90)));
+    assert!(parse_interval("0h").is_err());
+    assert!(parse_interval("soon").is_err());
+
+    let actions = QuickActions::from_config(&[
+        config(
+            "Nightly CTA scan",
+            "scanner-start-periodic",
+            Some(r"E:\testdata\CTA8280H"),
+            Some("12h"),
+        ),
+        config("Stop scan", "scanner-stop", None, None),
+    ])
+    .unwrap();
+    assert_eq!(
+        actions.names().collect::<Vec<_>>(),
+        ["Nightly CTA scan", "Stop scan"]
+    );
+    assert_eq!(
+        actions.get("Nightly CTA scan"),
+        Some(&QuickAction::ScannerStartPeriodic {
+            path: PathBuf::from(r"E:\testdata\CTA8280H"),
+            interval: Duration::from_secs(12 * 3600),
+            initial_delay_secs: None,
+            jitter_percent: None,
+        })
+    );
+    assert_eq!(actions.get("Undefined"), None);
+
+    let err = QuickActions::from_config(&[
+        config("a", "scanner-start-periodic", Some("E:"), None),
+        config("b", "scanner-start", None, None),
+        config("c", "reboot", None, None),
+        config("d", "scanner-stop", None, None),
+        config("d", "scanner-stop", None, None),
+    ])
+    .unwrap_err();
+    assert!(err.contains(r#""a": action scanner-start-periodic requires an interval"#));
+    assert!(err.contains(r#""b": action scanner-start requires a path"#));
+    assert!(err.contains(r#""c": unknown action "reboot""#));
+    assert!(err.contains(r#"duplicate quick action "d""#));
+}
@@ -0,0 +1,349 @@
+use std::collections::VecDeque;
+use std::path::Path;
+
+use chrono::{DateTime, FixedOffset, Utc};
+use ratatui::{
+    style::Color,
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, StatefulWidget, StatefulWidgetRef},
+};
+
+use serde::Serialize;
+
+use crate::{
+    ExportPathStyle, SizeUnits, TIME_ZONE,
+    apps::{
+        file_sync_manager::registry::{FileInfo, cust_code},
+        notifier::notify_ui,
+    },
+    my_widgets::theme::Theme,
+};
+
+/// 文件大小列的宽度，按单位格式化后右对齐
+const SIZE_WIDTH: usize = 10;
+
+/// 已写入数据库的单个文件记录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedFile {
+    pub path: String,
+    pub size: u64,
+    pub recorded_at: DateTime<FixedOffset>,
+}
+
+impl RecordedFile {
+    /// Customer code from the file name, which may be a Windows or Unix path.
+    pub fn cust_code(&self) -> Option<&str> {
+        let filename = self.path.rsplit(['\\', '/']).next().unwrap_or(&self.path);
+        cust_code(filename)
+    }
+}
+
+/// 导出 CSV 的一行
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    path: std::borrow::Cow<'a, str>,
+    size: u64,
+    cust_code: &'a str,
+    recorded_time: String,
+}
+
+/// 已处理文件列表，与事件日志分开保存，最新的记录在最前
+#[derive(Debug, Clone, Default)]
+pub struct RecordedFiles {
+    files: VecDeque<RecordedFile>,
+    capacity: usize,
+    filter: String,
+    theme: Theme,
+    size_units: SizeUnits,
+    export_path_style: ExportPathStyle,
+}
+
+impl RecordedFiles {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            files: VecDeque::with_capacity(capacity),
+            capacity,
+            filter: String::new(),
+            theme: Theme::detect(),
+            size_units: SizeUnits::default(),
+            export_path_style: ExportPathStyle::default(),
+        }
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    pub fn set_size_units(&mut self, size_units: SizeUnits) {
+        self.size_units = size_units;
+    }
+
+    /// Write paths in `style` when exporting, the recorded paths are kept as they are.
+    pub fn set_export_path_style(&mut self, style: ExportPathStyle) {
+        self.export_path_style = style;
+    }
+
+    /// Record the given file infos with the current time, dropping the oldest when full.
+    pub fn record(&mut self, infos: &[FileInfo]) {
+        let now = Utc::now().with_timezone(TIME_ZONE);
+        for info in infos {
+            if self.files.len() >= self.capacity {
+                self.files.pop_back();
+            }
+            self.files.push_front(RecordedFile {
+                path: info.path.clone(),
+                size: info.size,
+                recorded_at: now,
+            });
+        }
+        if !infos.is_empty() {
+            notify_ui();
+        }
+    }
+
+    /// Write every recorded file, oldest first, as CSV with a header row.
+    ///
+    /// Paths are written in the export path style. Returns the number of rows written,
+    /// the filter isn't applied.
+    pub fn write_csv<W: std::io::Write>(&self, writer: W) -> csv::Result<usize> {
+        let mut writer = csv::Writer::from_writer(writer);
+        for file in self.files.iter().rev() {
+            writer.serialize(CsvRow {
+                path: self.export_path_style.apply(&file.path),
+                size: file.size,
+                cust_code: file.cust_code().unwrap_or_default(),
+                recorded_time: file.recorded_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+            })?;
+        }
+        // 没有记录时也写出表头
+        if self.files.is_empty() {
+            writer.write_record(["path", "size", "cust_code", "recorded_time"])?;
+        }
+        writer.flush()?;
+        Ok(self.files.len())
+    }
+
+    /// Export the recorded files to a CSV file at `path`, see [`RecordedFiles::write_csv`].
+    pub fn export_csv(&self, path: &Path) -> std::io::Result<usize> {
+        let file = std::fs::File::create(path)?;
+        self.write_csv(std::io::BufWriter::new(file))
+            .map_err(std::io::Error::other)
+    }
+
+    pub fn set_filter(&mut self, filter: String) {
+        self.filter = filter;
+    }
+
+    pub fn get_filter(&self) -> &str {
+        &self.filter
+    }
+
+    /// Files whose path contains the current filter, newest first.
+    pub fn filtered(&self) -> impl Iterator<Item = &RecordedFile> {
+        self.files
+            .iter()
+            .filter(|f| self.filter.is_empty() || f.path.contains(&self.filter))
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn create_text(&self, f: &RecordedFile) -> String {
+        format!(
+            "{} {:>width$} {}",
+            f.recorded_at.format("%Y/%m/%d %H:%M:%S"),
+            self.size_units.format(f.size),
+            f.path,
+            width = SIZE_WIDTH
+        )
+    }
+
+    pub fn get_list_string(&self) -> Vec<String> {
+        self.filtered().map(|f| self.create_text(f)).collect()
+    }
+}
+
+impl StatefulWidget for &RecordedFiles {
+    type State = ListState;
+    fn render(
+        self,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+        state: &mut Self::State,
+    ) {
+        let items: Vec<ListItem> = self
+            .filtered()
+            .map(|f| {
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        f.recorded_at.format("%Y/%m/%d %H:%M:%S").to_string(),
+                        self.theme.fg(Color::Cyan),
+                    ),
+                    Span::from(" "),
+                    Span::styled(
+                        format!(
+                            "{:>width$}",
+                            self.size_units.format(f.size),
+                            width = SIZE_WIDTH
+                        ),
+                        self.theme.fg(Color::Yellow),
+                    ),
+                    Span::from(" "),
+                    Span::from(f.path.clone()),
+                ]))
+            })
+            .collect();
+
+        StatefulWidgetRef::render_ref(
+            &List::new(items)
+                .block(Block::default().borders(Borders::NONE))
+                .highlight_style(self.theme.highlight()),
+            area,
+            buf,
+            state,
+        );
+    }
+}
+
+// MARK: test
+#[test]
+fn test_recorded_files_after_process() {
+    use crate::apps::file_sync_manager::registry;
+
+    let base = std::env::temp_dir().join("test_recorded_files");
+    std::fs::create_dir_all(&base).unwrap();
+    let mut paths = Vec::new();
+    for i in 0..3 {
+        let file = base.join(format!("file{}", i));
+        std::fs::write(&file, "x".repeat(i + 1)).unwrap();
+        paths.push(file);
+    }
+
+    let before = Utc::now().with_timezone(TIME_ZONE);
+    let infos = registry::collect_file_infos(paths.clone());
+    let mut recorded = RecordedFiles::new(2);
+    recorded.record(&infos);
+
+    // 容量为2，最早的记录被丢弃，最新的在最前
+    assert_eq!(recorded.len(), 2);
+    let files: Vec<&RecordedFile> = recorded.filtered().collect();
+    assert_eq!(
+        files[0].path,
+        paths[2].canonicalize().unwrap().display().to_string()
+    );
+    assert_eq!(files[0].size, 3);
+    assert_eq!(files[1].size, 2);
+    assert!(files[0].recorded_at >= before);
+
+    recorded.set_filter("file1".to_string());
+    assert_eq!(recorded.filtered().count(), 1);
+    // 大小按单位格式化并右对齐
+    assert!(recorded.get_list_string()[0].contains("        2 B "));
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_export_csv_round_trip() {
+    let time = |s: &str| {
+        chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+            .unwrap()
+            .and_local_timezone(*TIME_ZONE)
+            .unwrap()
+    };
+    let mut recorded = RecordedFiles::new(10);
+    recorded.files.push_front(RecordedFile {
+        path: r"E:\testdata\CTA_20250601.csv".to_string(),
+        size: 2048,
+        recorded_at: time("2025-06-01 10:00:00"),
+    });
+    // 路径中的逗号和引号需要转义
+    recorded.files.push_front(RecordedFile {
+        path: r#"/mnt/testdata/a,b "c"/XYZ_1.csv"#.to_string(),
+        size: 7,
+        recorded_at: time("2025-06-01 10:00:01"),
+    });
+    recorded.files.push_front(RecordedFile {
+        path: "/mnt/testdata/nocode.csv".to_string(),
+        size: 0,
+        recorded_at: time("2025-06-01 10:00:02"),
+    });
+
+    let mut buf = Vec::new();
+    assert_eq!(recorded.write_csv(&mut buf).unwrap(), 3);
+    let text = String::from_utf8(buf).unwrap();
+    assert!(text.contains(r#""/mnt/testdata/a,b ""c""/XYZ_1.csv""#));
+
+    let mut reader = csv::Reader::from_reader(text.as_bytes());
+    assert_eq!(
+        reader.headers().unwrap(),
+        vec!["path", "size", "cust_code", "recorded_time"]
+    );
+    let rows: Vec<csv::StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+    // 最早的记录在最前
+    assert_eq!(
+        rows[0],
+        vec![
+            r"E:\testdata\CTA_20250601.csv",
+            "2048",
+            "CTA",
+            "2025-06-01 10:00:00"
+        ]
+    );
+    assert_eq!(
+        rows[1],
+        vec![
+            r#"/mnt/testdata/a,b "c"/XYZ_1.csv"#,
+            "7",
+            "XYZ",
+            "2025-06-01 10:00:01"
+        ]
+    );
+    assert_eq!(
+        rows[2],
+        vec!["/mnt/testdata/nocode.csv", "0", "", "2025-06-01 10:00:02"]
+    );
+
+    // 写入文件，没有记录时只有表头
+    let path = std::env::temp_dir().join("test_export_csv_round_trip.csv");
+    assert_eq!(RecordedFiles::new(10).export_csv(&path).unwrap(), 0);
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "path,size,cust_code,recorded_time\n"
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_export_forward_slash_paths() {
+    let mut recorded = RecordedFiles::new(10);
+    recorded.files.push_front(RecordedFile {
+        path: r"E:\testdata\CTA\CTA_20250601.csv".to_string(),
+        size: 1,
+        recorded_at: Utc::now().with_timezone(TIME_ZONE),
+    });
+    recorded.set_export_path_style(ExportPathStyle::Forward);
+
+    let mut buf = Vec::new();
+    recorded.write_csv(&mut buf).unwrap();
+    let text = String::from_utf8(buf).unwrap();
+    assert!(
+        text.contains("E:/testdata/CTA/CTA_20250601.csv,1,CTA,"),
+        "{}",
+        text
+    );
+    // 记录中的路径保持原样
+    assert_eq!(
+        recorded.filtered().next().unwrap().path,
+        r"E:\testdata\CTA\CTA_20250601.csv"
+    );
+    assert_eq!(
+        ExportPathStyle::Backslash.apply("/mnt/testdata/a.csv"),
+        r"\mnt\testdata\a.csv"
+    );
+}
@@ -0,0 +1,565 @@
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::io::Error;
+#[cfg(feature = "sqlite")]
+use std::path::Path;
+use std::sync::Arc;
+
+use mysql_async::Pool;
+
+#[cfg(feature = "sqlite")]
+use crate::apps::file_sync_manager::registry::{
+    Field, PATH_BATCH_SIZE, delete_sql, like_prefix, missing_paths, quote_ident, row_params,
+    select_hashes_sql,
+};
+use crate::{
+    DbBackendKind, FileMonitorConfig,
+    apps::file_sync_manager::registry::{
+        FileInfo, InsertSchema, RecentInsert, delete_missing_rows, insert_batches, recent_rows,
+        recorded_hash_rows,
+    },
+    util::retry::Backoff,
+};
+
+/// 记录文件信息的数据库
+pub trait RegistryBackend {
+    /// Insert `infos`, updating the rows of paths already recorded.
+    fn insert_file_infos(
+        &self,
+        infos: &[FileInfo],
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    /// Delete the rows whose path starts with `prefix` but is not in `seen`, in one
+    /// transaction, and return how many were deleted.
+    fn delete_missing(
+        &self,
+        prefix: &str,
+        seen: &HashSet<String>,
+    ) -> impl Future<Output = Result<u64, Error>> + Send;
+
+    /// The hashes recorded for those of `paths` that have one, empty if no hash column
+    /// is mapped.
+    fn recorded_hashes(
+        &self,
+        paths: &[String],
+    ) -> impl Future<Output = Result<HashMap<String, String>, Error>> + Send;
+
+    /// The `limit` most recently inserted rows, newest first.
+    fn recent_inserts(
+        &self,
+        limit: usize,
+    ) -> impl Future<Output = Result<Vec<RecentInsert>, Error>> + Send;
+}
+
+/// 批次重试前收到说明重试原因的消息
+pub type RetryHook = Arc<dyn Fn(String) + Send + Sync>;
+
+/// 配置的 db_url 或环境变量 DB_URL 指向的 MySQL
+pub struct MySqlBackend {
+    pool: Pool,
+    schema: Arc<InsertSchema>,
+    backoff: Backoff,
+    on_retry: RetryHook,
+    /// 插入失败时在错误中附上 SQL 和部分参数
+    verbose: bool,
+}
+
+impl MySqlBackend {
+    pub fn new(url: &str, schema: Arc<InsertSchema>, backoff: Backoff) -> Self {
+        Self {
+            pool: Pool::new(url),
+            schema,
+            backoff,
+            on_retry: Arc::new(|_| {}),
+            verbose: crate::verbose_mode(),
+        }
+    }
+
+    /// Pass a message to `on_retry` before each retry of a batch.
+    pub fn with_on_retry(mut self, on_retry: RetryHook) -> Self {
+        self.on_retry = on_retry;
+        self
+    }
+}
+
+impl RegistryBackend for MySqlBackend {
+    async fn insert_file_infos(&self, infos: &[FileInfo]) -> Result<(), Error> {
+        insert_batches(
+            &self.pool,
+            infos,
+            &self.schema,
+            self.backoff,
+            &*self.on_retry,
+            self.verbose,
+        )
+        .await
+    }
+
+    async fn delete_missing(&self, prefix: &str, seen: &HashSet<String>) -> Result<u64, Error> {
+        delete_missing_rows(&self.pool, &self.schema, self.backoff, prefix, seen).await
+    }
+
+    async fn recorded_hashes(&self, paths: &[String]) -> Result<HashMap<String, String>, Error> {
+        recorded_hash_rows(&self.pool, &self.schema, self.backoff, paths).await
+    }
+
+    async fn recent_inserts(&self, limit: usize) -> Result<Vec<RecentInsert>, Error> {
+        recent_rows(&self.pool, &self.schema, self.backoff, limit).await
+    }
+}
+
+/// db_path 指向的 SQLite 文件，首次使用时创建表
+#[cfg(feature = "sqlite")]
+pub struct SqliteBackend {
+    pool: sqlx::SqlitePool,
+    schema: Arc<InsertSchema>,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteBackend {
+    /// Open the database at `path`, creating the file and the configured table if missing.
+    pub async fn open(path: &Path, schema: Arc<InsertSchema>) -> Result<Self, Error> {
+        let options = sqlx::sqlite::SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true);
+        let pool = sqlx::SqlitePool::connect_with(options)
+            .await
+            .map_err(|e| Error::other(format!("Failed to open {}: {}", path.display(), e)))?;
+        sqlx::query(&create_table_sql(&schema))
+            .execute(&pool)
+            .await
+            .map_err(|e| {
+                Error::other(format!(
+                    "Failed to create table {}: {}",
+                    schema.table.table, e
+                ))
+            })?;
+        Ok(Self { pool, schema })
+    }
+
+    pub fn pool(&self) -> &sqlx::SqlitePool {
+        &self.pool
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl RegistryBackend for SqliteBackend {
+    async fn insert_file_infos(&self, infos: &[FileInfo]) -> Result<(), Error> {
+        if infos.is_empty() {
+            return Ok(());
+        }
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let sql = sqlite_insert_sql(&self.schema, infos.len());
+        let mut query = sqlx::query(&sql);
+        for value in infos
+            .iter()
+            .flat_map(|info| row_params(&self.schema, info, &now))
+        {
+            query = query.bind(value);
+        }
+        query
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+            .map_err(|e| Error::other(format!("Failed to insert file info with {}", e)))
+    }
+
+    async fn delete_missing(&self, prefix: &str, seen: &HashSet<String>) -> Result<u64, Error> {
+        let failed = |e: sqlx::Error| Error::other(format!("Failed to delete records with {}", e));
+        let column = self
+            .schema
+            .columns
+            .column(Field::FilePath)
+            .unwrap_or("file_path");
+        let table = quote_ident(&self.schema.table.table);
+        let mut tx = self.pool.begin().await.map_err(failed)?;
+        let recorded: Vec<(String,)> = sqlx::query_as(&format!(
+            "SELECT {column} FROM {table} WHERE {column} LIKE ? ESCAPE '!'"
+        ))
+        .bind(like_prefix(prefix))
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(failed)?;
+        let missing = missing_paths(recorded.into_iter().map(|(p,)| p), prefix, seen);
+        for chunk in missing.chunks(PATH_BATCH_SIZE) {
+            let sql = delete_sql(&table, column, chunk.len());
+            let mut query = sqlx::query(&sql);
+            for path in chunk {
+                query = query.bind(path);
+            }
+            query.execute(&mut *tx).await.map_err(failed)?;
+        }
+        tx.commit().await.map_err(failed)?;
+        Ok(missing.len() as u64)
+    }
+
+    async fn recorded_hashes(&self, paths: &[String]) -> Result<HashMap<String, String>, Error> {
+        let (Some(path), Some(hash)) = (
+            self.schema.columns.column(Field::FilePath),
+            self.schema.columns.column(Field::FileHash),
+        ) else {
+            return Ok(HashMap::new());
+        };
+        let table = quote_ident(&self.schema.table.table);
+        let mut hashes = HashMap::new();
+        for chunk in paths.chunks(PATH_BATCH_SIZE) {
+            let sql = select_hashes_sql(&table, path, hash, chunk.len());
+            let mut query = sqlx::query_as::<_, (String, Option<String>)>(&sql);
+            for path in chunk {
+                query = query.bind(path);
+            }
+            let rows = query
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| Error::other(format!("Failed to query file hashes with {}", e)))?;
+            hashes.extend(rows.into_iter().filter_map(|(p, h)| Some((p, h?))));
+        }
+        Ok(hashes)
+    }
+
+    async fn recent_inserts(&self, limit: usize) -> Result<Vec<RecentInsert>, Error> {
+        // 必填字段一定已映射，插入时间精度为秒，同一秒内按插入顺序
+        let column = |f: Field| self.schema.columns.column(f).unwrap_or("NULL");
+        let sql = format!(
+            "SELECT {}, {}, {}, {}, {time_inserted} FROM {} ORDER BY {time_inserted} DESC, id DESC LIMIT ?",
+            column(Field::FilePath),
+            column(Field::FileName),
+            column(Field::FileSize),
+            column(Field::CustCode),
+            quote_ident(&self.schema.table.table),
+            time_inserted = column(Field::TimeInserted),
+        );
+        let rows: Vec<(String, String, i64, Option<String>, String)> = sqlx::query_as(&sql)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::other(format!("Failed to query recent inserts with {}", e)))?;
+        Ok(rows
+            .into_iter()
+            .map(
+                |(path, filename, size, cust_code, time_inserted)| RecentInsert {
+                    path,
+                    filename,
+                    size: size as u64,
+                    cust_code,
+                    time_inserted,
+                },
+            )
+            .collect())
+    }
+}
+
+/// The mapped columns followed by the extra columns, in insert order.
+#[cfg(feature = "sqlite")]
+fn column_names(schema: &InsertSchema) -> Vec<&str> {
+    schema
+        .columns
+        .columns()
+        .map(|(_, c)| c.as_str())
+        .chain(schema.extras.iter().map(|c| c.column.as_str()))
+        .collect()
+}
+
+// 路径列唯一，已记录的路径插入时改为更新，与 MySQL 的 ON DUPLICATE KEY UPDATE 一致
+#[cfg(feature = "sqlite")]
+fn create_table_sql(schema: &InsertSchema) -> String {
+    let mut columns: Vec<String> = schema
+        .columns
+        .columns()
+        .map(|(f, c)| match f {
+            Field::FileSize => format!("{c} INTEGER"),
+            _ => format!("{c} TEXT"),
+        })
+        .chain(schema.extras.iter().map(|c| format!("{} TEXT", c.column)))
+        .collect();
+    // 必填字段一定已映射
+    let path = schema
+        .columns
+        .column(Field::FilePath)
+        .unwrap_or("file_path");
+    columns.push(format!("UNIQUE ({path})"));
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (id INTEGER PRIMARY KEY, {})",
+        quote_ident(&schema.table.table),
+        columns.join(", ")
+    )
+}
+
+// 列名已在加载配置时校验
+#[cfg(feature = "sqlite")]
+fn sqlite_insert_sql(schema: &InsertSchema, rows: usize) -> String {
+    let columns = column_names(schema);
+    let placeholders = format!("({})", vec!["?"; columns.len()].join(", "));
+    let values = vec![placeholders; rows].join(",");
+
+    let mut updates: Vec<String> = schema
+        .columns
+        .columns()
+        .filter(|(f, _)| f.is_updated())
+        .map(|(_, c)| c.clone())
+        .collect();
+    updates.extend(schema.extras.iter().map(|c| c.column.clone()));
+    let updates: Vec<String> = updates
+        .iter()
+        .map(|c| format!("{c}=excluded.{c}"))
+        .collect();
+
+    let path = schema
+        .columns
+        .column(Field::FilePath)
+        .unwrap_or("file_path");
+    format!(
+        "INSERT INTO {} ({}) VALUES {} ON CONFLICT ({}) DO UPDATE SET {}",
+        quote_ident(&schema.table.table),
+        columns.join(", "),
+        values,
+        path,
+        updates.join(", ")
+    )
+}
+
+/// 按配置中的 db_backend 打开的数据库
+pub enum ConfiguredBackend {
+    MySql(MySqlBackend),
+    #[cfg(feature = "sqlite")]
+    Sqlite(SqliteBackend),
+}
+
+impl ConfiguredBackend {
+    /// Open the backend chosen by `db_backend`. For MySQL, `url` overrides `db_url`.
+    pub async fn open(
+        config: &FileMonitorConfig,
+        url: Option<String>,
+        schema: Arc<InsertSchema>,
+        backoff: Backoff,
+    ) -> Result<Self, Error> {
+        match config.db_backend {
+            DbBackendKind::Mysql => {
+                let url = url
+                    .or_else(|| config.db_url.clone())
+                    .ok_or_else(|| Error::other("DB_URL or db_url in config must be set"))?;
+                Ok(Self::MySql(MySqlBackend::new(&url, schema, backoff)))
+            }
+            DbBackendKind::Sqlite => {
+                let path = config
+                    .db_path
+                    .as_deref()
+                    .ok_or_else(|| Error::other("db_path must be set for the sqlite db_backend"))?;
+                #[cfg(feature = "sqlite")]
+                return Ok(Self::Sqlite(SqliteBackend::open(path, schema).await?));
+                #[cfg(not(feature = "sqlite"))]
+                Err(Error::other(format!(
+                    "db_backend sqlite ({}) needs a build with the sqlite feature",
+                    path.display()
+                )))
+            }
+        }
+    }
+
+    /// See [`MySqlBackend::with_on_retry`]. SQLite batches are not retried.
+    pub fn with_on_retry(self, on_retry: RetryHook) -> Self {
+        match self {
+            Self::MySql(backend) => Self::MySql(backend.with_on_retry(on_retry)),
+            #[cfg(feature = "sqlite")]
+            backend => backend,
+        }
+    }
+}
+
+impl RegistryBackend for ConfiguredBackend {
+    async fn insert_file_infos(&self, infos: &[FileInfo]) -> Result<(), Error> {
+        match self {
+            Self::MySql(backend) => backend.insert_file_infos(infos).await,
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(backend) => backend.insert_file_infos(infos).await,
+        }
+    }
+
+    async fn delete_missing(&self, prefix: &str, seen: &HashSet<String>) -> Result<u64, Error> {
+        match self {
+            Self::MySql(backend) => backend.delete_missing(prefix, seen).await,
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(backend) => backend.delete_missing(prefix, seen).await,
+        }
+    }
+
+    async fn recorded_hashes(&self, paths: &[String]) -> Result<HashMap<String, String>, Error> {
+        match self {
+            Self::MySql(backend) => backend.recorded_hashes(paths).await,
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(backend) => backend.recorded_hashes(paths).await,
+        }
+    }
+
+    async fn recent_inserts(&self, limit: usize) -> Result<Vec<RecentInsert>, Error> {
+        match self {
+            Self::MySql(backend) => backend.recent_inserts(limit).await,
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(backend) => backend.recent_inserts(limit).await,
+        }
+    }
+}
+
+// MARK: test
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_sqlite_backend() {
+    use crate::apps::file_sync_manager::registry::insert_schema;
+
+    let base = std::env::temp_dir().join("test_sqlite_backend");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&base).unwrap();
+    let file = base.join("CTA_1.csv");
+    std::fs::write(&file, "abc").unwrap();
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        // 数据库文件和表都不存在，首次打开时创建
+        let db = base.join("registry.db");
+        let schema = insert_schema(&crate::load_config().file_sync_manager).unwrap();
+        let backend = SqliteBackend::open(&db, schema.clone()).await.unwrap();
+        let info = FileInfo::from_path(&file).unwrap();
+        backend
+            .insert_file_infos(std::slice::from_ref(&info))
+            .await
+            .unwrap();
+
+        // 同一路径再次插入时更新大小
+        std::fs::write(&file, "abcdef").unwrap();
+        let info = FileInfo::from_path(&file).unwrap();
+        let backend = SqliteBackend::open(&db, schema.clone()).await.unwrap();
+        backend
+            .insert_file_infos(std::slice::from_ref(&info))
+            .await
+            .unwrap();
+
+        let path = schema.columns.column(Field::FilePath).unwrap();
+        let size = schema.columns.column(Field::FileSize).unwrap();
+        let rows: Vec<(String, i64)> =
+            sqlx::query_as(&format!("SELECT {path}, {size} FROM file_info"))
+                .fetch_all(backend.pool())
+                .await
+                .unwrap();
+        assert_eq!(rows, [(info.path, 6)]);
+        backend.pool().close().await;
+    });
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_sqlite_delete_missing() {
+    use crate::apps::file_sync_manager::registry::insert_schema;
+
+    let base = std::env::temp_dir().join("test_sqlite_delete_missing");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&base).unwrap();
+    let info = |path: &str| FileInfo::from_raw(path);
+    let infos = [
+        info("/data_1/a.csv"),
+        info("/data_1/sub/b.csv"),
+        // 下划线在 LIKE 中是通配符，需要转义
+        info("/dataX1/c.csv"),
+    ];
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let schema = insert_schema(&crate::load_config().file_sync_manager).unwrap();
+        let backend = SqliteBackend::open(&base.join("registry.db"), schema.clone())
+            .await
+            .unwrap();
+        backend.insert_file_infos(&infos).await.unwrap();
+
+        let seen: HashSet<String> = ["/data_1/a.csv".to_string()].into();
+        assert_eq!(backend.delete_missing("/data_1/", &seen).await.unwrap(), 1);
+        assert_eq!(backend.delete_missing("/data_1/", &seen).await.unwrap(), 0);
+
+        let path = schema.columns.column(Field::FilePath).unwrap();
+        let rows: Vec<(String,)> =
+            sqlx::query_as(&format!("SELECT {path} FROM file_info ORDER BY {path}"))
+                .fetch_all(backend.pool())
+                .await
+                .unwrap();
+        let rows: Vec<String> = rows.into_iter().map(|(p,)| p).collect();
+        assert_eq!(rows, ["/dataX1/c.csv", "/data_1/a.csv"]);
+        backend.pool().close().await;
+    });
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_sqlite_recorded_hashes() {
+    use crate::apps::file_sync_manager::registry::ColumnMap;
+
+    let base = std::env::temp_dir().join("test_sqlite_recorded_hashes");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&base).unwrap();
+    let schema = Arc::new(
+        InsertSchema::new(ColumnMap::default().with_file_hash().unwrap(), vec![]).unwrap(),
+    );
+    let info = |path: &str, hash: Option<&str>| FileInfo {
+        file_hash: hash.map(str::to_string),
+        ..FileInfo::from_raw(path)
+    };
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let backend = SqliteBackend::open(&base.join("registry.db"), schema)
+            .await
+            .unwrap();
+        backend
+            .insert_file_infos(&[info("/d/a.csv", Some("aa")), info("/d/b.csv", None)])
+            .await
+            .unwrap();
+        let paths = ["/d/a.csv", "/d/b.csv", "/d/c.csv"].map(String::from);
+        let recorded = backend.recorded_hashes(&paths).await.unwrap();
+        assert_eq!(
+            recorded,
+            [("/d/a.csv".to_string(), "aa".to_string())].into()
+        );
+
+        // 哈希随重复插入更新
+        backend
+            .insert_file_infos(&[info("/d/a.csv", Some("a2"))])
+            .await
+            .unwrap();
+        let recorded = backend.recorded_hashes(&paths).await.unwrap();
+        assert_eq!(recorded.get("/d/a.csv").map(String::as_str), Some("a2"));
+        backend.pool().close().await;
+    });
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_sqlite_backend_needs_path() {
+    use crate::apps::file_sync_manager::registry::insert_schema;
+
+    let mut config = crate::load_config().file_sync_manager;
+    config.db_backend = DbBackendKind::Sqlite;
+    config.db_path = None;
+    let backoff = Backoff::new(1, std::time::Duration::ZERO);
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let open = |config: &FileMonitorConfig| {
+        rt.block_on(ConfiguredBackend::open(
+            config,
+            None,
+            insert_schema(&crate::load_config().file_sync_manager).unwrap(),
+            backoff,
+        ))
+    };
+    let e = open(&config).err().unwrap();
+    assert_eq!(
+        e.to_string(),
+        "db_path must be set for the sqlite db_backend"
+    );
+
+    // MySQL 仍需要连接地址
+    config.db_backend = DbBackendKind::Mysql;
+    config.db_url = None;
+    let e = open(&config).err().unwrap();
+    assert_eq!(e.to_string(), "DB_URL or db_url in config must be set");
+}
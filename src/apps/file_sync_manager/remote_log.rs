@@ -0,0 +1,234 @@
+use std::{io, path::Path};
+
+/// 远程日志每次最多读取的字节数
+pub const REMOTE_READ_CHUNK: usize = 64 * 1024;
+
+/// `ssh://user@host[:port]/path` 形式的远程日志地址
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteLog {
+    pub user: String,
+    pub host: String,
+    pub port: u16,
+    /// 远程主机上的绝对路径
+    pub path: String,
+}
+
+impl RemoteLog {
+    /// Parse an `ssh://` observed path, `None` if `path` isn't one.
+    pub fn parse(path: &Path) -> Option<Result<Self, String>> {
+        let path = path.to_str()?;
+        let rest = path.strip_prefix("ssh://")?;
+        Some(Self::parse_rest(rest).ok_or_else(|| {
+            format!(
+                "Invalid remote log {}, expected ssh://user@host[:port]/path",
+                path
+            )
+        }))
+    }
+
+    fn parse_rest(rest: &str) -> Option<Self> {
+        let (authority, path) = rest.split_at(rest.find('/')?);
+        let (user, host_port) = authority.split_once('@')?;
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().ok()?),
+            None => (host_port, 22),
+        };
+        if user.is_empty() || host.is_empty() || path.len() < 2 {
+            return None;
+        }
+        Some(Self {
+            user: user.to_string(),
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+}
+
+/// Whether `path` is an `ssh://` remote log, valid or not.
+pub fn is_remote(path: &Path) -> bool {
+    path.to_str().is_some_and(|p| p.starts_with("ssh://"))
+}
+
+/// 读取远程文件的方式，测试时替换为内存实现
+pub trait RemoteReader {
+    /// Current size of the remote file.
+    fn size(&mut self) -> io::Result<u64>;
+    /// Read at most `max` bytes starting at `offset`.
+    fn read_at(&mut self, offset: u64, max: usize) -> io::Result<Vec<u8>>;
+}
+
+/// 远程文件中的一行
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteLine {
+    pub text: String,
+    /// 从开始跟踪时算起的行号
+    pub line_no: u64,
+    /// 行首在远程文件中的偏移量
+    pub offset: u64,
+}
+
+/// 跟踪远程文件的读取位置，保留末尾未写完的半行
+#[derive(Debug, Default)]
+pub struct RemoteTail {
+    /// `None` 表示尚未取得基准大小
+    offset: Option<u64>,
+    partial: Vec<u8>,
+    line_no: u64,
+}
+
+impl RemoteTail {
+    /// Start from the end of the file, so only lines appended afterwards are read.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read what was appended since the last poll and return the complete lines,
+    /// along with the number of bytes read.
+    ///
+    /// A file that shrank was rotated or truncated, so it is read again from the start.
+    pub fn poll(&mut self, reader: &mut dyn RemoteReader) -> io::Result<(Vec<RemoteLine>, u64)> {
+        let size = reader.size()?;
+        let Some(mut offset) = self.offset else {
+            self.offset = Some(size);
+            return Ok((Vec::new(), 0));
+        };
+        if size < offset {
+            offset = 0;
+            self.partial.clear();
+        }
+
+        let mut lines = Vec::new();
+        let mut read = 0;
+        while offset < size {
+            let max = REMOTE_READ_CHUNK.min((size - offset) as usize);
+            let chunk = reader.read_at(offset, max)?;
+            if chunk.is_empty() {
+                break;
+            }
+            offset += chunk.len() as u64;
+            read += chunk.len() as u64;
+            self.partial.extend_from_slice(&chunk);
+            // 行首偏移量 = 当前偏移量 - 尚未切分的字节数
+            let mut start = offset - self.partial.len() as u64;
+            while let Some(end) = self.partial.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.partial.drain(..=end).collect();
+                self.line_no += 1;
+                lines.push(RemoteLine {
+                    text: String::from_utf8_lossy(&line).into_owned(),
+                    line_no: self.line_no,
+                    offset: start,
+                });
+                start += line.len() as u64;
+            }
+        }
+        self.offset = Some(offset);
+        Ok((lines, read))
+    }
+}
+
+/// 通过 SFTP 读取远程日志，使用 SSH agent 认证并校验 known_hosts
+#[cfg(feature = "sftp")]
+pub struct SftpReader {
+    // Sftp 依赖会话保持连接
+    _session: ssh2::Session,
+    sftp: ssh2::Sftp,
+    path: std::path::PathBuf,
+}
+
+#[cfg(feature = "sftp")]
+impl SftpReader {
+    /// Connect to `remote`, check its host key against `~/.ssh/known_hosts` and log in
+    /// with the SSH agent.
+    pub fn connect(remote: &RemoteLog) -> io::Result<Self> {
+        let tcp = std::net::TcpStream::connect((remote.host.as_str(), remote.port))?;
+        let mut session = ssh2::Session::new()?;
+        session.set_tcp_stream(tcp);
+        session.handshake()?;
+        Self::check_host_key(&session, remote)?;
+        session.userauth_agent(&remote.user)?;
+        let sftp = session.sftp()?;
+        Ok(Self {
+            _session: session,
+            sftp,
+            path: std::path::PathBuf::from(&remote.path),
+        })
+    }
+
+    fn check_host_key(session: &ssh2::Session, remote: &RemoteLog) -> io::Result<()> {
+        let home = std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .ok_or_else(|| io::Error::other("No home directory to find known_hosts"))?;
+        let mut known_hosts = session.known_hosts()?;
+        known_hosts.read_file(
+            &Path::new(&home).join(".ssh").join("known_hosts"),
+            ssh2::KnownHostFileKind::OpenSSH,
+        )?;
+        let (key, _) = session
+            .host_key()
+            .ok_or_else(|| io::Error::other("Server sent no host key"))?;
+        match known_hosts.check_port(&remote.host, remote.port, key) {
+            ssh2::CheckResult::Match => Ok(()),
+            ssh2::CheckResult::Mismatch => Err(io::Error::other(format!(
+                "Host key of {} doesn't match known_hosts",
+                remote.host
+            ))),
+            _ => Err(io::Error::other(format!(
+                "{} is not in known_hosts",
+                remote.host
+            ))),
+        }
+    }
+}
+
+#[cfg(feature = "sftp")]
+impl RemoteReader for SftpReader {
+    fn size(&mut self) -> io::Result<u64> {
+        self.sftp
+            .stat(&self.path)?
+            .size
+            .ok_or_else(|| io::Error::other("Remote file size is unknown"))
+    }
+
+    fn read_at(&mut self, offset: u64, max: usize) -> io::Result<Vec<u8>> {
+        use std::io::{Read, Seek, SeekFrom};
+
+        let mut file = self.sftp.open(&self.path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut buf = Vec::with_capacity(max);
+        file.take(max as u64).read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+// MARK: test
+#[test]
+fn test_parse_remote_log() {
+    let parse = |s: &str| RemoteLog::parse(Path::new(s));
+    assert_eq!(
+        parse("ssh://ftp@10.53.2.70/C:/inetpub/logs/u_ex250601.log"),
+        Some(Ok(RemoteLog {
+            user: "ftp".to_string(),
+            host: "10.53.2.70".to_string(),
+            port: 22,
+            path: "/C:/inetpub/logs/u_ex250601.log".to_string(),
+        }))
+    );
+    assert_eq!(
+        parse("ssh://ftp@nas:2222/var/log/ftp.log")
+            .unwrap()
+            .unwrap()
+            .port,
+        2222
+    );
+    for bad in [
+        "ssh://nas/var/log/ftp.log",
+        "ssh://ftp@nas",
+        "ssh://ftp@nas/",
+        "ssh://ftp@nas:port/a.log",
+    ] {
+        assert!(parse(bad).unwrap().is_err(), "{}", bad);
+    }
+    assert_eq!(parse(r"C:\inetpub\logs\LogFiles\FTPSVC2"), None);
+    assert!(is_remote(Path::new("ssh://nas")));
+}
@@ -0,0 +1,262 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use ratatui::{
+    layout::Constraint,
+    style::Color,
+    widgets::{ListState, Paragraph, Row, StatefulWidget, Table, TableState, Widget},
+};
+
+use crate::{
+    TIME_ZONE,
+    apps::{
+        file_sync_manager::registry::{LoggedPath, RecordSource},
+        notifier::notify_ui,
+    },
+    my_widgets::theme::Theme,
+};
+
+/// 重试队列最多保留的条目数，超出时丢弃最早的
+pub const RETRY_QUEUE_CAPACITY: usize = 1000;
+
+/// 一条写入数据库失败的路径
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailedInsert {
+    /// 队列内唯一，重试完成时用来找回条目
+    pub id: u64,
+    pub path: LoggedPath,
+    pub source: Option<RecordSource>,
+    pub error: String,
+    /// 已失败的次数
+    pub attempts: u32,
+    pub failed_at: DateTime<FixedOffset>,
+    /// 正在重试，完成前不能再次重试或丢弃
+    pub retrying: bool,
+}
+
+impl FailedInsert {
+    pub fn path_str(&self) -> String {
+        match &self.path {
+            LoggedPath::Mapped { path, .. } => path.display().to_string(),
+            LoggedPath::Raw(raw) => raw.clone(),
+        }
+    }
+}
+
+/// 观察器和扫描器写入失败的路径，操作员可在 retry 标签页中重试或丢弃
+#[derive(Debug, Clone)]
+pub struct RetryQueue {
+    items: Vec<FailedInsert>,
+    capacity: usize,
+    next_id: u64,
+    theme: Theme,
+}
+
+impl Default for RetryQueue {
+    fn default() -> Self {
+        Self::new(RETRY_QUEUE_CAPACITY)
+    }
+}
+
+impl RetryQueue {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            items: Vec::new(),
+            capacity,
+            next_id: 0,
+            theme: Theme::detect(),
+        }
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    pub fn items(&self) -> &[FailedInsert] {
+        &self.items
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Queue `paths` that failed to insert with `error`.
+    ///
+    /// A path already in the queue is updated in place instead of added again.
+    pub fn add_failed(
+        &mut self,
+        paths: Vec<LoggedPath>,
+        source: Option<RecordSource>,
+        error: &str,
+    ) {
+        let now = Utc::now().with_timezone(TIME_ZONE);
+        for path in paths {
+            if let Some(item) = self.items.iter_mut().find(|i| i.path == path) {
+                item.error = error.to_string();
+                item.attempts += 1;
+                item.failed_at = now;
+                continue;
+            }
+            if self.items.len() == self.capacity {
+                self.items.remove(0);
+            }
+            self.next_id += 1;
+            self.items.push(FailedInsert {
+                id: self.next_id,
+                path,
+                source: source.clone(),
+                error: error.to_string(),
+                attempts: 1,
+                failed_at: now,
+                retrying: false,
+            });
+        }
+        notify_ui();
+    }
+
+    /// Mark the entry at `index` as retrying and return a copy to retry with.
+    pub fn start_retry(&mut self, index: usize) -> Option<FailedInsert> {
+        let item = self.items.get_mut(index).filter(|i| !i.retrying)?;
+        item.retrying = true;
+        notify_ui();
+        Some(item.clone())
+    }
+
+    /// Remove the entry `id` if its retry succeeded, otherwise record the new error.
+    pub fn finish_retry(&mut self, id: u64, result: Result<(), String>) {
+        let Some(index) = self.items.iter().position(|i| i.id == id) else {
+            return;
+        };
+        match result {
+            Ok(()) => {
+                self.items.remove(index);
+            }
+            Err(e) => {
+                let item = &mut self.items[index];
+                item.retrying = false;
+                item.attempts += 1;
+                item.error = e;
+                item.failed_at = Utc::now().with_timezone(TIME_ZONE);
+            }
+        }
+        notify_ui();
+    }
+
+    /// Drop the entry at `index` without retrying it.
+    pub fn discard(&mut self, index: usize) -> Option<FailedInsert> {
+        if self.items.get(index)?.retrying {
+            return None;
+        }
+        notify_ui();
+        Some(self.items.remove(index))
+    }
+
+    pub fn create_text(&self, item: &FailedInsert) -> String {
+        format!(
+            "{} x{}{} {}: {}",
+            item.failed_at.format("%Y-%m-%d %H:%M:%S"),
+            item.attempts,
+            if item.retrying { " retrying" } else { "" },
+            item.path_str(),
+            item.error
+        )
+    }
+
+    pub fn get_list_string(&self) -> Vec<String> {
+        if self.items.is_empty() {
+            return vec!["No failed inserts".to_string()];
+        }
+        self.items.iter().map(|i| self.create_text(i)).collect()
+    }
+}
+
+impl StatefulWidget for &RetryQueue {
+    type State = ListState;
+    fn render(
+        self,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+        state: &mut Self::State,
+    ) {
+        if self.items.is_empty() {
+            Paragraph::new("No failed inserts")
+                .style(self.theme.text())
+                .render(area, buf);
+            return;
+        }
+
+        let rows: Vec<Row> = self
+            .items
+            .iter()
+            .map(|i| {
+                let row = Row::new(vec![
+                    i.path_str(),
+                    i.attempts.to_string(),
+                    if i.retrying {
+                        "retrying...".to_string()
+                    } else {
+                        i.error.clone()
+                    },
+                ]);
+                if i.retrying {
+                    row.style(self.theme.fg(Color::Yellow))
+                } else {
+                    row
+                }
+            })
+            .collect();
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Fill(2),
+                Constraint::Length(8),
+                Constraint::Fill(1),
+            ],
+        )
+        .header(
+            Row::new(vec!["path (r: retry, d: discard)", "attempts", "error"])
+                .style(self.theme.fg(Color::Cyan)),
+        )
+        .row_highlight_style(self.theme.highlight());
+        let mut table_state = TableState::default().with_selected(state.selected());
+        StatefulWidget::render(table, area, buf, &mut table_state);
+    }
+}
+
+// MARK: test
+#[test]
+fn test_retry_queue() {
+    let raw = |p: &str| LoggedPath::Raw(p.to_string());
+    let mut queue = RetryQueue::new(2);
+    assert_eq!(queue.get_list_string(), ["No failed inserts"]);
+
+    queue.add_failed(vec![raw("/CTA/a.csv"), raw("/CTA/b.csv")], None, "DB down");
+    // 同一路径再次失败只更新次数
+    queue.add_failed(vec![raw("/CTA/a.csv")], None, "timeout");
+    assert_eq!(queue.len(), 2);
+    assert_eq!(queue.items()[0].attempts, 2);
+    assert_eq!(queue.items()[0].error, "timeout");
+
+    // 重试中的条目不能重复重试或丢弃
+    let item = queue.start_retry(0).unwrap();
+    assert!(queue.start_retry(0).is_none());
+    assert!(queue.discard(0).is_none());
+    assert!(queue.get_list_string()[0].contains(" x2 retrying /CTA/a.csv: timeout"));
+
+    queue.finish_retry(item.id, Err("still down".to_string()));
+    assert_eq!(queue.items()[0].attempts, 3);
+    assert!(!queue.items()[0].retrying);
+
+    let item = queue.start_retry(0).unwrap();
+    queue.finish_retry(item.id, Ok(()));
+    assert_eq!(queue.len(), 1);
+    assert_eq!(queue.discard(0).unwrap().path, raw("/CTA/b.csv"));
+    assert!(queue.is_empty());
+
+    // 超出容量丢弃最早的
+    queue.add_failed(vec![raw("1"), raw("2"), raw("3")], None, "DB down");
+    let paths: Vec<_> = queue.items().iter().map(|i| i.path_str()).collect();
+    assert_eq!(paths, ["2", "3"]);
+}
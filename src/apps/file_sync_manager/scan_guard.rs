@@ -0,0 +1,275 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::apps::file_sync_manager::long_path::extended_path;
+
+/// 扫描前的保护检查，避免误扫整个磁盘
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct ScanGuard {
+    /// 扫描这些目录或其子目录前需要确认
+    pub deny_list: Vec<String>,
+    /// 预计数最多遍历的条目数
+    pub precount_limit: usize,
+    /// 预计数超过该值时警告，0 表示不检查
+    pub warn_threshold: usize,
+    /// 单次扫描最多收集的文件数，超出时中止且不写数据库，0 表示不限制
+    pub max_files_per_scan: usize,
+}
+
+impl Default for ScanGuard {
+    fn default() -> Self {
+        Self {
+            deny_list: Vec::new(),
+            precount_limit: 100_000,
+            warn_threshold: 50_000,
+            max_files_per_scan: 0,
+        }
+    }
+}
+
+impl ScanGuard {
+    /// Why scanning `path` needs explicit confirmation, `None` if it doesn't.
+    pub fn confirmation_reason(&self, path: &Path) -> Option<String> {
+        let path_str = path.to_string_lossy();
+        if is_root(&path_str) {
+            return Some(format!("{} is a filesystem root", path_str));
+        }
+        let key = match_key(&path_str);
+        self.deny_list
+            .iter()
+            .find(|denied| {
+                let denied = match_key(denied);
+                !denied.is_empty()
+                    && (key == denied
+                        || key
+                            .strip_prefix(&denied)
+                            .is_some_and(|rest| rest.starts_with('/')))
+            })
+            .map(|denied| format!("{} is in the scan deny list ({})", path_str, denied))
+    }
+
+    /// How many files a scan may collect before it is known to be over the cap,
+    /// `usize::MAX` without a cap.
+    pub fn collect_limit(&self) -> usize {
+        match self.max_files_per_scan {
+            0 => usize::MAX,
+            max => max + 1,
+        }
+    }
+
+    /// Error if a scan of `path` collected more than `max_files_per_scan` files.
+    pub fn check_file_count(&self, path: &Path, count: usize) -> Result<(), String> {
+        if self.max_files_per_scan == 0 || count <= self.max_files_per_scan {
+            return Ok(());
+        }
+        Err(format!(
+            "Scan of {} aborted: more than {} files (max_files_per_scan), narrow the path or raise the limit",
+            path.display(),
+            self.max_files_per_scan
+        ))
+    }
+
+    /// Count entries under `path` up to `precount_limit`, with a warning when the count
+    /// reaches `warn_threshold`.
+    pub fn precount(&self, path: &Path) -> (PreCount, Option<String>) {
+        let count = precount(path, self.precount_limit);
+        let warning =
+            (self.warn_threshold > 0 && count.entries >= self.warn_threshold).then(|| {
+                format!(
+                    "{} has {} entries, above the warning threshold of {}",
+                    path.display(),
+                    count,
+                    self.warn_threshold
+                )
+            });
+        (count, warning)
+    }
+}
+
+/// 预计数的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PreCount {
+    pub entries: usize,
+    /// 达到上限后停止遍历，实际条目更多
+    pub truncated: bool,
+}
+
+impl std::fmt::Display for PreCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.truncated {
+            write!(f, "at least {}", self.entries)
+        } else {
+            write!(f, "{}", self.entries)
+        }
+    }
+}
+
+/// Count the files and directories under `path`, stopping after `limit`.
+pub fn precount(path: &Path, limit: usize) -> PreCount {
+    let mut entries = 0;
+    // 根目录本身不计入
+    for _ in WalkDir::new(extended_path(path))
+        .min_depth(1)
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        if entries == limit {
+            return PreCount {
+                entries,
+                truncated: true,
+            };
+        }
+        entries += 1;
+    }
+    PreCount {
+        entries,
+        truncated: false,
+    }
+}
+
+/// Whether `path` is a filesystem root: `/`, a drive like `E:\`, or a share like
+/// `\\nas\share`.
+///
+/// Works on the string so both Windows and Unix shapes are recognized on any platform.
+pub fn is_root(path: &str) -> bool {
+    let path = path.trim();
+    let path = path
+        .strip_prefix(r"\\?\UNC\")
+        .map(|share| format!(r"\\{}", share))
+        .unwrap_or_else(|| path.strip_prefix(r"\\?\").unwrap_or(path).to_string());
+    let is_sep = |c: char| c == '/' || c == '\\';
+    if path.is_empty() {
+        return false;
+    }
+    if path.chars().all(is_sep) {
+        return true;
+    }
+    // 盘符根目录，E: 或 E:\
+    let mut chars = path.chars();
+    if let (Some(drive), Some(':')) = (chars.next(), chars.next())
+        && drive.is_ascii_alphabetic()
+        && chars.all(is_sep)
+    {
+        return true;
+    }
+    // 共享根目录，\\server\share
+    if let Some(share) = path.strip_prefix(r"\\").or_else(|| path.strip_prefix("//")) {
+        return share.split(is_sep).filter(|c| !c.is_empty()).count() <= 2;
+    }
+    false
+}
+
+/// Form of a path used to compare with the deny list: `/` separators, no trailing
+/// separator, and lowercase on Windows.
+fn match_key(path: &str) -> String {
+    let key = path.trim().replace('\\', "/");
+    let key = key.trim_end_matches('/');
+    if cfg!(windows) {
+        key.to_lowercase()
+    } else {
+        key.to_string()
+    }
+}
+
+// MARK: test
+#[test]
+fn test_root_detection() {
+    for root in [
+        "/",
+        "//",
+        r"\",
+        "E:",
+        r"E:\",
+        "e:/",
+        r"\\?\E:\",
+        r"\\nas\share",
+        r"\\nas\share\",
+        r"\\?\UNC\nas\share",
+    ] {
+        assert!(is_root(root), "{}", root);
+    }
+    for path in [
+        "",
+        "/home",
+        "/mnt/testdata/",
+        r"E:\testdata",
+        r"E:testdata",
+        r"\\nas\share\testdata",
+        r"\\?\E:\testdata",
+        "testdata",
+    ] {
+        assert!(!is_root(path), "{}", path);
+    }
+
+    let guard = ScanGuard {
+        deny_list: vec![r"E:\Windows\".to_string(), "/var".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(
+        guard.confirmation_reason(Path::new(r"E:\")),
+        Some(r"E:\ is a filesystem root".to_string())
+    );
+    assert!(guard.confirmation_reason(Path::new("/var/log")).is_some());
+    assert!(
+        guard
+            .confirmation_reason(Path::new(r"E:\Windows\System32"))
+            .is_some()
+    );
+    assert_eq!(guard.confirmation_reason(Path::new("/variable")), None);
+    assert_eq!(guard.confirmation_reason(Path::new(r"E:\testdata")), None);
+}
+
+#[test]
+fn test_precount_cutoff() {
+    let base = std::env::temp_dir().join("test_precount_cutoff");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(base.join("sub")).unwrap();
+    for i in 0..5 {
+        std::fs::write(base.join("sub").join(format!("{}.csv", i)), "x").unwrap();
+    }
+
+    // sub 目录加 5 个文件
+    let full = precount(&base, 100);
+    assert_eq!(
+        full,
+        PreCount {
+            entries: 6,
+            truncated: false
+        }
+    );
+    assert_eq!(full.to_string(), "6");
+    let cut = precount(&base, 3);
+    assert_eq!(
+        cut,
+        PreCount {
+            entries: 3,
+            truncated: true
+        }
+    );
+    assert_eq!(cut.to_string(), "at least 3");
+    assert!(!precount(&base, 6).truncated);
+
+    let guard = ScanGuard {
+        deny_list: Vec::new(),
+        precount_limit: 4,
+        warn_threshold: 4,
+        max_files_per_scan: 0,
+    };
+    let (count, warning) = guard.precount(&base);
+    assert!(count.truncated);
+    assert!(
+        warning
+            .unwrap()
+            .ends_with("at least 4 entries, above the warning threshold of 4")
+    );
+    let guard = ScanGuard {
+        warn_threshold: 10,
+        ..guard
+    };
+    assert_eq!(guard.precount(&base).1, None);
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::util::rng::SplitMix64;
+
+/// 定期扫描抖动比例的上限，保证抖动后的间隔不小于原间隔的一半
+pub const MAX_JITTER_PERCENT: u8 = 50;
+
+/// 定期扫描的首次延迟和抖动，避免多台机器同时开始扫描
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct ScanJitter {
+    /// 启动后等待多少秒再开始第一次扫描
+    pub initial_delay_secs: u64,
+    /// 每次等待在 ±interval * jitter_percent / 100 范围内随机偏移
+    pub jitter_percent: u8,
+}
+
+impl ScanJitter {
+    pub fn check(&self) -> Result<(), String> {
+        if self.jitter_percent > MAX_JITTER_PERCENT {
+            return Err(format!(
+                "Jitter {}% is above the maximum of {}%",
+                self.jitter_percent, MAX_JITTER_PERCENT
+            ));
+        }
+        Ok(())
+    }
+
+    /// Replace the fields a schedule sets explicitly, keeping the rest.
+    pub fn with_override(
+        self,
+        initial_delay_secs: Option<u64>,
+        jitter_percent: Option<u8>,
+    ) -> Self {
+        Self {
+            initial_delay_secs: initial_delay_secs.unwrap_or(self.initial_delay_secs),
+            jitter_percent: jitter_percent.unwrap_or(self.jitter_percent),
+        }
+    }
+}
+
+/// Delays between periodic scans, jittered from a seeded RNG.
+///
+/// The first delay is the initial delay, later ones are the interval. Each gets an
+/// independent offset of up to `interval * jitter_percent / 100` either way, a
+/// negative offset larger than the initial delay starts the first scan at once.
+#[derive(Debug, Clone)]
+pub struct ScanSchedule {
+    interval: Duration,
+    jitter: ScanJitter,
+    rng: SplitMix64,
+    started: bool,
+}
+
+impl ScanSchedule {
+    pub fn new(interval: Duration, jitter: ScanJitter, seed: u64) -> Self {
+        Self {
+            interval,
+            jitter,
+            rng: SplitMix64::new(seed),
+            started: false,
+        }
+    }
+
+    /// How long to wait before the next scan.
+    pub fn next_delay(&mut self) -> Duration {
+        let base = if self.started {
+            self.interval
+        } else {
+            self.started = true;
+            Duration::from_secs(self.jitter.initial_delay_secs)
+        };
+        if self.jitter.jitter_percent == 0 {
+            return base;
+        }
+        let span = self.interval.as_secs_f64() * self.jitter.jitter_percent as f64 / 100.0;
+        // 在 [-span, span] 内均匀取值
+        let offset = self.rng.next_signed_unit() * span;
+        Duration::from_secs_f64((base.as_secs_f64() + offset).max(0.0))
+    }
+}
+
+// MARK: test
+#[test]
+fn test_jittered_fire_times() {
+    let interval = Duration::from_secs(600);
+    let jitter = ScanJitter {
+        initial_delay_secs: 120,
+        jitter_percent: 10,
+    };
+
+    for seed in 0..100 {
+        let mut schedule = ScanSchedule::new(interval, jitter, seed);
+        let first = schedule.next_delay();
+        let second = first + schedule.next_delay();
+        // 首次在 120s ± 60s，第二次再过 600s ± 60s
+        assert!((60..=180).contains(&first.as_secs()), "{:?}", first);
+        let gap = (second - first).as_secs();
+        assert!((540..=660).contains(&gap), "{:?}", gap);
+    }
+
+    // 同一种子得到相同的时间，不同种子的首次时间不全相同
+    let delays = |seed| {
+        let mut schedule = ScanSchedule::new(interval, jitter, seed);
+        [schedule.next_delay(), schedule.next_delay()]
+    };
+    assert_eq!(delays(7), delays(7));
+    assert!((0..10).any(|seed| delays(seed)[0] != delays(7)[0]));
+
+    let mut schedule = ScanSchedule::new(interval, ScanJitter::default(), 7);
+    assert_eq!(schedule.next_delay(), Duration::ZERO);
+    assert_eq!(schedule.next_delay(), interval);
+}
+
+#[test]
+fn test_scan_jitter_check() {
+    let jitter = ScanJitter {
+        initial_delay_secs: 0,
+        jitter_percent: 51,
+    };
+    assert_eq!(
+        jitter.check(),
+        Err("Jitter 51% is above the maximum of 50%".to_string())
+    );
+    let jitter = jitter.with_override(Some(30), Some(20));
+    assert_eq!(jitter.initial_delay_secs, 30);
+    assert_eq!(jitter.check(), Ok(()));
+}
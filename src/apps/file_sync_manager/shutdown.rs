@@ -0,0 +1,171 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::apps::file_sync_manager::{CLOSE_TIMEOUT, StatusTransition};
+
+/// 关闭流程的各阶段，按执行顺序排列
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownPhase {
+    /// 观察器不再等待新的 notify 事件，扫描器不再安排新的扫描
+    StopInput,
+    /// 观察器处理完已收到的事件并写入数据库后退出
+    DrainObserver,
+    /// 等待进行中的扫描、单文件记录和导入完成
+    DrainScanner,
+    /// 停止心跳监视等后台线程
+    StopBackground,
+}
+
+impl ShutdownPhase {
+    pub const ALL: [ShutdownPhase; 4] = [
+        ShutdownPhase::StopInput,
+        ShutdownPhase::DrainObserver,
+        ShutdownPhase::DrainScanner,
+        ShutdownPhase::StopBackground,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ShutdownPhase::StopInput => "stop input",
+            ShutdownPhase::DrainObserver => "drain observer",
+            ShutdownPhase::DrainScanner => "drain scanner",
+            ShutdownPhase::StopBackground => "stop background",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PhaseOutcome {
+    Done,
+    /// 没有需要停止的工作
+    Skipped,
+    /// 超时后放弃等待，线程被分离
+    TimedOut,
+    Failed(String),
+}
+
+impl fmt::Display for PhaseOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PhaseOutcome::Done => write!(f, "done"),
+            PhaseOutcome::Skipped => write!(f, "skipped"),
+            PhaseOutcome::TimedOut => write!(f, "timed out"),
+            PhaseOutcome::Failed(e) => write!(f, "failed: {}", e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhaseReport {
+    pub phase: ShutdownPhase,
+    pub outcome: PhaseOutcome,
+    pub elapsed: Duration,
+}
+
+impl fmt::Display for PhaseReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Shutdown phase {}: {} in {:?}",
+            self.phase.name(),
+            self.outcome,
+            self.elapsed
+        )
+    }
+}
+
+/// 一次关闭中每个阶段的结果，按执行顺序保存
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    pub phases: Vec<PhaseReport>,
+    /// 本次会话中观察器和扫描器的状态变化
+    pub status_history: Vec<StatusTransition>,
+}
+
+impl ShutdownReport {
+    /// Run `phase`, recording its outcome and how long it took.
+    pub fn run(&mut self, phase: ShutdownPhase, f: impl FnOnce() -> PhaseOutcome) -> &PhaseReport {
+        let start = Instant::now();
+        let outcome = f();
+        self.phases.push(PhaseReport {
+            phase,
+            outcome,
+            elapsed: start.elapsed(),
+        });
+        self.phases.last().unwrap()
+    }
+
+    pub fn outcome(&self, phase: ShutdownPhase) -> Option<&PhaseOutcome> {
+        self.phases
+            .iter()
+            .find(|p| p.phase == phase)
+            .map(|p| &p.outcome)
+    }
+
+    /// Whether every phase either finished or had nothing to do.
+    pub fn is_clean(&self) -> bool {
+        self.phases
+            .iter()
+            .all(|p| matches!(p.outcome, PhaseOutcome::Done | PhaseOutcome::Skipped))
+    }
+
+    /// Whether there was nothing running to shut down.
+    pub fn is_noop(&self) -> bool {
+        self.phases
+            .iter()
+            .all(|p| p.outcome == PhaseOutcome::Skipped)
+    }
+}
+
+impl fmt::Display for ShutdownReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, phase) in self.phases.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", phase)?;
+        }
+        if !self.status_history.is_empty() {
+            write!(f, "\nStatus history:")?;
+            for transition in &self.status_history {
+                write!(f, "\n  {}", transition)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 关闭各阶段等待的最长秒数
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct ShutdownTimeouts {
+    pub drain_observer_secs: u64,
+    pub drain_scanner_secs: u64,
+    pub stop_background_secs: u64,
+}
+
+impl Default for ShutdownTimeouts {
+    fn default() -> Self {
+        let secs = CLOSE_TIMEOUT.as_secs();
+        Self {
+            drain_observer_secs: secs,
+            drain_scanner_secs: secs,
+            stop_background_secs: secs,
+        }
+    }
+}
+
+impl ShutdownTimeouts {
+    pub fn timeout(&self, phase: ShutdownPhase) -> Duration {
+        Duration::from_secs(match phase {
+            ShutdownPhase::StopInput => 0,
+            ShutdownPhase::DrainObserver => self.drain_observer_secs,
+            ShutdownPhase::DrainScanner => self.drain_scanner_secs,
+            ShutdownPhase::StopBackground => self.stop_background_secs,
+        })
+    }
+}
@@ -0,0 +1,155 @@
+use std::{collections::VecDeque, fmt};
+
+use chrono::{DateTime, FixedOffset, Utc};
+
+use crate::{ProgressStatus, TIME_ZONE};
+
+/// 每个组件保留的状态变化条数
+pub const STATUS_HISTORY_LEN: usize = 100;
+
+/// 一次状态变化
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusTransition {
+    pub time: DateTime<FixedOffset>,
+    /// observer 或 scanner
+    pub component: &'static str,
+    pub from: ProgressStatus,
+    pub to: ProgressStatus,
+    /// 调用处给出的原因
+    pub reason: Option<String>,
+}
+
+impl fmt::Display for StatusTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {:?} -> {:?}",
+            self.time.format("%Y-%m-%d %H:%M:%S"),
+            self.component,
+            self.from,
+            self.to
+        )?;
+        match &self.reason {
+            Some(reason) => write!(f, ": {}", reason),
+            None => Ok(()),
+        }
+    }
+}
+
+/// 最近的状态变化，超过容量时丢弃最早的
+#[derive(Debug, Clone)]
+pub struct StatusHistory {
+    component: &'static str,
+    capacity: usize,
+    transitions: VecDeque<StatusTransition>,
+}
+
+impl StatusHistory {
+    pub fn new(component: &'static str, capacity: usize) -> Self {
+        Self {
+            component,
+            capacity,
+            transitions: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Record a change from `from` to `to`, returning it unless the status didn't change.
+    pub fn record(
+        &mut self,
+        from: ProgressStatus,
+        to: ProgressStatus,
+        reason: Option<&str>,
+    ) -> Option<&StatusTransition> {
+        if from == to || self.capacity == 0 {
+            return None;
+        }
+        if self.transitions.len() == self.capacity {
+            self.transitions.pop_front();
+        }
+        self.transitions.push_back(StatusTransition {
+            time: Utc::now().with_timezone(TIME_ZONE),
+            component: self.component,
+            from,
+            to,
+            reason: reason.map(|r| r.to_string()),
+        });
+        self.transitions.back()
+    }
+
+    /// The recorded transitions, oldest first.
+    pub fn snapshot(&self) -> Vec<StatusTransition> {
+        self.transitions.iter().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.transitions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transitions.is_empty()
+    }
+}
+
+/// Merge the histories of several components into one list ordered by time.
+pub fn merge_histories(
+    histories: impl IntoIterator<Item = Vec<StatusTransition>>,
+) -> Vec<StatusTransition> {
+    let mut merged: Vec<StatusTransition> = histories.into_iter().flatten().collect();
+    // 稳定排序，同一时间的保持原顺序
+    merged.sort_by_key(|t| t.time);
+    merged
+}
+
+// MARK: test
+#[test]
+fn test_status_history_ring() {
+    use crate::{ProgressStatus::*, Running};
+
+    let mut history = StatusHistory::new("scanner", 3);
+    let script = [
+        (Stopped, Running(Running::Once), Some("scan started")),
+        (Running(Running::Once), Finished, Some("scan completed")),
+        // 状态未变化不记录
+        (Finished, Finished, None),
+        (Finished, Running(Running::Periodic), None),
+        (Running(Running::Periodic), Stopping, Some("stop requested")),
+    ];
+    for (from, to, reason) in script {
+        history.record(from, to, reason);
+    }
+
+    // 容量为 3，最早的一条被丢弃
+    assert_eq!(history.len(), 3);
+    let rows: Vec<_> = history
+        .snapshot()
+        .into_iter()
+        .map(|t| (t.component, t.from, t.to, t.reason))
+        .collect();
+    assert_eq!(
+        rows,
+        [
+            (
+                "scanner",
+                Running(Running::Once),
+                Finished,
+                Some("scan completed".to_string())
+            ),
+            ("scanner", Finished, Running(Running::Periodic), None),
+            (
+                "scanner",
+                Running(Running::Periodic),
+                Stopping,
+                Some("stop requested".to_string())
+            ),
+        ]
+    );
+    let text = history.snapshot()[2].to_string();
+    assert!(text.ends_with(" scanner Running(Periodic) -> Stopping: stop requested"));
+
+    let mut observer = StatusHistory::new("observer", 3);
+    observer.record(Stopped, Failed, Some("gave up watching"));
+    let merged = merge_histories([history.snapshot(), observer.snapshot()]);
+    assert_eq!(merged.len(), 4);
+    assert_eq!(merged[3].component, "observer");
+    assert!(merged.windows(2).all(|w| w[0].time <= w[1].time));
+}
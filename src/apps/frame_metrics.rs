@@ -0,0 +1,144 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// 保留最近多少帧的统计数据
+pub const FRAME_METRICS_CAPACITY: usize = 300;
+
+#[derive(Debug, Clone, Copy)]
+pub struct FrameSample {
+    pub at: Instant,
+    pub draw: Duration,
+    pub handle_event: Duration,
+    pub events: u32,
+}
+
+/// 界面主循环的帧统计，只记录时间，百分位数在需要展示时才计算
+#[derive(Debug, Clone)]
+pub struct FrameMetrics {
+    samples: VecDeque<FrameSample>,
+    capacity: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameSummary {
+    pub frames: usize,
+    pub draw_p50: Duration,
+    pub draw_p95: Duration,
+    pub handle_event_p95: Duration,
+    pub events_per_sec: f64,
+    /// 因输入事件而重绘的帧占全部重绘帧的比例
+    pub dirty_ratio: f64,
+}
+
+impl Default for FrameMetrics {
+    fn default() -> Self {
+        Self::new(FRAME_METRICS_CAPACITY)
+    }
+}
+
+impl FrameMetrics {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn record(&mut self, sample: FrameSample) {
+        if self.samples.len() >= self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn summary(&self) -> FrameSummary {
+        let mut draws: Vec<Duration> = self.samples.iter().map(|s| s.draw).collect();
+        draws.sort();
+        let mut handles: Vec<Duration> = self.samples.iter().map(|s| s.handle_event).collect();
+        handles.sort();
+
+        let events: u32 = self.samples.iter().map(|s| s.events).sum();
+        let dirty = self.samples.iter().filter(|s| s.events > 0).count();
+        let span = match (self.samples.front(), self.samples.back()) {
+            (Some(first), Some(last)) => last.at.duration_since(first.at).as_secs_f64(),
+            _ => 0.0,
+        };
+
+        FrameSummary {
+            frames: self.samples.len(),
+            draw_p50: percentile(&draws, 50.0),
+            draw_p95: percentile(&draws, 95.0),
+            handle_event_p95: percentile(&handles, 95.0),
+            events_per_sec: if span > 0.0 {
+                events as f64 / span
+            } else {
+                0.0
+            },
+            dirty_ratio: if self.samples.is_empty() {
+                0.0
+            } else {
+                dirty as f64 / self.samples.len() as f64
+            },
+        }
+    }
+
+    /// Render the summary in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let s = self.summary();
+        format!(
+            "# TYPE one_server_ui_draw_seconds summary\n\
+             one_server_ui_draw_seconds{{quantile=\"0.5\"}} {}\n\
+             one_server_ui_draw_seconds{{quantile=\"0.95\"}} {}\n\
+             # TYPE one_server_ui_handle_event_seconds summary\n\
+             one_server_ui_handle_event_seconds{{quantile=\"0.95\"}} {}\n\
+             # TYPE one_server_ui_events_per_second gauge\n\
+             one_server_ui_events_per_second {}\n\
+             # TYPE one_server_ui_dirty_ratio gauge\n\
+             one_server_ui_dirty_ratio {}\n",
+            s.draw_p50.as_secs_f64(),
+            s.draw_p95.as_secs_f64(),
+            s.handle_event_p95.as_secs_f64(),
+            s.events_per_sec,
+            s.dirty_ratio,
+        )
+    }
+}
+
+/// Nearest-rank percentile of already sorted samples.
+pub fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+// MARK: test
+#[test]
+fn test_frame_metrics_percentiles() {
+    let start = Instant::now();
+    let mut metrics = FrameMetrics::new(100);
+    // 多出的样本会挤掉最早的 1..=20ms
+    for i in 1..=120u64 {
+        metrics.record(FrameSample {
+            at: start + Duration::from_millis(i * 10),
+            draw: Duration::from_millis(i),
+            handle_event: Duration::ZERO,
+            events: (i % 2) as u32,
+        });
+    }
+
+    let summary = metrics.summary();
+    assert_eq!(summary.frames, 100);
+    assert_eq!(summary.draw_p50, Duration::from_millis(70));
+    assert_eq!(summary.draw_p95, Duration::from_millis(115));
+    assert_eq!(summary.dirty_ratio, 0.5);
+    // 50 个事件分布在 990ms 内
+    assert!((summary.events_per_sec - 50.0 / 0.99).abs() < 1e-9);
+
+    assert_eq!(percentile(&[], 50.0), Duration::ZERO);
+    assert_eq!(
+        percentile(&[Duration::from_millis(3)], 95.0),
+        Duration::from_millis(3)
+    );
+}
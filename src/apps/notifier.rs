@@ -0,0 +1,267 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Condvar, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use ratatui::crossterm::event::Event;
+
+/// 后台变化合并唤醒的时间窗口
+pub const BATCH_WINDOW: Duration = Duration::from_millis(50);
+
+/// 连续调整终端大小时，停下这么久后才重新布局
+pub const RESIZE_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// What woke the UI loop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Wake {
+    Input(Event),
+    /// 后台线程修改了要显示的状态
+    Changed,
+    Timeout,
+}
+
+#[derive(Debug, Default)]
+struct NotifyState {
+    inputs: VecDeque<Event>,
+    /// 上次唤醒后有未显示的变化
+    pending: bool,
+    last_change_wake: Option<Instant>,
+    change_wakeups: u64,
+}
+
+/// 后台线程通知界面重绘，窗口内的多次变化只唤醒一次
+///
+/// 终端输入由单独的线程读取后放入同一队列，界面循环只需等待这一处。
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    inner: Arc<(Mutex<NotifyState>, Condvar)>,
+    window: Duration,
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Self::new(BATCH_WINDOW)
+    }
+}
+
+impl Notifier {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            inner: Arc::new((Mutex::new(NotifyState::default()), Condvar::new())),
+            window,
+        }
+    }
+
+    /// Mark that displayed state changed, called from background threads.
+    pub fn notify(&self) {
+        let (state, condvar) = &*self.inner;
+        let mut state = state.lock().unwrap();
+        if !state.pending {
+            state.pending = true;
+            condvar.notify_all();
+        }
+    }
+
+    /// Queue a terminal event for the UI loop.
+    pub fn push_input(&self, event: Event) {
+        let (state, condvar) = &*self.inner;
+        state.lock().unwrap().inputs.push_back(event);
+        condvar.notify_all();
+    }
+
+    /// Wait for input, a batch of changes, or `timeout`, whichever comes first.
+    ///
+    /// Input is returned at once. The first change after a quiet window wakes at once,
+    /// later ones wait for the rest of the window so a burst wakes only once per window.
+    pub fn wait(&self, timeout: Duration) -> Wake {
+        let deadline = Instant::now() + timeout;
+        let (state, condvar) = &*self.inner;
+        let mut state = state.lock().unwrap();
+        loop {
+            if let Some(event) = state.inputs.pop_front() {
+                return Wake::Input(event);
+            }
+            let now = Instant::now();
+            let wait_until = if state.pending {
+                let ready_at = state
+                    .last_change_wake
+                    .map_or(now, |last| (last + self.window).max(now));
+                if ready_at <= now {
+                    state.pending = false;
+                    state.last_change_wake = Some(now);
+                    state.change_wakeups += 1;
+                    return Wake::Changed;
+                }
+                ready_at.min(deadline)
+            } else {
+                deadline
+            };
+            if now >= deadline {
+                return Wake::Timeout;
+            }
+            state = condvar.wait_timeout(state, wait_until - now).unwrap().0;
+        }
+    }
+
+    /// How many times changes have woken the loop.
+    pub fn change_wakeups(&self) -> u64 {
+        self.inner.0.lock().unwrap().change_wakeups
+    }
+}
+
+/// 拖动调整终端大小时合并连续的事件，停下后只按最终大小重新布局一次
+#[derive(Debug)]
+pub struct ResizeDebounce {
+    window: Duration,
+    /// 尚未布局的最新大小及收到的时间
+    pending: Option<((u16, u16), Instant)>,
+    relayouts: u64,
+}
+
+impl ResizeDebounce {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            pending: None,
+            relayouts: 0,
+        }
+    }
+
+    /// Note a resize to `size`, replacing any earlier one of the same burst.
+    pub fn on_resize(&mut self, size: (u16, u16), now: Instant) {
+        self.pending = Some((size, now));
+    }
+
+    /// Whether a burst of resizes is still going on, drawing should wait for it to end.
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// The final size once no resize came for the window, returned once per burst.
+    pub fn ready(&mut self, now: Instant) -> Option<(u16, u16)> {
+        let (size, at) = self.pending?;
+        if now.duration_since(at) < self.window {
+            return None;
+        }
+        self.pending = None;
+        self.relayouts += 1;
+        Some(size)
+    }
+
+    /// `idle`, shortened while a burst is pending so the relayout isn't late.
+    pub fn timeout(&self, now: Instant, idle: Duration) -> Duration {
+        self.pending.map_or(idle, |(_, at)| {
+            (at + self.window).saturating_duration_since(now).min(idle)
+        })
+    }
+
+    /// How many bursts have been laid out.
+    pub fn relayouts(&self) -> u64 {
+        self.relayouts
+    }
+}
+
+static UI_NOTIFIER: OnceLock<Notifier> = OnceLock::new();
+
+/// The notifier the TUI waits on, created on first use.
+pub fn ui_notifier() -> &'static Notifier {
+    UI_NOTIFIER.get_or_init(Notifier::default)
+}
+
+/// Tell the TUI, if it is running, that displayed state changed.
+pub fn notify_ui() {
+    if let Some(notifier) = UI_NOTIFIER.get() {
+        notifier.notify();
+    }
+}
+
+// MARK: test
+#[test]
+fn test_single_change_wakes_within_window() {
+    let notifier = Notifier::new(Duration::from_millis(50));
+    assert_eq!(notifier.wait(Duration::from_millis(20)), Wake::Timeout);
+
+    let background = notifier.clone();
+    let start = Instant::now();
+    let handle = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(30));
+        background.notify();
+    });
+    assert_eq!(notifier.wait(Duration::from_secs(5)), Wake::Changed);
+    // 空闲后的第一次变化立即唤醒，不等满窗口
+    assert!(start.elapsed() < Duration::from_millis(30 + 50));
+    handle.join().unwrap();
+
+    // 输入优先于变化
+    notifier.notify();
+    notifier.push_input(Event::FocusGained);
+    assert_eq!(
+        notifier.wait(Duration::ZERO),
+        Wake::Input(Event::FocusGained)
+    );
+}
+
+#[test]
+fn test_rapid_changes_wake_bounded() {
+    let window = Duration::from_millis(20);
+    let notifier = Notifier::new(window);
+    let background = notifier.clone();
+    let start = Instant::now();
+    let handle = std::thread::spawn(move || {
+        for i in 0..1000 {
+            background.notify();
+            if i % 10 == 0 {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+    });
+
+    let mut changes = 0;
+    loop {
+        match notifier.wait(Duration::from_millis(100)) {
+            Wake::Changed => changes += 1,
+            Wake::Timeout if handle.is_finished() => break,
+            _ => {}
+        }
+    }
+    handle.join().unwrap();
+    let elapsed = start.elapsed();
+
+    // 每个窗口最多唤醒一次，另加首次和末尾各一次
+    let bound = (elapsed.as_millis() / window.as_millis()) as u64 + 2;
+    assert_eq!(changes, notifier.change_wakeups());
+    assert!(changes >= 1);
+    assert!(changes <= bound, "{} wakeups in {:?}", changes, elapsed);
+    assert!(changes < 1000);
+}
+
+#[test]
+fn test_resize_burst_relayouts_once() {
+    let window = Duration::from_millis(100);
+    let mut resize = ResizeDebounce::new(window);
+    let start = Instant::now();
+    assert_eq!(
+        resize.timeout(start, Duration::from_secs(1)),
+        Duration::from_secs(1)
+    );
+
+    // 拖动时每 20ms 一个事件，期间不重新布局
+    for (i, width) in (80..90).enumerate() {
+        let now = start + Duration::from_millis(20 * i as u64);
+        resize.on_resize((width, 24), now);
+        assert!(resize.is_pending());
+        assert_eq!(resize.ready(now + Duration::from_millis(10)), None);
+    }
+    let last = start + Duration::from_millis(20 * 9);
+    assert_eq!(
+        resize.timeout(last + Duration::from_millis(30), Duration::from_secs(1)),
+        Duration::from_millis(70)
+    );
+
+    // 停下后按最终大小布局一次
+    assert_eq!(resize.ready(last + window), Some((89, 24)));
+    assert_eq!(resize.ready(last + window * 2), None);
+    assert!(!resize.is_pending());
+    assert_eq!(resize.relayouts(), 1);
+}
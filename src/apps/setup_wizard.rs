@@ -0,0 +1,355 @@
+use std::{fs, io, path::Path};
+
+use ratatui::{
+    DefaultTerminal,
+    buffer::Buffer,
+    crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, read},
+    layout::{Alignment, Rect},
+    text::{Line, Text},
+    widgets::{Block, Borders, Paragraph, Widget, WidgetRef},
+};
+
+use crate::{
+    MyConfig,
+    my_widgets::{render_detail_popup, render_input_popup},
+};
+
+/// 向导生成的配置中最多跟踪的日志文件数
+const DEFAULT_MAX_OBSERVED_FILES: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WizardStep {
+    ObservedPath,
+    DbUrl,
+    PrefixFrom,
+    PrefixTo,
+    Confirm,
+}
+
+impl WizardStep {
+    fn title(self) -> &'static str {
+        match self {
+            WizardStep::ObservedPath => "Observed FTP log directory",
+            WizardStep::DbUrl => "DB URL (empty to use the DB_URL env var)",
+            WizardStep::PrefixFrom => "FTP path prefix, e.g. \\AC03 (empty to skip)",
+            WizardStep::PrefixTo => "Local path prefix, e.g. E:\\CusData\\AC03",
+            WizardStep::Confirm => "Write config? (Enter to write, Esc to go back)",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum WizardAction {
+    Continue,
+    Finish,
+    Cancel,
+}
+
+/// 首次运行时引导用户填写配置并生成 cfg.json
+pub struct SetupWizard {
+    step: WizardStep,
+    input: String,
+    observed_path: String,
+    db_url: String,
+    prefix_from: String,
+    prefix_to: String,
+    error: Option<String>,
+}
+
+impl Default for SetupWizard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SetupWizard {
+    pub fn new() -> Self {
+        Self {
+            step: WizardStep::ObservedPath,
+            input: String::new(),
+            observed_path: String::new(),
+            db_url: String::new(),
+            prefix_from: String::new(),
+            prefix_to: String::new(),
+            error: None,
+        }
+    }
+
+    pub fn handle_event(&mut self, event: Event) -> WizardAction {
+        match event {
+            Event::Paste(s) if self.step != WizardStep::Confirm => {
+                self.input.push_str(&s);
+            }
+            Event::Key(KeyEvent {
+                code,
+                kind: KeyEventKind::Press,
+                ..
+            }) => match code {
+                KeyCode::Char(c) if self.step != WizardStep::Confirm => {
+                    self.input.push(c);
+                }
+                KeyCode::Backspace => {
+                    self.input.pop();
+                }
+                KeyCode::Enter => return self.submit(),
+                KeyCode::Esc => return self.back(),
+                _ => {}
+            },
+            _ => {}
+        }
+        WizardAction::Continue
+    }
+
+    // 保存当前输入并进入下一步
+    fn submit(&mut self) -> WizardAction {
+        let input = self.input.trim().to_string();
+        self.error = None;
+        self.step = match self.step {
+            WizardStep::ObservedPath => {
+                if input.is_empty() {
+                    self.error = Some("Observed path is required".to_string());
+                    return WizardAction::Continue;
+                }
+                self.observed_path = input;
+                WizardStep::DbUrl
+            }
+            WizardStep::DbUrl => {
+                self.db_url = input;
+                WizardStep::PrefixFrom
+            }
+            WizardStep::PrefixFrom => {
+                self.prefix_from = input;
+                if self.prefix_from.is_empty() {
+                    self.prefix_to.clear();
+                    WizardStep::Confirm
+                } else {
+                    WizardStep::PrefixTo
+                }
+            }
+            WizardStep::PrefixTo => {
+                self.prefix_to = input;
+                WizardStep::Confirm
+            }
+            WizardStep::Confirm => return WizardAction::Finish,
+        };
+        self.input = self.saved_input(self.step);
+        WizardAction::Continue
+    }
+
+    // 回到上一步，第一步时取消向导
+    fn back(&mut self) -> WizardAction {
+        self.error = None;
+        self.step = match self.step {
+            WizardStep::ObservedPath => return WizardAction::Cancel,
+            WizardStep::DbUrl => WizardStep::ObservedPath,
+            WizardStep::PrefixFrom => WizardStep::DbUrl,
+            WizardStep::PrefixTo => WizardStep::PrefixFrom,
+            WizardStep::Confirm if self.prefix_from.is_empty() => WizardStep::PrefixFrom,
+            WizardStep::Confirm => WizardStep::PrefixTo,
+        };
+        self.input = self.saved_input(self.step);
+        WizardAction::Continue
+    }
+
+    fn saved_input(&self, step: WizardStep) -> String {
+        match step {
+            WizardStep::ObservedPath => self.observed_path.clone(),
+            WizardStep::DbUrl => self.db_url.clone(),
+            WizardStep::PrefixFrom => self.prefix_from.clone(),
+            WizardStep::PrefixTo => self.prefix_to.clone(),
+            WizardStep::Confirm => String::new(),
+        }
+    }
+
+    /// Build the config from the answers, filling the other keys with their defaults.
+    pub fn build_config(&self) -> serde_json::Result<MyConfig> {
+        let mut prefix_map = serde_json::Map::new();
+        if !self.prefix_from.is_empty() {
+            prefix_map.insert(
+                "default".to_string(),
+                serde_json::json!([self.prefix_from, self.prefix_to]),
+            );
+        }
+        let db_url = (!self.db_url.is_empty()).then(|| self.db_url.clone());
+
+        serde_json::from_value(serde_json::json!({
+            "file_sync_manager": {
+                "observed_path": self.observed_path,
+                "prefix_map_of_extract_path": prefix_map,
+                "max_observed_files": DEFAULT_MAX_OBSERVED_FILES,
+                "db_url": db_url,
+            }
+        }))
+    }
+
+    pub fn config_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(&self.build_config()?)
+    }
+
+    /// Write the config to `path`, creating its parent directory if needed.
+    pub fn write_config(&self, path: &Path) -> io::Result<()> {
+        let json = self.config_json().map_err(io::Error::other)?;
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, json)
+    }
+}
+
+impl WidgetRef for SetupWizard {
+    fn render_ref(&self, area: Rect, buf: &mut Buffer) {
+        let mark = |done: bool| if done { "[x]" } else { "[ ]" };
+        let mut lines = vec![
+            Line::from("No config file found, answer a few questions to create one."),
+            Line::from("Enter: next    Esc: back (cancel on the first step)"),
+            Line::from(""),
+            Line::from(format!(
+                "{} Observed path: {}",
+                mark(self.step != WizardStep::ObservedPath),
+                self.observed_path
+            )),
+            Line::from(format!(
+                "{} DB URL: {}",
+                mark(!matches!(
+                    self.step,
+                    WizardStep::ObservedPath | WizardStep::DbUrl
+                )),
+                self.db_url
+            )),
+            Line::from(format!(
+                "{} Prefix mapping: {} -> {}",
+                mark(self.step == WizardStep::Confirm),
+                self.prefix_from,
+                self.prefix_to
+            )),
+        ];
+        if let Some(error) = &self.error {
+            lines.push(Line::from(""));
+            lines.push(Line::from(format!("Error: {}", error)));
+        }
+
+        Paragraph::new(Text::from(lines))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Setup Wizard")
+                    .title_alignment(Alignment::Center),
+            )
+            .render(area, buf);
+
+        if self.step == WizardStep::Confirm {
+            let preview = self
+                .config_json()
+                .unwrap_or_else(|e| format!("Invalid config: {}", e));
+            render_detail_popup(&preview, area, buf, self.step.title());
+        } else {
+            render_input_popup(&self.input, area, buf, self.step.title());
+        }
+    }
+}
+
+/// Run the wizard until the config is written to `path` or the user cancels.
+///
+/// Returns whether a config was written.
+pub fn run_setup_wizard(path: &Path) -> io::Result<bool> {
+    let mut terminal = ratatui::init();
+    let result = drive_wizard(&mut terminal, path);
+    ratatui::restore();
+    result
+}
+
+fn drive_wizard(terminal: &mut DefaultTerminal, path: &Path) -> io::Result<bool> {
+    let mut wizard = SetupWizard::new();
+    loop {
+        terminal.draw(|frame| wizard.render_ref(frame.area(), frame.buffer_mut()))?;
+        match wizard.handle_event(read()?) {
+            WizardAction::Continue => {}
+            WizardAction::Cancel => return Ok(false),
+            WizardAction::Finish => match wizard.write_config(path) {
+                Ok(()) => return Ok(true),
+                Err(e) => {
+                    wizard.error = Some(format!("Failed to write {}: {}", path.display(), e));
+                }
+            },
+        }
+    }
+}
+
+// MARK: test
+#[test]
+fn test_setup_wizard_writes_config() {
+    let key = |code| Event::Key(KeyEvent::from(code));
+    let type_line = |wizard: &mut SetupWizard, text: &str| {
+        for c in text.chars() {
+            assert_eq!(
+                wizard.handle_event(key(KeyCode::Char(c))),
+                WizardAction::Continue
+            );
+        }
+        wizard.handle_event(key(KeyCode::Enter))
+    };
+
+    let mut wizard = SetupWizard::new();
+    // 必填项为空时停留在当前步骤
+    assert_eq!(
+        wizard.handle_event(key(KeyCode::Enter)),
+        WizardAction::Continue
+    );
+    assert_eq!(wizard.step, WizardStep::ObservedPath);
+    assert!(wizard.error.is_some());
+
+    type_line(&mut wizard, r"C:\inetpub\logs\FTPSVC2");
+    type_line(&mut wizard, "mysql://u:p@localhost:3306/testdata");
+    type_line(&mut wizard, r"\AC03");
+    // 返回上一步时恢复已填写的内容
+    wizard.handle_event(key(KeyCode::Esc));
+    assert_eq!(wizard.input, r"\AC03");
+    wizard.handle_event(key(KeyCode::Enter));
+    assert_eq!(
+        type_line(&mut wizard, r"E:\CusData\AC03"),
+        WizardAction::Continue
+    );
+    assert_eq!(wizard.step, WizardStep::Confirm);
+    assert_eq!(
+        wizard.handle_event(key(KeyCode::Enter)),
+        WizardAction::Finish
+    );
+
+    let base = std::env::temp_dir().join("test_setup_wizard");
+    let path = base.join("cfg.json");
+    let _ = fs::remove_dir_all(&base);
+    wizard.write_config(&path).unwrap();
+
+    let written: MyConfig = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    let config = written.file_sync_manager;
+    assert_eq!(
+        config.observed_path,
+        std::path::PathBuf::from(r"C:\inetpub\logs\FTPSVC2").into()
+    );
+    assert_eq!(
+        config.db_url.as_deref(),
+        Some("mysql://u:p@localhost:3306/testdata")
+    );
+    assert_eq!(
+        config.prefix_map_of_extract_path["default"],
+        [r"\AC03".to_string(), r"E:\CusData\AC03".to_string()]
+    );
+    assert_eq!(config.max_observed_files, DEFAULT_MAX_OBSERVED_FILES);
+    assert_eq!(config.observe_file_glob, vec!["*.log".to_string()]);
+    // 再次序列化结果不变
+    assert_eq!(
+        serde_json::to_string_pretty(&MyConfig {
+            file_sync_manager: config
+        })
+        .unwrap(),
+        fs::read_to_string(&path).unwrap()
+    );
+
+    // 第一步按 Esc 取消
+    assert_eq!(
+        SetupWizard::new().handle_event(key(KeyCode::Esc)),
+        WizardAction::Cancel
+    );
+
+    fs::remove_dir_all(&base).unwrap();
+}
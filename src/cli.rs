@@ -1,16 +1,21 @@
 use std::{
     collections::HashMap,
     fs,
-    io::{self, Write},
-    path::PathBuf,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
     vec,
 };
 
 use std::time::Duration;
 
 use crate::{
-    apps::file_sync_manager::SyncEngine,
-    my_widgets::{LogKind, MyWidgets},
+    apps::file_sync_manager::{
+        DirScanner, OperationLimit, PhaseOutcome, QuickActions, SyncEngine, check_scan_interval,
+        csv_export_path,
+        importer::{ImportOptions, import_path_list},
+        registry,
+    },
+    my_widgets::{LogKind, MyWidgets, theme::Theme, wrap_list::WrapList},
     *,
 };
 
@@ -20,12 +25,25 @@ pub const CMD_HELP: &str = "ls";
 pub const CMD_INTO_FILESYNC_MGR: &str = "cd fm";
 pub const CMD_START_OBS: &str = "start obs";
 pub const CMD_STOP_OBS: &str = "stop obs";
+pub const CMD_TOGGLE_RECURSIVE: &str = "toggle rec";
+pub const CMD_RELOAD_CONFIG: &str = "reload cfg";
+pub const CMD_RESET_OFFSET: &str = "reset-offset";
 pub const CMD_START_SCAN: &str = "start sc";
+pub const CMD_START_SCAN_DRY_RUN: &str = "start sc --dry-run";
+pub const CMD_START_SCAN_TODAY: &str = "start sc --today";
 pub const CMD_START_PERIODIC_SCAN: &str = "start psc";
 pub const CMD_STOP_PERIODIC_SCAN: &str = "stop psc";
 pub const CMD_SHOW_STATUS: &str = "ds status";
 pub const CMD_SHOW_OBS_LOGS: &str = "ds log obs";
 pub const CMD_SHOW_SCAN_LOGS: &str = "ds log sc";
+pub const CMD_SHOW_RECORDED_FILES: &str = "ds files";
+pub const CMD_SHOW_RECENT_INSERTS: &str = "ds recent";
+pub const CMD_EXPORT_LAST_SCAN: &str = "ds export";
+pub const CMD_RECORD_FILE: &str = "record";
+pub const CMD_ADD_NOTE: &str = "note";
+pub const CMD_RUN_QUICK_ACTION: &str = "run";
+pub const CMD_IMPORT: &str = "import";
+pub const CMD_EXPORT_CSV: &str = "export csv";
 pub const CMD_INPUT_DIR: &str = "<dir>";
 pub const CMD_INPUT_INTERVAL: &str = "<interval>";
 pub const CMD_TEST_PANIC: &str = "test panic";
@@ -41,6 +59,23 @@ fn read_trimmed_line(prompt: &str) -> Option<String> {
     }
 }
 
+/// Ask before scanning a path the scan guard flags, `true` if the scan may go ahead.
+fn confirm_guarded_scan(scanner: &mut DirScanner) -> bool {
+    let Some(reason) = scanner.guard_check() else {
+        return true;
+    };
+    let answer =
+        read_trimmed_line(&format!("  {}，仍要扫描吗？(y/N) ", reason)).unwrap_or_default();
+    if matches!(answer.to_lowercase().as_str(), "y" | "yes") {
+        scanner.confirm_scan();
+        true
+    } else {
+        scanner.cancel_scan();
+        println!("  已取消扫描");
+        false
+    }
+}
+
 pub fn run_cli_mode() {
     println!("进入命令行模式，输入 ls 查看命令，:q 退出。");
     loop {
@@ -73,9 +108,43 @@ pub fn run_cli_mode() {
 }
 
 fn into_file_sync_mgr() {
-    // 创建文件监控器
-    let path = load_config().file_sync_manager.observed_path;
-    let mut file_sync_manager = SyncEngine::new("file_monitor".to_string(), path, 50);
+    // 创建文件监控器，配置在启动后可能被改坏，此时提示后返回
+    let config = match try_load_config() {
+        Ok(config) => config.file_sync_manager,
+        Err(e) => {
+            println!("无法进入文件监控：{}", e);
+            return;
+        }
+    };
+    let quick_actions = QuickActions::from_config(&config.quick_actions).unwrap_or_default();
+    let quick_action_names = quick_actions.names().collect::<Vec<_>>().join(", ");
+    let theme = Theme::from_mode(color_mode(), io::stdout().is_terminal());
+    // 观察器热加载配置文件的修改
+    let config_watcher = ConfigWatcher::start();
+    // 命令行模式只管理第一个站点，站点配置已在启动时校验
+    let site = config.site_configs().unwrap().remove(0);
+    // 前台的记录和导入与后台扫描共用并发上限
+    let op_limit = OperationLimit::new(config.max_concurrent_operations);
+    let mut file_sync_manager = SyncEngine::new(site.name.clone(), site.primary_path(), 50)
+        .with_checkpoint(config.checkpoint_path_of(&site.name))
+        .with_site(site)
+        .with_record_notes_to_db(config.record_notes_to_db)
+        .with_dry_run(dry_run_mode(&config))
+        .with_recursive(config.recursive)
+        .with_follow_log_symlink(config.follow_log_symlink)
+        .with_config_watcher(config_watcher.as_ref())
+        .with_max_log_memory_mb(config.max_log_memory_mb)
+        .with_shutdown_timeouts(config.shutdown_timeouts)
+        .with_size_units(config.size_units)
+        .with_export_path_style(config.export_path_style)
+        .with_skip_hidden(config.skip_hidden)
+        .with_today_time_zone(config.today_time_zone())
+        .with_scan_reconcile(config.scan_reconcile)
+        .with_scan_jitter(config.scan_jitter)
+        .with_scan_guard(config.scan_guard.clone())
+        .with_op_limit(op_limit.clone())
+        .with_quick_actions(quick_actions)
+        .with_theme(theme);
     loop {
         let cmd = read_trimmed_line("\\filemonitor> ").unwrap_or_else(|| {
             println!("读取输入失败");
@@ -90,30 +159,63 @@ fn into_file_sync_mgr() {
                     CMD_SHOW_STATUS,
                     CMD_SHOW_OBS_LOGS,
                     CMD_SHOW_SCAN_LOGS,
+                    CMD_SHOW_RECORDED_FILES,
+                    CMD_SHOW_RECENT_INSERTS,
+                    CMD_EXPORT_LAST_SCAN,
+                    CMD_RECORD_FILE,
+                    CMD_ADD_NOTE,
+                    CMD_RUN_QUICK_ACTION,
+                    CMD_IMPORT,
+                    CMD_EXPORT_CSV,
                     CMD_START_SCAN,
+                    CMD_START_SCAN_DRY_RUN,
+                    CMD_START_SCAN_TODAY,
                     CMD_START_PERIODIC_SCAN,
                     CMD_STOP_PERIODIC_SCAN,
                     CMD_START_OBS,
                     CMD_STOP_OBS,
+                    CMD_TOGGLE_RECURSIVE,
+                    CMD_RELOAD_CONFIG,
+                    CMD_RESET_OFFSET,
                 ]);
             }
             CMD_SHOW_STATUS => {
                 println!("监控器状态：{:?}", file_sync_manager.observer.get_status());
+                println!(
+                    "监视方式：{}",
+                    file_sync_manager.observer.snapshot().watch_mode_str()
+                );
+                println!(
+                    "监视子目录：{}",
+                    if file_sync_manager.observer.is_recursive() {
+                        "是"
+                    } else {
+                        "否"
+                    }
+                );
                 println!("扫描器状态：{:?}", file_sync_manager.scanner.get_status());
             }
             CMD_SHOW_OBS_LOGS => {
                 println!("日志：");
-                for log in file_sync_manager.get_logs_str(LogKind::Observer).iter().rev() {
-                    println!("{}", log);
-                }
+                let events = file_sync_manager.observer.get_logs_item();
+                let _ = print_events(&mut io::stdout(), &events, theme);
             }
             CMD_SHOW_SCAN_LOGS => {
                 println!("扫描日志：");
-                for log in file_sync_manager.get_logs_str(LogKind::Scanner).iter().rev() {
-                    println!("{}", log);
+                let events = file_sync_manager.scanner.get_logs_item();
+                let _ = print_events(&mut io::stdout(), &events, theme);
+            }
+            CMD_SHOW_RECORDED_FILES => {
+                println!("已记录文件：");
+                for file in file_sync_manager
+                    .get_logs_str(LogKind::RecordedFiles)
+                    .iter()
+                    .rev()
+                {
+                    println!("{}", file);
                 }
             }
-            CMD_START_SCAN => {
+            CMD_START_SCAN | CMD_START_SCAN_DRY_RUN | CMD_START_SCAN_TODAY => {
                 println!("  输入扫描路径：");
                 loop {
                     let path = read_trimmed_line("").unwrap_or_else(|| {
@@ -133,8 +235,22 @@ fn into_file_sync_mgr() {
                         path => {
                             if fs::metadata(path).is_ok() {
                                 file_sync_manager.scanner.set_path(PathBuf::from(path));
-                                file_sync_manager.scanner.start_scanner().unwrap();
-                                println!("开始扫描目录：{}", path);
+                                if !confirm_guarded_scan(&mut file_sync_manager.scanner) {
+                                    break;
+                                }
+                                if cmd == CMD_START_SCAN_DRY_RUN {
+                                    file_sync_manager.scanner.start_scanner_dry_run().unwrap();
+                                    println!("开始试扫描目录，不写数据库：{}", path);
+                                } else if cmd == CMD_START_SCAN_TODAY {
+                                    file_sync_manager
+                                        .scanner
+                                        .scan_today(PathBuf::from(path))
+                                        .unwrap();
+                                    println!("开始扫描目录中今天修改的文件：{}", path);
+                                } else {
+                                    file_sync_manager.scanner.start_scanner().unwrap();
+                                    println!("开始扫描目录：{}", path);
+                                }
                                 break;
                             } else {
                                 print!("目录不存在，请重新输入: ");
@@ -162,8 +278,11 @@ fn into_file_sync_mgr() {
                             continue;
                         }
                         path => {
-                            if fs::metadata(&path).is_ok() {
+                            if fs::metadata(path).is_ok() {
                                 file_sync_manager.scanner.set_path(PathBuf::from(path));
+                                if !confirm_guarded_scan(&mut file_sync_manager.scanner) {
+                                    break;
+                                }
                                 println!("输入时间间隔（单位：分钟）");
                                 loop {
                                     let interval = read_trimmed_line("").unwrap_or_else(|| {
@@ -187,9 +306,13 @@ fn into_file_sync_mgr() {
                                         continue;
                                     }
                                     if let Ok(interval) = interval.parse::<f64>() {
-                                        file_sync_manager.scanner.start_periodic_scan(
-                                            Duration::from_secs((interval * 60.0) as u64),
-                                        );
+                                        let interval =
+                                            Duration::from_secs((interval.max(0.0) * 60.0) as u64);
+                                        if let Err(e) = check_scan_interval(interval) {
+                                            println!("{}，请重新输入", e);
+                                            continue;
+                                        }
+                                        file_sync_manager.scanner.start_periodic_scan(interval);
                                         println!("开始定时扫描目录：{}", path);
                                         break;
                                     } else {
@@ -214,12 +337,234 @@ fn into_file_sync_mgr() {
             }
             CMD_STOP_OBS => {
                 println!(" 停止监控...");
-                file_sync_manager.observer.stop_observer();
+                match file_sync_manager.observer.stop_observer() {
+                    PhaseOutcome::Done => println!(" 监控已停止"),
+                    PhaseOutcome::Skipped => println!(" 监控未在运行"),
+                    outcome => println!(" 停止监控失败：{}", outcome),
+                }
+            }
+            CMD_TOGGLE_RECURSIVE => {
+                let recursive = !file_sync_manager.observer.is_recursive();
+                file_sync_manager.observer.set_recursive(recursive);
+                println!(
+                    "监视子目录：{}，下次开始监控时生效",
+                    if recursive { "开" } else { "关" }
+                );
+            }
+            CMD_RELOAD_CONFIG => match file_sync_manager.observer.reload_config() {
+                Ok(()) => println!("已重新读取配置，下一个事件起生效"),
+                Err(e) => println!("重新读取配置失败，保留原配置：{}", e),
+            },
+            cmd if cmd.split_whitespace().next() == Some(CMD_RESET_OFFSET) => {
+                let path = cmd[CMD_RESET_OFFSET.len()..].trim().trim_matches('"');
+                if path.is_empty() {
+                    println!("  用法：{} <path>", CMD_RESET_OFFSET);
+                    for (path, info) in file_sync_manager.observer.watched_offsets() {
+                        println!("  {}：已读到 {}", path.display(), info.last_read_pos());
+                    }
+                    continue;
+                }
+                match file_sync_manager.observer.reset_offset(Path::new(path)) {
+                    Ok(old) => println!("已将读取位置从 {} 重置为 0，下次变化时从头读取", old),
+                    Err(e) => println!("重置失败：{}", e),
+                }
+            }
+            cmd if cmd.split_whitespace().next() == Some(CMD_RECORD_FILE) => {
+                let path = cmd[CMD_RECORD_FILE.len()..]
+                    .trim()
+                    .trim_matches('"')
+                    .to_string();
+                if path.is_empty() {
+                    println!("  用法：{} <path>", CMD_RECORD_FILE);
+                    continue;
+                }
+                let db_config = match file_sync_manager.observer.loaded_config() {
+                    Ok(config) => config,
+                    Err(e) => {
+                        println!("记录失败：{}", e);
+                        continue;
+                    }
+                };
+                let op_limit = op_limit.clone();
+                match block_on(async move {
+                    let _permit = op_limit
+                        .acquire(|| println!("  等待其他扫描或数据库写入完成"))
+                        .await;
+                    registry::record_file(Path::new(&path), &db_config).await
+                }) {
+                    Ok(info) => println!(
+                        "已记录：{} ({})",
+                        info.path,
+                        config.size_units.format(info.size)
+                    ),
+                    Err(e) => println!("记录失败：{}", e),
+                }
+            }
+            cmd if cmd.split_whitespace().next() == Some(CMD_ADD_NOTE) => {
+                let note = cmd[CMD_ADD_NOTE.len()..].trim();
+                if note.is_empty() {
+                    println!("  用法：{} <text>", CMD_ADD_NOTE);
+                    continue;
+                }
+                file_sync_manager.add_note(note);
+                println!("已添加备注");
+            }
+            cmd if cmd.split_whitespace().next() == Some(CMD_IMPORT) => {
+                let list = PathBuf::from(cmd[CMD_IMPORT.len()..].trim().trim_matches('"'));
+                if list.as_os_str().is_empty() {
+                    println!("  用法：{} <file>", CMD_IMPORT);
+                    continue;
+                }
+                let options = try_load_config().map_err(|e| e.to_string()).and_then(|c| {
+                    ImportOptions::from_config(&c.file_sync_manager)
+                        .map(|options| (options, c.file_sync_manager))
+                        .map_err(|e| e.to_string())
+                });
+                let (options, db_config) = match options {
+                    Ok(options) => options,
+                    Err(e) => {
+                        println!("导入失败：{}", e);
+                        continue;
+                    }
+                };
+                let op_limit = op_limit.clone();
+                let result = block_on(async move {
+                    let _permit = op_limit
+                        .acquire(|| println!("  等待其他扫描或数据库写入完成"))
+                        .await;
+                    import_path_list(
+                        &list,
+                        &options,
+                        |infos| registry::insert_infos_to_db(infos, &db_config),
+                        |s| println!("  进度：{}", s),
+                    )
+                    .await
+                });
+                match result {
+                    Ok((summary, recorded)) => {
+                        file_sync_manager
+                            .recorded_files
+                            .lock()
+                            .unwrap()
+                            .record(&recorded);
+                        println!("导入完成：{}", summary);
+                    }
+                    Err(e) => println!("导入失败：{}", e),
+                }
+            }
+            cmd if cmd.starts_with(CMD_EXPORT_LAST_SCAN) => {
+                let path =
+                    PathBuf::from(cmd[CMD_EXPORT_LAST_SCAN.len()..].trim().trim_matches('"'));
+                if path.as_os_str().is_empty() {
+                    println!("  用法：{} <path>", CMD_EXPORT_LAST_SCAN);
+                    continue;
+                }
+                match file_sync_manager.scanner.export_last_scan_to_csv(&path) {
+                    Ok(()) => println!(
+                        "已导出最近一次扫描的文件到 {}",
+                        csv_export_path(&path).display()
+                    ),
+                    Err(e) => println!("导出失败：{}", e),
+                }
+            }
+            cmd if cmd.starts_with(CMD_EXPORT_CSV) => {
+                let path = PathBuf::from(cmd[CMD_EXPORT_CSV.len()..].trim().trim_matches('"'));
+                if path.as_os_str().is_empty() {
+                    println!("  用法：{} <path>", CMD_EXPORT_CSV);
+                    continue;
+                }
+                let result = file_sync_manager
+                    .recorded_files
+                    .lock()
+                    .unwrap()
+                    .export_csv(&path);
+                match result {
+                    Ok(rows) => println!("已导出 {} 条记录到 {}", rows, path.display()),
+                    Err(e) => println!("导出失败：{}", e),
+                }
+            }
+            cmd if cmd.split_whitespace().next() == Some(CMD_RUN_QUICK_ACTION) => {
+                let name = cmd[CMD_RUN_QUICK_ACTION.len()..].trim().trim_matches('"');
+                if name.is_empty() {
+                    println!("  用法：{} <name>", CMD_RUN_QUICK_ACTION);
+                    println!("  可用的快捷操作：{}", quick_action_names);
+                    continue;
+                }
+                match file_sync_manager.run_quick_action(name) {
+                    Ok(()) => println!("已执行快捷操作：{}", name),
+                    Err(e) => println!("执行失败：{}", e),
+                }
+                if let Some(reason) = file_sync_manager.pending_scan_reason() {
+                    let answer = read_trimmed_line(&format!("  {}，仍要扫描吗？(y/N) ", reason))
+                        .unwrap_or_default();
+                    let confirmed = matches!(answer.to_lowercase().as_str(), "y" | "yes");
+                    if let Err(e) = file_sync_manager.resolve_pending_scan(confirmed) {
+                        println!("执行失败：{}", e);
+                    }
+                }
+            }
+            cmd if cmd.starts_with(CMD_SHOW_RECENT_INSERTS) => {
+                let limit = match cmd[CMD_SHOW_RECENT_INSERTS.len()..].trim() {
+                    "" => 10,
+                    n => match n.parse::<usize>() {
+                        Ok(n) => n,
+                        Err(_) => {
+                            println!("数量格式错误：{}", n);
+                            continue;
+                        }
+                    },
+                };
+                let db_config = match file_sync_manager.observer.loaded_config() {
+                    Ok(config) => config,
+                    Err(e) => {
+                        println!("查询失败：{}", e);
+                        continue;
+                    }
+                };
+                match block_on(async move { registry::recent_inserts(limit, &db_config).await }) {
+                    Ok(rows) => {
+                        println!("最近插入的 {} 条记录：", rows.len());
+                        for row in rows {
+                            println!("  {}  {:>12}  {}", row.time_inserted, row.size, row.path);
+                        }
+                    }
+                    Err(e) => println!("查询失败：{}", e),
+                }
             }
             "" => {}
             _ => {}
         }
     }
+
+    let report = file_sync_manager.shutdown_with_report();
+    if !report.is_noop() {
+        println!("{}", report);
+    } else if !report.status_history.is_empty() {
+        // 没有需要关闭的工作时只输出状态变化
+        println!("状态变化：");
+        for transition in &report.status_history {
+            println!("  {}", transition);
+        }
+    }
+}
+
+/// Write `events` oldest first, colored only if `theme` is.
+fn print_events(out: &mut impl Write, events: &[OneEvent], theme: Theme) -> io::Result<()> {
+    for event in events.iter().rev() {
+        writeln!(out, "{}", WrapList::ansi_text(event, theme.is_colored()))?;
+    }
+    out.flush()
+}
+
+// 在独立线程的运行时中执行异步任务，避免在 tokio 运行时内部阻塞
+fn block_on<F>(future: F) -> F::Output
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    std::thread::spawn(move || tokio::runtime::Runtime::new().unwrap().block_on(future))
+        .join()
+        .unwrap()
 }
 
 fn help(cmds: Vec<&str>) {
@@ -237,8 +582,47 @@ fn help(cmds: Vec<&str>) {
         (CMD_SHOW_STATUS, (CMD_SHOW_STATUS, "查看状态")),
         (CMD_SHOW_OBS_LOGS, (CMD_SHOW_OBS_LOGS, "查看日志")),
         (CMD_SHOW_SCAN_LOGS, (CMD_SHOW_SCAN_LOGS, "查看扫描日志")),
+        (
+            CMD_SHOW_RECORDED_FILES,
+            (CMD_SHOW_RECORDED_FILES, "查看已记录文件"),
+        ),
+        (
+            CMD_RECORD_FILE,
+            (CMD_RECORD_FILE, "记录单个文件到数据库 <path>"),
+        ),
+        (CMD_ADD_NOTE, (CMD_ADD_NOTE, "添加操作员备注 <text>")),
+        (
+            CMD_IMPORT,
+            (CMD_IMPORT, "导入文本文件中列出的绝对路径 <file>"),
+        ),
+        (
+            CMD_EXPORT_CSV,
+            (CMD_EXPORT_CSV, "导出已记录文件为 CSV <path>"),
+        ),
+        (
+            CMD_RUN_QUICK_ACTION,
+            (CMD_RUN_QUICK_ACTION, "执行配置中的快捷操作 <name>"),
+        ),
+        (
+            CMD_SHOW_RECENT_INSERTS,
+            (CMD_SHOW_RECENT_INSERTS, "查看数据库最近插入的记录 [数量]"),
+        ),
         (CMD_START_OBS, (CMD_START_OBS, "开始监控")),
         (CMD_STOP_OBS, (CMD_STOP_OBS, "停止监控")),
+        (
+            CMD_TOGGLE_RECURSIVE,
+            (
+                CMD_TOGGLE_RECURSIVE,
+                "切换是否监视子目录，下次开始监控时生效",
+            ),
+        ),
+        (
+            CMD_RESET_OFFSET,
+            (
+                CMD_RESET_OFFSET,
+                "重置已监视日志的读取位置，下次变化时从头读取 <path>",
+            ),
+        ),
         (CMD_START_SCAN, (CMD_START_SCAN, "开始扫描")),
         (
             CMD_START_PERIODIC_SCAN,
@@ -267,3 +651,46 @@ fn help(cmds: Vec<&str>) {
         println!("  {:<10}  {}", cmd, desc);
     }
 }
+
+// MARK: test
+#[test]
+fn test_print_events_without_color() {
+    use crate::{EK, LOE};
+
+    let events: Vec<OneEvent> = [LOE::Error, LOE::Warning, LOE::Info]
+        .into_iter()
+        .map(|kind| OneEvent {
+            time: None,
+            kind: EK::LogObserverEvent(kind),
+            content: "event".to_string(),
+        })
+        .collect();
+
+    let mut plain = Vec::new();
+    print_events(
+        &mut plain,
+        &events,
+        Theme::from_mode(ColorMode::Never, true),
+    )
+    .unwrap();
+    assert!(!plain.contains(&0x1b));
+    let plain = String::from_utf8(plain).unwrap();
+    // 事件类型仍由前缀文本表示
+    assert!(
+        plain
+            .lines()
+            .next()
+            .unwrap()
+            .starts_with("[OBSERVER][INFO]")
+    );
+    assert!(plain.contains("[OBSERVER][ERR]"));
+
+    let mut colored = Vec::new();
+    print_events(
+        &mut colored,
+        &events,
+        Theme::from_mode(ColorMode::Always, false),
+    )
+    .unwrap();
+    assert!(colored.contains(&0x1b));
+}
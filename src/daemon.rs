@@ -0,0 +1,871 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError},
+    },
+    thread,
+    time::Duration,
+};
+
+use chrono::{DateTime, FixedOffset, Utc};
+
+use crate::{
+    ConfigWatcher, DSE, EK, FileMonitorConfig, LOE, OneEvent, ProgressStatus, Running, TIME_ZONE,
+    apps::file_sync_manager::{OperationLimit, PhaseOutcome, ShutdownReport, SyncEngine},
+    dry_run_mode, get_param,
+    my_widgets::wrap_list::WrapList,
+    param, try_load_config,
+};
+
+/// 守护模式下写出日志的间隔
+const FLUSH_INTERVAL: Duration = Duration::from_millis(200);
+const DAEMON_LOG_SIZE: usize = 500;
+
+/// 守护模式日志的输出位置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogTarget {
+    Stdout,
+    /// 所有事件追加到同一个文件
+    File(PathBuf),
+    /// 每次观察器启动或扫描运行时新建 `run_<时间>.log`
+    Dir(PathBuf),
+}
+
+impl LogTarget {
+    /// From `--log-dir=` or `--log-file=`, the directory wins if both are given.
+    pub fn from_params() -> Self {
+        if let Some(dir) = get_param(param::PARAM_LOG_DIR) {
+            LogTarget::Dir(PathBuf::from(dir))
+        } else if let Some(file) = get_param(param::PARAM_LOG_FILE) {
+            LogTarget::File(PathBuf::from(file))
+        } else {
+            LogTarget::Stdout
+        }
+    }
+}
+
+enum LogSink {
+    Single(BufWriter<Box<dyn Write + Send>>),
+    PerRun {
+        dir: PathBuf,
+        current: Option<BufWriter<File>>,
+        // 当前文件是否已写入启动事件以外的内容，连续的启动事件写入同一文件
+        has_content: bool,
+    },
+}
+
+impl LogSink {
+    fn open(target: LogTarget) -> io::Result<Self> {
+        Ok(match target {
+            LogTarget::Stdout => LogSink::Single(BufWriter::new(Box::new(io::stdout()))),
+            LogTarget::File(path) => LogSink::Single(BufWriter::new(Box::new(
+                OpenOptions::new().create(true).append(true).open(path)?,
+            ))),
+            LogTarget::Dir(dir) => {
+                fs::create_dir_all(&dir)?;
+                LogSink::PerRun {
+                    dir,
+                    current: None,
+                    has_content: false,
+                }
+            }
+        })
+    }
+
+    /// Write `event`, prefixed with `[site]` when several sites share the sink.
+    fn write_event(&mut self, event: &OneEvent, site: Option<&str>) -> io::Result<()> {
+        let (_, text, _) = WrapList::create_text(event);
+        let text = match site {
+            Some(site) => format!("[{}] {}", site, text),
+            None => text,
+        };
+        match self {
+            LogSink::Single(writer) => writeln!(writer, "{}", text),
+            LogSink::PerRun {
+                dir,
+                current,
+                has_content,
+            } => {
+                let run_start = is_run_start(event);
+                if current.is_none() || (run_start && *has_content) {
+                    if let Some(mut previous) = current.take() {
+                        previous.flush()?;
+                    }
+                    let time = event
+                        .time
+                        .unwrap_or_else(|| Utc::now().with_timezone(TIME_ZONE));
+                    *current = Some(BufWriter::new(File::create(run_log_path(dir, time))?));
+                    *has_content = false;
+                }
+                *has_content |= !run_start;
+                writeln!(current.as_mut().unwrap(), "{}", text)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            LogSink::Single(writer) => writer.flush(),
+            LogSink::PerRun { current, .. } => current.as_mut().map_or(Ok(()), |w| w.flush()),
+        }
+    }
+}
+
+/// Whether `event` marks an observer start or a scanner run.
+fn is_run_start(event: &OneEvent) -> bool {
+    matches!(
+        event.kind,
+        EK::LogObserverEvent(LOE::Start) | EK::DirScannerEvent(DSE::Start)
+    )
+}
+
+/// `run_<timestamp>.log` in `dir`, with a counter if a run in the same second already has one.
+fn run_log_path(dir: &Path, time: DateTime<FixedOffset>) -> PathBuf {
+    let stem = format!("run_{}", time.format("%Y%m%d_%H%M%S"));
+    let mut path = dir.join(format!("{}.log", stem));
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("{}_{}.log", stem, n));
+        n += 1;
+    }
+    path
+}
+
+/// 守护模式从标准输入读取的控制命令，每行一条
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DaemonCommand {
+    /// 停止所有站点的观察器
+    Pause,
+    /// 重新启动已停止的观察器
+    Resume,
+    /// 用第一个站点的扫描器扫描一次目录
+    Scan(PathBuf),
+    Status,
+    Quit,
+}
+
+impl DaemonCommand {
+    /// Parse one line of input, `None` for a blank line.
+    pub fn parse(line: &str) -> Option<Result<Self, String>> {
+        let line = line.trim();
+        let (name, arg) = match line.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, arg.trim().trim_matches('"')),
+            None => (line, ""),
+        };
+        let command = match (name, arg) {
+            ("", _) => return None,
+            ("pause", "") => Self::Pause,
+            ("resume", "") => Self::Resume,
+            ("scan", "") => return Some(Err("usage: scan <dir>".to_string())),
+            ("scan", dir) => Self::Scan(PathBuf::from(dir)),
+            ("status", "") => Self::Status,
+            ("quit", "") => Self::Quit,
+            _ => {
+                return Some(Err(format!(
+                    "unknown command {:?}, expected pause, resume, scan <dir>, status or quit",
+                    line
+                )));
+            }
+        };
+        Some(Ok(command))
+    }
+}
+
+/// 控制命令的输入和回复的输出
+struct CommandChannel {
+    lines: Receiver<String>,
+    responses: Box<dyn Write + Send>,
+}
+
+/// 无界面运行观察器和定期扫描，事件写入日志文件
+///
+/// 配置了多个站点时每个站点一个引擎，事件内容前加上站点名
+pub struct Daemon {
+    engines: Vec<SyncEngine>,
+    scan: Option<(PathBuf, Duration)>,
+    sink: LogSink,
+    stop: Arc<AtomicBool>,
+    // 每个引擎观察器和扫描器日志的读取游标
+    cursors: Vec<[u64; 2]>,
+    /// 扫描路径需要确认时仍然扫描
+    force: bool,
+    /// 从标准输入接收控制命令，未启用时为空
+    commands: Option<CommandChannel>,
+}
+
+impl Daemon {
+    /// Create a daemon writing events to `target`, with one engine per configured site.
+    pub fn new(config: &FileMonitorConfig, target: LogTarget) -> io::Result<Self> {
+        let sites = config
+            .site_configs()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let sink = LogSink::open(target)?;
+        let scan = config
+            .scan_path
+            .clone()
+            .map(|path| (path, Duration::from_secs(config.scan_interval_secs.max(1))));
+
+        // 所有站点共用一个并发上限
+        let op_limit = OperationLimit::new(config.max_concurrent_operations);
+        let engines: Vec<SyncEngine> = sites
+            .into_iter()
+            .map(|site| {
+                SyncEngine::new(site.name.clone(), site.primary_path(), DAEMON_LOG_SIZE)
+                    .with_checkpoint(config.checkpoint_path_of(&site.name))
+                    .with_site(site)
+                    .with_record_notes_to_db(config.record_notes_to_db)
+                    .with_dry_run(dry_run_mode(config))
+                    .with_recursive(config.recursive)
+                    .with_follow_log_symlink(config.follow_log_symlink)
+                    .with_max_log_memory_mb(config.max_log_memory_mb)
+                    .with_shutdown_timeouts(config.shutdown_timeouts)
+                    .with_size_units(config.size_units)
+                    .with_export_path_style(config.export_path_style)
+                    .with_skip_hidden(config.skip_hidden)
+                    .with_today_time_zone(config.today_time_zone())
+                    .with_scan_reconcile(config.scan_reconcile)
+                    .with_scan_jitter(config.scan_jitter)
+                    .with_scan_guard(config.scan_guard.clone())
+                    .with_op_limit(op_limit.clone())
+            })
+            .collect();
+        Ok(Self {
+            cursors: vec![[0; 2]; engines.len()],
+            engines,
+            scan,
+            sink,
+            stop: Arc::new(AtomicBool::new(false)),
+            force: false,
+            commands: None,
+        })
+    }
+
+    /// Let every observer follow edits to the config file.
+    pub fn with_config_watcher(mut self, watcher: Option<&ConfigWatcher>) -> Self {
+        if let Some(watcher) = watcher {
+            for engine in &mut self.engines {
+                engine.observer.set_config_watcher(watcher);
+            }
+        }
+        self
+    }
+
+    /// Scan paths the scan guard would otherwise refuse without confirmation.
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Accept control commands from `input`, one per line, answering each with one
+    /// `ok ...` or `error ...` line on `responses`.
+    ///
+    /// Input is read on its own thread; the end of input only stops reading commands.
+    pub fn with_commands(
+        mut self,
+        input: impl BufRead + Send + 'static,
+        responses: Box<dyn Write + Send>,
+    ) -> Self {
+        let (tx, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in input.lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        self.commands = Some(CommandChannel { lines, responses });
+        self
+    }
+
+    /// Flag that ends `run` once set, shared with the signal handler.
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    /// Start every site's observer, and the periodic scan on the first site.
+    ///
+    /// Fails before starting anything if the scan path needs confirmation and `force`
+    /// isn't set, as there is no one to ask.
+    pub fn start(&mut self) -> io::Result<()> {
+        if let Some((path, _)) = self.scan.clone() {
+            let scanner = &mut self.engines[0].scanner;
+            scanner.set_path(path);
+            if let Some(reason) = scanner.guard_check() {
+                if !self.force {
+                    return Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        format!("{}, pass --{} to scan anyway", reason, param::PARAM_FORCE),
+                    ));
+                }
+                scanner.confirm_scan();
+            }
+        }
+        for engine in &mut self.engines {
+            engine.observer.start_observer().map_err(io::Error::other)?;
+        }
+        if let Some((_, interval)) = self.scan.clone() {
+            self.engines[0].scanner.start_periodic_scan(interval);
+        }
+        self.flush_logs()
+    }
+
+    /// Write new events until the stop flag is set, then shut down and flush.
+    ///
+    /// The shutdown reports, one per site, are also written to the log before the final flush.
+    pub fn run(&mut self) -> io::Result<Vec<ShutdownReport>> {
+        while !self.stop.load(Ordering::SeqCst) {
+            self.flush_logs()?;
+            self.wait_for_commands(FLUSH_INTERVAL);
+        }
+        let reports = self
+            .engines
+            .iter_mut()
+            .map(|engine| engine.shutdown_with_report())
+            .collect();
+        self.flush_logs()?;
+        Ok(reports)
+    }
+
+    /// Handle the commands received within `timeout`, or just sleep if commands
+    /// aren't enabled.
+    fn wait_for_commands(&mut self, timeout: Duration) {
+        let Some(channel) = &self.commands else {
+            thread::sleep(timeout);
+            return;
+        };
+        let mut lines = match channel.lines.recv_timeout(timeout) {
+            Ok(line) => vec![line],
+            Err(RecvTimeoutError::Timeout) => return,
+            Err(RecvTimeoutError::Disconnected) => {
+                // 输入已结束，之后只等待信号
+                self.commands = None;
+                return;
+            }
+        };
+        lines.extend(channel.lines.try_iter());
+        for line in lines {
+            let Some(command) = DaemonCommand::parse(&line) else {
+                continue;
+            };
+            let response = match command.and_then(|c| self.execute(c)) {
+                Ok(msg) => format!("ok {}", msg),
+                Err(e) => format!("error {}", e),
+            };
+            if let Some(channel) = &mut self.commands {
+                // 回复写入失败时不影响观察器运行
+                let _ = writeln!(channel.responses, "{}", response);
+                let _ = channel.responses.flush();
+            }
+        }
+    }
+
+    /// Run one control command, returning the reply.
+    fn execute(&mut self, command: DaemonCommand) -> Result<String, String> {
+        match command {
+            DaemonCommand::Pause => {
+                let (mut paused, mut failed) = (0, 0);
+                for engine in &mut self.engines {
+                    if matches!(engine.observer.get_status(), ProgressStatus::Running(_)) {
+                        match engine.observer.stop_observer() {
+                            PhaseOutcome::Done | PhaseOutcome::Skipped => paused += 1,
+                            PhaseOutcome::TimedOut | PhaseOutcome::Failed(_) => failed += 1,
+                        }
+                    }
+                }
+                if failed > 0 {
+                    return Err(format!(
+                        "paused {} observers, {} didn't stop cleanly",
+                        paused, failed
+                    ));
+                }
+                Ok(format!("paused {} observers", paused))
+            }
+            DaemonCommand::Resume => {
+                let mut resumed = 0;
+                for engine in &mut self.engines {
+                    if !matches!(engine.observer.get_status(), ProgressStatus::Running(_)) {
+                        engine
+                            .observer
+                            .start_observer()
+                            .map_err(|e| format!("{}: {}", engine.get_title(), e))?;
+                        resumed += 1;
+                    }
+                }
+                Ok(format!("resumed {} observers", resumed))
+            }
+            DaemonCommand::Scan(dir) => {
+                if !dir.is_dir() {
+                    return Err(format!("{} is not a directory", dir.display()));
+                }
+                let scanner = &mut self.engines[0].scanner;
+                if matches!(
+                    scanner.get_status(),
+                    ProgressStatus::Running(_) | ProgressStatus::Stopping
+                ) {
+                    return Err("scanner is busy".to_string());
+                }
+                scanner.set_path(dir.clone());
+                if let Some(reason) = scanner.guard_check() {
+                    if !self.force {
+                        return Err(format!(
+                            "{}, restart with --{} to scan it",
+                            reason,
+                            param::PARAM_FORCE
+                        ));
+                    }
+                    scanner.confirm_scan();
+                }
+                scanner.start_scanner().map_err(|e| e.to_string())?;
+                Ok(format!("scanning {}", dir.display()))
+            }
+            DaemonCommand::Status => {
+                let sites: Vec<String> = self
+                    .engines
+                    .iter()
+                    .map(|engine| {
+                        let snapshot = engine.observer.snapshot();
+                        format!(
+                            "{}: observer {}, scanner {}, {} files recorded",
+                            engine.get_title(),
+                            status_str(snapshot.status),
+                            status_str(engine.scanner.get_status()),
+                            snapshot.files_recorded
+                        )
+                    })
+                    .collect();
+                Ok(sites.join("; "))
+            }
+            DaemonCommand::Quit => {
+                self.stop.store(true, Ordering::SeqCst);
+                Ok("quitting".to_string())
+            }
+        }
+    }
+
+    /// Write events added since the last flush to the log sink.
+    fn flush_logs(&mut self) -> io::Result<()> {
+        let multi_site = self.engines.len() > 1;
+        let mut events: Vec<(Option<&str>, OneEvent)> = Vec::new();
+        for (engine, cursors) in self.engines.iter().zip(self.cursors.iter_mut()) {
+            let site = multi_site.then(|| engine.get_title());
+            let (observer_events, observer_cursor) = engine
+                .observer
+                .shared_state
+                .lock()
+                .unwrap()
+                .logs
+                .events_since(cursors[0]);
+            let (scanner_events, scanner_cursor) = engine
+                .scanner
+                .shared_state
+                .lock()
+                .unwrap()
+                .logs
+                .events_since(cursors[1]);
+            *cursors = [observer_cursor, scanner_cursor];
+            events.extend(
+                observer_events
+                    .into_iter()
+                    .chain(scanner_events)
+                    .map(|e| (site, e)),
+            );
+        }
+
+        // 按时间合并，使每个运行日志文件只包含该次运行之后的事件
+        events.sort_by_key(|(_, e)| e.time);
+        for (site, event) in &events {
+            self.sink.write_event(event, *site)?;
+        }
+        // 已写出的事件才可能被裁剪
+        for engine in &self.engines {
+            engine.enforce_log_memory_cap();
+        }
+        self.sink.flush()
+    }
+}
+
+fn status_str(status: ProgressStatus) -> &'static str {
+    match status {
+        ProgressStatus::Running(Running::Periodic) => "running periodically",
+        ProgressStatus::Running(Running::Once) => "running",
+        ProgressStatus::Stopping => "stopping",
+        ProgressStatus::Stopped => "stopped",
+        ProgressStatus::Finished => "finished",
+        ProgressStatus::Failed => "failed",
+    }
+}
+
+/// Set `stop` on Ctrl-C, or SIGTERM on unix.
+fn spawn_signal_handler(stop: Arc<AtomicBool>) {
+    thread::spawn(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            #[cfg(unix)]
+            {
+                use tokio::signal::unix::{SignalKind, signal};
+                let mut term = signal(SignalKind::terminate()).unwrap();
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = term.recv() => {}
+                }
+            }
+            #[cfg(not(unix))]
+            let _ = tokio::signal::ctrl_c().await;
+        });
+        stop.store(true, Ordering::SeqCst);
+    });
+}
+
+pub fn run_daemon(target: LogTarget) {
+    let config = match try_load_config() {
+        Ok(config) => config.file_sync_manager,
+        Err(e) => {
+            eprintln!("Failed to start daemon: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let config_watcher = ConfigWatcher::start();
+    let mut daemon = match Daemon::new(&config, target) {
+        Ok(daemon) => daemon
+            .with_force(get_param(param::PARAM_FORCE).is_some())
+            .with_config_watcher(config_watcher.as_ref()),
+        Err(e) => {
+            eprintln!("Failed to start daemon: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if get_param(param::PARAM_STDIN_COMMANDS).is_some() {
+        daemon = daemon.with_commands(BufReader::new(io::stdin()), Box::new(io::stdout()));
+    }
+    spawn_signal_handler(daemon.stop_handle());
+
+    match daemon.start().and_then(|_| daemon.run()) {
+        Ok(reports) if reports.iter().any(|r| !r.is_clean()) => {
+            for report in reports.iter().filter(|r| !r.is_clean()) {
+                eprintln!("Daemon shutdown incomplete:\n{}", report);
+            }
+            std::process::exit(1);
+        }
+        Ok(_) => {}
+        Err(e) => {
+            eprintln!("Daemon failed: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// MARK: test
+#[test]
+fn test_daemon_processes_events_and_stops() {
+    let base = std::env::temp_dir().join("test_daemon");
+    let log_dir = base.join("logs");
+    let scan_dir = base.join("scan");
+    std::fs::create_dir_all(&log_dir).unwrap();
+    std::fs::create_dir_all(&scan_dir).unwrap();
+    std::fs::write(scan_dir.join("data.csv"), "x").unwrap();
+    let daemon_log = base.join("daemon.log");
+    let _ = std::fs::remove_file(&daemon_log);
+
+    let mut config: crate::MyConfig =
+        serde_json::from_str(&std::fs::read_to_string("asset/cfg.json").unwrap()).unwrap();
+    config.file_sync_manager.observed_path = log_dir.clone().into();
+    config.file_sync_manager.scan_path = Some(scan_dir.clone());
+    config.file_sync_manager.scan_interval_secs = 60;
+
+    let mut daemon = Daemon::new(
+        &config.file_sync_manager,
+        LogTarget::File(daemon_log.clone()),
+    )
+    .unwrap();
+    let stop = daemon.stop_handle();
+    daemon.start().unwrap();
+    let runner = thread::spawn(move || daemon.run());
+
+    thread::sleep(Duration::from_millis(300));
+    std::fs::write(
+        log_dir.join("u_ex250601.log"),
+        "2025-06-01 08:00:00 10.53.2.70 STOR 226 /AC03/DAEMON.csv\n",
+    )
+    .unwrap();
+    thread::sleep(Duration::from_millis(1500));
+
+    // 等同于收到 SIGTERM
+    stop.store(true, Ordering::SeqCst);
+    let start = std::time::Instant::now();
+    runner.join().unwrap().unwrap();
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    let written = std::fs::read_to_string(&daemon_log).unwrap();
+    assert!(written.contains("Observer started"));
+    assert!(written.contains("u_ex250601.log"));
+    assert!(written.contains("Found 1 files in the directory"));
+    assert!(written.contains("Observer stopped"));
+    assert!(written.contains("Periodic scanner stopped manually"));
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_log_dir_creates_file_per_run() {
+    let base = std::env::temp_dir().join("test_daemon_log_dir");
+    let _ = std::fs::remove_dir_all(&base);
+    let log_dir = base.join("logs");
+    let run_dir = base.join("runs");
+    std::fs::create_dir_all(&log_dir).unwrap();
+
+    let mut config: crate::MyConfig =
+        serde_json::from_str(&std::fs::read_to_string("asset/cfg.json").unwrap()).unwrap();
+    config.file_sync_manager.observed_path = log_dir.clone().into();
+    let mut daemon =
+        Daemon::new(&config.file_sync_manager, LogTarget::Dir(run_dir.clone())).unwrap();
+
+    for name in ["u_ex250601.log", "u_ex250602.log"] {
+        daemon.engines[0].observer.start_observer().unwrap();
+        thread::sleep(Duration::from_millis(300));
+        std::fs::write(
+            log_dir.join(name),
+            "2025-06-01 08:00:00 10.53.2.70 STOR 226 /AC03/RUN.csv\n",
+        )
+        .unwrap();
+        thread::sleep(Duration::from_millis(1000));
+        daemon.engines[0].observer.close();
+        daemon.flush_logs().unwrap();
+    }
+
+    let mut runs: Vec<PathBuf> = std::fs::read_dir(&run_dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .collect();
+    runs.sort();
+    assert_eq!(runs.len(), 2);
+    for run in &runs {
+        let name = run.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("run_") && name.ends_with(".log"));
+    }
+
+    let first = std::fs::read_to_string(&runs[0]).unwrap();
+    let second = std::fs::read_to_string(&runs[1]).unwrap();
+    for (content, name, other) in [
+        (&first, "u_ex250601.log", "u_ex250602.log"),
+        (&second, "u_ex250602.log", "u_ex250601.log"),
+    ] {
+        assert!(content.lines().next().unwrap().contains("Observer started"));
+        assert!(content.contains(name));
+        assert!(!content.contains(other));
+        assert!(content.contains("Observer stopped"));
+    }
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_sites_run_isolated() {
+    let base = std::env::temp_dir().join("test_daemon_sites");
+    let _ = std::fs::remove_dir_all(&base);
+    let dirs = [base.join("FTPSVC1"), base.join("FTPSVC2")];
+    for dir in &dirs {
+        std::fs::create_dir_all(dir).unwrap();
+    }
+    let daemon_log = base.join("daemon.log");
+
+    let mut config: crate::MyConfig =
+        serde_json::from_str(&std::fs::read_to_string("asset/cfg.json").unwrap()).unwrap();
+    config.file_sync_manager.sites = ["ftp1", "ftp2"]
+        .iter()
+        .zip(&dirs)
+        .map(|(name, dir)| crate::SiteConfig {
+            name: name.to_string(),
+            observed_path: dir.clone().into(),
+            prefix_map_of_extract_path: None,
+            source_tag: Some(name.to_uppercase()),
+        })
+        .collect();
+
+    let mut daemon = Daemon::new(
+        &config.file_sync_manager,
+        LogTarget::File(daemon_log.clone()),
+    )
+    .unwrap();
+    let stop = daemon.stop_handle();
+    daemon.start().unwrap();
+    let observers: Vec<_> = daemon
+        .engines
+        .iter()
+        .map(|e| e.observer.shared_state.clone())
+        .collect();
+    let runner = thread::spawn(move || daemon.run());
+
+    thread::sleep(Duration::from_millis(300));
+    std::fs::write(
+        dirs[0].join("u_ex250601.log"),
+        "2025-06-01 08:00:00 10.53.2.70 STOR 226 /AC03/SITE1_A.csv\n\
+         2025-06-01 08:00:01 10.53.2.70 STOR 226 /AC03/SITE1_B.csv\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dirs[1].join("u_ex250602.log"),
+        "2025-06-02 08:00:00 10.53.2.71 STOR 226 /AC03/SITE2.csv\n",
+    )
+    .unwrap();
+    thread::sleep(Duration::from_millis(1500));
+
+    stop.store(true, Ordering::SeqCst);
+    let reports = runner.join().unwrap().unwrap();
+    assert_eq!(reports.len(), 2);
+    assert!(reports.iter().all(|r| r.is_clean()));
+
+    // 每个站点只统计自己目录中的日志
+    let counts: Vec<usize> = observers
+        .iter()
+        .map(|ss| ss.lock().unwrap().snapshot().paths_rewritten)
+        .collect();
+    assert_eq!(counts, [2, 1]);
+    let logs: Vec<Vec<String>> = observers
+        .iter()
+        .map(|ss| ss.lock().unwrap().logs.get_raw_list_string())
+        .collect();
+    assert!(logs[0].iter().any(|l| l.contains("u_ex250601.log")));
+    assert!(!logs[0].iter().any(|l| l.contains("u_ex250602.log")));
+    assert!(logs[1].iter().any(|l| l.contains("u_ex250602.log")));
+    assert!(!logs[1].iter().any(|l| l.contains("u_ex250601.log")));
+
+    let written = std::fs::read_to_string(&daemon_log).unwrap();
+    for line in written.lines() {
+        assert!(line.starts_with("[ftp1] ") || line.starts_with("[ftp2] "));
+    }
+    assert!(
+        written
+            .lines()
+            .any(|l| l.starts_with("[ftp2] ") && l.contains("u_ex250602.log"))
+    );
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_daemon_refuses_guarded_scan_without_force() {
+    let base = std::env::temp_dir().join("test_daemon_guarded_scan");
+    let log_dir = base.join("logs");
+    let scan_dir = base.join("scan");
+    std::fs::create_dir_all(&log_dir).unwrap();
+    std::fs::create_dir_all(&scan_dir).unwrap();
+
+    let mut config: crate::MyConfig =
+        serde_json::from_str(&std::fs::read_to_string("asset/cfg.json").unwrap()).unwrap();
+    config.file_sync_manager.observed_path = log_dir.clone().into();
+    config.file_sync_manager.scan_path = Some(PathBuf::from("/"));
+    let target = || LogTarget::File(base.join("daemon.log"));
+
+    let mut daemon = Daemon::new(&config.file_sync_manager, target()).unwrap();
+    let err = daemon.start().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    assert!(err.to_string().contains("--force"));
+    assert_eq!(
+        daemon.engines[0].observer.get_status(),
+        crate::ProgressStatus::Stopped
+    );
+
+    // --force 时照常扫描拒绝列表中的路径
+    config.file_sync_manager.scan_path = Some(scan_dir.clone());
+    config.file_sync_manager.scan_guard.deny_list = vec![scan_dir.display().to_string()];
+    let mut daemon = Daemon::new(&config.file_sync_manager, target())
+        .unwrap()
+        .with_force(true);
+    daemon.start().unwrap();
+    assert_eq!(
+        daemon.engines[0].scanner.get_status(),
+        crate::ProgressStatus::Running(crate::Running::Periodic)
+    );
+    daemon.stop_handle().store(true, Ordering::SeqCst);
+    daemon.run().unwrap();
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct SharedBuf(Arc<std::sync::Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_stdin_commands() {
+    let base = std::env::temp_dir().join("test_daemon_stdin_commands");
+    let _ = std::fs::remove_dir_all(&base);
+    let log_dir = base.join("logs");
+    let scan_dir = base.join("scan");
+    std::fs::create_dir_all(&log_dir).unwrap();
+    std::fs::create_dir_all(&scan_dir).unwrap();
+    std::fs::write(scan_dir.join("data.csv"), "x").unwrap();
+    let daemon_log = base.join("daemon.log");
+
+    let mut config: crate::MyConfig =
+        serde_json::from_str(&std::fs::read_to_string("asset/cfg.json").unwrap()).unwrap();
+    config.file_sync_manager.observed_path = log_dir.clone().into();
+    let script = format!(
+        "status\npause\nstatus\nresume\nscan {}\nscan\nscan {}\nbogus\n\nquit\n",
+        scan_dir.display(),
+        base.join("missing").display()
+    );
+    let responses = SharedBuf::default();
+    let mut daemon = Daemon::new(
+        &config.file_sync_manager,
+        LogTarget::File(daemon_log.clone()),
+    )
+    .unwrap()
+    .with_commands(
+        io::Cursor::new(script.into_bytes()),
+        Box::new(responses.clone()),
+    );
+    let stop = daemon.stop_handle();
+    daemon.start().unwrap();
+    let runner = thread::spawn(move || daemon.run());
+
+    // quit 结束运行，不需要信号
+    let start = std::time::Instant::now();
+    while !runner.is_finished() && start.elapsed() < Duration::from_secs(10) {
+        thread::sleep(Duration::from_millis(50));
+    }
+    assert!(stop.load(Ordering::SeqCst));
+    runner.join().unwrap().unwrap();
+
+    let responses = String::from_utf8(responses.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<&str> = responses.lines().collect();
+    assert_eq!(lines.len(), 9, "{}", responses);
+    assert!(lines[0].starts_with("ok ") && lines[0].contains("observer running"));
+    assert_eq!(lines[1], "ok paused 1 observers");
+    assert!(lines[2].contains("observer stopped"));
+    assert_eq!(lines[3], "ok resumed 1 observers");
+    assert_eq!(lines[4], format!("ok scanning {}", scan_dir.display()));
+    assert_eq!(lines[5], "error usage: scan <dir>");
+    assert!(lines[6].starts_with("error ") && lines[6].ends_with("is not a directory"));
+    assert!(lines[7].starts_with("error unknown command \"bogus\""));
+    assert_eq!(lines[8], "ok quitting");
+
+    let written = std::fs::read_to_string(&daemon_log).unwrap();
+    assert!(written.contains("Observer stopped"));
+    assert!(written.contains("Scanner started"));
+    assert_eq!(written.matches("Observer started").count(), 2);
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
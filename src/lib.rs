@@ -1,37 +1,872 @@
 pub mod apps;
 pub mod cli;
+pub mod daemon;
 pub mod my_widgets;
 pub mod param;
+pub mod util;
 
 pub use DirScannerEventKind as DSE;
 pub use EventKind as EK;
 pub use LogObserverEventKind as LOE;
+pub use OperatorEventKind as OPE;
 
+use apps::file_sync_manager::{ScanGuard, ScanJitter, ShutdownTimeouts, long_path::MAX_PATH};
 use chrono::{DateTime, FixedOffset};
+use my_widgets::theme::Theme;
 use param::default_config_path;
-use serde::Deserialize;
-use std::{collections::HashMap, fs, path::PathBuf};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+};
 
 pub const TIME_ZONE: &FixedOffset = &FixedOffset::east_opt(8 * 3600).unwrap();
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct MyConfig {
     pub file_sync_manager: FileMonitorConfig,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct FileMonitorConfig {
     pub prefix_map_of_extract_path: HashMap<String, [String; 2]>,
-    pub observed_path: PathBuf,
+    /// 观察的日志目录，多个服务器写入不同目录时可写成数组，也可写作 observed_paths
+    #[serde(alias = "observed_paths")]
+    pub observed_path: OneOrMany<PathBuf>,
+    /// 所有观察目录合计最多跟踪的日志文件数
     pub max_observed_files: usize,
+    /// 日志列表中单条事件内容的最大显示字符数，0 表示不截断
+    #[serde(default = "default_max_display_chars")]
+    pub max_display_chars: usize,
+    /// 日志列表的换行方式：hyphenate、word 或 character
+    #[serde(default)]
+    pub wrap_mode: WrapMode,
+    /// 观察器只跟踪文件名匹配这些 glob 的日志文件
+    #[serde(default = "default_observe_file_glob")]
+    pub observe_file_glob: Vec<String>,
+    /// 同一文件大小相同的修改事件在该毫秒数内只处理一次，0 表示不去重
+    #[serde(default = "default_dedup_window_ms")]
+    pub dedup_window_ms: u64,
+    /// 守护模式下定期扫描的目录，不配置则只运行观察器
+    #[serde(default)]
+    pub scan_path: Option<PathBuf>,
+    /// 守护模式下定期扫描的间隔秒数
+    #[serde(default = "default_scan_interval_secs")]
+    pub scan_interval_secs: u64,
+    /// 诊断模式：包含 STOR 但不符合完整格式的行记录为警告
+    #[serde(default)]
+    pub strict_parse: bool,
+    /// 标记上传完成的日志行内容，如 "STOR 226"，其后的部分为文件路径，匹配任意一个即可
+    #[serde(default = "default_line_markers")]
+    pub line_markers: Vec<String>,
+    /// 匹配上传完成日志行的正则，名为 path 的捕获组为文件路径，配置后代替 line_markers
+    ///
+    /// 用于 vsftpd、proftpd 等格式不同的日志
+    #[serde(default)]
+    pub log_pattern: Option<String>,
+    /// 日志行的解析方式：iis 按 line_markers 匹配，regex 按 log_pattern 匹配，
+    /// 未配置时配置了 log_pattern 即为 regex
+    #[serde(default)]
+    pub log_parser: Option<LogParserKind>,
+    /// 所有日志列表合计的内存上限（MB），超出时裁剪最早的记录，0 表示不限制
+    #[serde(default)]
+    pub max_log_memory_mb: usize,
+    /// 是否把操作员备注写入数据库 operator_notes 表
+    #[serde(default)]
+    pub record_notes_to_db: bool,
+    /// 观察器只在日志中列出将要插入的路径，不写数据库，也可用 --dry-run 开启
+    #[serde(default)]
+    pub dry_run: bool,
+    /// FTP 路径的记录方式：改写为本地路径、原样记录或两者都记录
+    #[serde(default)]
+    pub path_mapping: PathMapping,
+    /// file_info 逻辑字段到实际列名的映射，为空时使用默认列名
+    #[serde(default)]
+    pub column_map: HashMap<String, String>,
+    /// 插入 file_info 时额外填充的列
+    #[serde(default)]
+    pub extra_columns: Vec<ExtraColumnConfig>,
+    /// 插入 file_info 时同时写入文件所在目录 parent_directory
+    #[serde(default)]
+    pub record_parent_directory: bool,
+    /// 计算文件的 SHA-256 写入 file_hash，已记录且哈希未变的文件不再写入
+    ///
+    /// 只在写入数据库前为通过客户代码筛选的文件计算，dry run 和日志观察不受影响
+    #[serde(default)]
+    pub compute_file_hash: bool,
+    /// 只记录这些客户代码的文件，为空时不限制
+    #[serde(default)]
+    pub cust_code_allowlist: Vec<String>,
+    /// 不记录这些客户代码的文件，优先于 cust_code_allowlist
+    #[serde(default)]
+    pub cust_code_blocklist: Vec<String>,
+    /// 界面运行时出现错误事件则发送系统通知，需要 desktop-notifications 功能
+    #[serde(default)]
+    pub desktop_notifications: bool,
+    /// 观察器同时监视观察目录的子目录，如按站点分开的 W3SVC1/
+    #[serde(default)]
+    pub recursive: bool,
+    /// 观察路径是指向日志文件的符号链接时跟随它，链接改指向新文件后读取新文件
+    #[serde(default)]
+    pub follow_log_symlink: bool,
+    /// 观察器发现日志变化的方式：native 使用系统通知，poll 定期检查，
+    /// 用于收不到通知的 SMB、NFS 共享
+    #[serde(default)]
+    pub watch_mode: WatchMode,
+    /// poll 方式检查日志变化的间隔毫秒数
+    #[serde(default = "default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+    /// 观察器保存读取位置的文件，重启后从上次的位置继续读取，为空时不保存
+    #[serde(default)]
+    pub checkpoint_path: Option<PathBuf>,
+    /// 观察器状态目录，未配置 checkpoint_path 时读取位置保存到其中的 <站点名>.watchinfo.json
+    #[serde(default)]
+    pub state_dir: Option<PathBuf>,
+    /// 记录文件信息的数据库：mysql 或 sqlite，sqlite 需要以 sqlite feature 构建
+    #[serde(default)]
+    pub db_backend: DbBackendKind,
+    /// 记录文件信息的表名
+    #[serde(default = "default_db_table")]
+    pub db_table: String,
+    /// 表所在的库，为 null 时使用连接地址中的库，sqlite 忽略此项
+    #[serde(default = "default_db_schema")]
+    pub db_schema: Option<String>,
+    /// 数据库连接地址，环境变量 DB_URL 优先
+    #[serde(default)]
+    pub db_url: Option<String>,
+    /// sqlite 数据库文件，不存在时创建
+    #[serde(default)]
+    pub db_path: Option<PathBuf>,
+    /// 数据库连接失败或超时时每批最多尝试的次数，包括第一次
+    #[serde(default = "default_db_retry_attempts")]
+    pub db_retry_attempts: u32,
+    /// 失败后重试的次数，不包括第一次；设置时每批最多尝试 db_retry_count + 1 次，
+    /// 覆盖 db_retry_attempts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub db_retry_count: Option<u32>,
+    /// 第一次重试前等待的毫秒数，之后每次翻倍
+    #[serde(
+        default = "default_db_retry_base_delay_ms",
+        alias = "db_retry_initial_ms"
+    )]
+    pub db_retry_base_delay_ms: u64,
+    /// 预先填好参数的快捷操作，出现在控制面板 quick 子菜单和 CLI run 命令中
+    #[serde(default)]
+    pub quick_actions: Vec<QuickActionConfig>,
+    /// 颜色输出：auto 按输出流是否为终端决定，always 总是着色，never 不输出任何颜色
+    #[serde(default)]
+    pub color: ColorMode,
+    /// 批量导入路径列表文件的编码，如 utf-8、gbk
+    #[serde(default = "default_import_encoding")]
+    pub import_encoding: String,
+    /// 批量导入时只接受这些扩展名（不含点，不区分大小写），为空时不过滤
+    #[serde(default)]
+    pub import_extensions: Vec<String>,
+    /// 批量导入时每处理多少行报告一次进度
+    #[serde(default = "default_import_progress_lines")]
+    pub import_progress_lines: usize,
+    /// 退出时各关闭阶段等待的最长秒数
+    #[serde(default)]
+    pub shutdown_timeouts: ShutdownTimeouts,
+    /// 日志、已记录文件列表和状态区中文件大小的单位：binary 或 decimal
+    #[serde(default)]
+    pub size_units: SizeUnits,
+    /// 导出文件中路径的分隔符：keep 保持原样，forward 改为 /，backslash 改为 \
+    #[serde(default)]
+    pub export_path_style: ExportPathStyle,
+    /// 映射后的本地路径超过该长度时在观察器日志中警告，0 表示不检查
+    #[serde(default = "default_max_path_len")]
+    pub max_path_len: usize,
+    /// 前缀映射的目标目录和映射后的路径必须位于其中之一，为空时不限制
+    #[serde(default)]
+    pub allowed_destination_roots: Vec<String>,
+    /// 扫描和观察时跳过以 . 开头的文件和目录，Windows 上还跳过隐藏或系统属性的文件
+    #[serde(default)]
+    pub skip_hidden: bool,
+    /// 单次完整扫描后删除扫描目录下本次未找到的文件记录，扫描结果为空、有读取失败的目录项、
+    /// 跳过了隐藏文件或达到文件数上限时不删除
+    #[serde(default)]
+    pub scan_reconcile: bool,
+    /// 当日扫描按该 UTC 偏移分钟数计算一天的开始，不配置时为东八区
+    #[serde(default)]
+    pub today_utc_offset_mins: Option<i32>,
+    /// 定期扫描的首次延迟秒数和间隔抖动百分比，快捷操作可单独覆盖
+    #[serde(default)]
+    pub scan_jitter: ScanJitter,
+    /// 扫描根目录或 deny_list 中的目录前需要确认，目录条目过多时警告
+    #[serde(default)]
+    pub scan_guard: ScanGuard,
+    /// 整个进程同时进行的扫描和数据库写入数量上限，0 表示不限制
+    #[serde(default)]
+    pub max_concurrent_operations: usize,
+    /// 同一进程中运行的多个站点，为空时只运行顶层 observed_path 对应的站点
+    #[serde(default)]
+    pub sites: Vec<SiteConfig>,
 }
 
+/// 默认单站点的名称
+pub const DEFAULT_SITE_NAME: &str = "file_monitor";
+
+impl FileMonitorConfig {
+    /// Attempts per DB batch, including the first, from `db_retry_count` if set.
+    pub fn db_retry_attempts(&self) -> u32 {
+        self.db_retry_count
+            .map_or(self.db_retry_attempts, |count| count.saturating_add(1))
+    }
+
+    /// Time zone of the "today only" scan, [`TIME_ZONE`] if unset or out of range.
+    pub fn today_time_zone(&self) -> FixedOffset {
+        self.today_utc_offset_mins
+            .and_then(|mins| FixedOffset::east_opt(mins * 60))
+            .unwrap_or(*TIME_ZONE)
+    }
+
+    /// Checkpoint file of the site `name`, the configured path with the site name
+    /// inserted before the extension when several sites are configured, or
+    /// `<name>.watchinfo.json` in `state_dir` when no path is configured.
+    pub fn checkpoint_path_of(&self, name: &str) -> Option<PathBuf> {
+        let Some(path) = self.checkpoint_path.clone() else {
+            let dir = self.state_dir.as_ref()?;
+            return Some(dir.join(format!("{}.watchinfo.json", name)));
+        };
+        if self.sites.is_empty() {
+            return Some(path);
+        }
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let file_name = match path.extension() {
+            Some(ext) => format!("{}.{}.{}", stem, name, ext.to_string_lossy()),
+            None => format!("{}.{}", stem, name),
+        };
+        Some(path.with_file_name(file_name))
+    }
+
+    /// The configured sites, or a single one from the top-level keys if `sites` is empty.
+    ///
+    /// Empty or duplicate names, missing observed paths and observed paths that contain
+    /// one another are rejected, every problem is reported at once.
+    pub fn site_configs(&self) -> Result<Vec<SiteConfig>, String> {
+        if self.sites.is_empty() {
+            if self.observed_path.first().is_none() {
+                return Err("observed_path is an empty list".to_string());
+            }
+            return Ok(vec![SiteConfig {
+                name: DEFAULT_SITE_NAME.to_string(),
+                observed_path: self.observed_path.clone(),
+                prefix_map_of_extract_path: None,
+                source_tag: None,
+            }]);
+        }
+
+        let mut errors = Vec::new();
+        for (i, site) in self.sites.iter().enumerate() {
+            let name = site.name.trim();
+            if name.is_empty() {
+                errors.push("site with an empty name".to_string());
+            }
+            if site.observed_path.first().is_none() {
+                errors.push(format!("site {:?} has no observed path", site.name));
+            }
+            for other in &self.sites[..i] {
+                if !name.is_empty() && other.name.trim() == name {
+                    errors.push(format!("duplicate site {:?}", name));
+                }
+                let overlaps = site.observed_path.as_slice().iter().any(|a| {
+                    other
+                        .observed_path
+                        .as_slice()
+                        .iter()
+                        .any(|b| a.starts_with(b) || b.starts_with(a))
+                });
+                if overlaps {
+                    errors.push(format!(
+                        "sites {:?} and {:?} have overlapping observed paths",
+                        other.name, site.name
+                    ));
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(self
+                .sites
+                .iter()
+                .map(|s| SiteConfig {
+                    name: s.name.trim().to_string(),
+                    ..s.clone()
+                })
+                .collect())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+}
+
+/// 一个 FTP 站点，未设置的项使用顶层配置
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SiteConfig {
+    pub name: String,
+    #[serde(alias = "observed_paths")]
+    pub observed_path: OneOrMany<PathBuf>,
+    #[serde(default)]
+    pub prefix_map_of_extract_path: Option<HashMap<String, [String; 2]>>,
+    /// 写入 file_info source 列的站点标记
+    #[serde(default)]
+    pub source_tag: Option<String>,
+}
+
+impl SiteConfig {
+    /// The first observed path, the one the site's observer is created with.
+    pub fn primary_path(&self) -> PathBuf {
+        self.observed_path.first().cloned().unwrap_or_default()
+    }
+}
+
+/// 单个值或数组，配置中两种写法都接受
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+impl<T> OneOrMany<T> {
+    pub fn as_slice(&self) -> &[T] {
+        match self {
+            OneOrMany::One(value) => std::slice::from_ref(value),
+            OneOrMany::Many(values) => values,
+        }
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.as_slice().first()
+    }
+}
+
+impl<T> From<T> for OneOrMany<T> {
+    fn from(value: T) -> Self {
+        OneOrMany::One(value)
+    }
+}
+
+/// 界面和命令行输出的颜色模式
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    /// Whether a stream gets colors, `is_terminal` and `NO_COLOR` only matter in auto mode.
+    pub fn use_color(self, is_terminal: bool) -> bool {
+        match self {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => is_terminal && Theme::detect().is_colored(),
+        }
+    }
+}
+
+/// 文件大小的显示单位
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SizeUnits {
+    /// 1024 进制，KiB、MiB
+    #[default]
+    Binary,
+    /// 1000 进制，kB、MB
+    Decimal,
+}
+
+impl SizeUnits {
+    /// Format `bytes` like `1.0 MiB`, sizes under one unit stay in bytes.
+    pub fn format(self, bytes: u64) -> String {
+        let (base, units) = match self {
+            SizeUnits::Binary => (1024.0, ["KiB", "MiB", "GiB", "TiB"]),
+            SizeUnits::Decimal => (1000.0, ["kB", "MB", "GB", "TB"]),
+        };
+        let mut size = bytes as f64;
+        if size < base {
+            return format!("{} B", bytes);
+        }
+        let mut unit = units[0];
+        for u in units {
+            unit = u;
+            size /= base;
+            if size < base {
+                break;
+            }
+        }
+        format!("{:.1} {}", size, unit)
+    }
+}
+
+/// 导出时路径分隔符的写法，不影响存储和显示的路径
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportPathStyle {
+    /// 与记录时相同
+    #[default]
+    Keep,
+    /// 全部写成 `/`
+    Forward,
+    /// 全部写成 `\`
+    Backslash,
+}
+
+impl ExportPathStyle {
+    /// `path` with its separators rewritten for export.
+    pub fn apply(self, path: &str) -> std::borrow::Cow<'_, str> {
+        match self {
+            ExportPathStyle::Keep => path.into(),
+            ExportPathStyle::Forward => path.replace('\\', "/").into(),
+            ExportPathStyle::Backslash => path.replace('/', "\\").into(),
+        }
+    }
+}
+
+/// 日志列表的换行方式
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WrapMode {
+    /// 在空白处换行，过长的英文单词按连字符词典断开
+    #[default]
+    Hyphenate,
+    /// 只在空白处换行，比一行还长的单词直接断开
+    Word,
+    /// 写满一行再换行，适合长路径和中文
+    Character,
+}
+
+/// 记录文件信息的数据库
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DbBackendKind {
+    #[default]
+    Mysql,
+    /// 本地文件，便于没有 MySQL 的开发和测试环境
+    Sqlite,
+}
+
+/// 观察器发现日志变化的方式
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchMode {
+    /// 系统的文件变化通知，如 inotify、ReadDirectoryChangesW
+    #[default]
+    Native,
+    /// 按 poll_interval_ms 定期检查文件的修改时间和大小
+    Poll,
+}
+
+/// 观察器解析日志行的方式
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogParserKind {
+    /// IIS FTP 日志，按 line_markers 和 `#Fields:` 头匹配
+    Iis,
+    /// 按 log_pattern 正则匹配
+    Regex,
+}
+
+/// 观察器记录 FTP 路径的方式
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum PathMapping {
+    /// 按 prefix_map_of_extract_path 改写为本地路径
+    #[default]
+    Rewrite,
+    /// 原样记录日志中的 FTP 路径，并在 path_is_raw 列标记
+    RecordRaw,
+    /// 记录改写后的路径，同时把 FTP 路径写入 source_path 列
+    Both,
+}
+
+/// 额外列配置，source 形如 `path[1]`、`filename_regex:"^(\w+)_"` 或 `"literal"`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ExtraColumnConfig {
+    pub column: String,
+    pub source: String,
+}
+
+/// 快捷操作配置，action 为控制面板菜单项，如 `scanner-start-periodic`
+///
+/// interval 形如 `12h`、`30m`、`90s`，不带单位时按分钟计；
+/// initial_delay_secs 和 jitter_percent 覆盖 scan_jitter 中的默认值
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct QuickActionConfig {
+    pub name: String,
+    pub action: String,
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub interval: Option<String>,
+    #[serde(default)]
+    pub initial_delay_secs: Option<u64>,
+    #[serde(default)]
+    pub jitter_percent: Option<u8>,
+}
+
+fn default_import_encoding() -> String {
+    "utf-8".to_string()
+}
+
+fn default_max_path_len() -> usize {
+    MAX_PATH
+}
+
+fn default_poll_interval_ms() -> u64 {
+    2000
+}
+
+fn default_db_table() -> String {
+    "file_info".to_string()
+}
+
+fn default_db_schema() -> Option<String> {
+    Some("testdata".to_string())
+}
+
+fn default_db_retry_attempts() -> u32 {
+    4
+}
+
+fn default_db_retry_base_delay_ms() -> u64 {
+    500
+}
+
+fn default_import_progress_lines() -> usize {
+    1000
+}
+
+fn default_observe_file_glob() -> Vec<String> {
+    vec!["*.log".to_string()]
+}
+
+/// 未配置 line_markers 时使用的日志行标记
+pub const DEFAULT_LINE_MARKER: &str = "STOR 226";
+
+fn default_line_markers() -> Vec<String> {
+    vec![DEFAULT_LINE_MARKER.to_string()]
+}
+
+fn default_scan_interval_secs() -> u64 {
+    3600
+}
+
+fn default_dedup_window_ms() -> u64 {
+    200
+}
+
+fn default_max_display_chars() -> usize {
+    500
+}
+
+/// Path of the config file, from `--cfg=` or the default location.
+pub fn config_path() -> PathBuf {
+    PathBuf::from(get_param(param::PARAM_CONFIG_PATH).unwrap_or_else(default_config_path))
+}
+
+/// 读取配置文件失败的原因
+#[derive(Debug)]
+pub enum ConfigError {
+    NotFound {
+        path: PathBuf,
+        /// 未指定 `--cfg=`，使用的是默认位置
+        default_path: bool,
+    },
+    Unreadable {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    InvalidJson {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    InvalidToml {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// 扩展名既不是 .json 也不是 .toml
+    UnknownFormat { path: PathBuf },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::NotFound { path, default_path } => write!(
+                f,
+                "Config file {} not found{}",
+                path.display(),
+                if *default_path {
+                    ", put cfg.json there or pass --cfg=<path>"
+                } else {
+                    " (from --cfg=)"
+                }
+            ),
+            ConfigError::Unreadable { path, error } => {
+                write!(
+                    f,
+                    "Failed to read config file {}: {}",
+                    path.display(),
+                    error
+                )
+            }
+            ConfigError::InvalidJson {
+                path,
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "Invalid JSON in config file {} at line {} column {}: {}",
+                path.display(),
+                line,
+                column,
+                message
+            ),
+            ConfigError::InvalidToml {
+                path,
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "Invalid TOML in config file {} at line {} column {}: {}",
+                path.display(),
+                line,
+                column,
+                message
+            ),
+            ConfigError::UnknownFormat { path } => write!(
+                f,
+                "Config file {} must have a .json or .toml extension",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Load the config, panicking with the reason if it can't be loaded.
+///
+/// The config is checked at startup, use [`try_load_config`] where it may have
+/// changed since.
 pub fn load_config() -> MyConfig {
-    let path = get_param(param::PARAM_CONFIG_PATH);
+    try_load_config().unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Load the config from `--cfg=` or the default location.
+pub fn try_load_config() -> Result<MyConfig, ConfigError> {
+    let default_path = get_param(param::PARAM_CONFIG_PATH).is_none();
+    load_config_from(&config_path(), default_path)
+}
+
+fn load_config_from(path: &Path, default_path: bool) -> Result<MyConfig, ConfigError> {
+    parse_config(path, &read_config_file(path, default_path)?)
+}
+
+fn read_config_file(path: &Path, default_path: bool) -> Result<String, ConfigError> {
+    fs::read_to_string(path).map_err(|error| {
+        if error.kind() == std::io::ErrorKind::NotFound {
+            ConfigError::NotFound {
+                // 报告解析后的绝对路径，便于找到放置位置
+                path: std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()),
+                default_path,
+            }
+        } else {
+            ConfigError::Unreadable {
+                path: path.to_path_buf(),
+                error,
+            }
+        }
+    })
+}
+
+/// Parse `config_str` as JSON or TOML depending on the extension of `path`.
+fn parse_config(path: &Path, config_str: &str) -> Result<MyConfig, ConfigError> {
+    let path_buf = path.to_path_buf();
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("json") => {
+            serde_json::from_str(config_str).map_err(|e| ConfigError::InvalidJson {
+                path: path_buf,
+                line: e.line(),
+                column: e.column(),
+                message: e.to_string(),
+            })
+        }
+        Some(ext) if ext.eq_ignore_ascii_case("toml") => {
+            toml::from_str(config_str).map_err(|e| {
+                // 由出错位置的字节偏移换算行列，均从 1 开始
+                let before = &config_str[..e.span().map_or(0, |span| span.start)];
+                let line = before.matches('\n').count() + 1;
+                let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+                ConfigError::InvalidToml {
+                    path: path_buf,
+                    line,
+                    column,
+                    message: e.message().to_string(),
+                }
+            })
+        }
+        _ => Err(ConfigError::UnknownFormat { path: path_buf }),
+    }
+}
+
+/// 运行中共享的配置，配置文件修改后整体替换
+pub type SharedConfig = Arc<RwLock<MyConfig>>;
+
+/// 重新加载后的回调，参数为加载结果
+type ReloadListener = Box<dyn Fn(&Result<(), ConfigError>) + Send>;
+
+struct ConfigSource {
+    path: PathBuf,
+    config: SharedConfig,
+    /// 上次加载的文件内容，内容未变的修改事件不重新加载
+    last_loaded: Mutex<String>,
+    listeners: Mutex<Vec<ReloadListener>>,
+}
+
+impl ConfigSource {
+    /// Re-read the file and swap in the new config, `Ok(false)` if it didn't change.
+    fn reload(&self) -> Result<bool, ConfigError> {
+        let config_str = read_config_file(&self.path, false)?;
+        let mut last_loaded = self.last_loaded.lock().unwrap();
+        if *last_loaded == config_str {
+            return Ok(false);
+        }
+        let config = parse_config(&self.path, &config_str)?;
+        *self.config.write().unwrap() = config;
+        *last_loaded = config_str;
+        Ok(true)
+    }
 
-    let config_str = fs::read_to_string(path.unwrap_or_else(|| default_config_path())).unwrap();
-    let config: MyConfig = serde_json::from_str(&config_str).unwrap();
-    config
+    fn reload_and_notify(&self) {
+        let result = match self.reload() {
+            Ok(false) => return,
+            Ok(true) => Ok(()),
+            Err(e) => Err(e),
+        };
+        for listener in self.listeners.lock().unwrap().iter() {
+            listener(&result);
+        }
+    }
+}
+
+/// 加载一次配置并监视配置文件，文件修改后重新加载
+///
+/// 修改后的内容无效时保留之前的配置，并把错误交给订阅者。
+pub struct ConfigWatcher {
+    source: Arc<ConfigSource>,
+    watcher: Option<notify::RecommendedWatcher>,
+}
+
+impl ConfigWatcher {
+    /// Load the config from `--cfg=` or the default location and watch it.
+    ///
+    /// `None` if the config can't be loaded. Failing to watch only disables hot reload,
+    /// the loaded config is still returned.
+    pub fn start() -> Option<Self> {
+        let mut watcher = Self::new(config_path()).ok()?;
+        let _ = watcher.watch();
+        Some(watcher)
+    }
+
+    /// Load the config at `path`, call [`ConfigWatcher::watch`] to follow later edits.
+    pub fn new(path: PathBuf) -> Result<Self, ConfigError> {
+        let config_str = read_config_file(&path, false)?;
+        let config = parse_config(&path, &config_str)?;
+        Ok(Self {
+            source: Arc::new(ConfigSource {
+                path,
+                config: Arc::new(RwLock::new(config)),
+                last_loaded: Mutex::new(config_str),
+                listeners: Mutex::new(Vec::new()),
+            }),
+            watcher: None,
+        })
+    }
+
+    /// The config currently in effect, replaced as a whole on every reload.
+    pub fn config(&self) -> SharedConfig {
+        Arc::clone(&self.source.config)
+    }
+
+    /// Call `listener` after each reload with `Ok` or the error that kept the old config.
+    pub fn subscribe(&self, listener: impl Fn(&Result<(), ConfigError>) + Send + 'static) {
+        self.source
+            .listeners
+            .lock()
+            .unwrap()
+            .push(Box::new(listener));
+    }
+
+    /// Reload now instead of waiting for a file event, see [`ConfigWatcher::watch`].
+    pub fn reload(&self) {
+        self.source.reload_and_notify();
+    }
+
+    /// Reload whenever the config file changes.
+    ///
+    /// The parent directory is watched, as editors often save by replacing the file.
+    pub fn watch(&mut self) -> notify::Result<()> {
+        use notify::{EventKind, RecursiveMode, Watcher};
+
+        let source = Arc::clone(&self.source);
+        let file_name = source.path.file_name().map(|n| n.to_os_string());
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let Ok(event) = event else {
+                    return;
+                };
+                if matches!(event.kind, EventKind::Modify(_) | EventKind::Create(_))
+                    && event
+                        .paths
+                        .iter()
+                        .any(|p| p.file_name().map(|n| n.to_os_string()) == file_name)
+                {
+                    source.reload_and_notify();
+                }
+            })?;
+        let dir = match self.source.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        self.watcher = Some(watcher);
+        Ok(())
+    }
+}
+
+/// Whether the observer starts in dry-run mode, from `--dry-run` or the config.
+pub fn dry_run_mode(config: &FileMonitorConfig) -> bool {
+    config.dry_run || get_param(param::PARAM_DRY_RUN).is_some()
+}
+
+/// Whether DB errors carry the SQL and a redacted sample of its params, from `--verbose`.
+pub fn verbose_mode() -> bool {
+    get_param(param::PARAM_VERBOSE).is_some()
+}
+
+/// Color mode from `--no-color` or the config, `Auto` if the config can't be read yet.
+pub fn color_mode() -> ColorMode {
+    if get_param(param::PARAM_NO_COLOR).is_some() {
+        return ColorMode::Never;
+    }
+    let path = config_path();
+    fs::read_to_string(&path)
+        .ok()
+        .and_then(|s| parse_config(&path, &s).ok())
+        .map(|c| c.file_sync_manager.color)
+        .unwrap_or_default()
 }
 
 pub fn get_param(param: &str) -> Option<String> {
@@ -68,33 +903,45 @@ pub struct OneEvent {
     time: Option<DateTime<FixedOffset>>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum EventKind {
     LogObserverEvent(LogObserverEventKind),
     DirScannerEvent(DirScannerEventKind),
+    OperatorEvent(OperatorEventKind),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum LogObserverEventKind {
     Stop,
     Error,
     CreatedFile,
     ModifiedFile,
     DeletedFile,
+    /// 日志被轮换或截断，从头重新读取
+    RotatedFile,
     Info,
+    Warning,
+    Debug,
     Start,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum DirScannerEventKind {
     Start,
     Stop,
     Complete,
     Error,
+    Warning,
     Info,
     DBInfo,
 }
 
+/// 操作员手动添加的事件
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum OperatorEventKind {
+    Note,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Copy)]
 pub enum ProgressStatus {
     Running(Running),
@@ -115,3 +962,275 @@ fn validate_config() {
     let config_str = fs::read_to_string("asset/cfg.json").unwrap();
     let _config: MyConfig = serde_json::from_str(&config_str).unwrap();
 }
+
+#[test]
+fn validate_toml_config() {
+    let json = load_config_from(Path::new("asset/cfg.json"), true).unwrap();
+    let toml = load_config_from(Path::new("asset/cfg.toml"), true).unwrap();
+    // 两种格式的示例配置应完全一致
+    assert_eq!(
+        serde_json::to_value(&toml).unwrap(),
+        serde_json::to_value(&json).unwrap()
+    );
+
+    let path = Path::new("cfg.toml");
+    let err = parse_config(path, "[file_sync_manager]\nobserved_path = \n")
+        .err()
+        .unwrap();
+    assert!(
+        matches!(err, ConfigError::InvalidToml { line: 2, .. }),
+        "{}",
+        err
+    );
+    let err = parse_config(Path::new("cfg.yaml"), "").err().unwrap();
+    assert!(matches!(err, ConfigError::UnknownFormat { .. }));
+    assert!(
+        err.to_string()
+            .contains("must have a .json or .toml extension")
+    );
+}
+
+#[test]
+fn test_db_retry_count() {
+    let config: MyConfig =
+        serde_json::from_str(&fs::read_to_string("asset/cfg.json").unwrap()).unwrap();
+    let mut config = config.file_sync_manager;
+    assert_eq!(config.db_retry_attempts(), config.db_retry_attempts);
+
+    // db_retry_count 按重试次数计，不包括第一次
+    config.db_retry_count = Some(3);
+    assert_eq!(config.db_retry_attempts(), 4);
+    config.db_retry_count = Some(0);
+    assert_eq!(config.db_retry_attempts(), 1);
+}
+
+#[test]
+fn test_size_units() {
+    assert_eq!(SizeUnits::Binary.format(1024), "1.0 KiB");
+    assert_eq!(SizeUnits::Decimal.format(1024), "1.0 kB");
+    assert_eq!(SizeUnits::Binary.format(0), "0 B");
+    assert_eq!(SizeUnits::Binary.format(1023), "1023 B");
+    assert_eq!(SizeUnits::Decimal.format(999), "999 B");
+    assert_eq!(SizeUnits::Binary.format(1048576), "1.0 MiB");
+    assert_eq!(SizeUnits::Decimal.format(1_500_000), "1.5 MB");
+    assert_eq!(SizeUnits::Binary.format(5 * 1024u64.pow(5)), "5120.0 TiB");
+}
+
+#[test]
+fn test_site_configs() {
+    let config: MyConfig =
+        serde_json::from_str(&fs::read_to_string("asset/cfg.json").unwrap()).unwrap();
+    let mut config = config.file_sync_manager;
+    let site = |name: &str, path: &str| SiteConfig {
+        name: name.to_string(),
+        observed_path: PathBuf::from(path).into(),
+        prefix_map_of_extract_path: None,
+        source_tag: None,
+    };
+
+    // 没有配置站点时使用顶层配置
+    let sites = config.site_configs().unwrap();
+    assert_eq!(sites.len(), 1);
+    assert_eq!(sites[0].name, DEFAULT_SITE_NAME);
+    assert_eq!(sites[0].observed_path, config.observed_path);
+
+    config.sites = vec![
+        site(" ftp1 ", "/logs/FTPSVC1"),
+        site("ftp2", "/logs/FTPSVC2"),
+    ];
+    let names: Vec<String> = config
+        .site_configs()
+        .unwrap()
+        .into_iter()
+        .map(|s| s.name)
+        .collect();
+    assert_eq!(names, ["ftp1", "ftp2"]);
+
+    config.sites = vec![
+        site("ftp1", "/logs/FTPSVC1"),
+        site("ftp1", "/logs/FTPSVC2"),
+        site("ftp3", "/logs/FTPSVC2/archive"),
+        site("", "/logs/FTPSVC4"),
+    ];
+    let err = config.site_configs().unwrap_err();
+    assert!(err.contains(r#"duplicate site "ftp1""#));
+    assert!(err.contains(r#"sites "ftp1" and "ftp3" have overlapping observed paths"#));
+    assert!(err.contains("site with an empty name"));
+    assert!(!err.contains(r#""ftp1" and "ftp1""#));
+}
+
+#[test]
+fn test_checkpoint_path_of() {
+    let config: MyConfig =
+        serde_json::from_str(&fs::read_to_string("asset/cfg.json").unwrap()).unwrap();
+    let mut config = config.file_sync_manager;
+    config.checkpoint_path = None;
+    config.state_dir = None;
+    assert_eq!(config.checkpoint_path_of("ftp1"), None);
+
+    config.state_dir = Some(PathBuf::from("state"));
+    assert_eq!(
+        config.checkpoint_path_of("ftp1"),
+        Some(PathBuf::from("state/ftp1.watchinfo.json"))
+    );
+
+    // 配置的检查点路径优先
+    config.checkpoint_path = Some(PathBuf::from("checkpoint.json"));
+    assert_eq!(
+        config.checkpoint_path_of("ftp1"),
+        Some(PathBuf::from("checkpoint.json"))
+    );
+}
+
+#[test]
+fn test_load_config_errors() {
+    let base = std::env::temp_dir().join("test_load_config_errors");
+    let _ = fs::remove_dir_all(&base);
+    fs::create_dir_all(&base).unwrap();
+
+    let missing = base.join("cfg.json");
+    let err = load_config_from(&missing, true).err().unwrap();
+    assert!(matches!(&err, ConfigError::NotFound { path, .. } if path == &missing));
+    assert!(err.to_string().contains("or pass --cfg=<path>"));
+    let err = load_config_from(&missing, false).err().unwrap();
+    assert!(err.to_string().ends_with("not found (from --cfg=)"));
+
+    // 目录无法按文件读取
+    let err = load_config_from(&base, false).err().unwrap();
+    assert!(matches!(err, ConfigError::Unreadable { .. }));
+
+    fs::write(
+        &missing,
+        "{\n  \"file_sync_manager\": {\n    \"observed_path\": ,\n",
+    )
+    .unwrap();
+    let err = load_config_from(&missing, false).err().unwrap();
+    assert!(
+        matches!(
+            err,
+            ConfigError::InvalidJson {
+                line: 3,
+                column: 22,
+                ..
+            }
+        ),
+        "{}",
+        err
+    );
+    assert!(err.to_string().contains("at line 3 column 22"));
+
+    assert!(load_config_from(Path::new("asset/cfg.json"), true).is_ok());
+    fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_config_watcher_reload() {
+    let base = std::env::temp_dir().join("test_config_watcher_reload");
+    let _ = fs::remove_dir_all(&base);
+    fs::create_dir_all(&base).unwrap();
+    let path = base.join("cfg.json");
+    let original = fs::read_to_string("asset/cfg.json").unwrap();
+    fs::write(&path, &original).unwrap();
+
+    let mut watcher = ConfigWatcher::new(path.clone()).unwrap();
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let results_clone = results.clone();
+    watcher.subscribe(move |r| results_clone.lock().unwrap().push(r.is_ok()));
+    let config = watcher.config();
+    assert_eq!(
+        config.read().unwrap().file_sync_manager.max_observed_files,
+        1000
+    );
+
+    // 内容未变不重新加载
+    watcher.reload();
+    assert!(results.lock().unwrap().is_empty());
+
+    // 无效的修改保留之前的配置
+    fs::write(&path, "{\"file_sync_manager\": ").unwrap();
+    watcher.reload();
+    assert_eq!(*results.lock().unwrap(), [false]);
+    assert_eq!(
+        config.read().unwrap().file_sync_manager.max_observed_files,
+        1000
+    );
+
+    // 监视文件，修改后自动替换
+    watcher.watch().unwrap();
+    fs::write(
+        &path,
+        original.replace("\"max_observed_files\": 1000", "\"max_observed_files\": 20"),
+    )
+    .unwrap();
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    while config.read().unwrap().file_sync_manager.max_observed_files != 20 {
+        assert!(std::time::Instant::now() < deadline, "config not reloaded");
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    assert_eq!(results.lock().unwrap().last(), Some(&true));
+
+    drop(watcher);
+    fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_observed_path_one_or_many() {
+    let parse = |value: &str| -> OneOrMany<PathBuf> { serde_json::from_str(value).unwrap() };
+    assert_eq!(
+        parse(r#""/logs/FTPSVC1""#),
+        PathBuf::from("/logs/FTPSVC1").into()
+    );
+    let many = parse(r#"["/logs/FTPSVC1", "/logs/FTPSVC2"]"#);
+    assert_eq!(
+        many.as_slice(),
+        [
+            PathBuf::from("/logs/FTPSVC1"),
+            PathBuf::from("/logs/FTPSVC2")
+        ]
+    );
+
+    let mut config = load_config_from(Path::new("asset/cfg.json"), true)
+        .unwrap()
+        .file_sync_manager;
+    config.observed_path = many.clone();
+    let sites = config.site_configs().unwrap();
+    assert_eq!(sites[0].observed_path, many);
+    assert_eq!(sites[0].primary_path(), PathBuf::from("/logs/FTPSVC1"));
+
+    config.observed_path = OneOrMany::Many(Vec::new());
+    assert!(config.site_configs().is_err());
+
+    // 任一目录与其他站点的目录重叠都报错
+    let site = |name: &str, paths: &[&str]| SiteConfig {
+        name: name.to_string(),
+        observed_path: OneOrMany::Many(paths.iter().map(PathBuf::from).collect()),
+        prefix_map_of_extract_path: None,
+        source_tag: None,
+    };
+    config.sites = vec![
+        site("ftp1", &["/logs/FTPSVC1", "/logs/FTPSVC3"]),
+        site("ftp2", &["/logs/FTPSVC2", "/logs/FTPSVC3/old"]),
+        site("ftp4", &[]),
+    ];
+    let err = config.site_configs().unwrap_err();
+    assert!(err.contains(r#"sites "ftp1" and "ftp2" have overlapping observed paths"#));
+    assert!(err.contains(r#"site "ftp4" has no observed path"#));
+
+    // 复数写法的键名
+    let site: SiteConfig =
+        serde_json::from_str(r#"{"name": "ftp1", "observed_paths": ["/logs/FTPSVC1"]}"#).unwrap();
+    assert_eq!(
+        site.observed_path,
+        OneOrMany::Many(vec![PathBuf::from("/logs/FTPSVC1")])
+    );
+    let mut json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string("asset/cfg.json").unwrap()).unwrap();
+    let fsm = json["file_sync_manager"].as_object_mut().unwrap();
+    let path = fsm.remove("observed_path").unwrap();
+    fsm.insert("observed_paths".to_string(), path.clone());
+    let config: MyConfig = serde_json::from_value(json).unwrap();
+    assert_eq!(
+        config.file_sync_manager.observed_path,
+        serde_json::from_value(path).unwrap()
+    );
+}
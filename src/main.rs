@@ -1,6 +1,6 @@
-use std::{fs::OpenOptions, io::Write};
+use std::io::IsTerminal;
 
-use ratatui::{crossterm::execute, restore};
+use ratatui::crossterm::execute;
 
 use one_server::*;
 
@@ -9,17 +9,24 @@ async fn main() {
     #[cfg(not(debug_assertions))]
     set_panic_hook();
 
-    execute!(
-        std::io::stdout(),
-        ratatui::crossterm::terminal::SetTitle("One Server 文件同步")
-    )
-    .unwrap();
+    // 输出被重定向或禁用颜色时不写入转义序列
+    if color_mode() != ColorMode::Never && std::io::stdout().is_terminal() {
+        execute!(
+            std::io::stdout(),
+            ratatui::crossterm::terminal::SetTitle("One Server 文件同步")
+        )
+        .unwrap();
+    }
 
     param::handle_params();
 }
 
 #[cfg(not(debug_assertions))]
 fn set_panic_hook() {
+    use one_server::apps::file_sync_manager::log_observer;
+    use ratatui::restore;
+    use std::{fs::OpenOptions, io::Write};
+
     let hook = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |info| {
         if let Ok(mut file) = OpenOptions::new()
@@ -44,6 +51,8 @@ fn set_panic_hook() {
             let _ = file.write_all(msg.as_bytes());
         }
 
+        // 保存读取位置，重启后不重复处理已读的日志
+        log_observer::save_checkpoints_on_panic();
         restore();
 
         hook(info);
@@ -2,23 +2,40 @@ use ratatui::{
     buffer::Buffer,
     crossterm::event::Event,
     layout::{Constraint, Direction, Flex, Layout, Rect},
-    widgets::{Block, Clear, Paragraph, Widget, WidgetRef},
+    widgets::{Block, Clear, Paragraph, Widget, WidgetRef, Wrap},
 };
 
-use crate::apps::AppAction;
+use crate::apps::{AppAction, file_sync_manager::ShutdownReport};
 
 pub mod menu;
+pub mod theme;
 pub mod wrap_list;
 
 pub enum LogKind {
     All,
     Observer,
     Scanner,
+    RecordedFiles,
 }
 
 pub trait MyWidgets: WidgetRef {
     fn handle_event(&mut self, event: Event) -> Result<AppAction, std::io::Error>;
     fn get_logs_str(&self, kind: LogKind) -> Vec<String>;
+    /// Estimated bytes held by the widget's log lists.
+    fn memory_estimate(&self) -> usize {
+        0
+    }
+    /// What is still running in the background, `None` if nothing is.
+    fn busy_summary(&self) -> Option<String> {
+        None
+    }
+    /// Stop background work before exit, `None` if the widget has none.
+    fn shutdown_report(&mut self) -> Option<ShutdownReport> {
+        None
+    }
+    /// Upkeep run once per UI loop iteration before drawing, so rendering stays free
+    /// of side effects.
+    fn tick(&mut self) {}
 }
 
 pub fn get_center_rect(area: Rect, width_percentage: f32, height_percentage: f32) -> Rect {
@@ -68,9 +85,18 @@ pub fn center(area: Rect, horizontal: Constraint, vertical: Constraint) -> Rect
     area
 }
 
-pub fn render_input_popup<'a>(content: &'a str, area: Rect, buf: &mut Buffer, title: &str) {
+pub fn render_input_popup(content: &str, area: Rect, buf: &mut Buffer, title: &str) {
     let area = center(area, Constraint::Percentage(50), Constraint::Length(3));
     let popup = Paragraph::new(content).block(Block::bordered().title(title));
     Clear.render(area, buf);
     popup.render(area, buf);
 }
+
+pub fn render_detail_popup(content: &str, area: Rect, buf: &mut Buffer, title: &str) {
+    let area = center(area, Constraint::Percentage(80), Constraint::Percentage(80));
+    let popup = Paragraph::new(content)
+        .wrap(Wrap { trim: false })
+        .block(Block::bordered().title(title));
+    Clear.render(area, buf);
+    popup.render(area, buf);
+}
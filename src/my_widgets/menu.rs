@@ -1,5 +1,6 @@
 pub use self::menu_state::MenuState;
 
+mod menu_preview;
 mod menu_render;
 pub mod menu_state;
 
@@ -7,10 +8,12 @@ use std::cell::RefCell;
 use std::rc::{Rc, Weak};
 
 use ratatui::widgets::Block;
+
+use crate::my_widgets::theme::Theme;
 use serde::{Deserialize, Serialize};
 
 // 定义一个辅助结构体，用于序列化和反序列化 MenuItem
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SerializableMenuItem {
     pub name: String,
     pub content: String,
@@ -25,6 +28,7 @@ pub struct MenuItem<'a> {
     selected: bool,
     parent: Weak<RefCell<MenuItem<'a>>>,
     block: Option<Block<'a>>,
+    theme: Theme,
 }
 
 impl<'a> MenuItem<'a> {
@@ -41,15 +45,21 @@ impl<'a> MenuItem<'a> {
             selected: false,
             parent,
             block: None,
+            theme: Theme::detect(),
         }
     }
 
     // 从 JSON 字符串反序列化为 MenuItem
-    pub fn from_json(json_str: &str) -> Result<Rc<RefCell<MenuItem>>, serde_json::Error> {
+    pub fn from_json(json_str: &str) -> Result<Rc<RefCell<MenuItem<'_>>>, serde_json::Error> {
         let serializable_item: SerializableMenuItem = serde_json::from_str(json_str)?;
         Ok(Self::from_serializable(serializable_item, Weak::new()))
     }
 
+    // 从可序列化的根节点构建菜单，用于运行时追加了子项的菜单
+    pub fn from_item(item: SerializableMenuItem) -> Rc<RefCell<MenuItem<'a>>> {
+        Self::from_serializable(item, Weak::new())
+    }
+
     // 序列化 MenuItem 为 JSON 字符串
     pub fn to_json(&self) -> Result<String, serde_json::Error> {
         let serializable_item = self.to_serializable();
@@ -68,6 +78,7 @@ impl<'a> MenuItem<'a> {
             selected: false,
             parent,
             block: None,
+            theme: Theme::detect(),
         }));
 
         let mut children = Vec::new();
@@ -94,6 +105,10 @@ impl<'a> MenuItem<'a> {
     pub fn set_block(&mut self, block: Block<'a>) {
         self.block = Some(block);
     }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
 }
 
 impl<'a> PartialEq for MenuItem<'a> {
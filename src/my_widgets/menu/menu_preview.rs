@@ -0,0 +1,154 @@
+use std::{cell::RefCell, rc::Rc};
+
+use ratatui::{
+    style::{Modifier, Style},
+    text::{Line, Span},
+};
+
+use crate::my_widgets::menu::MenuItem;
+
+/// 预览区最多显示的行数，不含分隔线
+pub const PREVIEW_MAX_LINES: u16 = 3;
+
+/// Render `text` as styled lines, supporting `**bold**` and `- ` or `* ` bullet lines.
+///
+/// Text without markup comes back as plain lines, an unclosed `**` is kept as is.
+pub fn markdown_lite(text: &str) -> Vec<Line<'static>> {
+    text.lines()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            match trimmed
+                .strip_prefix("- ")
+                .or_else(|| trimmed.strip_prefix("* "))
+            {
+                Some(item) => {
+                    let indent = &line[..line.len() - trimmed.len()];
+                    let mut spans = vec![Span::raw(format!("{}• ", indent))];
+                    spans.extend(inline_spans(item));
+                    Line::from(spans)
+                }
+                None => Line::from(inline_spans(line)),
+            }
+        })
+        .collect()
+}
+
+// 按 ** 切分，奇数段加粗
+fn inline_spans(text: &str) -> Vec<Span<'static>> {
+    let parts: Vec<&str> = text.split("**").collect();
+    // 没有成对的 ** 时原样返回
+    if parts.len() < 3 {
+        return vec![Span::raw(text.to_string())];
+    }
+    let closed = if parts.len() % 2 == 1 {
+        parts.len()
+    } else {
+        parts.len() - 1
+    };
+    let mut spans = Vec::new();
+    for (i, part) in parts[..closed].iter().enumerate() {
+        if part.is_empty() {
+            continue;
+        }
+        if i % 2 == 1 {
+            spans.push(Span::styled(
+                part.to_string(),
+                Style::default().add_modifier(Modifier::BOLD),
+            ));
+        } else {
+            spans.push(Span::raw(part.to_string()));
+        }
+    }
+    // 最后一个 ** 没有闭合
+    if closed < parts.len() {
+        spans.push(Span::raw(format!("**{}", parts[closed])));
+    }
+    spans
+}
+
+impl<'a> MenuItem<'a> {
+    /// The item `indices` point at, `None` if nothing is selected or an index is out of range.
+    pub fn selected_item(&self, indices: &[usize]) -> Option<Rc<RefCell<MenuItem<'a>>>> {
+        let (first, rest) = indices.split_first()?;
+        let mut item = Rc::clone(self.children.get(*first)?);
+        for &i in rest {
+            let child = Rc::clone(item.borrow().children.get(i)?);
+            item = child;
+        }
+        Some(item)
+    }
+
+    /// Content of the selected item rendered for the preview pane.
+    pub fn preview_lines(&self, indices: &[usize]) -> Vec<Line<'static>> {
+        self.selected_item(indices)
+            .map(|item| markdown_lite(&item.borrow().content))
+            .unwrap_or_default()
+    }
+}
+
+// MARK: test
+#[test]
+fn test_markdown_lite() {
+    let lines = markdown_lite("Start the **observer** now.\n- one\n  * two **x**");
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0].spans.len(), 3);
+    assert_eq!(lines[0].spans[1].content, "observer");
+    assert!(
+        lines[0].spans[1]
+            .style
+            .add_modifier
+            .contains(Modifier::BOLD)
+    );
+    assert!(
+        !lines[0].spans[0]
+            .style
+            .add_modifier
+            .contains(Modifier::BOLD)
+    );
+    assert_eq!(lines[1].to_string(), "• one");
+    assert_eq!(lines[2].to_string(), "  • two x");
+
+    // 没有标记时保持纯文本
+    let plain = markdown_lite("Start a scan (y to confirm).");
+    assert_eq!(plain, vec![Line::raw("Start a scan (y to confirm).")]);
+    let unclosed = markdown_lite("a **b** c **d");
+    assert_eq!(unclosed[0].to_string(), "a b c **d");
+    assert_eq!(markdown_lite("2 ** 3")[0].to_string(), "2 ** 3");
+}
+
+#[test]
+fn test_bold_in_menu_preview() {
+    use ratatui::{buffer::Buffer, layout::Rect, widgets::StatefulWidgetRef};
+
+    use crate::my_widgets::{menu::MenuState, theme::Theme};
+
+    let root = MenuItem::from_json(
+        r#"{"name": "root", "content": "", "children": [
+            {"name": "scan", "content": "Scan **once**", "children": []}
+        ]}"#,
+    )
+    .unwrap();
+    root.borrow_mut().set_theme(Theme::monochrome());
+    let mut state = MenuState {
+        selected_indices: vec![0],
+    };
+
+    let area = Rect::new(0, 0, 30, 8);
+    let mut buf = Buffer::empty(area);
+    root.borrow().render_ref(area, &mut buf, &mut state);
+
+    // 预览在最后一行，分隔线在其上方
+    let row = area.bottom() - 1;
+    let text: String = (0..area.width)
+        .map(|x| buf[(x, row)].symbol().to_string())
+        .collect();
+    assert!(text.starts_with("Scan once"), "{:?}", text);
+    assert!(!buf[(0, row)].modifier.contains(Modifier::BOLD));
+    assert!(buf[(5, row)].modifier.contains(Modifier::BOLD));
+
+    // 没有选中项时不显示预览
+    let mut buf = Buffer::empty(area);
+    root.borrow()
+        .render_ref(area, &mut buf, &mut MenuState::default());
+    assert_eq!(buf[(0, row)].symbol(), " ");
+}
@@ -2,22 +2,20 @@ use std::{cell::RefCell, rc::Rc};
 
 use ratatui::{
     buffer::Buffer,
-    layout::{Constraint, Direction, Rect},
+    layout::{Constraint, Direction, Layout, Rect},
     prelude::BlockExt,
-    style::{Color::*, Modifier, Style},
+    style::Style,
     widgets::{
-        Block, Borders, List, ListState, StatefulWidget, StatefulWidgetRef, Widget, WidgetRef,
+        Block, Borders, List, ListState, Paragraph, StatefulWidget, StatefulWidgetRef, Widget,
+        WidgetRef, Wrap,
     },
 };
 
 use crate::my_widgets::{
     dichotomize_area_with_midlines,
-    menu::{MenuItem, MenuState},
+    menu::{MenuItem, MenuState, menu_preview::PREVIEW_MAX_LINES},
 };
 
-pub const MENU_HIGHLIGHT: Style = Style::new().bg(Indexed(30)).add_modifier(Modifier::BOLD);
-pub const MENU_SELECTED: Style = Style::new().fg(Red).bg(Indexed(43));
-
 impl<'a> MenuItem<'a> {
     fn render_list(
         items: &Vec<Rc<RefCell<MenuItem<'a>>>>,
@@ -46,7 +44,7 @@ impl<'a> MenuItem<'a> {
         buf: &mut Buffer,
         index: Option<usize>,
     ) {
-        Self::render_list(children, area, buf, index, MENU_HIGHLIGHT);
+        Self::render_list(children, area, buf, index, self.theme.menu_item_highlight());
     }
 
     fn render_to_right(
@@ -56,7 +54,7 @@ impl<'a> MenuItem<'a> {
         buf: &mut Buffer,
         index: Option<usize>,
     ) {
-        Self::render_list(children, area, buf, index, MENU_SELECTED);
+        Self::render_list(children, area, buf, index, self.theme.menu_item_selected());
     }
 }
 
@@ -71,6 +69,27 @@ impl<'a> StatefulWidgetRef for MenuItem<'a> {
         self.block.render_ref(area, buf);
         let menu_area = self.block.inner_if_some(area);
 
+        // 选中项有说明且空间足够时，在底部预览
+        let preview = self.preview_lines(&state.selected_indices);
+        let menu_area = if preview.is_empty() || menu_area.height < PREVIEW_MAX_LINES + 4 {
+            menu_area
+        } else {
+            let height = (preview.len() as u16).min(PREVIEW_MAX_LINES) + 1;
+            let [list_area, preview_area] =
+                Layout::vertical([Constraint::Fill(1), Constraint::Length(height)])
+                    .areas(menu_area);
+            Paragraph::new(preview)
+                .wrap(Wrap { trim: false })
+                .style(self.theme.text())
+                .block(
+                    Block::default()
+                        .borders(Borders::TOP)
+                        .border_style(self.theme.border()),
+                )
+                .render(preview_area, buf);
+            list_area
+        };
+
         let (left_area, midline, right_area) = dichotomize_area_with_midlines(
             menu_area,
             Direction::Horizontal,
@@ -81,7 +100,7 @@ impl<'a> StatefulWidgetRef for MenuItem<'a> {
 
         Block::default()
             .borders(Borders::LEFT)
-            .border_style(Style::new().fg(Gray))
+            .border_style(self.theme.border())
             .render(midline, buf);
 
         // 判断是否有选中的菜单项
@@ -97,7 +116,7 @@ impl<'a> StatefulWidgetRef for MenuItem<'a> {
                 state.selected_indices[0] = selected_index;
                 self.render_to_left(&self.children, left_area, buf, Some(selected_index));
 
-                if self.children[selected_index].borrow().children.len() > 0 {
+                if !self.children[selected_index].borrow().children.is_empty() {
                     self.render_to_right(
                         &self.children[selected_index].borrow().children,
                         right_area,
@@ -113,7 +132,7 @@ impl<'a> StatefulWidgetRef for MenuItem<'a> {
 
                 // 获取最终选中的菜单项，清除异常项
                 for i in 0..state.selected_indices.len() {
-                    if last_item.borrow().children.len() == 0 {
+                    if last_item.borrow().children.is_empty() {
                         state.selected_indices.truncate(i);
                         return;
                     } else {
@@ -5,19 +5,19 @@ pub struct MenuState {
 
 impl MenuState {
     pub fn select_up(&mut self) {
-        if self.selected_indices.len() == 0 {
+        if self.selected_indices.is_empty() {
             self.select_right();
             return;
         }
-        if let Some(index) = self.selected_indices.last_mut() {
-            if *index > 0 {
-                *index -= 1;
-            }
+        if let Some(index) = self.selected_indices.last_mut()
+            && *index > 0
+        {
+            *index -= 1;
         }
     }
 
     pub fn select_down(&mut self) {
-        if self.selected_indices.len() == 0 {
+        if self.selected_indices.is_empty() {
             self.select_right();
             return;
         }
@@ -27,7 +27,7 @@ impl MenuState {
     }
 
     pub fn select_left(&mut self) {
-        if self.selected_indices.len() > 0 {
+        if !self.selected_indices.is_empty() {
             self.selected_indices.pop();
         }
     }
@@ -0,0 +1,192 @@
+use ratatui::style::{Color, Modifier, Style, palette::tailwind::SLATE};
+
+use crate::ColorMode;
+
+/// 界面配色，终端不支持颜色时退化为粗体、反色等属性样式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    colored: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::detect()
+    }
+}
+
+impl Theme {
+    /// Use colors unless `NO_COLOR` is set to a non-empty value or the terminal is `dumb`.
+    pub fn detect() -> Self {
+        Self::detect_from(
+            std::env::var("NO_COLOR").ok().as_deref(),
+            std::env::var("TERM").ok().as_deref(),
+        )
+    }
+
+    /// Like [`Self::detect`], from the values of `NO_COLOR` and `TERM`.
+    pub fn detect_from(no_color: Option<&str>, term: Option<&str>) -> Self {
+        let no_color = no_color.is_some_and(|v| !v.is_empty());
+        let dumb_term = term == Some("dumb");
+        Self {
+            colored: !no_color && !dumb_term,
+        }
+    }
+
+    /// Theme for a stream under `mode`, see [`ColorMode::use_color`].
+    pub fn from_mode(mode: ColorMode, is_terminal: bool) -> Self {
+        Self {
+            colored: mode.use_color(is_terminal),
+        }
+    }
+
+    pub fn colored() -> Self {
+        Self { colored: true }
+    }
+
+    pub fn monochrome() -> Self {
+        Self { colored: false }
+    }
+
+    pub fn is_colored(&self) -> bool {
+        self.colored
+    }
+
+    /// Foreground style for `color`, collapsed to bold or reverse when colors are off.
+    pub fn fg(&self, color: Color) -> Style {
+        if self.colored {
+            return Style::new().fg(color);
+        }
+        match color {
+            Color::Red => Style::new().add_modifier(Modifier::BOLD | Modifier::REVERSED),
+            Color::Green | Color::Cyan | Color::Yellow | Color::LightYellow => {
+                Style::new().add_modifier(Modifier::BOLD)
+            }
+            _ => Style::new(),
+        }
+    }
+
+    pub fn title(&self) -> Style {
+        self.pick(
+            Style::new().fg(Color::Green).add_modifier(Modifier::BOLD),
+            Style::new().add_modifier(Modifier::BOLD),
+        )
+    }
+
+    pub fn text(&self) -> Style {
+        self.pick(Style::new().fg(Color::White), Style::new())
+    }
+
+    /// Highlight of the selected row in log lists.
+    pub fn highlight(&self) -> Style {
+        self.pick(
+            Style::new().bg(SLATE.c800).fg(Color::Green),
+            Style::new().add_modifier(Modifier::REVERSED),
+        )
+    }
+
+    pub fn tab_highlight(&self) -> Style {
+        self.pick(
+            Style::new().fg(Color::Green).bg(Color::Yellow),
+            Style::new().add_modifier(Modifier::REVERSED | Modifier::BOLD),
+        )
+    }
+
+    pub fn menu(&self) -> Style {
+        self.pick(
+            Style::new().bg(SLATE.c600).add_modifier(Modifier::BOLD),
+            Style::new().add_modifier(Modifier::BOLD),
+        )
+    }
+
+    pub fn menu_selected(&self) -> Style {
+        self.pick(
+            Style::new().bg(SLATE.c800).add_modifier(Modifier::BOLD),
+            Style::new().add_modifier(Modifier::REVERSED | Modifier::BOLD),
+        )
+    }
+
+    /// Selected item in the left column of the control panel menu.
+    pub fn menu_item_highlight(&self) -> Style {
+        self.pick(
+            Style::new()
+                .bg(Color::Indexed(30))
+                .add_modifier(Modifier::BOLD),
+            Style::new().add_modifier(Modifier::REVERSED | Modifier::BOLD),
+        )
+    }
+
+    /// Selected item in the right column of the control panel menu.
+    pub fn menu_item_selected(&self) -> Style {
+        self.pick(
+            Style::new().fg(Color::Red).bg(Color::Indexed(43)),
+            Style::new().add_modifier(Modifier::REVERSED),
+        )
+    }
+
+    pub fn border(&self) -> Style {
+        self.pick(Style::new().fg(Color::Gray), Style::new())
+    }
+
+    fn pick(&self, colored: Style, monochrome: Style) -> Style {
+        if self.colored { colored } else { monochrome }
+    }
+}
+
+// MARK: test
+#[test]
+fn test_no_color_theme() {
+    // NO_COLOR 为空时不生效
+    assert!(Theme::detect_from(None, Some("xterm-256color")).is_colored());
+    assert!(Theme::detect_from(Some(""), None).is_colored());
+    assert!(!Theme::detect_from(None, Some("dumb")).is_colored());
+
+    let theme = Theme::detect_from(Some("1"), Some("xterm-256color"));
+    assert!(!theme.is_colored());
+    let styles = [
+        theme.fg(Color::Red),
+        theme.fg(Color::Green),
+        theme.fg(Color::Blue),
+        theme.title(),
+        theme.text(),
+        theme.highlight(),
+        theme.tab_highlight(),
+        theme.menu(),
+        theme.menu_selected(),
+        theme.menu_item_highlight(),
+        theme.menu_item_selected(),
+        theme.border(),
+    ];
+    for style in styles {
+        assert_eq!(style.fg, None);
+        assert_eq!(style.bg, None);
+    }
+    assert!(
+        theme
+            .fg(Color::Red)
+            .add_modifier
+            .contains(Modifier::REVERSED)
+    );
+}
+
+#[test]
+fn test_color_mode() {
+    assert!(Theme::from_mode(ColorMode::Always, false).is_colored());
+    assert!(!Theme::from_mode(ColorMode::Never, true).is_colored());
+    assert!(!Theme::from_mode(ColorMode::Auto, false).is_colored());
+
+    // never 模式只使用默认、粗体和反色属性
+    let theme = Theme::from_mode(ColorMode::Never, true);
+    let allowed = Modifier::BOLD | Modifier::REVERSED;
+    for color in [
+        Color::Red,
+        Color::Green,
+        Color::Yellow,
+        Color::LightYellow,
+        Color::Blue,
+    ] {
+        assert!(allowed.contains(theme.fg(color).add_modifier));
+    }
+    for style in [theme.menu_item_selected(), theme.tab_highlight()] {
+        assert!(allowed.contains(style.add_modifier));
+    }
+}
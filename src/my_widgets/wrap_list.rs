@@ -1,16 +1,20 @@
-use std::collections::VecDeque;
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use hyphenation::{Language, Load, Standard};
+use hyphenation::{Hyphenator, Language, Load, Standard};
 use ratatui::{
     style::{Color, Style},
     text::{Line, Span, Text},
     widgets::{Block, Borders, List, ListItem, ListState, StatefulWidget, StatefulWidgetRef},
 };
-use textwrap::WordSplitter;
+use textwrap::{WordSeparator, WordSplitter, core::Word};
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::{
-    DirScannerEventKind as DSE, EventKind::*, LogObserverEventKind as LOE, OneEvent,
-    apps::MENU_HIGHLIGHT_STYLE,
+    DirScannerEventKind as DSE, EventKind, EventKind::*, LogObserverEventKind as LOE, OneEvent,
+    OperatorEventKind as OPE, WrapMode, my_widgets::theme::Theme,
 };
 
 #[derive(Clone)]
@@ -18,23 +22,117 @@ pub struct WrapList {
     raw_list: VecDeque<OneEvent>,
     list: VecDeque<ListItem<'static>>,
     wrap_len: Option<usize>,
-    dictionary: Standard,
+    wrap_mode: WrapMode,
+    follow: bool,
+    pending: usize,
+    unseen: usize,
+    max_display_chars: usize,
+    theme: Theme,
+    /// 累计添加的事件数，作为增量读取的游标
+    total: u64,
+    /// 列表中各类事件的数量，随添加和淘汰增减
+    counts: HashMap<EventKind, usize>,
+    /// 批量操作中，推迟到结束时一次性重建列表项
+    batching: bool,
+    /// 生成列表项的次数，逐条添加和整体重建各计一次
+    renders: usize,
+}
+
+const TRUNCATED_HINT: &str = "… (press Enter for full text)";
+/// 估算内存时每条事件的固定开销，包括事件本身和渲染后的列表项
+pub const ITEM_OVERHEAD: usize = std::mem::size_of::<OneEvent>() + 128;
+
+/// 连字符词典的加载次数
+static DICTIONARY_LOADS: AtomicUsize = AtomicUsize::new(0);
+
+/// The EnglishUS hyphenation dictionary, loaded on first use and shared by all lists.
+///
+/// `None` if it fails to load, words are then only broken when longer than a line.
+fn dictionary() -> Option<&'static Standard> {
+    static DICTIONARY: OnceLock<Option<Standard>> = OnceLock::new();
+    DICTIONARY
+        .get_or_init(|| {
+            DICTIONARY_LOADS.fetch_add(1, Ordering::Relaxed);
+            Standard::from_embedded(Language::EnglishUS).ok()
+        })
+        .as_ref()
+}
+
+fn split_hyphenated(word: &str) -> Vec<usize> {
+    dictionary()
+        .map(|dictionary| dictionary.hyphenate(word).breaks)
+        .unwrap_or_default()
+}
+
+/// Split `line` into single characters, each keeping the spaces after it.
+fn split_characters(line: &str) -> Box<dyn Iterator<Item = Word<'_>> + '_> {
+    let mut starts: Vec<usize> = line
+        .grapheme_indices(true)
+        .filter(|(i, g)| *i == 0 || *g != " ")
+        .map(|(i, _)| i)
+        .collect();
+    starts.push(line.len());
+    Box::new((1..starts.len()).map(move |k| Word::from(&line[starts[k - 1]..starts[k]])))
+}
+
+/// Options wrapping lines to `width` columns in `mode`.
+fn wrap_options(width: usize, mode: WrapMode) -> textwrap::Options<'static> {
+    let options = textwrap::Options::new(width);
+    match mode {
+        WrapMode::Hyphenate => options.word_splitter(WordSplitter::Custom(split_hyphenated)),
+        WrapMode::Word => options.word_splitter(WordSplitter::NoHyphenation),
+        WrapMode::Character => options
+            .word_separator(WordSeparator::Custom(split_characters))
+            .word_splitter(WordSplitter::NoHyphenation),
+    }
 }
 
 impl WrapList {
     pub fn new(capacity: usize) -> Self {
-        let dictionary = Standard::from_embedded(Language::EnglishUS)
-            .expect("Failed to load EnglishUS hyphenation dictionary");
         Self {
             raw_list: VecDeque::with_capacity(capacity),
             list: VecDeque::with_capacity(capacity),
             wrap_len: None,
-            dictionary,
+            wrap_mode: WrapMode::default(),
+            follow: true,
+            pending: 0,
+            unseen: 0,
+            max_display_chars: 0,
+            theme: Theme::detect(),
+            total: 0,
+            counts: HashMap::new(),
+            batching: false,
+            renders: 0,
         }
     }
 
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        self.update_list();
+    }
+
+    /// Limit the displayed content of each event to `max_display_chars` graphemes, 0 disables it.
+    pub fn with_max_display_chars(mut self, max_display_chars: usize) -> Self {
+        self.set_max_display_chars(max_display_chars);
+        self
+    }
+
+    pub fn set_max_display_chars(&mut self, max_display_chars: usize) {
+        self.max_display_chars = max_display_chars;
+        self.update_list();
+    }
+
+    pub fn set_wrap_mode(&mut self, wrap_mode: WrapMode) {
+        self.wrap_mode = wrap_mode;
+        self.update_list();
+    }
+
     pub fn with_raw_list(mut self, raw_list: VecDeque<OneEvent>) -> Self {
         self.raw_list = raw_list;
+        self.counts.clear();
+        for e in &self.raw_list {
+            *self.counts.entry(e.kind.clone()).or_default() += 1;
+        }
         self.update_list();
         self
     }
@@ -46,7 +144,10 @@ impl WrapList {
                 LOE::CreatedFile => ("[OBSERVER][CREATE]", Color::Green),
                 LOE::ModifiedFile => ("[OBSERVER][MODIFY]", Color::Blue),
                 LOE::DeletedFile => ("[OBSERVER][DELETE]", Color::Magenta),
+                LOE::RotatedFile => ("[OBSERVER][ROTATE]", Color::Yellow),
                 LOE::Info => ("[OBSERVER][INFO]  ", Color::Magenta),
+                LOE::Warning => ("[OBSERVER][WARN]  ", Color::Yellow),
+                LOE::Debug => ("[OBSERVER][DEBUG] ", Color::DarkGray),
                 LOE::Start => ("[OBSERVER][START]  ", Color::Cyan),
                 LOE::Stop => ("[OBSERVER][STOP]  ", Color::Red),
             },
@@ -56,9 +157,12 @@ impl WrapList {
                 DSE::Stop => ("[SCANNER][STOP]  ", Color::Yellow),
                 DSE::Complete => ("[SCANNER][COMPLETE]", Color::Green),
                 DSE::Error => ("[SCANNER][ERR]  ", Color::Red),
+                DSE::Warning => ("[SCANNER][WARN]  ", Color::Yellow),
                 DSE::Info => ("[SCANNER][INFO]  ", Color::Magenta),
                 DSE::DBInfo => ("[SCANNER][DBINFO]", Color::Blue),
             },
+
+            OperatorEvent(OPE::Note) => ("[OPERATOR][NOTE] ", Color::LightYellow),
         };
 
         let time_str = e
@@ -70,12 +174,42 @@ impl WrapList {
         (prefix, text, color)
     }
 
-    /// Create a ListItem from a MonitorEvent, use `self.wrap_len`` and `self.dictionary` to wrap the text.
-    fn create_list_item(&self, e: &OneEvent) -> ListItem<'static> {
+    /// The event as one line for plain-text output, with the prefix in ANSI color if `colored`.
+    pub fn ansi_text(e: &OneEvent, colored: bool) -> String {
         let (prefix, text, color) = Self::create_text(e);
+        match ansi_color_code(color) {
+            Some(code) if colored => {
+                format!("\x1b[{}m{}\x1b[0m{}", code, prefix, &text[prefix.len()..])
+            }
+            _ => text,
+        }
+    }
 
-        let options = textwrap::Options::new(self.wrap_len.unwrap_or(usize::MAX))
-            .word_splitter(WordSplitter::Hyphenation(self.dictionary.clone()));
+    /// Cut `content` to at most `max` graphemes, keeping CJK and combined characters whole.
+    pub fn truncate_content(content: &str, max: usize) -> Cow<'_, str> {
+        if max == 0 {
+            return Cow::Borrowed(content);
+        }
+        match content.grapheme_indices(true).nth(max) {
+            Some((end, _)) => Cow::Owned(format!("{}{}", &content[..end], TRUNCATED_HINT)),
+            None => Cow::Borrowed(content),
+        }
+    }
+
+    /// Create a ListItem from a `OneEvent`, use `self.wrap_len` and `self.wrap_mode` to wrap the text.
+    ///
+    /// The content is truncated for display only, the raw event keeps the full text.
+    fn create_list_item(&self, e: &OneEvent) -> ListItem<'static> {
+        let e = match Self::truncate_content(&e.content, self.max_display_chars) {
+            Cow::Borrowed(_) => Cow::Borrowed(e),
+            Cow::Owned(content) => Cow::Owned(OneEvent {
+                content,
+                ..e.clone()
+            }),
+        };
+        let (prefix, text, color) = Self::create_text(&e);
+
+        let options = wrap_options(self.wrap_len.unwrap_or(usize::MAX), self.wrap_mode);
 
         let wrapped_lines: Vec<String> = textwrap::wrap(&text, options)
             .iter()
@@ -87,14 +221,22 @@ impl WrapList {
             .enumerate()
             .map(|(index, line)| {
                 if index == 0 {
-                    let parts: Vec<&str> = line.splitn(2, prefix).collect();
-                    if parts.len() < 2 {
-                        panic!("Unexpected line format when splitting prefix: {}", line);
+                    // 无颜色时前缀文本本身已表明事件类型，不再附加样式
+                    let prefix_style = if self.theme.is_colored() {
+                        self.theme.fg(color)
+                    } else {
+                        Style::new()
+                    };
+                    // 按字符换行时前缀也可能被断开，只给第一行中的部分加样式
+                    let head = prefix.trim_end();
+                    let head = &head[..head.len().min(line.len())];
+                    match line.strip_prefix(head) {
+                        Some(rest) => Line::from(vec![
+                            Span::styled(head.to_string(), prefix_style),
+                            Span::from(rest.to_string()),
+                        ]),
+                        None => Line::from(line),
                     }
-                    Line::from(vec![
-                        Span::styled(prefix.to_string(), Style::new().fg(color)),
-                        Span::from(parts[1].to_string()),
-                    ])
                 } else {
                     Line::from(line)
                 }
@@ -106,6 +248,7 @@ impl WrapList {
 
     /// Add ListItem to `self.list`.
     pub fn add_item(&mut self, e: OneEvent) {
+        self.renders += 1;
         let item = self.create_list_item(&e);
         self.list.push_front(item);
         if self.list.len() > self.wrap_len.unwrap_or(500) {
@@ -114,7 +257,13 @@ impl WrapList {
     }
 
     /// Update `self.list` from `self.raw_list`.
+    ///
+    /// Inside [`Self::batch`] this waits until the batch ends.
     pub fn update_list(&mut self) {
+        if self.batching {
+            return;
+        }
+        self.renders += 1;
         let items: Vec<ListItem> = self
             .raw_list
             .iter()
@@ -123,15 +272,125 @@ impl WrapList {
         self.list = items.into_iter().collect();
     }
 
-    /// Add raw item of MonitorEvent to `self.raw_list`.
+    /// Add raw item of `OneEvent` to `self.raw_list`.
+    ///
+    /// When not following, the item is counted as pending so the next render keeps the
+    /// user's scroll position.
     pub fn add_raw_item(&mut self, item: OneEvent) {
+        if !self.follow {
+            self.pending += 1;
+            self.unseen += 1;
+        }
+
         let max_len = self.wrap_len.unwrap_or(500);
-        if self.list.len() == max_len {
-            self.raw_list.pop_back();
+        if self.raw_list.len() >= max_len
+            && let Some(evicted) = self.raw_list.pop_back()
+        {
+            self.uncount(&evicted.kind);
         }
+        *self.counts.entry(item.kind.clone()).or_default() += 1;
         self.raw_list.push_front(item.clone());
+        self.total += 1;
+
+        // 批量操作结束时统一生成
+        if !self.batching {
+            self.add_item(item);
+        }
+    }
+
+    /// Run `f` with the list items left stale, then rebuild them once.
+    ///
+    /// Saves rendering each event on its own when adding many at once, e.g. history
+    /// loaded at startup. Nested batches rebuild when the outermost one ends.
+    pub fn batch<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        if self.batching {
+            return f(self);
+        }
+        self.batching = true;
+        let result = f(self);
+        self.batching = false;
+        self.update_list();
+        result
+    }
+
+    /// Events added after `cursor`, oldest first, and the cursor to pass next time.
+    ///
+    /// Events already dropped from the list are skipped.
+    pub fn events_since(&self, cursor: u64) -> (Vec<OneEvent>, u64) {
+        let new = (self.total.saturating_sub(cursor) as usize).min(self.raw_list.len());
+        let events = self.raw_list.iter().take(new).rev().cloned().collect();
+        (events, self.total)
+    }
+
+    pub fn set_follow(&mut self, follow: bool) {
+        self.follow = follow;
+        if follow {
+            self.pending = 0;
+            self.unseen = 0;
+        }
+    }
+
+    pub fn toggle_follow(&mut self) {
+        self.set_follow(!self.follow);
+    }
+
+    pub fn is_follow(&self) -> bool {
+        self.follow
+    }
+
+    /// Number of entries added above the view since auto-follow was turned off.
+    pub fn unseen_count(&self) -> usize {
+        self.unseen
+    }
+
+    /// Rough number of bytes held by the raw and rendered lists.
+    ///
+    /// The content is counted twice since the rendered item keeps a wrapped copy.
+    pub fn memory_estimate(&self) -> usize {
+        self.raw_list
+            .iter()
+            .map(|e| ITEM_OVERHEAD + 2 * e.content.len())
+            .sum()
+    }
+
+    /// Drop the oldest entries until the estimate is at most `target` bytes.
+    ///
+    /// Returns how many entries were removed.
+    pub fn trim_to(&mut self, target: usize) -> usize {
+        let mut estimate = self.memory_estimate();
+        let mut removed = 0;
+        while estimate > target {
+            let Some(e) = self.raw_list.pop_back() else {
+                break;
+            };
+            self.list.pop_back();
+            self.uncount(&e.kind);
+            estimate -= ITEM_OVERHEAD + 2 * e.content.len();
+            removed += 1;
+        }
+        removed
+    }
+
+    fn uncount(&mut self, kind: &EventKind) {
+        if let Some(n) = self.counts.get_mut(kind) {
+            *n -= 1;
+            if *n == 0 {
+                self.counts.remove(kind);
+            }
+        }
+    }
+
+    /// Number of events of each kind currently in the list.
+    pub fn counts_by_kind(&self) -> HashMap<EventKind, usize> {
+        self.counts.clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.raw_list.len()
+    }
 
-        self.add_item(item);
+    pub fn is_empty(&self) -> bool {
+        self.raw_list.is_empty()
     }
 
     pub fn get_raw_list(&self) -> VecDeque<OneEvent> {
@@ -143,12 +402,88 @@ impl WrapList {
             .iter()
             .map(|e| {
                 let (_, text, _) = Self::create_text(e);
-                format!("{text}")
+                text
             })
             .collect()
     }
 }
 
+/// 汇总时各类事件的显示顺序
+const COUNT_LABELS: [&str; 12] = [
+    "Errors", "Warnings", "Info", "Created", "Modified", "Deleted", "Rotated", "Complete",
+    "DB info", "Start", "Stop", "Notes",
+];
+
+/// Summary label of `kind`, the same for observer and scanner events of one kind.
+fn count_label(kind: &EventKind) -> &'static str {
+    match kind {
+        LogObserverEvent(l) => match l {
+            LOE::Error => "Errors",
+            LOE::Warning => "Warnings",
+            LOE::Info | LOE::Debug => "Info",
+            LOE::CreatedFile => "Created",
+            LOE::ModifiedFile => "Modified",
+            LOE::DeletedFile => "Deleted",
+            LOE::RotatedFile => "Rotated",
+            LOE::Start => "Start",
+            LOE::Stop => "Stop",
+        },
+        DirScannerEvent(d) => match d {
+            DSE::Error => "Errors",
+            DSE::Warning => "Warnings",
+            DSE::Info => "Info",
+            DSE::Complete => "Complete",
+            DSE::DBInfo => "DB info",
+            DSE::Start => "Start",
+            DSE::Stop => "Stop",
+        },
+        OperatorEvent(OPE::Note) => "Notes",
+    }
+}
+
+/// Counts from [`WrapList::counts_by_kind`] added up, e.g. "Errors: 3, Info: 120, Created: 45".
+///
+/// Kinds without events are left out, "none" if there are no events at all.
+pub fn counts_summary(counts: &[HashMap<EventKind, usize>]) -> String {
+    let mut totals = [0usize; COUNT_LABELS.len()];
+    for (kind, n) in counts.iter().flatten() {
+        let label = count_label(kind);
+        if let Some(i) = COUNT_LABELS.iter().position(|l| *l == label) {
+            totals[i] += n;
+        }
+    }
+    let parts: Vec<String> = COUNT_LABELS
+        .iter()
+        .zip(totals)
+        .filter(|(_, n)| *n > 0)
+        .map(|(label, n)| format!("{label}: {n}"))
+        .collect();
+    if parts.is_empty() {
+        "none".to_string()
+    } else {
+        parts.join(", ")
+    }
+}
+
+/// Shrink `lists` proportionally when their total estimate exceeds `cap` bytes.
+///
+/// Each list keeps its share of 90% of the cap, so the next few events don't
+/// trigger another trim right away. Returns the number of entries removed.
+pub fn trim_to_memory_cap(lists: &mut [&mut WrapList], cap: usize) -> usize {
+    let total: usize = lists.iter().map(|l| l.memory_estimate()).sum();
+    if cap == 0 || total <= cap {
+        return 0;
+    }
+    let budget = cap / 10 * 9;
+    lists
+        .iter_mut()
+        .map(|l| {
+            let share = (l.memory_estimate() as u128 * budget as u128 / total as u128) as usize;
+            l.trim_to(share)
+        })
+        .sum()
+}
+
 impl StatefulWidget for &mut WrapList {
     type State = ListState;
     fn render(
@@ -163,14 +498,330 @@ impl StatefulWidget for &mut WrapList {
             self.update_list();
         }
 
+        // 新条目插入在最前，暂停跟随时将偏移量和选中项下移，保持当前查看的条目不变
+        if !self.follow && self.pending > 0 {
+            let max_index = self.list.len().saturating_sub(1);
+            *state.offset_mut() = (state.offset() + self.pending).min(max_index);
+            if let Some(selected) = state.selected() {
+                state.select(Some((selected + self.pending).min(max_index)));
+            }
+            self.pending = 0;
+        } else if state.offset() == 0 {
+            self.unseen = 0;
+        }
+
         let items = self.list.clone();
         StatefulWidgetRef::render_ref(
             &List::new(items)
                 .block(Block::default().borders(Borders::NONE))
-                .highlight_style(MENU_HIGHLIGHT_STYLE),
+                .highlight_style(self.theme.highlight()),
             area,
             buf,
             state,
         );
     }
 }
+
+/// SGR foreground code for the colors used by event prefixes.
+fn ansi_color_code(color: Color) -> Option<u8> {
+    match color {
+        Color::Red => Some(31),
+        Color::Green => Some(32),
+        Color::Yellow => Some(33),
+        Color::Blue => Some(34),
+        Color::Magenta => Some(35),
+        Color::Cyan => Some(36),
+        Color::DarkGray => Some(90),
+        Color::LightYellow => Some(93),
+        _ => None,
+    }
+}
+
+// MARK: test
+#[cfg(test)]
+fn test_event(content: String) -> OneEvent {
+    OneEvent {
+        kind: LogObserverEvent(LOE::Info),
+        content,
+        time: None,
+    }
+}
+
+#[test]
+fn test_follow_off_keeps_position() {
+    use ratatui::{buffer::Buffer, layout::Rect};
+
+    let area = Rect::new(0, 0, 80, 5);
+    let mut buf = Buffer::empty(area);
+    let mut list = WrapList::new(50);
+    for i in 0..20 {
+        list.add_raw_item(test_event(format!("event {}", i)));
+    }
+
+    let mut state = ListState::default();
+    state.select(Some(8));
+    StatefulWidget::render(&mut list, area, &mut buf, &mut state);
+    let selected_content = list.get_raw_list()[8].content.clone();
+    let offset = state.offset();
+
+    list.set_follow(false);
+    for i in 20..23 {
+        list.add_raw_item(test_event(format!("event {}", i)));
+    }
+    StatefulWidget::render(&mut list, area, &mut buf, &mut state);
+
+    let selected = state.selected().unwrap();
+    assert_eq!(list.get_raw_list()[selected].content, selected_content);
+    assert_eq!(state.offset(), offset + 3);
+    assert_eq!(list.unseen_count(), 3);
+
+    list.set_follow(true);
+    assert_eq!(list.unseen_count(), 0);
+}
+
+#[test]
+fn test_truncate_long_event() {
+    let content: String = "数据库错误 SQL INSERT INTO é ".repeat(420);
+    assert!(content.chars().count() > 10_000);
+
+    let mut list = WrapList::new(10).with_max_display_chars(500);
+    list.wrap_len = Some(80);
+    list.add_raw_item(test_event(content.clone()));
+
+    // 显示内容截断，行数有界
+    assert!(list.list[0].height() <= 16);
+
+    // 导出保留完整内容
+    let exported = list.get_raw_list_string();
+    assert!(exported[0].ends_with(&content));
+    assert_eq!(list.get_raw_list()[0].content, content);
+
+    // 组合字符不会被截断
+    let combined = "e\u{301}".repeat(3);
+    let truncated = WrapList::truncate_content(&combined, 2);
+    assert!(truncated.starts_with(&"e\u{301}".repeat(2)));
+    assert!(truncated.ends_with(TRUNCATED_HINT));
+}
+
+#[test]
+fn test_memory_estimate_and_cap() {
+    let mut observer = WrapList::new(100);
+    let mut scanner = WrapList::new(100);
+    assert_eq!(observer.memory_estimate(), 0);
+
+    for _ in 0..40 {
+        observer.add_raw_item(test_event("o".repeat(1000)));
+    }
+    for _ in 0..10 {
+        scanner.add_raw_item(test_event("s".repeat(1000)));
+    }
+    let item = ITEM_OVERHEAD + 2000;
+    assert_eq!(observer.memory_estimate(), 40 * item);
+    assert_eq!(scanner.memory_estimate(), 10 * item);
+
+    // 更长的内容估算值更大
+    let mut longer = WrapList::new(100);
+    longer.add_raw_item(test_event("x".repeat(1001)));
+    assert!(longer.memory_estimate() > item);
+
+    // 未超出上限时不裁剪
+    let cap = 50 * item;
+    assert_eq!(
+        trim_to_memory_cap(&mut [&mut observer, &mut scanner], cap),
+        0
+    );
+
+    // 上限减半，按比例裁剪最早的记录
+    let cap = 25 * item;
+    observer.add_raw_item(test_event("newest".to_string()));
+    let removed = trim_to_memory_cap(&mut [&mut observer, &mut scanner], cap);
+    assert!(removed > 0);
+    assert!(observer.memory_estimate() + scanner.memory_estimate() <= cap);
+    assert_eq!(observer.len(), 18);
+    assert_eq!(scanner.len(), 4);
+    assert_eq!(observer.get_raw_list()[0].content, "newest");
+    assert_eq!(observer.list.len(), observer.len());
+}
+
+#[test]
+fn test_never_mode_renders_without_colors() {
+    use ratatui::{buffer::Buffer, layout::Rect};
+
+    let area = Rect::new(0, 0, 60, 3);
+    let mut buf = Buffer::empty(area);
+    let mut list = WrapList::new(10);
+    list.set_theme(Theme::monochrome());
+    list.add_raw_item(OneEvent {
+        kind: LogObserverEvent(LOE::Error),
+        content: "failed".to_string(),
+        time: None,
+    });
+    StatefulWidget::render(&mut list, area, &mut buf, &mut ListState::default());
+
+    let row: String = (0..area.width).map(|x| buf[(x, 0)].symbol()).collect();
+    assert!(row.starts_with("[OBSERVER][ERR]"));
+    for x in 0..area.width {
+        let cell = &buf[(x, 0)];
+        assert_eq!((cell.fg, cell.bg), (Color::Reset, Color::Reset));
+        assert!(cell.modifier.is_empty());
+    }
+}
+
+#[test]
+fn test_wrap_modes() {
+    let path = r"Copied E:\testdata\customer_uploads\CTA_inventory_report_2025.csv to archive";
+    let zh = "观察器读取 u_ex250601.log 时发现文件被截断，已从头重新读取";
+    let wrap = |text, mode| textwrap::wrap(text, wrap_options(24, mode));
+
+    assert_eq!(
+        wrap(path, WrapMode::Hyphenate),
+        [
+            r"Copied E:\testdata\cus-",
+            r"tomer_uploads\CTA_inven-",
+            "tory_report_2025.csv to",
+            "archive"
+        ]
+    );
+    assert_eq!(
+        wrap(path, WrapMode::Word),
+        [
+            "Copied E:",
+            r"\testdata\customer_uploa",
+            r"ds\CTA_inventory_report_",
+            "2025.csv to archive"
+        ]
+    );
+    assert_eq!(
+        wrap(path, WrapMode::Character),
+        [
+            r"Copied E:\testdata\custo",
+            r"mer_uploads\CTA_inventor",
+            "y_report_2025.csv to arc",
+            "hive"
+        ]
+    );
+
+    let by_word = [
+        "观察器读取",
+        "u_ex250601.log 时发现文",
+        "件被截断，已从头重新读取",
+    ];
+    assert_eq!(wrap(zh, WrapMode::Hyphenate), by_word);
+    assert_eq!(wrap(zh, WrapMode::Word), by_word);
+    assert_eq!(
+        wrap(zh, WrapMode::Character),
+        [
+            "观察器读取 u_ex250601.lo",
+            "g 时发现文件被截断，已从",
+            "头重新读取"
+        ]
+    );
+
+    // 按字符换行时前缀被断开也能渲染
+    let mut list = WrapList::new(50);
+    list.set_wrap_mode(WrapMode::Character);
+    list.wrap_len = Some(10);
+    list.add_raw_item(test_event(path.to_string()));
+    assert_eq!(list.list.len(), 1);
+}
+
+#[test]
+fn test_single_dictionary_instance() {
+    let lists: Vec<WrapList> = (0..3)
+        .map(|_| {
+            let mut list = WrapList::new(10);
+            list.wrap_len = Some(20);
+            list.add_raw_item(test_event("hyphenation dictionary".to_string()));
+            list
+        })
+        .collect();
+    assert_eq!(lists.len(), 3);
+    assert!(std::ptr::eq(dictionary().unwrap(), dictionary().unwrap()));
+    assert_eq!(DICTIONARY_LOADS.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn test_counts_by_kind() {
+    let event = |kind: EventKind| OneEvent {
+        kind,
+        content: "x".to_string(),
+        time: None,
+    };
+    let mut list = WrapList::new(20);
+    list.wrap_len = Some(20);
+    assert!(list.counts_by_kind().is_empty());
+    assert_eq!(counts_summary(&[list.counts_by_kind()]), "none");
+
+    for _ in 0..3 {
+        list.add_raw_item(event(LogObserverEvent(LOE::Error)));
+    }
+    for _ in 0..12 {
+        list.add_raw_item(event(LogObserverEvent(LOE::Info)));
+    }
+    for _ in 0..5 {
+        list.add_raw_item(event(LogObserverEvent(LOE::CreatedFile)));
+    }
+    let counts = list.counts_by_kind();
+    assert_eq!(counts[&LogObserverEvent(LOE::Error)], 3);
+    assert_eq!(counts[&LogObserverEvent(LOE::Info)], 12);
+    assert_eq!(counts[&LogObserverEvent(LOE::CreatedFile)], 5);
+    assert_eq!(
+        counts_summary(&[list.counts_by_kind()]),
+        "Errors: 3, Info: 12, Created: 5"
+    );
+
+    // 列表已满，新事件挤掉最早的错误
+    list.add_raw_item(event(LogObserverEvent(LOE::CreatedFile)));
+    list.add_raw_item(event(LogObserverEvent(LOE::CreatedFile)));
+    let counts = list.counts_by_kind();
+    assert_eq!(counts[&LogObserverEvent(LOE::Error)], 1);
+    assert_eq!(counts[&LogObserverEvent(LOE::CreatedFile)], 7);
+    assert_eq!(counts.values().sum::<usize>(), list.len());
+
+    // 按内存裁剪同样更新计数，最后一个错误被淘汰后不再出现
+    list.trim_to(18 * (ITEM_OVERHEAD + 2));
+    assert_eq!(list.len(), 18);
+    assert!(
+        !list
+            .counts_by_kind()
+            .contains_key(&LogObserverEvent(LOE::Error))
+    );
+
+    // 观察器和扫描器的同类事件合并显示
+    let mut scanner = WrapList::new(20);
+    scanner.add_raw_item(event(DirScannerEvent(DSE::Error)));
+    scanner.add_raw_item(event(DirScannerEvent(DSE::Info)));
+    assert_eq!(
+        counts_summary(&[list.counts_by_kind(), scanner.counts_by_kind()]),
+        "Errors: 1, Info: 12, Created: 7"
+    );
+
+    let restored = WrapList::new(20).with_raw_list(list.get_raw_list());
+    assert_eq!(restored.counts_by_kind(), list.counts_by_kind());
+}
+
+#[test]
+fn test_batch_rebuilds_once() {
+    let mut list = WrapList::new(2000);
+    list.wrap_len = Some(2000);
+    list.add_raw_item(test_event("before".to_string()));
+    let renders = list.renders;
+
+    let added = list.batch(|list| {
+        for i in 0..1000 {
+            list.add_raw_item(test_event(format!("history {}", i)));
+        }
+        // 嵌套的批量操作和设置变更也推迟到最后
+        list.batch(|list| list.set_max_display_chars(100));
+        list.len()
+    });
+    assert_eq!(added, 1001);
+    assert_eq!(list.renders, renders + 1);
+    assert_eq!(list.list.len(), 1001);
+    assert_eq!(list.get_raw_list()[0].content, "history 999");
+
+    // 批量操作之外仍逐条生成
+    list.add_raw_item(test_event("after".to_string()));
+    assert_eq!(list.renders, renders + 2);
+    assert_eq!(list.list.len(), 1002);
+}
@@ -1,16 +1,75 @@
-use crate::{apps::run_tui, cli::run_cli_mode, get_param};
+use crate::{
+    apps::{
+        file_sync_manager::{QuickActions, registry},
+        run_tui,
+        setup_wizard::run_setup_wizard,
+    },
+    cli::run_cli_mode,
+    config_path,
+    daemon::{LogTarget, run_daemon},
+    get_param, load_config, try_load_config,
+};
 
 pub const PARAM_HELP: &str = "help";
 pub const PARAM_CONFIG_PATH: &str = "cfg=";
 pub const PARAM_CLI: &str = "cli";
+pub const PARAM_DAEMON: &str = "daemon";
+pub const PARAM_LOG_FILE: &str = "log-file=";
+pub const PARAM_LOG_DIR: &str = "log-dir=";
+pub const PARAM_NO_COLOR: &str = "no-color";
+pub const PARAM_FORCE: &str = "force";
+pub const PARAM_DRY_RUN: &str = "dry-run";
+pub const PARAM_STDIN_COMMANDS: &str = "stdin-commands";
+pub const PARAM_VERBOSE: &str = "verbose";
 
 pub fn handle_params() {
-    if let Some(_) = get_param(PARAM_HELP) {
+    if get_param(PARAM_HELP).is_some() {
         print_params_help();
     }
-    if let Some(_) = get_param(PARAM_CLI) {
+    // 首次运行没有配置文件时，界面模式下通过向导生成
+    let config_path = config_path();
+    if !config_path.exists() {
+        if get_param(PARAM_DAEMON).is_some() || get_param(PARAM_CLI).is_some() {
+            eprintln!(
+                "Config file {} not found, run without --cli/--daemon to create it",
+                config_path.display()
+            );
+            std::process::exit(1);
+        }
+        match run_setup_wizard(&config_path) {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(0),
+            Err(e) => {
+                eprintln!("Setup wizard failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Err(e) = try_load_config() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    // 启动时校验列映射和额外列配置，避免运行中插入失败
+    if let Err(e) = registry::insert_schema(&load_config().file_sync_manager) {
+        eprintln!("Invalid file_info column config: {}", e);
+        std::process::exit(1);
+    }
+    if let Err(e) = QuickActions::from_config(&load_config().file_sync_manager.quick_actions) {
+        eprintln!("Invalid quick_actions config: {}", e);
+        std::process::exit(1);
+    }
+    if let Err(e) = load_config().file_sync_manager.site_configs() {
+        eprintln!("Invalid sites config: {}", e);
+        std::process::exit(1);
+    }
+    if let Err(e) = load_config().file_sync_manager.scan_jitter.check() {
+        eprintln!("Invalid scan_jitter config: {}", e);
+        std::process::exit(1);
+    }
+    if get_param(PARAM_DAEMON).is_some() {
+        run_daemon(LogTarget::from_params());
+    } else if get_param(PARAM_CLI).is_some() {
         run_cli_mode();
-        return;
     } else {
         run_tui();
     }
@@ -27,6 +86,16 @@ pub fn default_config_path() -> String {
 fn print_params_help() {
     println!("参数列表：");
     println!("  --help                   显示帮助信息");
-    println!("  --cfg=<path>             指定配置文件路径");
+    println!("  --cfg=<path>             指定配置文件路径，支持 .json 和 .toml");
     println!("  --cli                    cli模式");
+    println!("  --daemon                 守护模式，无界面运行观察器和定期扫描");
+    println!("  --log-file=<path>        守护模式的日志文件，默认输出到标准输出");
+    println!("  --log-dir=<dir>          守护模式每次观察器启动或扫描运行新建 run_<时间>.log");
+    println!("  --no-color               不输出颜色和转义序列，等同于配置 color: never");
+    println!("  --dry-run                观察器只在日志中列出将要插入的路径，不写数据库");
+    println!("  --force                  守护模式下扫描根目录或拒绝列表中的路径，不再要求确认");
+    println!("  --verbose                写入数据库失败时在错误中附上 SQL、行数和部分参数");
+    println!(
+        "  --stdin-commands         守护模式从标准输入逐行读取 pause、resume、scan <dir>、status、quit，回复写到标准输出"
+    );
 }
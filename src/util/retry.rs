@@ -0,0 +1,278 @@
+use std::{future::Future, time::Duration};
+
+use crate::util::rng::{SplitMix64, default_seed};
+
+/// 失败后按指数退避重试的策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    /// 包括第一次在内的最多尝试次数
+    pub attempts: u32,
+    /// 第一次重试前的等待，之后每次翻倍
+    pub base_delay: Duration,
+    /// 单次等待的上限，抖动前生效
+    pub max_delay: Duration,
+    /// 每次等待在 ±delay * jitter_percent / 100 范围内随机偏移
+    pub jitter_percent: u8,
+}
+
+impl Backoff {
+    pub const fn new(attempts: u32, base_delay: Duration) -> Self {
+        Self {
+            attempts,
+            base_delay,
+            max_delay: Duration::MAX,
+            jitter_percent: 0,
+        }
+    }
+
+    pub const fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub const fn with_jitter(mut self, jitter_percent: u8) -> Self {
+        self.jitter_percent = if jitter_percent > 100 {
+            100
+        } else {
+            jitter_percent
+        };
+        self
+    }
+
+    /// Delay before retry number `retry`, counted from 1 after the first failure.
+    pub fn delay(&self, retry: u32, rng: &mut SplitMix64) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let delay = self
+            .base_delay
+            .checked_mul(factor)
+            .unwrap_or(Duration::MAX)
+            .min(self.max_delay);
+        if self.jitter_percent == 0 {
+            return delay;
+        }
+        let span = delay.as_secs_f64() * self.jitter_percent as f64 / 100.0;
+        let jittered = delay.as_secs_f64() + rng.next_signed_unit() * span;
+        Duration::try_from_secs_f64(jittered.max(0.0)).unwrap_or(Duration::MAX)
+    }
+
+    /// Run `op` until it succeeds or all attempts fail, returning the last error.
+    ///
+    /// `op` gets the attempt number, starting at 1.
+    pub async fn retry<T, E, F, Fut>(&self, op: F) -> Result<T, E>
+    where
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        self.retry_if(op, |_| true).await
+    }
+
+    /// Like [`Self::retry`], but an error `retryable` rejects is returned right away.
+    pub async fn retry_if<T, E, F, Fut>(
+        &self,
+        op: F,
+        retryable: impl Fn(&E) -> bool,
+    ) -> Result<T, E>
+    where
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        self.retry_notify(op, retryable, |_, _, _| {}).await
+    }
+
+    /// Like [`Self::retry_if`], calling `on_retry` with the failed attempt, its error and
+    /// the delay before each retry.
+    pub async fn retry_notify<T, E, F, Fut>(
+        &self,
+        op: F,
+        retryable: impl Fn(&E) -> bool,
+        on_retry: impl FnMut(u32, &E, Duration),
+    ) -> Result<T, E>
+    where
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut rng = SplitMix64::new(default_seed());
+        self.retry_with(op, retryable, on_retry, &mut rng, tokio::time::sleep)
+            .await
+    }
+
+    async fn retry_with<T, E, F, Fut, S, SFut>(
+        &self,
+        mut op: F,
+        retryable: impl Fn(&E) -> bool,
+        mut on_retry: impl FnMut(u32, &E, Duration),
+        rng: &mut SplitMix64,
+        mut sleep: S,
+    ) -> Result<T, E>
+    where
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        S: FnMut(Duration) -> SFut,
+        SFut: Future<Output = ()>,
+    {
+        let mut attempt = 1;
+        loop {
+            match op(attempt).await {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt >= self.attempts || !retryable(&e) => return Err(e),
+                Err(e) => {
+                    let delay = self.delay(attempt, rng);
+                    on_retry(attempt, &e, delay);
+                    sleep(delay).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Run `op` up to `attempts` times, waiting `base_delay` doubled after each failure.
+pub async fn with_backoff<T, E, F, Fut>(attempts: u32, base_delay: Duration, op: F) -> Result<T, E>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    Backoff::new(attempts, base_delay).retry(op).await
+}
+
+// MARK: test
+#[cfg(test)]
+fn run_recorded<T, E>(
+    backoff: Backoff,
+    seed: u64,
+    mut op: impl FnMut(u32) -> Result<T, E>,
+) -> (Result<T, E>, Vec<u32>, Vec<Duration>) {
+    let mut attempts = Vec::new();
+    let mut sleeps = Vec::new();
+    let result = futures::executor::block_on(backoff.retry_with(
+        |attempt| {
+            attempts.push(attempt);
+            std::future::ready(op(attempt))
+        },
+        |_| true,
+        |_, _, _| {},
+        &mut SplitMix64::new(seed),
+        |delay| {
+            sleeps.push(delay);
+            std::future::ready(())
+        },
+    ));
+    (result, attempts, sleeps)
+}
+
+#[test]
+fn test_backoff_timing() {
+    let ms = Duration::from_millis;
+    let backoff = Backoff::new(6, ms(100)).with_max_delay(ms(1000));
+    let (result, _, sleeps) = run_recorded(backoff, 0, |_| Err::<(), _>("down"));
+    assert_eq!(result, Err("down"));
+    assert_eq!(sleeps, [ms(100), ms(200), ms(400), ms(800), ms(1000)]);
+
+    // 抖动在 ±20% 内，同一种子结果相同
+    let backoff = backoff.with_jitter(20);
+    let (_, _, jittered) = run_recorded(backoff, 7, |_| Err::<(), _>("down"));
+    for (delay, base) in jittered.iter().zip(&sleeps) {
+        assert!(delay.as_secs_f64() >= base.as_secs_f64() * 0.8);
+        assert!(delay.as_secs_f64() <= base.as_secs_f64() * 1.2);
+    }
+    assert_ne!(jittered, sleeps);
+    assert_eq!(
+        run_recorded(backoff, 7, |_| Err::<(), _>("down")).2,
+        jittered
+    );
+}
+
+#[test]
+fn test_backoff_max_attempts() {
+    let backoff = Backoff::new(3, Duration::from_millis(10));
+    let (result, attempts, sleeps) = run_recorded(backoff, 0, Err::<(), u32>);
+    assert_eq!(result, Err(3));
+    assert_eq!(attempts, [1, 2, 3]);
+    assert_eq!(sleeps.len(), 2);
+
+    let (result, attempts, sleeps) =
+        run_recorded(Backoff::new(0, Duration::ZERO), 0, Err::<(), u32>);
+    assert_eq!(result, Err(1));
+    assert_eq!(attempts, [1]);
+    assert!(sleeps.is_empty());
+}
+
+#[test]
+fn test_backoff_succeeds_on_nth_try() {
+    let backoff = Backoff::new(5, Duration::from_millis(10));
+    let (result, attempts, sleeps) =
+        run_recorded(backoff, 0, |n| if n < 3 { Err("busy") } else { Ok(n * 10) });
+    assert_eq!(result, Ok(30));
+    assert_eq!(attempts, [1, 2, 3]);
+    assert_eq!(
+        sleeps,
+        [Duration::from_millis(10), Duration::from_millis(20)]
+    );
+
+    let result = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(with_backoff(2, Duration::from_millis(1), |n| async move {
+            if n == 2 { Ok(n) } else { Err("busy") }
+        }));
+    assert_eq!(result, Ok(2));
+}
+
+#[test]
+fn test_backoff_stops_on_permanent_error() {
+    let backoff = Backoff::new(5, Duration::from_millis(1));
+    let mut attempts = Vec::new();
+    let result = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(backoff.retry_if(
+            |n| {
+                attempts.push(n);
+                std::future::ready(Err::<(), _>(if n < 3 { "timeout" } else { "syntax" }))
+            },
+            |e| *e == "timeout",
+        ));
+    // 不可重试的错误直接返回，不用完所有次数
+    assert_eq!(result, Err("syntax"));
+    assert_eq!(attempts, [1, 2, 3]);
+}
+
+#[test]
+fn test_backoff_notifies_retries() {
+    let ms = Duration::from_millis;
+    let backoff = Backoff::new(3, ms(500));
+    let mut retries = Vec::new();
+    // 模拟前两次连接被拒绝的数据库
+    let connect = |attempt: u32| {
+        std::future::ready(if attempt <= 2 {
+            Err(format!("connection refused, attempt {}", attempt))
+        } else {
+            Ok("conn")
+        })
+    };
+    let result = futures::executor::block_on(backoff.retry_with(
+        connect,
+        |_| true,
+        |attempt, e: &String, delay| retries.push((attempt, e.clone(), delay)),
+        &mut SplitMix64::new(0),
+        |_| std::future::ready(()),
+    ));
+    assert_eq!(result, Ok("conn"));
+    assert_eq!(
+        retries,
+        [
+            (1, "connection refused, attempt 1".to_string(), ms(500)),
+            (2, "connection refused, attempt 2".to_string(), ms(1000)),
+        ]
+    );
+
+    // 用完次数后返回最后一次的错误，不再通知
+    retries.clear();
+    let result = futures::executor::block_on(backoff.retry_with(
+        |_| std::future::ready(Err::<(), _>("refused".to_string())),
+        |_| true,
+        |attempt, e: &String, delay| retries.push((attempt, e.clone(), delay)),
+        &mut SplitMix64::new(0),
+        |_| std::future::ready(()),
+    ));
+    assert_eq!(result, Err("refused".to_string()));
+    assert_eq!(retries.len(), 2);
+}